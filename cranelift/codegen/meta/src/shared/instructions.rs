@@ -1262,6 +1262,46 @@ pub(crate) fn define(
         .other_side_effects(),
     );
 
+    ig.push(
+        Inst::new(
+            "stack_switch",
+            r#"
+        Suspends execution of the current stack and resumes execution of
+        another one.
+
+        Both context pointers point to a control context of three pointer-sized
+        words: the stack pointer, the frame pointer and the address at which to
+        resume execution, in that order.
+
+        The control context of the current stack is written to
+        ``store_context_ptr`` so that a later ``stack_switch`` can resume
+        execution right after this instruction. The stack described by the
+        control context at ``load_context_ptr`` is then resumed, receiving
+        ``in_payload0`` as the ``out_payload0`` of the ``stack_switch`` that
+        suspended it. Both pointers may be equal.
+
+        All registers are clobbered, so the values live across a switch are
+        saved on the stack. A stack that was never suspended can be started by
+        pointing the address to resume at at a ``system_v`` function taking a
+        single pointer-sized argument, which receives ``in_payload0``; such a
+        function must not return.
+
+        This is only supported on x86_64 with the System V calling convention;
+        other targets fail to compile it, and the interpreter can't run it.
+        "#,
+            &formats.ternary,
+        )
+        .operands_in(vec![
+            Operand::new("store_context_ptr", iAddr),
+            Operand::new("load_context_ptr", iAddr),
+            Operand::new("in_payload0", iAddr),
+        ])
+        .operands_out(vec![Operand::new("out_payload0", iAddr)])
+        .other_side_effects()
+        .can_load()
+        .can_store(),
+    );
+
     ig.push(
         Inst::new(
             "get_frame_pointer",
@@ -14,7 +14,8 @@ use crate::dce::do_dce;
 use crate::dominator_tree::DominatorTree;
 use crate::egraph::EgraphPass;
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::Function;
+use crate::inline::{do_inline, FunctionStore, InlineOptions};
+use crate::ir::{Function, FunctionProfile};
 use crate::isa::TargetIsa;
 use crate::legalizer::simple_legalize;
 use crate::loop_analysis::LoopAnalysis;
@@ -96,6 +97,15 @@ impl Context {
         self.compiled_code.as_ref()
     }
 
+    /// Attach an execution profile to the function, to guide the layout of
+    /// its machine code. See [`FunctionProfile`] for details.
+    ///
+    /// The profile refers to the blocks of `func`, and is cleared along with
+    /// it.
+    pub fn set_profile(&mut self, profile: FunctionProfile) {
+        self.func.profile = profile;
+    }
+
     /// Set the flag to request a disassembly when compiling with a
     /// `MachBackend` backend.
     pub fn set_disasm(&mut self, val: bool) {
@@ -257,6 +267,29 @@ impl Context {
         Ok(())
     }
 
+    /// Inline calls to the functions of `store`, returning whether any call
+    /// was inlined.
+    ///
+    /// This is not part of the `compile` pipeline, as it needs the bodies of
+    /// the callees. Call it before `compile` so that the inlined code is
+    /// optimized along with the rest of the function.
+    pub fn inline<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        store: &dyn FunctionStore,
+        options: &InlineOptions,
+        fisa: FOI,
+    ) -> CodegenResult<bool> {
+        let inlined = do_inline(&mut self.func, store, options);
+        if inlined {
+            // Inlining changes the CFG.
+            self.cfg.clear();
+            self.domtree.clear();
+            self.loop_analysis.clear();
+        }
+        self.verify_if(fisa)?;
+        Ok(inlined)
+    }
+
     /// Perform dead-code elimination on the function.
     pub fn dce<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
         do_dce(&mut self.func, &mut self.domtree);
@@ -0,0 +1,573 @@
+//! A function inlining pass.
+//!
+//! Cranelift compiles one function at a time, so the bodies of the functions
+//! which may be inlined are supplied by the embedder through a
+//! [`FunctionStore`]. Direct calls (`call` and `return_call`) to functions
+//! found in the store are replaced with a copy of the callee's body when the
+//! callee is small enough according to the [`InlineOptions`].
+//!
+//! Inlining is meant to run before the egraph pass so that the inlined code is
+//! optimized along with the rest of the caller.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::entity::SecondaryMap;
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::{
+    self, ArgumentPurpose, Block, BlockCall, ExtFuncData, ExternalName, FuncRef, Function,
+    GlobalValue, GlobalValueData, Inst, InstBuilder, InstructionData, InstructionMapper,
+    JumpTableData, MemFlags, Opcode, SigRef, SourceLoc, StackSlot, UserStackMapEntry, Value,
+    ValueList,
+};
+use crate::packed_option::PackedOption;
+use crate::timing;
+use alloc::vec::Vec;
+
+/// Supplies the bodies of the functions called by the function being
+/// compiled.
+pub trait FunctionStore {
+    /// Get the body of the function which `caller` calls through `func_ref`,
+    /// or `None` if it isn't available for inlining.
+    ///
+    /// The returned function must be the one the call resolves to at run
+    /// time, and its signature must match the signature of `func_ref`.
+    fn get(&self, caller: &Function, func_ref: FuncRef) -> Option<&Function>;
+}
+
+/// Heuristics deciding which calls are inlined.
+#[derive(Clone, Debug)]
+pub struct InlineOptions {
+    /// Callees with more instructions than this are not inlined.
+    pub max_callee_size: usize,
+
+    /// The maximum number of instructions inlining may add to a function.
+    pub max_growth: usize,
+
+    /// The maximum depth of inlining: calls of the original function are at
+    /// depth 1, calls within code inlined from them at depth 2, and so on.
+    ///
+    /// This bounds the inlining of recursive functions.
+    pub max_depth: usize,
+}
+
+impl Default for InlineOptions {
+    fn default() -> Self {
+        Self {
+            max_callee_size: 40,
+            max_growth: 2000,
+            max_depth: 4,
+        }
+    }
+}
+
+/// Inline calls of `func` to functions of `store`, returning whether any call
+/// was inlined.
+pub fn do_inline(func: &mut Function, store: &dyn FunctionStore, options: &InlineOptions) -> bool {
+    let _tt = timing::inline();
+
+    // Calls are inlined in program order, and calls within inlined code right
+    // after the call they were inlined for.
+    let mut worklist = Vec::new();
+    for block in func.layout.blocks() {
+        for inst in func.layout.block_insts(block) {
+            if is_direct_call(func, inst) {
+                worklist.push((inst, 1));
+            }
+        }
+    }
+    worklist.reverse();
+
+    let mut growth = 0;
+    let mut inlined = false;
+    while let Some((call, depth)) = worklist.pop() {
+        // The roots live across a call with a stack map would be missing from
+        // the stack maps of the calls in the inlined code.
+        if func.dfg.user_stack_map_entries(call).is_some() {
+            continue;
+        }
+        let func_ref = match func.dfg.insts[call] {
+            InstructionData::Call { func_ref, .. } => func_ref,
+            _ => unreachable!(),
+        };
+        let callee = match store.get(func, func_ref) {
+            Some(callee) => callee,
+            None => continue,
+        };
+        let size = match inlining_size(func, func_ref, callee) {
+            Some(size) => size,
+            None => continue,
+        };
+        if size > options.max_callee_size || growth + size > options.max_growth {
+            continue;
+        }
+        log::trace!("inlining {} into {}", callee.name, func.name);
+        growth += size;
+        inlined = true;
+
+        let calls = Inliner::new(func, callee, call).inline(call);
+        if depth < options.max_depth {
+            worklist.extend(calls.into_iter().rev().map(|call| (call, depth + 1)));
+        }
+    }
+    inlined
+}
+
+/// Is `inst` a `call` or `return_call` instruction?
+fn is_direct_call(func: &Function, inst: Inst) -> bool {
+    matches!(
+        func.dfg.insts[inst].opcode(),
+        Opcode::Call | Opcode::ReturnCall
+    )
+}
+
+/// Get the number of instructions `callee` adds to `caller` when inlined for
+/// a call through `func_ref`, or `None` if it can't be inlined.
+fn inlining_size(caller: &Function, func_ref: FuncRef, callee: &Function) -> Option<usize> {
+    let sig = &caller.dfg.signatures[caller.dfg.ext_funcs[func_ref].signature];
+    let types = |params: &[ir::AbiParam]| params.iter().map(|p| p.value_type).collect::<Vec<_>>();
+    if types(&sig.params) != types(&callee.signature.params)
+        || types(&sig.returns) != types(&callee.signature.returns)
+    {
+        return None;
+    }
+
+    // A struct argument is a pointer to the callee's own copy of the struct in
+    // its argument area, and a struct return pointer may be rewritten by the
+    // ABI; passing the caller's pointers through would share memory that the
+    // call doesn't share.
+    let is_struct = |p: &ir::AbiParam| {
+        matches!(
+            p.purpose,
+            ArgumentPurpose::StructArgument(_) | ArgumentPurpose::StructReturn
+        )
+    };
+    if sig.params.iter().any(is_struct) || callee.signature.params.iter().any(is_struct) {
+        return None;
+    }
+
+    // Dynamic vector types and tables are only supported within the function
+    // they were declared in.
+    if !callee.dynamic_stack_slots.is_empty()
+        || !callee.dfg.dynamic_types.is_empty()
+        || !callee.tables.is_empty()
+    {
+        return None;
+    }
+
+    // Global values rooted in the vmctx are computed from the vmctx argument
+    // of the call.
+    let has_vmctx = callee
+        .signature
+        .special_param_index(ArgumentPurpose::VMContext)
+        .is_some();
+    if !has_vmctx
+        && callee
+            .global_values
+            .values()
+            .any(|gv| matches!(gv, GlobalValueData::VMContext))
+    {
+        return None;
+    }
+
+    let mut size = 0;
+    for block in callee.layout.blocks() {
+        for inst in callee.layout.block_insts(block) {
+            match callee.dfg.insts[inst].opcode() {
+                // These observe the callee's own frame.
+                Opcode::GetFramePointer | Opcode::GetStackPointer | Opcode::GetReturnAddress => {
+                    return None
+                }
+                _ => size += 1,
+            }
+        }
+    }
+    if size == 0 {
+        return None;
+    }
+    Some(size)
+}
+
+/// Copies the body of a callee into its caller, mapping the entities of the
+/// callee to entities of the caller.
+struct Inliner<'a> {
+    func: &'a mut Function,
+    callee: &'a Function,
+
+    /// The source location of the call, which all inlined instructions get.
+    srcloc: SourceLoc,
+
+    /// The argument passed for the callee's vmctx parameter.
+    vmctx: Option<Value>,
+
+    values: SecondaryMap<Value, PackedOption<Value>>,
+    blocks: SecondaryMap<Block, PackedOption<Block>>,
+    func_refs: SecondaryMap<FuncRef, PackedOption<FuncRef>>,
+    sig_refs: SecondaryMap<SigRef, PackedOption<SigRef>>,
+    stack_slots: SecondaryMap<StackSlot, PackedOption<StackSlot>>,
+    global_values: SecondaryMap<GlobalValue, PackedOption<GlobalValue>>,
+    jump_tables: SecondaryMap<ir::JumpTable, PackedOption<ir::JumpTable>>,
+    constants: SecondaryMap<ir::Constant, PackedOption<ir::Constant>>,
+    immediates: SecondaryMap<ir::Immediate, PackedOption<ir::Immediate>>,
+}
+
+impl<'a> Inliner<'a> {
+    fn new(func: &'a mut Function, callee: &'a Function, call: Inst) -> Self {
+        let srcloc = func.srcloc(call);
+        let vmctx = callee
+            .signature
+            .special_param_index(ArgumentPurpose::VMContext)
+            .map(|i| func.dfg.inst_args(call)[i]);
+        Self {
+            func,
+            callee,
+            srcloc,
+            vmctx,
+            values: SecondaryMap::new(),
+            blocks: SecondaryMap::new(),
+            func_refs: SecondaryMap::new(),
+            sig_refs: SecondaryMap::new(),
+            stack_slots: SecondaryMap::new(),
+            global_values: SecondaryMap::new(),
+            jump_tables: SecondaryMap::new(),
+            constants: SecondaryMap::new(),
+            immediates: SecondaryMap::new(),
+        }
+    }
+
+    /// Replace `call` with the body of the callee, returning the direct calls
+    /// within the inlined code.
+    fn inline(mut self, call: Inst) -> Vec<Inst> {
+        let callee = self.callee;
+        let call_block = self.func.layout.inst_block(call).unwrap();
+        let args = self.func.dfg.inst_args(call).to_vec();
+
+        // A `call` continues in a new block which receives the results as
+        // parameters, while the callee's returns from a `return_call` return
+        // from the caller.
+        let cont = if self.func.dfg.insts[call].opcode() == Opcode::Call {
+            let cont = self.func.dfg.make_block();
+            let next = self.func.layout.next_inst(call).unwrap();
+            self.func.layout.split_block(cont, next);
+            self.func.profile.split_block(call_block, cont);
+            let results = self.func.dfg.detach_results(call);
+            let results = results.as_slice(&self.func.dfg.value_lists).to_vec();
+            for result in results {
+                self.func.dfg.attach_block_param(cont, result);
+            }
+            Some(cont)
+        } else {
+            None
+        };
+
+        let cfg = ControlFlowGraph::with_function(callee);
+        let domtree = DominatorTree::with_function(callee, &cfg);
+        let mut last = call_block;
+        for block in callee.layout.blocks() {
+            if !domtree.is_reachable(block) {
+                continue;
+            }
+            let new_block = self.func.dfg.make_block();
+            for &param in callee.dfg.block_params(block) {
+                let ty = callee.dfg.value_type(param);
+                self.values[param] = self.func.dfg.append_block_param(new_block, ty).into();
+            }
+            self.func.layout.insert_block_after(new_block, last);
+            self.blocks[block] = new_block.into();
+            last = new_block;
+        }
+        let entry = self.blocks[callee.layout.entry_block().unwrap()].unwrap();
+        self.func.dfg.replace(call).jump(entry, &args);
+
+        // Visiting the blocks in reverse postorder ensures that values are
+        // mapped before their uses.
+        let mut calls = Vec::new();
+        for &block in domtree.cfg_postorder().iter().rev() {
+            let new_block = self.blocks[block].unwrap();
+            for inst in callee.layout.block_insts(block) {
+                self.copy_inst(inst, new_block, cont, &mut calls);
+            }
+        }
+        calls
+    }
+
+    /// Copy `inst` of the callee to the end of `block`.
+    fn copy_inst(&mut self, inst: Inst, block: Block, cont: Option<Block>, calls: &mut Vec<Inst>) {
+        let callee = self.callee;
+
+        match callee.dfg.insts[inst] {
+            InstructionData::UnaryGlobalValue {
+                opcode: Opcode::GlobalValue,
+                global_value,
+            } if self.is_vmctx_rooted(global_value) => {
+                let value = self.expand_global_value(global_value, block);
+                self.values[callee.dfg.first_result(inst)] = value.into();
+                return;
+            }
+            InstructionData::MultiAry {
+                opcode: Opcode::Return,
+                args,
+            } if cont.is_some() => {
+                let args = self.map_values(args.as_slice(&callee.dfg.value_lists));
+                self.cursor(block).ins().jump(cont.unwrap(), &args);
+                return;
+            }
+            _ => {}
+        }
+
+        // Tail calls of the callee return to the continuation block when
+        // inlined for a `call`.
+        let mut data = callee.dfg.insts[inst].map(&mut *self);
+        let tail_call = match (cont, &mut data) {
+            (
+                Some(_),
+                InstructionData::Call { opcode, .. } | InstructionData::CallIndirect { opcode, .. },
+            ) if opcode.is_return() => {
+                *opcode = match *opcode {
+                    Opcode::ReturnCall => Opcode::Call,
+                    _ => Opcode::CallIndirect,
+                };
+                true
+            }
+            _ => false,
+        };
+
+        let new_inst = self.func.dfg.make_inst(data);
+        self.func
+            .dfg
+            .make_inst_results(new_inst, callee.dfg.ctrl_typevar(inst));
+        for (i, &result) in callee.dfg.inst_results(inst).iter().enumerate() {
+            self.values[result] = self.func.dfg.inst_results(new_inst)[i].into();
+        }
+        self.cursor(block).insert_inst(new_inst);
+        if !self.srcloc.is_default() {
+            self.func.set_srcloc(new_inst, self.srcloc);
+        }
+        for entry in callee.dfg.user_stack_map_entries(inst).unwrap_or(&[]) {
+            let entry = UserStackMapEntry {
+                slot: self.map_stack_slot(entry.slot),
+                ..entry.clone()
+            };
+            self.func.dfg.append_user_stack_map_entry(new_inst, entry);
+        }
+        if is_direct_call(self.func, new_inst) {
+            calls.push(new_inst);
+        }
+
+        if tail_call {
+            let results = self.func.dfg.inst_results(new_inst).to_vec();
+            self.cursor(block).ins().jump(cont.unwrap(), &results);
+        }
+    }
+
+    /// Get a cursor appending to `block`.
+    fn cursor(&mut self, block: Block) -> FuncCursor<'_> {
+        let pos = FuncCursor::new(self.func).at_bottom(block);
+        if self.srcloc.is_default() {
+            pos
+        } else {
+            pos.with_srcloc(self.srcloc)
+        }
+    }
+
+    fn map_values(&mut self, values: &[Value]) -> Vec<Value> {
+        values.iter().map(|&v| self.map_value(v)).collect()
+    }
+
+    /// Is the callee's global value `gv` computed from its vmctx parameter?
+    fn is_vmctx_rooted(&self, mut gv: GlobalValue) -> bool {
+        loop {
+            match self.callee.global_values[gv] {
+                GlobalValueData::VMContext => return true,
+                GlobalValueData::Load { base, .. } | GlobalValueData::IAddImm { base, .. } => {
+                    gv = base
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    /// Compute the callee's global value `gv` from the vmctx argument of the
+    /// call, at the end of `block`.
+    fn expand_global_value(&mut self, gv: GlobalValue, block: Block) -> Value {
+        match self.callee.global_values[gv] {
+            GlobalValueData::VMContext => self.vmctx.unwrap(),
+            GlobalValueData::Load {
+                base,
+                offset,
+                global_type,
+                readonly,
+            } => {
+                let base = self.expand_global_value(base, block);
+                // Global-value loads are always notrap and aligned.
+                let mut flags = MemFlags::trusted();
+                if readonly {
+                    flags.set_readonly();
+                }
+                self.cursor(block)
+                    .ins()
+                    .load(global_type, flags, base, offset)
+            }
+            GlobalValueData::IAddImm { base, offset, .. } => {
+                let base = self.expand_global_value(base, block);
+                self.cursor(block).ins().iadd_imm(base, offset)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn map_external_name(&mut self, name: &ExternalName) -> ExternalName {
+        match *name {
+            ExternalName::User(name) => {
+                let name = self.callee.params.user_named_funcs()[name].clone();
+                ExternalName::User(self.func.declare_imported_user_function(name))
+            }
+            ref name => name.clone(),
+        }
+    }
+}
+
+impl InstructionMapper for Inliner<'_> {
+    fn map_value(&mut self, value: Value) -> Value {
+        let value = self.callee.dfg.resolve_aliases(value);
+        self.values[value].expect("value used before its definition")
+    }
+
+    fn map_value_list(&mut self, value_list: ValueList) -> ValueList {
+        let values = self.map_values(value_list.as_slice(&self.callee.dfg.value_lists));
+        ValueList::from_slice(&values, &mut self.func.dfg.value_lists)
+    }
+
+    fn map_global_value(&mut self, global_value: GlobalValue) -> GlobalValue {
+        if let Some(gv) = self.global_values[global_value].expand() {
+            return gv;
+        }
+        let data = match self.callee.global_values[global_value] {
+            GlobalValueData::Load {
+                base,
+                offset,
+                global_type,
+                readonly,
+            } => GlobalValueData::Load {
+                base: self.map_global_value(base),
+                offset,
+                global_type,
+                readonly,
+            },
+            GlobalValueData::IAddImm {
+                base,
+                offset,
+                global_type,
+            } => GlobalValueData::IAddImm {
+                base: self.map_global_value(base),
+                offset,
+                global_type,
+            },
+            GlobalValueData::Symbol {
+                ref name,
+                offset,
+                colocated,
+                tls,
+            } => GlobalValueData::Symbol {
+                name: self.map_external_name(name),
+                offset,
+                colocated,
+                tls,
+            },
+            ref data => data.clone(),
+        };
+        let gv = self.func.create_global_value(data);
+        self.global_values[global_value] = gv.into();
+        gv
+    }
+
+    fn map_jump_table(&mut self, jump_table: ir::JumpTable) -> ir::JumpTable {
+        if let Some(jt) = self.jump_tables[jump_table].expand() {
+            return jt;
+        }
+        let branches = self.callee.dfg.jump_tables[jump_table]
+            .all_branches()
+            .iter()
+            .map(|&block_call| self.map_block_call(block_call))
+            .collect::<Vec<_>>();
+        let jt = self
+            .func
+            .create_jump_table(JumpTableData::new(branches[0], &branches[1..]));
+        self.jump_tables[jump_table] = jt.into();
+        jt
+    }
+
+    fn map_block_call(&mut self, block_call: BlockCall) -> BlockCall {
+        let pool = &self.callee.dfg.value_lists;
+        let block = self.blocks[block_call.block(pool)].unwrap();
+        let args = self.map_values(block_call.args_slice(pool));
+        BlockCall::new(block, &args, &mut self.func.dfg.value_lists)
+    }
+
+    fn map_func_ref(&mut self, func_ref: FuncRef) -> FuncRef {
+        if let Some(func_ref) = self.func_refs[func_ref].expand() {
+            return func_ref;
+        }
+        let data = &self.callee.dfg.ext_funcs[func_ref];
+        let data = ExtFuncData {
+            name: self.map_external_name(&data.name),
+            signature: self.map_sig_ref(data.signature),
+            colocated: data.colocated,
+        };
+        let new_func_ref = self.func.import_function(data);
+        self.func_refs[func_ref] = new_func_ref.into();
+        new_func_ref
+    }
+
+    fn map_sig_ref(&mut self, sig_ref: SigRef) -> SigRef {
+        if let Some(sig_ref) = self.sig_refs[sig_ref].expand() {
+            return sig_ref;
+        }
+        let new_sig_ref = self
+            .func
+            .import_signature(self.callee.dfg.signatures[sig_ref].clone());
+        self.sig_refs[sig_ref] = new_sig_ref.into();
+        new_sig_ref
+    }
+
+    fn map_stack_slot(&mut self, stack_slot: StackSlot) -> StackSlot {
+        if let Some(stack_slot) = self.stack_slots[stack_slot].expand() {
+            return stack_slot;
+        }
+        let new_stack_slot = self
+            .func
+            .create_sized_stack_slot(self.callee.sized_stack_slots[stack_slot].clone());
+        self.stack_slots[stack_slot] = new_stack_slot.into();
+        new_stack_slot
+    }
+
+    fn map_dynamic_stack_slot(
+        &mut self,
+        _dynamic_stack_slot: ir::DynamicStackSlot,
+    ) -> ir::DynamicStackSlot {
+        unreachable!("functions with dynamic stack slots are not inlined")
+    }
+
+    fn map_table(&mut self, _table: ir::Table) -> ir::Table {
+        unreachable!("functions with tables are not inlined")
+    }
+
+    fn map_constant(&mut self, constant: ir::Constant) -> ir::Constant {
+        if let Some(constant) = self.constants[constant].expand() {
+            return constant;
+        }
+        let data = self.callee.dfg.constants.get(constant).clone();
+        let new_constant = self.func.dfg.constants.insert(data);
+        self.constants[constant] = new_constant.into();
+        new_constant
+    }
+
+    fn map_immediate(&mut self, immediate: ir::Immediate) -> ir::Immediate {
+        if let Some(immediate) = self.immediates[immediate].expand() {
+            return immediate;
+        }
+        let data = self.callee.dfg.immediates[immediate].clone();
+        let new_immediate = self.func.dfg.immediates.push(data);
+        self.immediates[immediate] = new_immediate.into();
+        new_immediate
+    }
+}
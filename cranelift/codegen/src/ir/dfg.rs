@@ -7,8 +7,8 @@ use crate::ir::dynamic_type::{DynamicTypeData, DynamicTypes};
 use crate::ir::instructions::{CallInfo, InstructionData};
 use crate::ir::{
     types, Block, BlockCall, ConstantData, ConstantPool, DynamicType, ExtFuncData, FuncRef,
-    Immediate, Inst, JumpTables, RelSourceLoc, SigRef, Signature, Type, Value,
-    ValueLabelAssignments, ValueList, ValueListPool,
+    Immediate, Inst, JumpTables, RelSourceLoc, SigRef, Signature, Type, UserStackMapEntry,
+    UserStackMapEntryVec, Value, ValueLabelAssignments, ValueList, ValueListPool,
 };
 use crate::packed_option::ReservedValue;
 use crate::write::write_operands;
@@ -146,6 +146,10 @@ pub struct DataFlowGraph {
 
     /// Jump tables used in this function.
     pub jump_tables: JumpTables,
+
+    /// User-defined stack maps of call instructions, see
+    /// `append_user_stack_map_entry`.
+    user_stack_maps: BTreeMap<Inst, UserStackMapEntryVec>,
 }
 
 impl DataFlowGraph {
@@ -165,6 +169,7 @@ impl DataFlowGraph {
             constants: ConstantPool::new(),
             immediates: PrimaryMap::new(),
             jump_tables: JumpTables::new(),
+            user_stack_maps: BTreeMap::new(),
         }
     }
 
@@ -183,6 +188,7 @@ impl DataFlowGraph {
         self.constants.clear();
         self.immediates.clear();
         self.jump_tables.clear();
+        self.user_stack_maps.clear();
     }
 
     /// Get the total number of instructions created in this function, whether they are currently
@@ -999,6 +1005,23 @@ impl DataFlowGraph {
         }
     }
 
+    /// Record that the call instruction `inst` has a live GC root stored at
+    /// `entry`, which is reported in the instruction's stack map.
+    ///
+    /// Panics if `inst` is not a non-tail call instruction.
+    pub fn append_user_stack_map_entry(&mut self, inst: Inst, entry: UserStackMapEntry) {
+        assert!(
+            self.non_tail_call_signature(inst).is_some(),
+            "only non-tail calls can have stack maps"
+        );
+        self.user_stack_maps.entry(inst).or_default().push(entry);
+    }
+
+    /// Get the entries of the user stack map of `inst`, if any.
+    pub fn user_stack_map_entries(&self, inst: Inst) -> Option<&[UserStackMapEntry]> {
+        self.user_stack_maps.get(&inst).map(|entries| &entries[..])
+    }
+
     /// Like `call_signature` but returns none for tail call instructions.
     fn non_tail_call_signature(&self, inst: Inst) -> Option<SigRef> {
         let sig = self.call_signature(inst)?;
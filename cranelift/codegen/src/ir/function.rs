@@ -6,9 +6,9 @@
 use crate::entity::{PrimaryMap, SecondaryMap};
 use crate::ir::{
     self, Block, DataFlowGraph, DynamicStackSlot, DynamicStackSlotData, DynamicStackSlots,
-    DynamicType, ExtFuncData, FuncRef, GlobalValue, GlobalValueData, Inst, JumpTable,
-    JumpTableData, Layout, Opcode, SigRef, Signature, SourceLocs, StackSlot, StackSlotData,
-    StackSlots, Table, TableData, Type,
+    DynamicType, ExtFuncData, FuncRef, FunctionProfile, GlobalValue, GlobalValueData, Inst,
+    JumpTable, JumpTableData, Layout, Opcode, SigRef, Signature, SourceLocs, StackSlot,
+    StackSlotData, StackSlots, Table, TableData, Type,
 };
 use crate::isa::CallConv;
 use crate::write::write_function;
@@ -193,6 +193,10 @@ pub struct FunctionStencil {
     /// ensure that a trap happens if the stack pointer goes below the
     /// threshold specified here.
     pub stack_limit: Option<ir::GlobalValue>,
+
+    /// Execution counts of the blocks and edges of this function, used to
+    /// guide the layout of its machine code. Empty if no profile is available.
+    pub profile: FunctionProfile,
 }
 
 impl FunctionStencil {
@@ -206,6 +210,7 @@ impl FunctionStencil {
         self.layout.clear();
         self.srclocs.clear();
         self.stack_limit = None;
+        self.profile.clear();
     }
 
     /// Creates a jump table in the function, to be used by `br_table` instructions.
@@ -413,6 +418,7 @@ impl Function {
                 layout: Layout::new(),
                 srclocs: SecondaryMap::new(),
                 stack_limit: None,
+                profile: FunctionProfile::new(),
             },
             params: FunctionParameters::new(),
         }
@@ -471,6 +471,105 @@ impl InstructionData {
     }
 }
 
+/// A trait for mapping the values, blocks and entities referenced by an
+/// `InstructionData`, see `InstructionData::map`.
+///
+/// Each method is given an operand of the original instruction and returns
+/// the operand to use in its place.
+pub trait InstructionMapper {
+    /// Map a value operand.
+    fn map_value(&mut self, value: Value) -> Value;
+
+    /// Map a list of value operands.
+    fn map_value_list(&mut self, value_list: ValueList) -> ValueList;
+
+    /// Map a global value operand.
+    fn map_global_value(&mut self, global_value: ir::GlobalValue) -> ir::GlobalValue;
+
+    /// Map a jump table operand.
+    fn map_jump_table(&mut self, jump_table: ir::JumpTable) -> ir::JumpTable;
+
+    /// Map a block call operand.
+    fn map_block_call(&mut self, block_call: BlockCall) -> BlockCall;
+
+    /// Map a function reference operand.
+    fn map_func_ref(&mut self, func_ref: FuncRef) -> FuncRef;
+
+    /// Map a signature reference operand.
+    fn map_sig_ref(&mut self, sig_ref: SigRef) -> SigRef;
+
+    /// Map a stack slot operand.
+    fn map_stack_slot(&mut self, stack_slot: StackSlot) -> StackSlot;
+
+    /// Map a dynamic stack slot operand.
+    fn map_dynamic_stack_slot(
+        &mut self,
+        dynamic_stack_slot: ir::DynamicStackSlot,
+    ) -> ir::DynamicStackSlot;
+
+    /// Map a table operand.
+    fn map_table(&mut self, table: ir::Table) -> ir::Table;
+
+    /// Map a constant pool operand.
+    fn map_constant(&mut self, constant: ir::Constant) -> ir::Constant;
+
+    /// Map an immediate operand.
+    fn map_immediate(&mut self, immediate: ir::Immediate) -> ir::Immediate;
+}
+
+impl<T: InstructionMapper> InstructionMapper for &mut T {
+    fn map_value(&mut self, value: Value) -> Value {
+        (**self).map_value(value)
+    }
+
+    fn map_value_list(&mut self, value_list: ValueList) -> ValueList {
+        (**self).map_value_list(value_list)
+    }
+
+    fn map_global_value(&mut self, global_value: ir::GlobalValue) -> ir::GlobalValue {
+        (**self).map_global_value(global_value)
+    }
+
+    fn map_jump_table(&mut self, jump_table: ir::JumpTable) -> ir::JumpTable {
+        (**self).map_jump_table(jump_table)
+    }
+
+    fn map_block_call(&mut self, block_call: BlockCall) -> BlockCall {
+        (**self).map_block_call(block_call)
+    }
+
+    fn map_func_ref(&mut self, func_ref: FuncRef) -> FuncRef {
+        (**self).map_func_ref(func_ref)
+    }
+
+    fn map_sig_ref(&mut self, sig_ref: SigRef) -> SigRef {
+        (**self).map_sig_ref(sig_ref)
+    }
+
+    fn map_stack_slot(&mut self, stack_slot: StackSlot) -> StackSlot {
+        (**self).map_stack_slot(stack_slot)
+    }
+
+    fn map_dynamic_stack_slot(
+        &mut self,
+        dynamic_stack_slot: ir::DynamicStackSlot,
+    ) -> ir::DynamicStackSlot {
+        (**self).map_dynamic_stack_slot(dynamic_stack_slot)
+    }
+
+    fn map_table(&mut self, table: ir::Table) -> ir::Table {
+        (**self).map_table(table)
+    }
+
+    fn map_constant(&mut self, constant: ir::Constant) -> ir::Constant {
+        (**self).map_constant(constant)
+    }
+
+    fn map_immediate(&mut self, immediate: ir::Immediate) -> ir::Immediate {
+        (**self).map_immediate(immediate)
+    }
+}
+
 /// Information about call instructions.
 pub enum CallInfo<'a> {
     /// This is not a call instruction.
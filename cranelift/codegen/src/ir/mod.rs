@@ -18,12 +18,14 @@ pub(crate) mod known_symbol;
 pub mod layout;
 pub(crate) mod libcall;
 mod memflags;
+mod profile;
 mod progpoint;
 mod sourceloc;
 pub mod stackslot;
 mod table;
 mod trapcode;
 pub mod types;
+mod user_stack_maps;
 
 #[cfg(feature = "enable-serde")]
 use serde_derive::{Deserialize, Serialize};
@@ -46,13 +48,14 @@ pub use crate::ir::extname::{ExternalName, UserExternalName, UserFuncName};
 pub use crate::ir::function::Function;
 pub use crate::ir::globalvalue::GlobalValueData;
 pub use crate::ir::instructions::{
-    BlockCall, InstructionData, Opcode, ValueList, ValueListPool, VariableArgs,
+    BlockCall, InstructionData, InstructionMapper, Opcode, ValueList, ValueListPool, VariableArgs,
 };
 pub use crate::ir::jumptable::JumpTableData;
 pub use crate::ir::known_symbol::KnownSymbol;
 pub use crate::ir::layout::Layout;
 pub use crate::ir::libcall::{get_probestack_funcref, LibCall};
 pub use crate::ir::memflags::{Endianness, MemFlags};
+pub use crate::ir::profile::FunctionProfile;
 pub use crate::ir::progpoint::ProgramPoint;
 pub use crate::ir::sourceloc::RelSourceLoc;
 pub use crate::ir::sourceloc::SourceLoc;
@@ -62,6 +65,7 @@ pub use crate::ir::stackslot::{
 pub use crate::ir::table::TableData;
 pub use crate::ir::trapcode::TrapCode;
 pub use crate::ir::types::Type;
+pub use crate::ir::user_stack_maps::{UserStackMap, UserStackMapEntry, UserStackMapEntryVec};
 
 use crate::entity::{entity_impl, PrimaryMap, SecondaryMap};
 
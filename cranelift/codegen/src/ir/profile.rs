@@ -0,0 +1,89 @@
+//! Execution profiles.
+//!
+//! A [`FunctionProfile`] records how often the blocks and control-flow edges
+//! of a function were executed, typically as measured by an instrumented
+//! build of the same function. It is attached to the function being compiled
+//! (see [`crate::Context::set_profile`]) and guides the layout of the machine
+//! code: the hottest successor of a block is placed right after it so that
+//! the common path falls through, and blocks that were never executed are
+//! moved to the end of the function alongside the blocks marked cold.
+//!
+//! Blocks and edges that the profile has no count for are laid out as if no
+//! profile was given. In particular, a block is only considered never
+//! executed if the profile explicitly records a count of zero for it.
+
+use crate::ir::Block;
+use crate::packed_option::ReservedValue;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+#[cfg(feature = "enable-serde")]
+use serde_derive::{Deserialize, Serialize};
+
+/// Execution counts of the blocks and control-flow edges of a function.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct FunctionProfile {
+    blocks: BTreeMap<Block, u64>,
+    edges: BTreeMap<(Block, Block), u64>,
+}
+
+impl FunctionProfile {
+    /// Create an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `block` was executed `count` times.
+    pub fn set_block_count(&mut self, block: Block, count: u64) {
+        self.blocks.insert(block, count);
+    }
+
+    /// Record that control transferred from `from` to its successor `to`
+    /// `count` times.
+    ///
+    /// If `from` branches to `to` along several edges, e.g. from a `br_table`,
+    /// `count` is the total over all of them.
+    pub fn set_edge_count(&mut self, from: Block, to: Block, count: u64) {
+        self.edges.insert((from, to), count);
+    }
+
+    /// The number of times `block` was executed, if known.
+    pub fn block_count(&self, block: Block) -> Option<u64> {
+        self.blocks.get(&block).copied()
+    }
+
+    /// The number of times control transferred from `from` to `to`, if known.
+    pub fn edge_count(&self, from: Block, to: Block) -> Option<u64> {
+        self.edges.get(&(from, to)).copied()
+    }
+
+    /// Is this profile empty?
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.edges.is_empty()
+    }
+
+    /// Update the profile after the instructions at the end of `block` have
+    /// been moved to the new block `new_block`, which takes over its outgoing
+    /// edges and its count.
+    pub(crate) fn split_block(&mut self, block: Block, new_block: Block) {
+        if let Some(count) = self.block_count(block) {
+            self.set_block_count(new_block, count);
+        }
+        let out_edges: Vec<_> = self
+            .edges
+            .range((block, Block::from_u32(0))..=(block, Block::reserved_value()))
+            .map(|(&(_, to), &count)| (to, count))
+            .collect();
+        for (to, count) in out_edges {
+            self.edges.remove(&(block, to));
+            self.set_edge_count(new_block, to, count);
+        }
+    }
+
+    /// Remove all counts from this profile.
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.edges.clear();
+    }
+}
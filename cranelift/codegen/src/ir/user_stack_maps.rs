@@ -0,0 +1,78 @@
+//! User-defined stack maps.
+//!
+//! Unlike the stack maps computed for reference-typed values (see
+//! `binemit::StackMap`), user stack maps are not inferred by the compiler.
+//! Instead, the producer of the CLIF (typically `cranelift-frontend`) spills
+//! the GC roots that are live across a call to stack slots and annotates the
+//! call with the slots holding them:
+//!
+//! ```text
+//! call fn0(v0), stack_map=[i32 @ ss0+0, i64 @ ss1+8]
+//! ```
+//!
+//! When the function is compiled, each annotated call produces a
+//! [`UserStackMap`] at its return address, recording the offsets of those
+//! stack slot locations relative to the stack pointer at the call.
+
+use crate::ir::{StackSlot, Type};
+use alloc::vec::Vec;
+use core::fmt;
+use smallvec::SmallVec;
+
+#[cfg(feature = "enable-serde")]
+use serde_derive::{Deserialize, Serialize};
+
+/// A vector of [`UserStackMapEntry`]s, as attached to a call instruction.
+pub type UserStackMapEntryVec = SmallVec<[UserStackMapEntry; 4]>;
+
+/// A location in a stack slot holding a live GC root of type `ty` at a call.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct UserStackMapEntry {
+    /// The type of the value stored in this location.
+    pub ty: Type,
+
+    /// The stack slot the value is stored in.
+    pub slot: StackSlot,
+
+    /// The offset of the value within the stack slot.
+    pub offset: u32,
+}
+
+impl fmt::Display for UserStackMapEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} @ {}+{}", self.ty, self.slot, self.offset)
+    }
+}
+
+/// The GC roots live at a call's return address, as offsets from the stack
+/// pointer at the call.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct UserStackMap {
+    /// The type and SP-relative offset of each root, sorted by offset.
+    entries: Vec<(Type, u32)>,
+}
+
+impl UserStackMap {
+    /// Create the stack map for `entries`, given the offsets of the function's
+    /// sized stack slots from the stack pointer at the call.
+    pub(crate) fn new(
+        entries: &[UserStackMapEntry],
+        slot_offset: impl Fn(StackSlot) -> u32,
+    ) -> Self {
+        let mut entries: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.ty, slot_offset(entry.slot) + entry.offset))
+            .collect();
+        entries.sort_unstable_by_key(|&(_, offset)| offset);
+        entries.dedup();
+        UserStackMap { entries }
+    }
+
+    /// The type and SP-relative offset of each live GC root, in order of
+    /// increasing offset.
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = (Type, u32)> + '_ {
+        self.entries.iter().copied()
+    }
+}
@@ -72,7 +72,7 @@ pub mod aarch64;
 pub mod riscv64;
 
 #[cfg(feature = "s390x")]
-mod s390x;
+pub(crate) mod s390x;
 
 pub mod unwind;
 
@@ -1977,6 +1977,8 @@ impl CbOp {
         // https://github.com/michaeljclark/riscv-meta/blob/master/opcodes
         match self {
             CbOp::CSrli | CbOp::CSrai | CbOp::CAndi => 0b100,
+            CbOp::CBeqz => 0b110,
+            CbOp::CBnez => 0b111,
         }
     }
 
@@ -1986,13 +1988,23 @@ impl CbOp {
             CbOp::CSrli => 0b00,
             CbOp::CSrai => 0b01,
             CbOp::CAndi => 0b10,
+            CbOp::CBeqz | CbOp::CBnez => unreachable!("c.beqz / c.bnez have no funct2 field"),
         }
     }
 
     pub fn op(&self) -> COpcodeSpace {
         // https://five-embeddev.com/riscv-isa-manual/latest/rvc-opcode-map.html#rvcopcodemap
         match self {
-            CbOp::CSrli | CbOp::CSrai | CbOp::CAndi => COpcodeSpace::C1,
+            CbOp::CSrli | CbOp::CSrai | CbOp::CAndi | CbOp::CBeqz | CbOp::CBnez => COpcodeSpace::C1,
+        }
+    }
+
+    /// The branch with the opposite condition, used when inverting branches.
+    pub fn inverse(&self) -> CbOp {
+        match self {
+            CbOp::CBeqz => CbOp::CBnez,
+            CbOp::CBnez => CbOp::CBeqz,
+            _ => unreachable!("{self:?} is not a branch"),
         }
     }
 }
@@ -454,6 +454,25 @@ impl MachInstEmit for Inst {
     }
 }
 
+/// Returns the `c.beqz`/`c.bnez` form of a conditional branch, if it compares
+/// a compressible register against zero.
+fn compressed_cond_branch(kind: &IntegerCompare) -> Option<(CbOp, Reg)> {
+    let op = match kind.kind {
+        IntCC::Equal => CbOp::CBeqz,
+        IntCC::NotEqual => CbOp::CBnez,
+        _ => return None,
+    };
+    let rs = match (kind.rs1, kind.rs2) {
+        (rs, zero) | (zero, rs) if zero == zero_reg() => rs,
+        _ => return None,
+    };
+    let compressible = rs
+        .to_real_reg()
+        .map(|r| r.hw_enc() >= 8 && r.hw_enc() < 16)
+        .unwrap_or(false);
+    compressible.then_some((op, rs))
+}
+
 impl Inst {
     /// Tries to emit an instruction as compressed, if we can't return false.
     fn try_emit_compressed(
@@ -482,6 +501,16 @@ impl Inst {
                 .unwrap_or(false)
         }
 
+        fn backward_label_in_range(
+            sink: &MachBuffer<Inst>,
+            label: MachLabel,
+            offset: CodeOffset,
+            kind: LabelUse,
+        ) -> bool {
+            let label_offset = sink.resolve_label_offset(label);
+            label_offset <= offset && offset - label_offset <= kind.max_neg_range()
+        }
+
         match *self {
             // C.ADD
             Inst::AluRRR {
@@ -544,6 +573,41 @@ impl Inst {
                 sink.put2(encode_cj_type(CjOp::CJ, Imm12::ZERO));
             }
 
+            // c.beqz / c.bnez
+            //
+            // Only comparisons of a compressible register against zero have
+            // a compressed form. The `not_taken` jump, if any, is emitted
+            // separately and may itself be compressed into a `c.j`.
+            //
+            // These only reach ±256 bytes, so we only emit them directly for
+            // backward branches whose target is already known to be in range.
+            // For forward branches the `MachBuffer` has to assume the worst
+            // case size for every instruction up to the target, and would end
+            // up emitting a veneer for nearly every one of them. Other
+            // branches are emitted in their full form, and the `MachBuffer`
+            // shrinks them once their target turns out to be in range.
+            Inst::CondBr {
+                taken: CondBrTarget::Label(label),
+                not_taken,
+                kind,
+            } if compressed_cond_branch(&kind).is_some()
+                && backward_label_in_range(sink, label, *start_off, LabelUse::RVCBranch) =>
+            {
+                let (op, rs) = compressed_cond_branch(&kind).unwrap();
+                let code = encode_cb_branch(op, rs, 0);
+                let code_inverse = encode_cb_branch(op.inverse(), rs, 0).to_le_bytes();
+                sink.use_label_at_offset(*start_off, label, LabelUse::RVCBranch);
+                sink.add_cond_branch(*start_off, *start_off + 2, label, &code_inverse);
+                sink.put2(code);
+
+                match not_taken {
+                    CondBrTarget::Label(label) => {
+                        Inst::gen_jump(label).emit(&[], sink, emit_info, state)
+                    }
+                    CondBrTarget::Fallthrough => {}
+                };
+            }
+
             // c.jr
             Inst::Jalr { rd, base, offset }
                 if rd.to_reg() == zero_reg() && base != zero_reg() && offset.as_i16() == 0 =>
@@ -1468,6 +1532,17 @@ impl Inst {
                         let code_inverse = kind.inverse().emit().to_le_bytes();
                         sink.use_label_at_offset(*start_off, label, LabelUse::B12);
                         sink.add_cond_branch(*start_off, *start_off + 4, label, &code_inverse);
+                        let short_form =
+                            compressed_cond_branch(&kind).filter(|_| emit_info.isa_flags.has_zca());
+                        if let Some((op, rs)) = short_form {
+                            let short = encode_cb_branch(op, rs, 0).to_le_bytes();
+                            let short_inverse = encode_cb_branch(op.inverse(), rs, 0).to_le_bytes();
+                            sink.set_last_branch_short_form(
+                                LabelUse::RVCBranch,
+                                &short,
+                                &short_inverse,
+                            );
+                        }
                         sink.put4(code);
                     }
                     CondBrTarget::Fallthrough => panic!("Cannot fallthrough in taken target"),
@@ -498,6 +498,35 @@ pub fn encode_cb_type(op: CbOp, rd: WritableReg, imm: Imm6) -> u16 {
     bits.try_into().unwrap()
 }
 
+// Encode a CB type branch instruction (`c.beqz` / `c.bnez`).
+//
+// The offset is a 9 bit signed immediate in multiples of 2.
+//
+// 0--1-2----------------6-7-------9-10-------12-13--------15
+// |op | off[7:6|2:1|5]  |  rs1   | off[8|4:3] |  funct3  |
+pub fn encode_cb_branch(op: CbOp, rs1: Reg, offset: i16) -> u16 {
+    debug_assert!(offset & 1 == 0);
+    let offset = offset as u32;
+
+    let mut bits = 0;
+    bits |= unsigned_field_width(op.op().bits(), 2);
+    bits |= encode_cb_branch_offset(offset);
+    bits |= reg_to_compressed_gpr_num(rs1) << 7;
+    bits |= unsigned_field_width(op.funct3(), 3) << 13;
+    bits.try_into().unwrap()
+}
+
+/// Scatters a CB type branch offset into its instruction bit positions.
+pub fn encode_cb_branch_offset(offset: u32) -> u32 {
+    let mut bits = 0;
+    bits |= ((offset >> 5) & 1) << 2;
+    bits |= ((offset >> 1) & 3) << 3;
+    bits |= ((offset >> 6) & 3) << 5;
+    bits |= ((offset >> 3) & 3) << 10;
+    bits |= ((offset >> 8) & 1) << 12;
+    bits
+}
+
 // Encode a CSS type instruction.
 //
 // The imm field is a 6 bit unsigned immediate.
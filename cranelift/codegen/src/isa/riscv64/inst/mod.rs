@@ -1916,6 +1916,11 @@ pub enum LabelUse {
 
     /// 11-bit PC-relative jump offset. Equivalent to the `RVC_JUMP` relocation
     RVCJump,
+
+    /// 9-bit PC-relative branch offset, used by `c.beqz` and `c.bnez`. The
+    /// offset is in multiples of 2, so the branch range is ±256 bytes.
+    /// Equivalent to the `RVC_BRANCH` relocation.
+    RVCBranch,
 }
 
 impl MachInstLabelUse for LabelUse {
@@ -1932,6 +1937,7 @@ impl MachInstLabelUse for LabelUse {
             }
             LabelUse::B12 => ((1 << 11) - 1) * 2,
             LabelUse::RVCJump => ((1 << 10) - 1) * 2,
+            LabelUse::RVCBranch => ((1 << 7) - 1) * 2,
         }
     }
 
@@ -1946,7 +1952,7 @@ impl MachInstLabelUse for LabelUse {
     /// Size of window into code needed to do the patch.
     fn patch_size(self) -> CodeOffset {
         match self {
-            LabelUse::RVCJump => 2,
+            LabelUse::RVCJump | LabelUse::RVCBranch => 2,
             LabelUse::Jal20 | LabelUse::B12 | LabelUse::PCRelHi20 | LabelUse::PCRelLo12I => 4,
             LabelUse::PCRel32 => 8,
         }
@@ -1973,7 +1979,7 @@ impl MachInstLabelUse for LabelUse {
     /// Is a veneer supported for this label reference type?
     fn supports_veneer(self) -> bool {
         match self {
-            Self::Jal20 | Self::B12 | Self::RVCJump => true,
+            Self::Jal20 | Self::B12 | Self::RVCJump | Self::RVCBranch => true,
             _ => false,
         }
     }
@@ -1981,7 +1987,7 @@ impl MachInstLabelUse for LabelUse {
     /// How large is the veneer, if supported?
     fn veneer_size(self) -> CodeOffset {
         match self {
-            Self::B12 | Self::Jal20 | Self::RVCJump => 8,
+            Self::B12 | Self::Jal20 | Self::RVCJump | Self::RVCBranch => 8,
             _ => unreachable!(),
         }
     }
@@ -2032,7 +2038,9 @@ impl LabelUse {
 
     fn patch_raw_offset(self, buffer: &mut [u8], offset: i64) {
         let insn = match self {
-            LabelUse::RVCJump => u16::from_le_bytes(buffer[..2].try_into().unwrap()) as u32,
+            LabelUse::RVCJump | LabelUse::RVCBranch => {
+                u16::from_le_bytes(buffer[..2].try_into().unwrap()) as u32
+            }
             _ => u32::from_le_bytes(buffer[..4].try_into().unwrap()),
         };
 
@@ -2113,6 +2121,16 @@ impl LabelUse {
                     Imm12::from_i16(i16::try_from(offset).unwrap()),
                 )));
             }
+            LabelUse::RVCBranch => {
+                debug_assert!(offset & 1 == 0);
+
+                // Keep the opcode, condition and register, and replace the
+                // offset bits. The branch may have been inverted by the
+                // `MachBuffer`, so the condition is not known here.
+                let offset_mask = encode_cb_branch_offset(u32::MAX);
+                let insn = (insn & !offset_mask) | encode_cb_branch_offset(offset as u32);
+                buffer[0..2].clone_from_slice(&u16::to_le_bytes(insn as u16));
+            }
         }
     }
 }
@@ -2127,5 +2145,7 @@ mod test {
         assert!(LabelUse::PCRel32.max_pos_range() == (Inst::imm_max() as CodeOffset));
         assert!(LabelUse::PCRel32.max_neg_range() == (Inst::imm_min().abs() as CodeOffset));
         assert!(LabelUse::B12.max_pos_range() == ((1 << 11) - 1) * 2);
+        assert!(LabelUse::RVCBranch.max_pos_range() == 254);
+        assert!(LabelUse::RVCBranch.max_neg_range() == 256);
     }
 }
@@ -200,6 +200,13 @@ impl fmt::Display for S390xBackend {
     }
 }
 
+/// The source position of each ISLE lowering rule and how many times it has
+/// fired; see [`crate::isle_coverage`].
+#[cfg(feature = "isle-coverage")]
+pub(crate) fn isle_rule_coverage() -> impl Iterator<Item = (&'static str, usize)> {
+    lower::isle::generated_code::rule_coverage()
+}
+
 /// Create a new `isa::Builder`.
 pub fn isa_builder(triple: Triple) -> IsaBuilder {
     assert!(triple.architecture == Architecture::S390x);
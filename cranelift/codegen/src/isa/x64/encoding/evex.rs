@@ -438,7 +438,7 @@ impl EvexRoundingControl {
 
 /// Defines the EVEX masking behavior; masking support is described in section 2.6.4 of the Intel
 /// Software Development Manual, volume 2A.
-#[allow(dead_code, missing_docs)] // Masking is not yet used.
+#[allow(dead_code, missing_docs)] // Zeroing-masking is not yet used.
 pub enum EvexMasking {
     None,
     Merging { k: u8 },
@@ -1832,14 +1832,31 @@ impl Avx512Opcode {
         match self {
             Avx512Opcode::Vcvtudq2ps
             | Avx512Opcode::Vpabsq
+            | Avx512Opcode::Vpblendmd
+            | Avx512Opcode::Vpblendmq
+            | Avx512Opcode::Vpcmpd
+            | Avx512Opcode::Vpcmpq
+            | Avx512Opcode::Vpcmpud
+            | Avx512Opcode::Vpcmpuq
+            | Avx512Opcode::Vpmaxsq
+            | Avx512Opcode::Vpmaxuq
+            | Avx512Opcode::Vpminsq
+            | Avx512Opcode::Vpminuq
             | Avx512Opcode::Vpsraq
-            | Avx512Opcode::VpsraqImm => {
+            | Avx512Opcode::VpsraqImm
+            | Avx512Opcode::Vpternlogq => {
                 smallvec![InstructionSet::AVX512F, InstructionSet::AVX512VL]
             }
             Avx512Opcode::Vpermi2b => {
                 smallvec![InstructionSet::AVX512VL, InstructionSet::AVX512VBMI]
             }
-            Avx512Opcode::Vpmullq => smallvec![InstructionSet::AVX512VL, InstructionSet::AVX512DQ],
+            Avx512Opcode::Vcvtqq2pd
+            | Avx512Opcode::Vcvttpd2qq
+            | Avx512Opcode::Vcvttpd2uqq
+            | Avx512Opcode::Vcvtuqq2pd
+            | Avx512Opcode::Vpmullq => {
+                smallvec![InstructionSet::AVX512VL, InstructionSet::AVX512DQ]
+            }
             Avx512Opcode::Vpopcntb => {
                 smallvec![InstructionSet::AVX512VL, InstructionSet::AVX512BITALG]
             }
@@ -1857,7 +1874,9 @@ impl Avx512Opcode {
         use Avx512TupleType::*;
 
         match self {
-            Vcvtudq2ps | Vpabsq | Vpmullq | VpsraqImm => Full,
+            Vcvtqq2pd | Vcvttpd2qq | Vcvttpd2uqq | Vcvtudq2ps | Vcvtuqq2pd | Vpabsq | Vpblendmd
+            | Vpblendmq | Vpcmpd | Vpcmpq | Vpcmpud | Vpcmpuq | Vpmaxsq | Vpmaxuq | Vpminsq
+            | Vpminuq | Vpmullq | VpsraqImm | Vpternlogq => Full,
             Vpermi2b | Vpopcntb => FullMem,
             Vpsraq => Mem128,
         }
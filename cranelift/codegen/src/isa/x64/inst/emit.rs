@@ -3,7 +3,9 @@ use crate::ir;
 use crate::ir::immediates::{Ieee32, Ieee64};
 use crate::ir::TrapCode;
 use crate::ir::{KnownSymbol, LibCall, MemFlags};
-use crate::isa::x64::encoding::evex::{EvexInstruction, EvexVectorLength, RegisterOrAmode};
+use crate::isa::x64::encoding::evex::{
+    EvexInstruction, EvexMasking, EvexVectorLength, RegisterOrAmode,
+};
 use crate::isa::x64::encoding::rex::{
     emit_simm, emit_std_enc_enc, emit_std_enc_mem, emit_std_reg_mem, emit_std_reg_reg, int_reg_enc,
     low8_will_sign_extend_to_32, low8_will_sign_extend_to_64, reg_enc, LegacyPrefixes, OpcodeMap,
@@ -2053,7 +2055,11 @@ pub(crate) fn emit(
             };
 
             let (prefix, map, w, opcode) = match op {
+                Avx512Opcode::Vcvtqq2pd => (LegacyPrefixes::_F3, OpcodeMap::_0F, true, 0xe6),
+                Avx512Opcode::Vcvttpd2qq => (LegacyPrefixes::_66, OpcodeMap::_0F, true, 0x7a),
+                Avx512Opcode::Vcvttpd2uqq => (LegacyPrefixes::_66, OpcodeMap::_0F, true, 0x78),
                 Avx512Opcode::Vcvtudq2ps => (LegacyPrefixes::_F2, OpcodeMap::_0F, false, 0x7a),
+                Avx512Opcode::Vcvtuqq2pd => (LegacyPrefixes::_F3, OpcodeMap::_0F, true, 0x7a),
                 Avx512Opcode::Vpabsq => (LegacyPrefixes::_66, OpcodeMap::_0F38, true, 0x1f),
                 Avx512Opcode::Vpopcntb => (LegacyPrefixes::_66, OpcodeMap::_0F38, false, 0x54),
                 _ => unimplemented!("Opcode {:?} not implemented", op),
@@ -2926,6 +2932,10 @@ pub(crate) fn emit(
 
             let (w, opcode, map) = match op {
                 Avx512Opcode::Vpermi2b => (false, 0x75, OpcodeMap::_0F38),
+                Avx512Opcode::Vpmaxsq => (true, 0x3D, OpcodeMap::_0F38),
+                Avx512Opcode::Vpmaxuq => (true, 0x3F, OpcodeMap::_0F38),
+                Avx512Opcode::Vpminsq => (true, 0x39, OpcodeMap::_0F38),
+                Avx512Opcode::Vpminuq => (true, 0x3B, OpcodeMap::_0F38),
                 Avx512Opcode::Vpmullq => (true, 0x40, OpcodeMap::_0F38),
                 Avx512Opcode::Vpsraq => (true, 0xE2, OpcodeMap::_0F),
                 _ => unimplemented!("Opcode {:?} not implemented", op),
@@ -2943,6 +2953,101 @@ pub(crate) fn emit(
                 .encode(sink);
         }
 
+        Inst::XmmRmRImmEvex3 {
+            op,
+            src1,
+            src2,
+            src3,
+            imm,
+            dst,
+        } => {
+            let src1 = allocs.next(src1.to_reg());
+            let src2 = allocs.next(src2.to_reg());
+            let src3 = match src3.clone().to_reg_mem().with_allocs(allocs) {
+                RegMem::Reg { reg } => {
+                    RegisterOrAmode::Register(reg.to_real_reg().unwrap().hw_enc().into())
+                }
+                RegMem::Mem { addr } => RegisterOrAmode::Amode(addr.finalize(state, sink)),
+            };
+            let dst = allocs.next(dst.to_reg().to_reg());
+            debug_assert_eq!(src1, dst);
+
+            let (w, opcode, map) = match op {
+                Avx512Opcode::Vpternlogq => (true, 0x25, OpcodeMap::_0F3A),
+                _ => unimplemented!("Opcode {:?} not implemented", op),
+            };
+            EvexInstruction::new()
+                .length(EvexVectorLength::V128)
+                .prefix(LegacyPrefixes::_66)
+                .map(map)
+                .w(w)
+                .opcode(opcode)
+                .tuple_type(op.tuple_type())
+                .reg(dst.to_real_reg().unwrap().hw_enc())
+                .vvvvv(src2.to_real_reg().unwrap().hw_enc())
+                .rm(src3)
+                .imm(*imm)
+                .encode(sink);
+        }
+
+        Inst::XmmCmpBlendEvex {
+            cmp,
+            imm,
+            lhs,
+            rhs,
+            blend,
+            if_true,
+            if_false,
+            dst,
+        } => {
+            let lhs = allocs.next(lhs.to_reg());
+            let rhs = allocs.next(rhs.to_reg());
+            let if_true = allocs.next(if_true.to_reg());
+            let if_false = allocs.next(if_false.to_reg());
+            let dst = allocs.next(dst.to_reg().to_reg());
+
+            // Nothing else uses the mask registers, so `k1` is free here.
+            let k = 1;
+
+            let (w, opcode) = match cmp {
+                Avx512Opcode::Vpcmpd => (false, 0x1f),
+                Avx512Opcode::Vpcmpq => (true, 0x1f),
+                Avx512Opcode::Vpcmpud => (false, 0x1e),
+                Avx512Opcode::Vpcmpuq => (true, 0x1e),
+                _ => unimplemented!("Opcode {:?} not implemented", cmp),
+            };
+            EvexInstruction::new()
+                .length(EvexVectorLength::V128)
+                .prefix(LegacyPrefixes::_66)
+                .map(OpcodeMap::_0F3A)
+                .w(w)
+                .opcode(opcode)
+                .tuple_type(cmp.tuple_type())
+                .reg(k)
+                .vvvvv(lhs.to_real_reg().unwrap().hw_enc())
+                .rm(rhs.to_real_reg().unwrap().hw_enc())
+                .imm(*imm)
+                .encode(sink);
+
+            let w = match blend {
+                Avx512Opcode::Vpblendmd => false,
+                Avx512Opcode::Vpblendmq => true,
+                _ => unimplemented!("Opcode {:?} not implemented", blend),
+            };
+            EvexInstruction::new()
+                .length(EvexVectorLength::V128)
+                .prefix(LegacyPrefixes::_66)
+                .map(OpcodeMap::_0F38)
+                .w(w)
+                .opcode(0x64)
+                .tuple_type(blend.tuple_type())
+                .mask(EvexMasking::Merging { k })
+                .reg(dst.to_real_reg().unwrap().hw_enc())
+                .vvvvv(if_false.to_real_reg().unwrap().hw_enc())
+                .rm(if_true.to_real_reg().unwrap().hw_enc())
+                .encode(sink);
+        }
+
         Inst::XmmMinMaxSeq {
             size,
             is_min,
@@ -4242,6 +4347,79 @@ pub(crate) fn emit(
             sink.put4(0); // offset
         }
 
+        Inst::StackSwitchBasic {
+            store_context_ptr,
+            load_context_ptr,
+            in_payload0,
+            out_payload0,
+        } => {
+            let store_context_ptr = allocs.next(store_context_ptr.to_reg());
+            let load_context_ptr = allocs.next(load_context_ptr.to_reg());
+            let in_payload0 = allocs.next(in_payload0.to_reg());
+            let out_payload0 = allocs.next(out_payload0.to_reg().to_reg());
+            debug_assert_eq!(store_context_ptr, regs::rsi());
+            debug_assert_eq!(load_context_ptr, regs::rdx());
+            debug_assert_eq!(in_payload0, regs::rdi());
+            debug_assert_eq!(out_payload0, regs::rdi());
+
+            // Every register is clobbered by this instruction, so any that
+            // isn't an operand can serve as a temporary.
+            let tmp1 = Writable::from_reg(regs::r10());
+            let tmp2 = Writable::from_reg(regs::r11());
+
+            // The layout of a control context: the stack pointer, the frame
+            // pointer and the address to resume at.
+            let rsp_offset = 0;
+            let rbp_offset = 8;
+            let pc_offset = 16;
+
+            // Exchange the stack and frame pointers. The new value is loaded
+            // before the old one is stored since both contexts may be the
+            // same.
+            for (offset, reg) in [(rsp_offset, regs::rsp()), (rbp_offset, regs::rbp())] {
+                Inst::mov64_m_r(Amode::imm_reg(offset, load_context_ptr), tmp1).emit(
+                    &[],
+                    sink,
+                    info,
+                    state,
+                );
+                Inst::mov_r_m(
+                    OperandSize::Size64,
+                    reg,
+                    Amode::imm_reg(offset, store_context_ptr),
+                )
+                .emit(&[], sink, info, state);
+                Inst::mov_r_r(OperandSize::Size64, tmp1.to_reg(), Writable::from_reg(reg)).emit(
+                    &[],
+                    sink,
+                    info,
+                    state,
+                );
+            }
+
+            // Whoever switches back to this stack resumes right after this
+            // instruction.
+            let resume = sink.get_label();
+            Inst::mov64_m_r(Amode::imm_reg(pc_offset, load_context_ptr), tmp1).emit(
+                &[],
+                sink,
+                info,
+                state,
+            );
+            Inst::lea(Amode::rip_relative(resume), tmp2).emit(&[], sink, info, state);
+            Inst::mov_r_m(
+                OperandSize::Size64,
+                tmp2.to_reg(),
+                Amode::imm_reg(pc_offset, store_context_ptr),
+            )
+            .emit(&[], sink, info, state);
+            Inst::JmpUnknown {
+                target: RegMem::reg(tmp1.to_reg()),
+            }
+            .emit(&[], sink, info, state);
+            sink.bind_label(resume, state.ctrl_plane_mut());
+        }
+
         Inst::Unwind { ref inst } => {
             sink.add_unwind(inst.clone());
         }
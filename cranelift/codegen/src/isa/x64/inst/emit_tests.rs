@@ -99,6 +99,26 @@ impl Inst {
         }
     }
 
+    fn xmm_rm_r_imm_evex3(
+        op: Avx512Opcode,
+        src2: Reg,
+        src3: RegMem,
+        imm: u8,
+        dst: Writable<Reg>,
+    ) -> Self {
+        src3.assert_regclass_is(RegClass::Float);
+        debug_assert!(src2.class() == RegClass::Float);
+        debug_assert!(dst.to_reg().class() == RegClass::Float);
+        Inst::XmmRmRImmEvex3 {
+            op,
+            src1: Xmm::new(dst.to_reg()).unwrap(),
+            src2: Xmm::new(src2).unwrap(),
+            src3: XmmMem::new(src3).unwrap(),
+            imm,
+            dst: WritableXmm::from_writable_reg(dst).unwrap(),
+        }
+    }
+
     // TODO Can be replaced by `Inst::move` (high-level) and `Inst::unary_rm_r` (low-level)
     fn xmm_mov(op: SseOpcode, src: RegMem, dst: Writable<Reg>) -> Inst {
         src.assert_regclass_is(RegClass::Float);
@@ -4598,6 +4618,84 @@ fn test_x64_emit() {
         "vpermi2b %xmm0, %xmm1, %xmm2",
     ));
 
+    insns.push((
+        Inst::xmm_rm_r_evex(Avx512Opcode::Vpmaxsq, xmm10, RegMem::reg(xmm14), w_xmm1),
+        "62D2AD083DCE",
+        "vpmaxsq %xmm10, %xmm14, %xmm1",
+    ));
+
+    insns.push((
+        Inst::xmm_rm_r_evex(Avx512Opcode::Vpmaxuq, xmm10, RegMem::reg(xmm14), w_xmm1),
+        "62D2AD083FCE",
+        "vpmaxuq %xmm10, %xmm14, %xmm1",
+    ));
+
+    insns.push((
+        Inst::xmm_rm_r_evex(Avx512Opcode::Vpminsq, xmm10, RegMem::reg(xmm14), w_xmm1),
+        "62D2AD0839CE",
+        "vpminsq %xmm10, %xmm14, %xmm1",
+    ));
+
+    insns.push((
+        Inst::xmm_rm_r_evex(Avx512Opcode::Vpminuq, xmm10, RegMem::reg(xmm14), w_xmm1),
+        "62D2AD083BCE",
+        "vpminuq %xmm10, %xmm14, %xmm1",
+    ));
+
+    insns.push((
+        Inst::xmm_rm_r_imm_evex3(
+            Avx512Opcode::Vpternlogq,
+            xmm10,
+            RegMem::reg(xmm14),
+            0xca,
+            w_xmm1,
+        ),
+        "62D3AD0825CECA",
+        "vpternlogq $202, %xmm1, %xmm10, %xmm14, %xmm1",
+    ));
+
+    insns.push((
+        Inst::xmm_rm_r_imm_evex3(
+            Avx512Opcode::Vpternlogq,
+            xmm3,
+            RegMem::reg(xmm2),
+            0xca,
+            w_xmm4,
+        ),
+        "62F3E50825E2CA",
+        "vpternlogq $202, %xmm4, %xmm3, %xmm2, %xmm4",
+    ));
+
+    insns.push((
+        Inst::XmmCmpBlendEvex {
+            cmp: Avx512Opcode::Vpcmpq,
+            imm: 6,
+            lhs: Xmm::new(xmm10).unwrap(),
+            rhs: Xmm::new(xmm14).unwrap(),
+            blend: Avx512Opcode::Vpblendmq,
+            if_true: Xmm::new(xmm4).unwrap(),
+            if_false: Xmm::new(xmm5).unwrap(),
+            dst: WritableXmm::from_writable_reg(w_xmm1).unwrap(),
+        },
+        "62D3AD081FCE0662F2D50964CC",
+        "vpcmpq  $6, %xmm10, %xmm14, %k1; vpblendmq %xmm4, %xmm5, %xmm1{%k1}",
+    ));
+
+    insns.push((
+        Inst::XmmCmpBlendEvex {
+            cmp: Avx512Opcode::Vpcmpud,
+            imm: 5,
+            lhs: Xmm::new(xmm10).unwrap(),
+            rhs: Xmm::new(xmm14).unwrap(),
+            blend: Avx512Opcode::Vpblendmd,
+            if_true: Xmm::new(xmm14).unwrap(),
+            if_false: Xmm::new(xmm10).unwrap(),
+            dst: WritableXmm::from_writable_reg(w_xmm9).unwrap(),
+        },
+        "62D32D081ECE0562522D0964CE",
+        "vpcmpud $5, %xmm10, %xmm14, %k1; vpblendmd %xmm14, %xmm10, %xmm9{%k1}",
+    ));
+
     insns.push((
         Inst::xmm_rm_r(SseOpcode::Pmuludq, RegMem::reg(xmm8), w_xmm9),
         "66450FF4C8",
@@ -4944,6 +5042,30 @@ fn test_x64_emit() {
         "vcvtudq2ps %xmm2, %xmm8",
     ));
 
+    insns.push((
+        Inst::xmm_unary_rm_r_evex(Avx512Opcode::Vcvtqq2pd, RegMem::reg(xmm2), w_xmm8),
+        "6271FE08E6C2",
+        "vcvtqq2pd %xmm2, %xmm8",
+    ));
+
+    insns.push((
+        Inst::xmm_unary_rm_r_evex(Avx512Opcode::Vcvtuqq2pd, RegMem::reg(xmm2), w_xmm8),
+        "6271FE087AC2",
+        "vcvtuqq2pd %xmm2, %xmm8",
+    ));
+
+    insns.push((
+        Inst::xmm_unary_rm_r_evex(Avx512Opcode::Vcvttpd2qq, RegMem::reg(xmm2), w_xmm8),
+        "6271FD087AC2",
+        "vcvttpd2qq %xmm2, %xmm8",
+    ));
+
+    insns.push((
+        Inst::xmm_unary_rm_r_evex(Avx512Opcode::Vcvttpd2uqq, RegMem::reg(xmm2), w_xmm8),
+        "6271FD0878C2",
+        "vcvttpd2uqq %xmm2, %xmm8",
+    ));
+
     insns.push((
         Inst::xmm_unary_rm_r_evex(Avx512Opcode::Vpopcntb, RegMem::reg(xmm2), w_xmm8),
         "62727D0854C2",
@@ -156,6 +156,7 @@ impl Inst {
             | Inst::ElfTlsGetAddr { .. }
             | Inst::MachOTlsGetAddr { .. }
             | Inst::CoffTlsGetAddr { .. }
+            | Inst::StackSwitchBasic { .. }
             | Inst::Unwind { .. }
             | Inst::DummyUse { .. }
             | Inst::AluConstOp { .. } => smallvec![],
@@ -184,8 +185,12 @@ impl Inst {
             Inst::XmmUnaryRmREvex { op, .. }
             | Inst::XmmRmREvex { op, .. }
             | Inst::XmmRmREvex3 { op, .. }
+            | Inst::XmmRmRImmEvex3 { op, .. }
             | Inst::XmmUnaryRmRImmEvex { op, .. } => op.available_from(),
 
+            // The compare and the blend need the same extensions.
+            Inst::XmmCmpBlendEvex { cmp, .. } => cmp.available_from(),
+
             Inst::XmmRmiRVex { op, .. }
             | Inst::XmmRmRVex3 { op, .. }
             | Inst::XmmRmRImmVex { op, .. }
@@ -1176,6 +1181,45 @@ impl PrettyPrint for Inst {
                 format!("{op} {src1}, {src2}, {src3}, {dst}")
             }
 
+            Inst::XmmRmRImmEvex3 {
+                op,
+                src1,
+                src2,
+                src3,
+                imm,
+                dst,
+                ..
+            } => {
+                let src1 = pretty_print_reg(src1.to_reg(), 8, allocs);
+                let src2 = pretty_print_reg(src2.to_reg(), 8, allocs);
+                let src3 = src3.pretty_print(8, allocs);
+                let dst = pretty_print_reg(dst.to_reg().to_reg(), 8, allocs);
+                let op = ljustify(op.to_string());
+                format!("{op} ${imm}, {src1}, {src2}, {src3}, {dst}")
+            }
+
+            Inst::XmmCmpBlendEvex {
+                cmp,
+                imm,
+                lhs,
+                rhs,
+                blend,
+                if_true,
+                if_false,
+                dst,
+            } => {
+                let lhs = pretty_print_reg(lhs.to_reg(), 8, allocs);
+                let rhs = pretty_print_reg(rhs.to_reg(), 8, allocs);
+                let if_true = pretty_print_reg(if_true.to_reg(), 8, allocs);
+                let if_false = pretty_print_reg(if_false.to_reg(), 8, allocs);
+                let dst = pretty_print_reg(dst.to_reg().to_reg(), 8, allocs);
+                let cmp = ljustify(cmp.to_string());
+                let blend = ljustify(blend.to_string());
+                format!(
+                    "{cmp} ${imm}, {lhs}, {rhs}, %k1; {blend} {if_true}, {if_false}, {dst}{{%k1}}"
+                )
+            }
+
             Inst::XmmMinMaxSeq {
                 lhs,
                 rhs,
@@ -1867,6 +1911,19 @@ impl PrettyPrint for Inst {
                 s
             }
 
+            Inst::StackSwitchBasic {
+                store_context_ptr,
+                load_context_ptr,
+                in_payload0,
+                out_payload0,
+            } => {
+                let store_context_ptr = pretty_print_reg(store_context_ptr.to_reg(), 8, allocs);
+                let load_context_ptr = pretty_print_reg(load_context_ptr.to_reg(), 8, allocs);
+                let in_payload0 = pretty_print_reg(in_payload0.to_reg(), 8, allocs);
+                let out_payload0 = pretty_print_reg(out_payload0.to_reg().to_reg(), 8, allocs);
+                format!("{out_payload0} = stack_switch_basic {store_context_ptr}, {load_context_ptr}, {in_payload0}")
+            }
+
             Inst::Unwind { inst } => format!("unwind {inst:?}"),
 
             Inst::DummyUse { reg } => {
@@ -2135,6 +2192,34 @@ fn x64_get_operands<F: Fn(VReg) -> VReg>(inst: &Inst, collector: &mut OperandCol
             src3.get_operands(collector);
             collector.reg_reuse_def(dst.to_writable_reg(), 0); // Reuse `src1`.
         }
+        Inst::XmmRmRImmEvex3 {
+            op,
+            src1,
+            src2,
+            src3,
+            dst,
+            ..
+        } => {
+            assert_eq!(*op, Avx512Opcode::Vpternlogq);
+            collector.reg_use(src1.to_reg());
+            collector.reg_use(src2.to_reg());
+            src3.get_operands(collector);
+            collector.reg_reuse_def(dst.to_writable_reg(), 0); // Reuse `src1`.
+        }
+        Inst::XmmCmpBlendEvex {
+            lhs,
+            rhs,
+            if_true,
+            if_false,
+            dst,
+            ..
+        } => {
+            collector.reg_use(lhs.to_reg());
+            collector.reg_use(rhs.to_reg());
+            collector.reg_use(if_true.to_reg());
+            collector.reg_use(if_false.to_reg());
+            collector.reg_def(dst.to_writable_reg());
+        }
         Inst::XmmRmRImm {
             src1, src2, dst, ..
         } => {
@@ -2497,6 +2582,26 @@ fn x64_get_operands<F: Fn(VReg) -> VReg>(inst: &Inst, collector: &mut OperandCol
             collector.reg_fixed_def(tmp.to_writable_reg(), regs::rcx());
         }
 
+        Inst::StackSwitchBasic {
+            store_context_ptr,
+            load_context_ptr,
+            in_payload0,
+            out_payload0,
+        } => {
+            // Values live across this instruction can't stay in registers,
+            // which regalloc copes with best when all operands are fixed.
+            collector.reg_fixed_use(store_context_ptr.to_reg(), regs::rsi());
+            collector.reg_fixed_use(load_context_ptr.to_reg(), regs::rdx());
+            // The payload is passed in `rdi` in both directions.
+            collector.reg_fixed_use(in_payload0.to_reg(), regs::rdi());
+            collector.reg_fixed_def(out_payload0.to_writable_reg(), regs::rdi());
+            // Whatever stack is switched to may use any register, so all of
+            // them are clobbered.
+            let mut clobbers = X64ABIMachineSpec::get_regs_clobbered_by_call(CallConv::Tail);
+            clobbers.remove(regs::gpr_preg(regs::ENC_RDI));
+            collector.reg_clobbers(clobbers);
+        }
+
         Inst::Unwind { .. } => {}
 
         Inst::DummyUse { reg } => {
@@ -0,0 +1,27 @@
+//! Coverage of the ISLE rules used for lowering and mid-end optimizations.
+//!
+//! When this crate is built with the `isle-coverage` feature, the code
+//! generated from the ISLE rules counts how many times each rule fires. The
+//! counts accumulate over everything the process compiles, so they can be
+//! used to find rules that are never exercised by the tests or by fuzzing.
+
+use alloc::vec::Vec;
+
+/// Get the source position of every ISLE rule in this build, as
+/// `file:line:column`, along with how many times it has fired so far.
+///
+/// Rules from files which are shared between backends, such as the prelude,
+/// are listed once for each backend.
+pub fn rule_counts() -> Vec<(&'static str, usize)> {
+    let mut counts = Vec::new();
+    counts.extend(crate::opts::generated_code::rule_coverage());
+    #[cfg(feature = "x86")]
+    counts.extend(crate::isa::x64::isle_rule_coverage());
+    #[cfg(feature = "arm64")]
+    counts.extend(crate::isa::aarch64::isle_rule_coverage());
+    #[cfg(feature = "riscv64")]
+    counts.extend(crate::isa::riscv64::isle_rule_coverage());
+    #[cfg(feature = "s390x")]
+    counts.extend(crate::isa::s390x::isle_rule_coverage());
+    counts
+}
@@ -25,6 +25,7 @@ use hashbrown::{hash_map, HashMap, HashSet};
 use std::collections::{hash_map, HashMap, HashSet};
 
 pub use crate::context::Context;
+pub use crate::inline::{FunctionStore, InlineOptions};
 pub use crate::value_label::{LabelValueLoc, ValueLabelsRanges, ValueLocRange};
 pub use crate::verifier::verify_function;
 pub use crate::write::write_function;
@@ -57,7 +58,7 @@ pub mod write;
 pub use crate::entity::packed_option;
 pub use crate::machinst::buffer::{
     FinalizedMachReloc, FinalizedRelocTarget, MachCallSite, MachSrcLoc, MachStackMap,
-    MachTextSectionBuilder, MachTrap,
+    MachTextSectionBuilder, MachTrap, MachUserStackMap,
 };
 pub use crate::machinst::{
     CompiledCode, Final, MachBuffer, MachBufferFinalized, MachInst, MachInstEmit,
@@ -73,6 +74,7 @@ mod ctxhash;
 mod dce;
 mod egraph;
 mod fx;
+mod inline;
 mod inst_predicates;
 mod isle_prelude;
 mod iterators;
@@ -94,6 +96,9 @@ pub use crate::result::{CodegenError, CodegenResult, CompileError};
 #[cfg(feature = "incremental-cache")]
 pub mod incremental_cache;
 
+#[cfg(feature = "isle-coverage")]
+pub mod isle_coverage;
+
 /// Even when trace logging is disabled, the trace macro has a significant performance cost so we
 /// disable it by default.
 #[macro_export]
@@ -105,7 +105,10 @@ use crate::binemit::StackMap;
 use crate::entity::{PrimaryMap, SecondaryMap};
 use crate::fx::FxHashMap;
 use crate::ir::types::*;
-use crate::ir::{ArgumentExtension, ArgumentPurpose, DynamicStackSlot, Signature, StackSlot};
+use crate::ir::{
+    ArgumentExtension, ArgumentPurpose, DynamicStackSlot, Signature, StackSlot, UserStackMap,
+    UserStackMapEntry,
+};
 use crate::isa::TargetIsa;
 use crate::settings;
 use crate::settings::ProbestackStrategy;
@@ -1847,6 +1850,26 @@ impl<M: ABIMachineSpec> Callee<M> {
         StackMap::from_slice(&bits[..])
     }
 
+    /// Generate a user stack map for a call, given its entries and the
+    /// emission state prior to emission of the call.
+    ///
+    /// Returns the distance from the stack pointer at the call to the frame
+    /// pointer along with the stack map.
+    pub fn user_stack_map(
+        &self,
+        entries: &[UserStackMapEntry],
+        state: &<M::I as MachInstEmit>::State,
+    ) -> (u32, UserStackMap) {
+        let virtual_sp_offset = M::get_virtual_sp_offset_from_state(state);
+        let nominal_sp_to_fp = M::get_nominal_sp_to_fp(state);
+        assert!(virtual_sp_offset >= 0);
+        let span = (virtual_sp_offset + nominal_sp_to_fp) as u32;
+        let stack_map = UserStackMap::new(entries, |slot| {
+            virtual_sp_offset as u32 + self.sized_stackslots[slot]
+        });
+        (span, stack_map)
+    }
+
     /// Compute the final frame layout, post-regalloc.
     ///
     /// This must be called before gen_prologue or gen_epilogue.
@@ -81,13 +81,17 @@ pub struct BlockLoweringOrder {
     /// Ranges in `lowered_succ_indices` giving the successor lists for each lowered
     /// block. Indexed by lowering-order index (`BlockIndex`).
     lowered_succ_ranges: Vec<(Option<Inst>, std::ops::Range<usize>)>,
-    /// Cold blocks. These blocks are not reordered in the
-    /// `lowered_order` above; the lowered order must respect RPO
-    /// (uses after defs) in order for lowering to be
-    /// correct. Instead, this set is used to provide `is_cold()`,
-    /// which is used by VCode emission to sink the blocks at the last
-    /// moment (when we actually emit bytes into the MachBuffer).
-    cold_blocks: FxHashSet<BlockIndex>,
+    /// The order in which lowered blocks are emitted into the MachBuffer.
+    /// This is the lowered order with cold blocks sunk to the end, unless
+    /// the function has a profile with edge counts, in which case hot
+    /// successors are placed right after their predecessors.
+    ///
+    /// Cold blocks are not reordered in the `lowered_order` above; the
+    /// lowered order must respect RPO (uses after defs) in order for
+    /// lowering to be correct. Instead, they are only sunk here, which
+    /// VCode emission uses at the last moment (when we actually emit bytes
+    /// into the MachBuffer).
+    emission_order: Vec<BlockIndex>,
     /// Lowered blocks that are indirect branch targets.
     indirect_branch_targets: FxHashSet<BlockIndex>,
 }
@@ -248,7 +252,11 @@ impl BlockLoweringOrder {
                         lowered_succ_indices
                             .extend(block_succs[range].iter().map(|lb| lb_to_bindex[lb]));
 
-                        if f.layout.is_cold(block) {
+                        // Blocks which the profile says never execute are cold
+                        // too. The entry block is always emitted first.
+                        if f.layout.is_cold(block)
+                            || (ix != 0 && f.profile.block_count(block) == Some(0))
+                        {
                             cold_blocks.insert(bindex);
                         }
 
@@ -266,14 +274,18 @@ impl BlockLoweringOrder {
 
                     // Critical edges won't have successor information in block_succ_range, but
                     // they only have a single known successor to record anyway.
-                    &LoweredBlock::CriticalEdge { succ, .. } => {
+                    &LoweredBlock::CriticalEdge { pred, succ, .. } => {
                         let succ_index = lb_to_bindex[&LoweredBlock::Orig { block: succ }];
                         lowered_succ_indices.push(succ_index);
 
                         // Edges inherit indirect branch and cold block metadata from their
-                        // successor.
+                        // successor. They are also cold if the profile says they are never
+                        // taken.
 
-                        if f.layout.is_cold(succ) {
+                        if f.layout.is_cold(succ)
+                            || f.profile.block_count(succ) == Some(0)
+                            || f.profile.edge_count(pred, succ) == Some(0)
+                        {
                             cold_blocks.insert(bindex);
                         }
 
@@ -288,11 +300,20 @@ impl BlockLoweringOrder {
                 (opt_inst, start..end)
             }));
 
+        // Step 4: compute the order in which blocks are emitted.
+        let emission_order = compute_emission_order(
+            f,
+            &lowered_order,
+            &lowered_succ_indices,
+            &lowered_succ_ranges,
+            &cold_blocks,
+        );
+
         let result = BlockLoweringOrder {
             lowered_order,
             lowered_succ_indices,
             lowered_succ_ranges,
-            cold_blocks,
+            emission_order,
             indirect_branch_targets,
         };
 
@@ -311,9 +332,10 @@ impl BlockLoweringOrder {
         (opt_inst.clone(), &self.lowered_succ_indices[range.clone()])
     }
 
-    /// Determine whether the given lowered-block index is cold.
-    pub fn is_cold(&self, block: BlockIndex) -> bool {
-        self.cold_blocks.contains(&block)
+    /// Get the order in which blocks are emitted. This is a permutation of the
+    /// lowered order which starts with the entry block.
+    pub fn emission_order(&self) -> &[BlockIndex] {
+        &self.emission_order[..]
     }
 
     /// Determine whether the given lowered block index is an indirect branch
@@ -323,6 +345,97 @@ impl BlockLoweringOrder {
     }
 }
 
+/// Compute the order in which to emit the lowered blocks.
+///
+/// Without edge counts in the function's profile, this is the lowered order
+/// with cold blocks moved to the end. Otherwise blocks are first merged into
+/// chains which fall through from one block to the next, greedily taking the
+/// hottest edges first (as in Pettis and Hansen's "Profile Guided Code
+/// Positioning"). The chains are then emitted in the lowered order of their
+/// first blocks, followed by the cold blocks.
+fn compute_emission_order(
+    f: &Function,
+    lowered_order: &[LoweredBlock],
+    lowered_succ_indices: &[BlockIndex],
+    lowered_succ_ranges: &[(Option<Inst>, std::ops::Range<usize>)],
+    cold_blocks: &FxHashSet<BlockIndex>,
+) -> Vec<BlockIndex> {
+    let num_blocks = lowered_order.len();
+    let is_cold = |ix: usize| cold_blocks.contains(&BlockIndex::new(ix));
+
+    // `next[ix]` and `prev[ix]` link the blocks of a chain together, and
+    // `chain` is a union-find forest identifying the chain of each block.
+    let mut next: Vec<Option<usize>> = vec![None; num_blocks];
+    let mut prev: Vec<Option<usize>> = vec![None; num_blocks];
+    let mut chain: Vec<usize> = (0..num_blocks).collect();
+    fn find(chain: &mut [usize], mut ix: usize) -> usize {
+        while chain[ix] != ix {
+            chain[ix] = chain[chain[ix]];
+            ix = chain[ix];
+        }
+        ix
+    }
+
+    let mut edges = vec![];
+    for (from, (_, range)) in lowered_succ_ranges.iter().enumerate() {
+        if is_cold(from) {
+            continue;
+        }
+        for &to in &lowered_succ_indices[range.clone()] {
+            let to = to.index();
+            // Nothing can be placed before the entry block.
+            if to == 0 || to == from || is_cold(to) {
+                continue;
+            }
+            let (pred, succ) = match (lowered_order[from], lowered_order[to]) {
+                (LoweredBlock::Orig { block: pred }, LoweredBlock::Orig { block: succ })
+                | (LoweredBlock::Orig { .. }, LoweredBlock::CriticalEdge { pred, succ, .. })
+                | (LoweredBlock::CriticalEdge { pred, succ, .. }, LoweredBlock::Orig { .. }) => {
+                    (pred, succ)
+                }
+                (LoweredBlock::CriticalEdge { .. }, LoweredBlock::CriticalEdge { .. }) => {
+                    unreachable!("critical edges are only adjacent to original blocks")
+                }
+            };
+            match f.profile.edge_count(pred, succ) {
+                Some(count) if count > 0 => edges.push((count, from, to)),
+                _ => {}
+            }
+        }
+    }
+
+    // Hottest edges first; ties are broken by the lowered order so that the
+    // result is deterministic.
+    edges.sort_unstable_by_key(|&(count, from, to)| (core::cmp::Reverse(count), from, to));
+    for (_, from, to) in edges {
+        if next[from].is_none() && prev[to].is_none() {
+            let (from_chain, to_chain) = (find(&mut chain, from), find(&mut chain, to));
+            if from_chain != to_chain {
+                next[from] = Some(to);
+                prev[to] = Some(from);
+                chain[to_chain] = from_chain;
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(num_blocks);
+    let mut cold = vec![];
+    let heads = (0..num_blocks).filter(|&ix| prev[ix].is_none());
+    for head in heads {
+        let mut ix = Some(head);
+        while let Some(block) = ix {
+            if is_cold(block) {
+                cold.push(BlockIndex::new(block));
+            } else {
+                order.push(BlockIndex::new(block));
+            }
+            ix = next[block];
+        }
+    }
+    order.extend(cold);
+    order
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -334,6 +447,15 @@ mod test {
     use crate::isa::CallConv;
 
     fn build_test_func(n_blocks: usize, edges: &[(usize, usize)]) -> BlockLoweringOrder {
+        build_test_func_with_profile(n_blocks, edges, &[], &[])
+    }
+
+    fn build_test_func_with_profile(
+        n_blocks: usize,
+        edges: &[(usize, usize)],
+        block_counts: &[(usize, u64)],
+        edge_counts: &[(usize, usize, u64)],
+    ) -> BlockLoweringOrder {
         assert!(n_blocks > 0);
 
         let name = UserFuncName::testcase("test0");
@@ -372,6 +494,13 @@ mod test {
             }
         }
 
+        for &(block, count) in block_counts {
+            func.profile.set_block_count(blocks[block], count);
+        }
+        for &(from, to, count) in edge_counts {
+            func.profile.set_edge_count(blocks[from], blocks[to], count);
+        }
+
         let mut cfg = ControlFlowGraph::new();
         cfg.compute(&func);
         let dom_tree = DominatorTree::with_function(&func, &cfg);
@@ -464,4 +593,66 @@ mod test {
         assert!(order.lowered_order[8].in_edge().is_none());
         assert!(order.lowered_order[8].out_edge().is_none());
     }
+
+    fn emitted_blocks(order: &BlockLoweringOrder) -> Vec<u32> {
+        order
+            .emission_order()
+            .iter()
+            .map(|&b| {
+                order.lowered_order[b.index()]
+                    .orig_block()
+                    .unwrap()
+                    .as_u32()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_blockorder_emission_without_profile() {
+        let order = build_test_func(4, &[(0, 1), (0, 2), (1, 3), (2, 3)]);
+        assert_eq!(emitted_blocks(&order), vec![0, 2, 1, 3]);
+    }
+
+    #[test]
+    fn test_blockorder_profile_hot_path() {
+        // 0 -> 1 -> 3 is the hot path, so it should fall through.
+        let order = build_test_func_with_profile(
+            4,
+            &[(0, 1), (0, 2), (1, 3), (2, 3)],
+            &[],
+            &[(0, 1, 100), (0, 2, 1), (1, 3, 100), (2, 3, 1)],
+        );
+        assert_eq!(emitted_blocks(&order), vec![0, 1, 3, 2]);
+    }
+
+    #[test]
+    fn test_blockorder_profile_never_executed() {
+        // Block 2 never executed, so it is cold and sunk to the end.
+        let order = build_test_func_with_profile(
+            4,
+            &[(0, 1), (0, 2), (1, 3), (2, 3)],
+            &[(0, 1), (1, 1), (2, 0), (3, 1)],
+            &[],
+        );
+        assert_eq!(emitted_blocks(&order), vec![0, 1, 3, 2]);
+    }
+
+    #[test]
+    fn test_blockorder_profile_loop() {
+        //    0
+        //    |
+        //    1 <-.
+        //   / \  |
+        //  3   2-'
+        //
+        // The loop back edge is hot, so the loop body follows the header
+        // and the exit is placed after it.
+        let order = build_test_func_with_profile(
+            4,
+            &[(0, 1), (1, 2), (1, 3), (2, 1)],
+            &[],
+            &[(0, 1, 1), (1, 2, 1000), (1, 3, 1), (2, 1, 1000)],
+        );
+        assert_eq!(emitted_blocks(&order), vec![0, 1, 2, 3]);
+    }
 }
@@ -172,7 +172,7 @@
 
 use crate::binemit::{Addend, CodeOffset, Reloc, StackMap};
 use crate::ir::function::FunctionParameters;
-use crate::ir::{ExternalName, Opcode, RelSourceLoc, SourceLoc, TrapCode};
+use crate::ir::{ExternalName, Opcode, RelSourceLoc, SourceLoc, TrapCode, UserStackMap};
 use crate::isa::unwind::UnwindInst;
 use crate::machinst::{
     BlockIndex, MachInstLabelUse, TextSectionBuilder, VCodeConstant, VCodeConstants, VCodeInst,
@@ -251,6 +251,8 @@ pub struct MachBuffer<I: VCodeInst> {
     srclocs: SmallVec<[MachSrcLoc<Stencil>; 64]>,
     /// Any stack maps referring to this code.
     stack_maps: SmallVec<[MachStackMap; 8]>,
+    /// Any user stack maps referring to this code.
+    user_stack_maps: SmallVec<[MachUserStackMap; 8]>,
     /// Any unwind info at a given location.
     unwind_info: SmallVec<[(CodeOffset, UnwindInst); 8]>,
     /// The current source location in progress (after `start_srcloc()` and
@@ -283,7 +285,7 @@ pub struct MachBuffer<I: VCodeInst> {
     fixup_records: BinaryHeap<MachLabelFixup<I>>,
     /// Latest branches, to facilitate in-place editing for better fallthrough
     /// behavior and empty-block removal.
-    latest_branches: SmallVec<[MachBranch; 4]>,
+    latest_branches: SmallVec<[MachBranch<I>; 4]>,
     /// All labels at the current offset (emission tail). This is lazily
     /// cleared: it is actually accurate as long as the current offset is
     /// `labels_at_tail_off`, but if `cur_offset()` has grown larger, it should
@@ -327,6 +329,7 @@ impl MachBufferFinalized<Stencil> {
                 .map(|srcloc| srcloc.apply_base_srcloc(base_srcloc))
                 .collect(),
             stack_maps: self.stack_maps,
+            user_stack_maps: self.user_stack_maps,
             unwind_info: self.unwind_info,
             alignment: self.alignment,
         }
@@ -355,6 +358,8 @@ pub struct MachBufferFinalized<T: CompilePhase> {
     pub(crate) srclocs: SmallVec<[T::MachSrcLocType; 64]>,
     /// Any stack maps referring to this code.
     pub(crate) stack_maps: SmallVec<[MachStackMap; 8]>,
+    /// Any user stack maps referring to this code.
+    pub(crate) user_stack_maps: SmallVec<[MachUserStackMap; 8]>,
     /// Any unwind info at a given location.
     pub unwind_info: SmallVec<[(CodeOffset, UnwindInst); 8]>,
     /// The requireed alignment of this buffer
@@ -422,6 +427,7 @@ impl<I: VCodeInst> MachBuffer<I> {
             call_sites: SmallVec::new(),
             srclocs: SmallVec::new(),
             stack_maps: SmallVec::new(),
+            user_stack_maps: SmallVec::new(),
             unwind_info: SmallVec::new(),
             cur_srcloc: None,
             label_offsets: SmallVec::new(),
@@ -718,6 +724,7 @@ impl<I: VCodeInst> MachBuffer<I> {
             target,
             fixup,
             inverted: None,
+            short: None,
             labels_at_this_branch: self.labels_at_tail.clone(),
         });
 
@@ -751,6 +758,7 @@ impl<I: VCodeInst> MachBuffer<I> {
             target,
             fixup,
             inverted,
+            short: None,
             labels_at_this_branch: self.labels_at_tail.clone(),
         });
 
@@ -758,6 +766,26 @@ impl<I: VCodeInst> MachBuffer<I> {
         // branch list is cloned from list of labels at current tail.
     }
 
+    /// Provide a shorter encoding for the conditional branch most recently
+    /// added with `add_cond_branch()`, along with its inverted form and the
+    /// label-use kind that patches it. The short form's label use must be at
+    /// the start of the branch.
+    ///
+    /// If the branch is still at the tail of the buffer once its target
+    /// resolves to an earlier offset within the short form's negative range,
+    /// `optimize_branches()` replaces the branch with its short form.
+    pub fn set_last_branch_short_form(&mut self, kind: I::LabelUse, code: &[u8], inverted: &[u8]) {
+        debug_assert_eq!(code.len(), inverted.len());
+        let b = self.latest_branches.last_mut().unwrap();
+        assert!(b.is_cond());
+        debug_assert!(code.len() < (b.end - b.start) as usize);
+        b.short = Some(MachBranchShortForm {
+            kind,
+            code: SmallVec::from(code),
+            inverted: SmallVec::from(inverted),
+        });
+    }
+
     fn truncate_last_branch(&mut self) {
         self.lazily_clear_labels_at_tail();
         // Invariants hold at this point.
@@ -852,6 +880,60 @@ impl<I: VCodeInst> MachBuffer<I> {
         //   fixup record referring to that last branch is removed.
     }
 
+    fn shrink_last_branch(&mut self) {
+        self.lazily_clear_labels_at_tail();
+        // Invariants hold at this point.
+
+        let cur_off = self.cur_offset();
+        let b = self.latest_branches.last_mut().unwrap();
+        assert!(b.end == cur_off);
+        let short = b.short.take().unwrap();
+
+        // Replace the branch's bytes with the short form, and retarget the
+        // branch's fixup to the short form's label-use kind. The target has
+        // already been resolved to an earlier offset in range, so the fixup
+        // will be patched in place at the next island and there is no
+        // forward deadline to track.
+        self.data.truncate(b.start as usize);
+        self.data.extend_from_slice(&short.code[..]);
+        let new_end = b.start + short.code.len() as CodeOffset;
+        let fixup = &mut self.pending_fixup_records[b.fixup];
+        fixup.offset = b.start;
+        fixup.kind = short.kind;
+        b.end = new_end;
+        b.inverted = Some(short.inverted);
+        while let Some(last_srcloc) = self.srclocs.last_mut() {
+            if last_srcloc.end <= new_end {
+                break;
+            }
+            if last_srcloc.start < new_end {
+                last_srcloc.end = new_end;
+                break;
+            }
+            self.srclocs.pop();
+        }
+
+        // Labels at the tail move back to the new, shorter end of the branch.
+        // Labels at the start of the branch are unaffected.
+        let cur_off = self.cur_offset();
+        self.labels_at_tail_off = cur_off;
+        for &l in &self.labels_at_tail {
+            self.label_offsets[l.0 as usize] = cur_off;
+        }
+
+        trace!(
+            "shrink_last_branch: shrunk {:?}; off now {}",
+            self.latest_branches.last().unwrap(),
+            cur_off
+        );
+
+        // Post-invariant: the branch remains the last entry in
+        // latest_branches, ends at the (new) tail of the buffer, and keeps its
+        // labels_at_this_branch list, since its start did not move. All labels
+        // at the old tail are in labels_at_tail, which is now resolved to the
+        // new tail.
+    }
+
     /// Performs various optimizations on branches pointing at the current label.
     pub fn optimize_branches(&mut self, ctrl_plane: &mut ControlPlane) {
         if ctrl_plane.get_decision() {
@@ -1107,6 +1189,9 @@ impl<I: VCodeInst> MachBuffer<I> {
                         // inverted branch, in case we later edit this branch
                         // again.
                         prev_b.inverted = Some(not_inverted);
+                        if let Some(short) = &mut prev_b.short {
+                            mem::swap(&mut short.code, &mut short.inverted);
+                        }
                         self.pending_fixup_records[prev_b.fixup].label = target;
                         trace!(" -> reassigning target of condbr to {:?}", target);
                         prev_b.target = target;
@@ -1116,6 +1201,24 @@ impl<I: VCodeInst> MachBuffer<I> {
                 }
             }
 
+            // If latest is a conditional branch with a short form, and its
+            // target now resolves to an earlier offset within the short
+            // form's range, then replace it with the short form.
+            //
+            // - Preserves execution semantics: the short form branches to the
+            //   same target under the same condition.
+            //
+            // - Post-invariant: ensured by shrink_last_branch().
+            let b = self.latest_branches.last().unwrap();
+            if let Some(short) = &b.short {
+                let target_off = self.resolve_label_offset(b.target);
+                if target_off <= b.start && b.start - target_off <= short.kind.max_neg_range() {
+                    trace!(" -> cond branch target is in range of short form; shrinking");
+                    self.shrink_last_branch();
+                    continue;
+                }
+            }
+
             // If we couldn't do anything with the last branch, then break.
             break;
         }
@@ -1481,6 +1584,7 @@ impl<I: VCodeInst> MachBuffer<I> {
             call_sites: self.call_sites,
             srclocs,
             stack_maps: self.stack_maps,
+            user_stack_maps: self.user_stack_maps,
             unwind_info: self.unwind_info,
             alignment,
         }
@@ -1606,6 +1710,21 @@ impl<I: VCodeInst> MachBuffer<I> {
             stack_map,
         });
     }
+
+    /// Add a user stack map for the call whose return address is at the
+    /// current offset.
+    ///
+    /// `span` is the distance from the stack pointer at the call, which the
+    /// offsets in `stack_map` are relative to, to the frame pointer.
+    pub fn add_user_stack_map(&mut self, span: u32, stack_map: UserStackMap) {
+        let offset = self.cur_offset();
+        trace!("Adding user stack map at offset {offset:#x}");
+        self.user_stack_maps.push(MachUserStackMap {
+            offset,
+            span,
+            stack_map,
+        });
+    }
 }
 
 impl<T: CompilePhase> MachBufferFinalized<T> {
@@ -1661,6 +1780,12 @@ impl<T: CompilePhase> MachBufferFinalized<T> {
         &self.stack_maps[..]
     }
 
+    /// Get the user stack maps of the calls in this code, sorted by return
+    /// address.
+    pub fn user_stack_maps(&self) -> &[MachUserStackMap] {
+        &self.user_stack_maps[..]
+    }
+
     /// Get the list of call sites for this code.
     pub fn call_sites(&self) -> &[MachCallSite] {
         &self.call_sites[..]
@@ -1881,14 +2006,32 @@ pub struct MachStackMap {
     pub stack_map: StackMap,
 }
 
+/// Record of a user stack map: the GC roots live at a call.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "enable-serde",
+    derive(serde_derive::Serialize, serde_derive::Deserialize)
+)]
+pub struct MachUserStackMap {
+    /// The return address of the call, which is where this stack map applies.
+    pub offset: CodeOffset,
+    /// The distance from the stack pointer at the call to the frame pointer.
+    pub span: u32,
+    /// The stack map itself.
+    pub stack_map: UserStackMap,
+}
+
 /// Record of branch instruction in the buffer, to facilitate editing.
 #[derive(Clone, Debug)]
-struct MachBranch {
+struct MachBranch<I: VCodeInst> {
     start: CodeOffset,
     end: CodeOffset,
     target: MachLabel,
     fixup: usize,
     inverted: Option<SmallVec<[u8; 8]>>,
+    /// A shorter encoding of this (conditional) branch, if the target is close
+    /// enough. See `set_last_branch_short_form()`.
+    short: Option<MachBranchShortForm<I>>,
     /// All labels pointing to the start of this branch. For correctness, this
     /// *must* be complete (i.e., must contain all labels whose resolved offsets
     /// are at the start of this branch): we rely on being able to redirect all
@@ -1897,7 +2040,7 @@ struct MachBranch {
     labels_at_this_branch: SmallVec<[MachLabel; 4]>,
 }
 
-impl MachBranch {
+impl<I: VCodeInst> MachBranch<I> {
     fn is_cond(&self) -> bool {
         self.inverted.is_some()
     }
@@ -1906,6 +2049,14 @@ impl MachBranch {
     }
 }
 
+/// Shorter encoding of a conditional branch, used when its target is in range.
+#[derive(Clone, Debug)]
+struct MachBranchShortForm<I: VCodeInst> {
+    kind: I::LabelUse,
+    code: SmallVec<[u8; 8]>,
+    inverted: SmallVec<[u8; 8]>,
+}
+
 /// Implementation of the `TextSectionBuilder` trait backed by `MachBuffer`.
 ///
 /// Note that `MachBuffer` was primarily written for intra-function references
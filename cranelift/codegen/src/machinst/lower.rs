@@ -10,15 +10,15 @@ use crate::fx::{FxHashMap, FxHashSet};
 use crate::inst_predicates::{has_lowering_side_effect, is_constant_64bit};
 use crate::ir::{
     ArgumentPurpose, Block, Constant, ConstantData, DataFlowGraph, ExternalName, Function,
-    GlobalValue, GlobalValueData, Immediate, Inst, InstructionData, MemFlags, RelSourceLoc, Type,
-    Value, ValueDef, ValueLabelAssignments, ValueLabelStart,
+    GlobalValue, GlobalValueData, Immediate, Inst, InstructionData, MemFlags, Opcode, RelSourceLoc,
+    Type, Value, ValueDef, ValueLabelAssignments, ValueLabelStart,
 };
 use crate::machinst::{
     writable_value_regs, BlockIndex, BlockLoweringOrder, Callee, LoweredBlock, MachLabel, Reg,
     SigSet, VCode, VCodeBuilder, VCodeConstant, VCodeConstantData, VCodeConstants, VCodeInst,
     ValueRegs, Writable,
 };
-use crate::{trace, CodegenResult};
+use crate::{trace, CodegenError, CodegenResult};
 use alloc::vec::Vec;
 use cranelift_control::ControlPlane;
 use smallvec::{smallvec, SmallVec};
@@ -734,18 +734,28 @@ impl<'func, I: VCodeInst> Lower<'func, I> {
             // or any of its outputs its used.
             if has_side_effect || value_needed {
                 trace!("lowering: inst {}: {:?}", inst, self.f.dfg.insts[inst]);
-                let temp_regs = backend.lower(self, inst).unwrap_or_else(|| {
-                    let ty = if self.num_outputs(inst) > 0 {
-                        Some(self.output_ty(inst, 0))
-                    } else {
-                        None
-                    };
-                    panic!(
-                        "should be implemented in ISLE: inst = `{}`, type = `{:?}`",
-                        self.f.dfg.display_inst(inst),
-                        ty
-                    )
-                });
+                let temp_regs = match backend.lower(self, inst) {
+                    Some(regs) => regs,
+                    // Stack switching is only lowered by some backends.
+                    None if data.opcode() == Opcode::StackSwitch => {
+                        return Err(CodegenError::Unsupported(format!(
+                            "`{}` isn't supported on this target",
+                            data.opcode()
+                        )));
+                    }
+                    None => {
+                        let ty = if self.num_outputs(inst) > 0 {
+                            Some(self.output_ty(inst, 0))
+                        } else {
+                            None
+                        };
+                        panic!(
+                            "should be implemented in ISLE: inst = `{}`, type = `{:?}`",
+                            self.f.dfg.display_inst(inst),
+                            ty
+                        )
+                    }
+                };
 
                 // The ISLE generated code emits its own registers to define the
                 // instruction's lowered values in. However, other instructions
@@ -768,6 +778,19 @@ impl<'func, I: VCodeInst> Lower<'func, I> {
                 }
             }
 
+            // Attach the user stack map of a call, if any, to the lowered
+            // call instruction.
+            if let Some(entries) = self.f.dfg.user_stack_map_entries(inst) {
+                let safepoint = self
+                    .ir_insts
+                    .iter()
+                    .rposition(|inst| inst.is_safepoint())
+                    .expect("a call with a stack map must be lowered to a safepoint");
+                // `finish_ir_inst` pushes the lowered instructions in reverse.
+                self.vcode
+                    .add_user_stack_map(self.ir_insts.len() - 1 - safepoint, entries);
+            }
+
             let loc = self.srcloc(inst);
             self.finish_ir_inst(loc);
 
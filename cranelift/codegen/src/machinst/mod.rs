@@ -390,6 +390,7 @@ impl<T: CompilePhase> CompiledCodeBase<T> {
 
         let relocs = self.buffer.relocs();
         let traps = self.buffer.traps();
+        let user_stack_maps = self.buffer.user_stack_maps();
 
         // Normalize the block starts to include an initial block of offset 0.
         let mut block_starts = Vec::new();
@@ -439,6 +440,20 @@ impl<T: CompilePhase> CompiledCodeBase<T> {
                     write!(buf, " ; trap: {}", trap.code)?;
                 }
 
+                if let Some(map) = user_stack_maps
+                    .iter()
+                    .find(|map| u64::from(map.offset) == end)
+                {
+                    write!(buf, " ; user_stack_map: span {}, [", map.span)?;
+                    for (i, (ty, offset)) in map.stack_map.entries().enumerate() {
+                        if i > 0 {
+                            write!(buf, ", ")?;
+                        }
+                        write!(buf, "{} @ sp+{}", ty, offset)?;
+                    }
+                    write!(buf, "]")?;
+                }
+
                 writeln!(buf)?;
             }
         }
@@ -20,7 +20,10 @@
 use crate::fx::FxHashMap;
 use crate::fx::FxHashSet;
 use crate::ir::RelSourceLoc;
-use crate::ir::{self, types, Constant, ConstantData, DynamicStackSlot, ValueLabel};
+use crate::ir::{
+    self, types, Constant, ConstantData, DynamicStackSlot, UserStackMapEntry, UserStackMapEntryVec,
+    ValueLabel,
+};
 use crate::machinst::*;
 use crate::timing;
 use crate::trace;
@@ -171,6 +174,9 @@ pub struct VCode<I: VCodeInst> {
     /// Value labels for debuginfo attached to vregs.
     debug_value_labels: Vec<(VReg, InsnIndex, InsnIndex, u32)>,
 
+    /// User stack maps of call instructions, keyed by instruction index.
+    user_stack_maps: FxHashMap<InsnIndex, UserStackMapEntryVec>,
+
     pub(crate) sigs: SigSet,
 }
 
@@ -381,6 +387,17 @@ impl<I: VCodeInst> VCodeBuilder<I> {
         self.add_branch_args_for_succ(args);
     }
 
+    /// Attach a user stack map to an instruction which is yet to be pushed:
+    /// `index` is the number of instructions pushed before it.
+    pub fn add_user_stack_map(&mut self, index: usize, entries: &[UserStackMapEntry]) {
+        // Instructions are pushed in reverse; the index is translated in
+        // `reverse_and_finalize`.
+        let inst = InsnIndex::new(self.vcode.insts.len() + index);
+        self.vcode
+            .user_stack_maps
+            .insert(inst, entries.iter().cloned().collect());
+    }
+
     /// Set the current source location.
     pub fn set_srcloc(&mut self, srcloc: RelSourceLoc) {
         self.cur_srcloc = srcloc;
@@ -507,6 +524,14 @@ impl<I: VCodeInst> VCodeBuilder<I> {
         // (exclusive) end of 10.
         let translate = |inst: InsnIndex| InsnIndex::new(n_insts - inst.index());
 
+        // User stack maps are attached to individual instructions.
+        self.vcode.user_stack_maps = self
+            .vcode
+            .user_stack_maps
+            .drain()
+            .map(|(inst, entries)| (InsnIndex::new(n_insts - 1 - inst.index()), entries))
+            .collect();
+
         // Edit the block-range instruction indices.
         for tuple in &mut self.vcode.block_ranges {
             let (start, end) = *tuple;
@@ -653,6 +678,7 @@ impl<I: VCodeInst> VCode<I> {
             reftyped_vregs: vec![],
             constants,
             debug_value_labels: vec![],
+            user_stack_maps: FxHashMap::default(),
             vreg_aliases: FxHashMap::with_capacity_and_hasher(10 * n_blocks, Default::default()),
         }
     }
@@ -772,18 +798,10 @@ impl<I: VCodeInst> VCode<I> {
         // correctly.
         buffer.register_constants(&self.constants);
 
-        // Construct the final order we emit code in: cold blocks at the end.
-        let mut final_order: SmallVec<[BlockIndex; 16]> = smallvec![];
-        let mut cold_blocks: SmallVec<[BlockIndex; 16]> = smallvec![];
-        for block in 0..self.num_blocks() {
-            let block = BlockIndex::new(block);
-            if self.block_order.is_cold(block) {
-                cold_blocks.push(block);
-            } else {
-                final_order.push(block);
-            }
-        }
-        final_order.extend(cold_blocks.clone());
+        // Get the final order we emit code in: cold blocks at the end, and
+        // hot paths laid out as fall-throughs if we have a profile.
+        let final_order: SmallVec<[BlockIndex; 16]> =
+            SmallVec::from_slice(self.block_order.emission_order());
 
         // Compute/save info we need for the prologue: clobbers and
         // number of spillslots.
@@ -802,12 +820,14 @@ impl<I: VCodeInst> VCode<I> {
         let mut cur_srcloc = None;
         let mut last_offset = None;
         let mut inst_offsets = vec![];
+        let mut block_offsets = vec![];
         let mut state = I::State::new(&self.abi, std::mem::take(ctrl_plane));
 
         let mut disasm = String::new();
 
         if !self.debug_value_labels.is_empty() {
             inst_offsets.resize(self.insts.len(), NO_INST_OFFSET);
+            block_offsets.resize(self.num_blocks(), (0, 0));
         }
 
         // Count edits per block ahead of time; this is needed for
@@ -875,6 +895,10 @@ impl<I: VCodeInst> VCode<I> {
 
             buffer.bind_label(MachLabel::from_block(block), state.ctrl_plane_mut());
 
+            if !self.debug_value_labels.is_empty() {
+                block_offsets[block.index()].0 = buffer.cur_offset();
+            }
+
             if want_disasm {
                 writeln!(&mut disasm, "block{}:", block.index()).unwrap();
             }
@@ -910,17 +934,7 @@ impl<I: VCodeInst> VCode<I> {
                             // record the offset of each instruction
                             // so that we can translate value-label
                             // ranges to machine-code offsets.
-
-                            // Cold blocks violate monotonicity
-                            // assumptions elsewhere (that
-                            // instructions in inst-index order are in
-                            // order in machine code), so we omit
-                            // their offsets here. Value-label range
-                            // generation below will skip empty ranges
-                            // and ranges with to-offsets of zero.
-                            if !self.block_order.is_cold(block) {
-                                inst_offsets[iix.index()] = buffer.cur_offset();
-                            }
+                            inst_offsets[iix.index()] = buffer.cur_offset();
                         }
 
                         // Update the srcloc at this point in the buffer.
@@ -980,6 +994,14 @@ impl<I: VCodeInst> VCode<I> {
                                 do_emit(&inst, &[], &mut disasm, &mut buffer, &mut state);
                             }
                         } else {
+                            // If this is a call with a user stack map,
+                            // compute the stack map before emission, as
+                            // the call may adjust the virtual SP offset.
+                            let user_stack_map = self
+                                .user_stack_maps
+                                .get(&iix)
+                                .map(|entries| self.abi.user_stack_map(entries, &state));
+
                             // Emit the instruction!
                             do_emit(
                                 &self.insts[iix.index()],
@@ -988,6 +1010,12 @@ impl<I: VCodeInst> VCode<I> {
                                 &mut buffer,
                                 &mut state,
                             );
+
+                            // The stack map applies at the return address,
+                            // just after the call.
+                            if let Some((span, stack_map)) = user_stack_map {
+                                buffer.add_user_stack_map(span, stack_map);
+                            }
                         }
                     }
 
@@ -1031,6 +1059,10 @@ impl<I: VCodeInst> VCode<I> {
                 cur_srcloc = None;
             }
 
+            if !self.debug_value_labels.is_empty() {
+                block_offsets[block.index()].1 = buffer.cur_offset();
+            }
+
             // Do we need an island? Get the worst-case size of the next BB, add
             // it to the optional padding behind the block, and pass this to the
             // `MachBuffer` to determine if an island is necessary.
@@ -1080,20 +1112,21 @@ impl<I: VCodeInst> VCode<I> {
 
         let func_body_len = buffer.cur_offset();
 
-        // Create `bb_edges` and final (filtered) `bb_starts`.
+        // Create `bb_edges` and final (filtered) `bb_starts`. The latter are
+        // in emission order.
         let mut bb_edges = vec![];
         let mut bb_offsets = vec![];
         if flags.machine_code_cfg_info() {
-            for block in 0..self.num_blocks() {
-                if bb_starts[block].is_none() {
+            for (&block, &start) in final_order.iter().zip(bb_starts.iter()) {
+                if start.is_none() {
                     // Block was deleted by MachBuffer; skip.
                     continue;
                 }
-                let from = bb_starts[block].unwrap();
+                let from = start.unwrap();
 
                 bb_offsets.push(from);
                 // Resolve each `succ` label and add edges.
-                let succs = self.block_succs(BlockIndex::new(block));
+                let succs = self.block_succs(block);
                 for &succ in succs.iter() {
                     let to = buffer.resolve_label_offset(MachLabel::from_block(succ));
                     bb_edges.push((from, to));
@@ -1101,9 +1134,14 @@ impl<I: VCodeInst> VCode<I> {
             }
         }
 
-        self.monotonize_inst_offsets(&mut inst_offsets[..], func_body_len);
+        self.monotonize_inst_offsets(
+            &mut inst_offsets[..],
+            &mut block_offsets[..],
+            &final_order[..],
+            func_body_len,
+        );
         let value_labels_ranges =
-            self.compute_value_labels_ranges(regalloc, &inst_offsets[..], func_body_len);
+            self.compute_value_labels_ranges(regalloc, &inst_offsets[..], &block_offsets[..]);
         let frame_size = self.abi.frame_size();
 
         EmitResult {
@@ -1119,7 +1157,13 @@ impl<I: VCodeInst> VCode<I> {
         }
     }
 
-    fn monotonize_inst_offsets(&self, inst_offsets: &mut [CodeOffset], func_body_len: u32) {
+    fn monotonize_inst_offsets(
+        &self,
+        inst_offsets: &mut [CodeOffset],
+        block_offsets: &mut [(CodeOffset, CodeOffset)],
+        final_order: &[BlockIndex],
+        func_body_len: u32,
+    ) {
         if self.debug_value_labels.is_empty() {
             return;
         }
@@ -1135,27 +1179,30 @@ impl<I: VCodeInst> VCode<I> {
         // [insi][jmp0][jmp1][jmp2][insj][...]
         // [30]  [34]  [34]  [34]  [34]
         //
+        // Removed branches are always at the end of a block, and may take
+        // whole blocks with them, so this is done per block, going backwards
+        // in emission order: each block ends where the next one starts at
+        // the latest, and its instructions end where the block ends.
         let mut next_offset = func_body_len;
-        for inst_index in (0..(inst_offsets.len() - 1)).rev() {
-            let inst_offset = inst_offsets[inst_index];
-
-            // Not all instructions get their offsets recorded.
-            if inst_offset == NO_INST_OFFSET {
-                continue;
-            }
-
-            if inst_offset > next_offset {
-                trace!(
-                    "Fixing code offset of the removed Inst {}: {} -> {}",
-                    inst_index,
-                    inst_offset,
-                    next_offset
-                );
-                inst_offsets[inst_index] = next_offset;
-                continue;
+        for &block in final_order.iter().rev() {
+            let (start, end) = &mut block_offsets[block.index()];
+            *end = (*end).min(next_offset);
+            *start = (*start).min(*end);
+            next_offset = *start;
+
+            let (first_inst, last_inst) = self.block_ranges[block.index()];
+            let insts = first_inst.index()..last_inst.index();
+            for (inst_index, inst_offset) in insts.clone().zip(&mut inst_offsets[insts]) {
+                if *inst_offset > *end {
+                    trace!(
+                        "Fixing code offset of the removed Inst {}: {} -> {}",
+                        inst_index,
+                        inst_offset,
+                        end
+                    );
+                    *inst_offset = *end;
+                }
             }
-
-            next_offset = inst_offset;
         }
     }
 
@@ -1163,7 +1210,7 @@ impl<I: VCodeInst> VCode<I> {
         &self,
         regalloc: &regalloc2::Output,
         inst_offsets: &[CodeOffset],
-        func_body_len: u32,
+        block_offsets: &[(CodeOffset, CodeOffset)],
     ) -> ValueLabelsRanges {
         if self.debug_value_labels.is_empty() {
             return ValueLabelsRanges::default();
@@ -1174,21 +1221,6 @@ impl<I: VCodeInst> VCode<I> {
             let ranges = value_labels_ranges
                 .entry(ValueLabel::from_u32(label))
                 .or_insert_with(|| vec![]);
-            let from_offset = inst_offsets[from.inst().index()];
-            let to_offset = if to.inst().index() == inst_offsets.len() {
-                func_body_len
-            } else {
-                inst_offsets[to.inst().index()]
-            };
-
-            // Empty ranges or unavailable offsets can happen
-            // due to cold blocks and branch removal (see above).
-            if from_offset == NO_INST_OFFSET
-                || to_offset == NO_INST_OFFSET
-                || from_offset == to_offset
-            {
-                continue;
-            }
 
             let loc = if let Some(preg) = alloc.as_reg() {
                 LabelValueLoc::Reg(Reg::from(preg))
@@ -1202,49 +1234,83 @@ impl<I: VCodeInst> VCode<I> {
                 LabelValueLoc::CFAOffset(cfa_to_sp_offset + sp_offset)
             };
 
-            // ValueLocRanges are recorded by *instruction-end
-            // offset*. `from_offset` is the *start* of the
-            // instruction; that is the same as the end of another
-            // instruction, so we only want to begin coverage once
-            // we are past the previous instruction's end.
-            let start = from_offset + 1;
-
-            // Likewise, `end` is exclusive, but we want to
-            // *include* the end of the last
-            // instruction. `to_offset` is the start of the
-            // `to`-instruction, which is the exclusive end, i.e.,
-            // the first instruction not covered. That
-            // instruction's start is the same as the end of the
-            // last instruction that is included, so we go one
-            // byte further to be sure to include it.
-            let end = to_offset + 1;
-
-            // Coalesce adjacent ranges that for the same location
-            // to minimize output size here and for the consumers.
-            if let Some(last_loc_range) = ranges.last_mut() {
-                if last_loc_range.loc == loc && last_loc_range.end == start {
-                    trace!(
-                        "Extending debug range for VL{} in {:?} to {}",
-                        label,
-                        loc,
-                        end
-                    );
-                    last_loc_range.end = end;
+            // The instructions from `from` up to `to` are contiguous
+            // in machine code only within a block, as blocks may be
+            // emitted in a different order than their indices; so
+            // record a range for each block they span.
+            let (from, to) = (from.inst().index(), to.inst().index());
+            let first_block = self
+                .block_ranges
+                .partition_point(|&(_, last_inst)| last_inst.index() <= from);
+            for (block, &(first_inst, last_inst)) in
+                self.block_ranges.iter().enumerate().skip(first_block)
+            {
+                if first_inst.index() >= to {
+                    break;
+                }
+                let from_offset = inst_offsets[from.max(first_inst.index())];
+                let to_offset = if to >= last_inst.index() {
+                    block_offsets[block].1
+                } else {
+                    inst_offsets[to]
+                };
+
+                // Empty ranges can happen due to branch removal (see
+                // above).
+                if from_offset == to_offset {
                     continue;
                 }
-            }
 
-            trace!(
-                "Recording debug range for VL{} in {:?}: [Inst {}..Inst {}) [{}..{})",
-                label,
-                loc,
-                from.inst().index(),
-                to.inst().index(),
-                start,
-                end
-            );
+                // ValueLocRanges are recorded by *instruction-end
+                // offset*. `from_offset` is the *start* of the
+                // instruction; that is the same as the end of another
+                // instruction, so we only want to begin coverage once
+                // we are past the previous instruction's end.
+                let start = from_offset + 1;
+
+                // Likewise, `end` is exclusive, but we want to
+                // *include* the end of the last
+                // instruction. `to_offset` is the start of the
+                // `to`-instruction, which is the exclusive end, i.e.,
+                // the first instruction not covered. That
+                // instruction's start is the same as the end of the
+                // last instruction that is included, so we go one
+                // byte further to be sure to include it.
+                let end = to_offset + 1;
 
-            ranges.push(ValueLocRange { loc, start, end });
+                trace!(
+                    "Recording debug range for VL{} in {:?}: [Inst {}..Inst {}) [{}..{})",
+                    label,
+                    loc,
+                    from.max(first_inst.index()),
+                    to.min(last_inst.index()),
+                    start,
+                    end
+                );
+
+                ranges.push(ValueLocRange { loc, start, end });
+            }
+        }
+
+        // Blocks are not emitted in index order, so sort the ranges by
+        // offset. Then coalesce adjacent ranges for the same location to
+        // minimize output size here and for the consumers.
+        for (label, ranges) in value_labels_ranges.iter_mut() {
+            ranges.sort_by_key(|range| range.start);
+            ranges.dedup_by(|range, last_loc_range| {
+                if last_loc_range.loc == range.loc && last_loc_range.end == range.start {
+                    trace!(
+                        "Extending debug range for {:?} in {:?} to {}",
+                        label,
+                        range.loc,
+                        range.end
+                    );
+                    last_loc_range.end = range.end;
+                    true
+                } else {
+                    false
+                }
+            });
         }
 
         value_labels_ranges
@@ -1699,4 +1765,76 @@ mod test {
         // With certain versions of Rust, each `HashMap` in `VCodeConstants` occupied at
         // least 48 bytes, making an empty `VCodeConstants` cost 120 bytes.
     }
+
+    #[test]
+    #[cfg(feature = "x86")]
+    fn value_labels_of_reordered_blocks() {
+        use crate::cursor::{Cursor, FuncCursor};
+        use crate::ir::{
+            types, AbiParam, Function, InstBuilder, Signature, UserFuncName, ValueLabelAssignments,
+            ValueLabelStart,
+        };
+        use crate::isa::{lookup, CallConv};
+        use crate::settings::{builder, Configurable, Flags};
+        use crate::Context;
+        use core::str::FromStr;
+        use target_lexicon::triple;
+
+        // `block1` is the hot successor of `block0`, so it is emitted before
+        // `block2` although it comes after it in lowering order. The label
+        // of `v1`, which is used in both, must cover both.
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I64));
+        sig.returns.push(AbiParam::new(types::I64));
+        let mut func = Function::with_name_signature(UserFuncName::default(), sig);
+        func.dfg.collect_debug_info();
+        let block0 = func.dfg.make_block();
+        let block1 = func.dfg.make_block();
+        let block2 = func.dfg.make_block();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(block0);
+        let v0 = pos.func.dfg.append_block_param(block0, types::I64);
+        let v1 = pos.ins().imul(v0, v0);
+        pos.ins().brif(v0, block1, &[], block2, &[]);
+        pos.insert_block(block1);
+        let v2 = pos.ins().imul(v0, v0);
+        let v2 = pos.ins().bxor(v1, v2);
+        pos.ins().return_(&[v2]);
+        pos.insert_block(block2);
+        let v3 = pos.ins().imul(v0, v0);
+        let v3 = pos.ins().band(v1, v3);
+        pos.ins().return_(&[v3]);
+
+        let label = ValueLabel::from_u32(0);
+        func.dfg.values_labels.as_mut().unwrap().insert(
+            v1,
+            ValueLabelAssignments::Starts(vec![ValueLabelStart {
+                from: RelSourceLoc::default(),
+                label,
+            }]),
+        );
+        func.profile.set_edge_count(block0, block1, 100);
+        func.profile.set_edge_count(block0, block2, 1);
+
+        let mut flags = builder();
+        flags.set("machine_code_cfg_info", "true").unwrap();
+        let isa = lookup(triple!("x86_64"))
+            .unwrap()
+            .finish(Flags::new(flags))
+            .unwrap();
+        let mut context = Context::for_function(func);
+        let code = context.compile(&*isa, &mut Default::default()).unwrap();
+
+        let ranges = &code.value_labels_ranges[&label];
+        assert!(ranges.windows(2).all(|w| w[0].end <= w[1].start));
+        let mut block_ends = code.bb_starts[1..].to_vec();
+        block_ends.push(code.code_buffer().len() as u32);
+        assert_eq!(block_ends.len(), 3);
+        for (&start, end) in code.bb_starts.iter().zip(block_ends) {
+            assert!(
+                ranges.iter().any(|r| r.start <= end && start < r.end),
+                "no range for block at {start:#x} in {ranges:?}"
+            );
+        }
+    }
 }
@@ -563,6 +563,19 @@ impl<'a> Verifier<'a> {
             self.verify_inst_result(inst, res, errors)?;
         }
 
+        for entry in self.func.dfg.user_stack_map_entries(inst).unwrap_or(&[]) {
+            self.verify_stack_slot(inst, entry.slot, errors)?;
+            if let Some(slot) = self.func.sized_stack_slots.get(entry.slot) {
+                if u64::from(entry.offset) + u64::from(entry.ty.bytes()) > u64::from(slot.size) {
+                    errors.report((
+                        inst,
+                        self.context(inst),
+                        format!("stack map entry {} is out of bounds of its slot", entry),
+                    ));
+                }
+            }
+        }
+
         match self.func.dfg.insts[inst] {
             MultiAry { ref args, .. } => {
                 self.verify_value_list(inst, args, errors)?;
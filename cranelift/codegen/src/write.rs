@@ -360,6 +360,7 @@ fn write_instruction(
     }
 
     write_operands(w, &func.dfg, inst)?;
+    write_user_stack_map_entries(w, &func.dfg, inst)?;
     writeln!(w)?;
 
     // Value aliases come out on lines after the instruction defining the referent.
@@ -369,6 +370,21 @@ fn write_instruction(
     Ok(())
 }
 
+/// Write the user stack map of `inst`, if any, to `w`.
+fn write_user_stack_map_entries(w: &mut dyn Write, dfg: &DataFlowGraph, inst: Inst) -> fmt::Result {
+    if let Some(entries) = dfg.user_stack_map_entries(inst) {
+        write!(w, ", stack_map=[")?;
+        for (i, entry) in entries.iter().enumerate() {
+            if i > 0 {
+                write!(w, ", ")?;
+            }
+            write!(w, "{}", entry)?;
+        }
+        write!(w, "]")?;
+    }
+    Ok(())
+}
+
 /// Write the operands of `inst` to `w` with a prepended space.
 pub fn write_operands(w: &mut dyn Write, dfg: &DataFlowGraph, inst: Inst) -> fmt::Result {
     let pool = &dfg.value_lists;
@@ -39,14 +39,13 @@
 
 ;; function u0:0:
 ;; block0:
-;;   movz w7, #61436
-;;   movk w7, w7, #4095, LSL #16
-;;   subs xzr, x0, x7
+;;   movz x6, #4096, LSL #16
+;;   subs xzr, x0, x6
 ;;   b.hi label3 ; b label1
 ;; block1:
-;;   ldr x9, [x2]
-;;   add x9, x9, #4096
-;;   str w1, [x9, x0]
+;;   ldr x8, [x2]
+;;   add x8, x8, #4096
+;;   str w1, [x8, x0]
 ;;   b label2
 ;; block2:
 ;;   ret
@@ -55,14 +54,13 @@
 ;;
 ;; function u0:1:
 ;; block0:
-;;   movz w7, #61436
-;;   movk w7, w7, #4095, LSL #16
-;;   subs xzr, x0, x7
+;;   movz x6, #4096, LSL #16
+;;   subs xzr, x0, x6
 ;;   b.hi label3 ; b label1
 ;; block1:
-;;   ldr x9, [x1]
-;;   add x8, x9, #4096
-;;   ldr w0, [x8, x0]
+;;   ldr x8, [x1]
+;;   add x7, x8, #4096
+;;   ldr w0, [x7, x0]
 ;;   b label2
 ;; block2:
 ;;   ret
@@ -39,7 +39,7 @@
 
 ;; function u0:0:
 ;; block0:
-;;   orr x5, xzr, #268435455
+;;   movz x5, #4096, LSL #16
 ;;   subs xzr, x0, x5
 ;;   b.hi label3 ; b label1
 ;; block1:
@@ -53,7 +53,7 @@
 ;;
 ;; function u0:1:
 ;; block0:
-;;   orr x5, xzr, #268435455
+;;   movz x5, #4096, LSL #16
 ;;   subs xzr, x0, x5
 ;;   b.hi label3 ; b label1
 ;; block1:
@@ -42,7 +42,7 @@
 ;;   ldr x8, [x2]
 ;;   add x8, x8, x0
 ;;   movz x9, #0
-;;   orr x7, xzr, #268435452
+;;   movz x7, #4096, LSL #16
 ;;   subs xzr, x0, x7
 ;;   csel x10, x9, x8, hi
 ;;   csdb
@@ -56,7 +56,7 @@
 ;;   ldr x8, [x1]
 ;;   add x8, x8, x0
 ;;   movz x9, #0
-;;   orr x7, xzr, #268435452
+;;   movz x7, #4096, LSL #16
 ;;   subs xzr, x0, x7
 ;;   csel x10, x9, x8, hi
 ;;   csdb
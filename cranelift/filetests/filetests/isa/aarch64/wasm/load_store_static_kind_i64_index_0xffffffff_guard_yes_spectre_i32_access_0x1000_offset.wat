@@ -39,32 +39,30 @@
 
 ;; function u0:0:
 ;; block0:
-;;   ldr x10, [x2]
-;;   add x10, x10, x0
-;;   add x10, x10, #4096
-;;   movz x11, #0
-;;   movz w9, #61436
-;;   movk w9, w9, #4095, LSL #16
-;;   subs xzr, x0, x9
-;;   csel x13, x11, x10, hi
+;;   ldr x9, [x2]
+;;   add x9, x9, x0
+;;   add x9, x9, #4096
+;;   movz x10, #0
+;;   movz x8, #4096, LSL #16
+;;   subs xzr, x0, x8
+;;   csel x11, x10, x9, hi
 ;;   csdb
-;;   str w1, [x13]
+;;   str w1, [x11]
 ;;   b label1
 ;; block1:
 ;;   ret
 ;;
 ;; function u0:1:
 ;; block0:
-;;   ldr x10, [x1]
-;;   add x10, x10, x0
-;;   add x10, x10, #4096
-;;   movz x11, #0
-;;   movz w9, #61436
-;;   movk w9, w9, #4095, LSL #16
-;;   subs xzr, x0, x9
-;;   csel x13, x11, x10, hi
+;;   ldr x9, [x1]
+;;   add x9, x9, x0
+;;   add x9, x9, #4096
+;;   movz x10, #0
+;;   movz x8, #4096, LSL #16
+;;   subs xzr, x0, x8
+;;   csel x11, x10, x9, hi
 ;;   csdb
-;;   ldr w0, [x13]
+;;   ldr w0, [x11]
 ;;   b label1
 ;; block1:
 ;;   ret
@@ -40,7 +40,6 @@
 ;; function u0:0:
 ;; block0:
 ;;   lui a3,65536
-;;   addi a3,a3,-4
 ;;   bgtu a0,a3,taken(label3),not_taken(label1)
 ;; block1:
 ;;   ld a2,0(a2)
@@ -55,12 +54,11 @@
 ;; function u0:1:
 ;; block0:
 ;;   lui a2,65536
-;;   addi a2,a2,-4
 ;;   bgtu a0,a2,taken(label3),not_taken(label1)
 ;; block1:
-;;   ld a2,0(a1)
-;;   add a2,a2,a0
-;;   lw a0,0(a2)
+;;   ld a1,0(a1)
+;;   add a1,a1,a0
+;;   lw a0,0(a1)
 ;;   j label2
 ;; block2:
 ;;   ret
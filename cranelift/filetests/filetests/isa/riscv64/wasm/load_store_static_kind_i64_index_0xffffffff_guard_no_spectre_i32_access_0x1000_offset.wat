@@ -39,8 +39,7 @@
 
 ;; function u0:0:
 ;; block0:
-;;   lui a3,65535
-;;   addi a3,a3,-4
+;;   lui a3,65536
 ;;   bgtu a0,a3,taken(label3),not_taken(label1)
 ;; block1:
 ;;   ld a2,0(a2)
@@ -54,13 +53,12 @@
 ;;
 ;; function u0:1:
 ;; block0:
-;;   lui a2,65535
-;;   addi a2,a2,-4
+;;   lui a2,65536
 ;;   bgtu a0,a2,taken(label3),not_taken(label1)
 ;; block1:
-;;   ld a2,0(a1)
-;;   add a2,a2,a0
-;;   lw a0,4096(a2)
+;;   ld a1,0(a1)
+;;   add a1,a1,a0
+;;   lw a0,4096(a1)
 ;;   j label2
 ;; block2:
 ;;   ret
@@ -40,17 +40,16 @@
 ;; function u0:0:
 ;; block0:
 ;;   lui a3,65536
-;;   addi a4,a3,-1
-;;   ugt a4,a0,a4##ty=i64
-;;   ld a3,0(a2)
-;;   add a3,a3,a0
-;;   li a5,0
-;;   sltu a4,zero,a4
-;;   sub a0,zero,a4
-;;   and a2,a5,a0
-;;   not a4,a0
-;;   and a0,a3,a4
-;;   or a2,a2,a0
+;;   ugt a3,a0,a3##ty=i64
+;;   ld a2,0(a2)
+;;   add a2,a2,a0
+;;   li a4,0
+;;   sltu a3,zero,a3
+;;   sub a5,zero,a3
+;;   and a3,a4,a5
+;;   not a4,a5
+;;   and a5,a2,a4
+;;   or a2,a3,a5
 ;;   sb a1,0(a2)
 ;;   j label1
 ;; block1:
@@ -59,18 +58,17 @@
 ;; function u0:1:
 ;; block0:
 ;;   lui a2,65536
-;;   addi a4,a2,-1
-;;   ugt a4,a0,a4##ty=i64
-;;   ld a3,0(a1)
-;;   add a3,a3,a0
-;;   li a5,0
-;;   sltu a4,zero,a4
-;;   sub a0,zero,a4
-;;   and a2,a5,a0
-;;   not a4,a0
-;;   and a0,a3,a4
-;;   or a2,a2,a0
-;;   lbu a0,0(a2)
+;;   ugt a3,a0,a2##ty=i64
+;;   ld a2,0(a1)
+;;   add a2,a2,a0
+;;   li a4,0
+;;   sltu a3,zero,a3
+;;   sub a5,zero,a3
+;;   and a1,a4,a5
+;;   not a3,a5
+;;   and a5,a2,a3
+;;   or a1,a1,a5
+;;   lbu a0,0(a1)
 ;;   j label1
 ;; block1:
 ;;   ret
@@ -41,7 +41,7 @@
 ;;   unwind DefineNewFrame { offset_upward_to_caller_sp: 160, offset_downward_to_clobbers: 0 }
 ;;   unwind StackAlloc { size: 0 }
 ;; block0:
-;;   clgfi %r2, 268431356
+;;   clgfi %r2, 268435456
 ;;   jgh label3 ; jg label1
 ;; block1:
 ;;   lgr %r5, %r2
@@ -58,7 +58,7 @@
 ;;   unwind DefineNewFrame { offset_upward_to_caller_sp: 160, offset_downward_to_clobbers: 0 }
 ;;   unwind StackAlloc { size: 0 }
 ;; block0:
-;;   clgfi %r2, 268431356
+;;   clgfi %r2, 268435456
 ;;   jgh label3 ; jg label1
 ;; block1:
 ;;   lgr %r5, %r2
@@ -41,7 +41,7 @@
 ;;   unwind DefineNewFrame { offset_upward_to_caller_sp: 160, offset_downward_to_clobbers: 0 }
 ;;   unwind StackAlloc { size: 0 }
 ;; block0:
-;;   clgfi %r2, 268435455
+;;   clgfi %r2, 268435456
 ;;   jgh label3 ; jg label1
 ;; block1:
 ;;   lg %r4, 0(%r4)
@@ -56,7 +56,7 @@
 ;;   unwind DefineNewFrame { offset_upward_to_caller_sp: 160, offset_downward_to_clobbers: 0 }
 ;;   unwind StackAlloc { size: 0 }
 ;; block0:
-;;   clgfi %r2, 268435455
+;;   clgfi %r2, 268435456
 ;;   jgh label3 ; jg label1
 ;; block1:
 ;;   lg %r4, 0(%r3)
@@ -44,7 +44,7 @@
 ;;   lgr %r5, %r2
 ;;   ag %r5, 0(%r4)
 ;;   lghi %r4, 0
-;;   clgfi %r2, 268435455
+;;   clgfi %r2, 268435456
 ;;   locgrh %r5, %r4
 ;;   stc %r3, 0(%r5)
 ;;   jg label1
@@ -58,7 +58,7 @@
 ;;   lgr %r5, %r2
 ;;   ag %r5, 0(%r3)
 ;;   lghi %r4, 0
-;;   clgfi %r2, 268435455
+;;   clgfi %r2, 268435456
 ;;   locgrh %r5, %r4
 ;;   llc %r2, 0(%r5)
 ;;   jg label1
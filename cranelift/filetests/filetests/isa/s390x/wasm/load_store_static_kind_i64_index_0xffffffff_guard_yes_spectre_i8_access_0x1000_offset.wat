@@ -45,7 +45,7 @@
 ;;   ag %r5, 0(%r4)
 ;;   aghik %r4, %r5, 4096
 ;;   lghi %r5, 0
-;;   clgfi %r2, 268431359
+;;   clgfi %r2, 268435456
 ;;   locgrh %r4, %r5
 ;;   stc %r3, 0(%r4)
 ;;   jg label1
@@ -60,7 +60,7 @@
 ;;   ag %r5, 0(%r3)
 ;;   aghik %r3, %r5, 4096
 ;;   lghi %r5, 0
-;;   clgfi %r2, 268431359
+;;   clgfi %r2, 268435456
 ;;   locgrh %r3, %r5
 ;;   llc %r2, 0(%r3)
 ;;   jg label1
@@ -43,7 +43,7 @@
 ;;   movq    %rsp, %rbp
 ;;   unwind DefineNewFrame { offset_upward_to_caller_sp: 16, offset_downward_to_clobbers: 0 }
 ;; block0:
-;;   cmpq    $268431356, %rdi
+;;   cmpq    $268435456, %rdi
 ;;   jnbe    label3; j label1
 ;; block1:
 ;;   movq    0(%rdx), %r9
@@ -62,7 +62,7 @@
 ;;   movq    %rsp, %rbp
 ;;   unwind DefineNewFrame { offset_upward_to_caller_sp: 16, offset_downward_to_clobbers: 0 }
 ;; block0:
-;;   cmpq    $268431356, %rdi
+;;   cmpq    $268435456, %rdi
 ;;   jnbe    label3; j label1
 ;; block1:
 ;;   movq    0(%rsi), %r9
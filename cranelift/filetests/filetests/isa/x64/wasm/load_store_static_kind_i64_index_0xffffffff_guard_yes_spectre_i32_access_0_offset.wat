@@ -46,7 +46,7 @@
 ;;   movq    %rdi, %r8
 ;;   addq    %r8, 0(%rdx), %r8
 ;;   xorq    %r9, %r9, %r9
-;;   cmpq    $268435452, %rdi
+;;   cmpq    $268435456, %rdi
 ;;   cmovnbeq %r9, %r8, %r8
 ;;   movl    %esi, 0(%r8)
 ;;   jmp     label1
@@ -64,7 +64,7 @@
 ;;   movq    %rdi, %rdx
 ;;   addq    %rdx, 0(%rsi), %rdx
 ;;   xorq    %r9, %r9, %r9
-;;   cmpq    $268435452, %rdi
+;;   cmpq    $268435456, %rdi
 ;;   cmovnbeq %r9, %rdx, %rdx
 ;;   movl    0(%rdx), %eax
 ;;   jmp     label1
@@ -46,7 +46,7 @@
 ;;   movq    0(%rdx), %r10
 ;;   lea     4096(%r10,%rdi,1), %r8
 ;;   xorq    %r10, %r10, %r10
-;;   cmpq    $268431359, %rdi
+;;   cmpq    $268435456, %rdi
 ;;   cmovnbeq %r10, %r8, %r8
 ;;   movb    %sil, 0(%r8)
 ;;   jmp     label1
@@ -64,7 +64,7 @@
 ;;   movq    0(%rsi), %r10
 ;;   lea     4096(%r10,%rdi,1), %r8
 ;;   xorq    %r10, %r10, %r10
-;;   cmpq    $268431359, %rdi
+;;   cmpq    $268435456, %rdi
 ;;   cmovnbeq %r10, %r8, %r8
 ;;   movzbq  0(%r8), %rax
 ;;   jmp     label1
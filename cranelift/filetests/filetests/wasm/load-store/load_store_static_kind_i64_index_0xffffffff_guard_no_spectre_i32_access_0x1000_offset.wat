@@ -42,7 +42,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i32, v2: i64):
-;; @0040                               v3 = icmp_imm ugt v0, 0x0fff_effc
+;; @0040                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0040                               trapnz v3, heap_oob
 ;; @0040                               v4 = global_value.i64 gv1
 ;; @0040                               v5 = iadd v4, v0
@@ -59,7 +59,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i64):
-;; @0049                               v3 = icmp_imm ugt v0, 0x0fff_effc
+;; @0049                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0049                               trapnz v3, heap_oob
 ;; @0049                               v4 = global_value.i64 gv1
 ;; @0049                               v5 = iadd v4, v0
@@ -69,4 +69,4 @@
 ;;
 ;;                                 block1(v2: i32):
 ;; @004d                               return v2
-;; }
\ No newline at end of file
+;; }
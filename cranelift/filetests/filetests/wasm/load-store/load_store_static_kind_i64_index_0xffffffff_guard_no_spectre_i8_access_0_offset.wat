@@ -42,7 +42,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i32, v2: i64):
-;; @0040                               v3 = icmp_imm ugt v0, 0x0fff_ffff
+;; @0040                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0040                               trapnz v3, heap_oob
 ;; @0040                               v4 = global_value.i64 gv1
 ;; @0040                               v5 = iadd v4, v0
@@ -58,7 +58,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i64):
-;; @0048                               v3 = icmp_imm ugt v0, 0x0fff_ffff
+;; @0048                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0048                               trapnz v3, heap_oob
 ;; @0048                               v4 = global_value.i64 gv1
 ;; @0048                               v5 = iadd v4, v0
@@ -67,4 +67,4 @@
 ;;
 ;;                                 block1(v2: i32):
 ;; @004b                               return v2
-;; }
\ No newline at end of file
+;; }
@@ -42,7 +42,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i32, v2: i64):
-;; @0040                               v3 = icmp_imm ugt v0, 0x0fff_fffc
+;; @0040                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0040                               v4 = global_value.i64 gv1
 ;; @0040                               v5 = iadd v4, v0
 ;; @0040                               v6 = iconst.i64 0
@@ -59,7 +59,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i64):
-;; @0048                               v3 = icmp_imm ugt v0, 0x0fff_fffc
+;; @0048                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0048                               v4 = global_value.i64 gv1
 ;; @0048                               v5 = iadd v4, v0
 ;; @0048                               v6 = iconst.i64 0
@@ -42,7 +42,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i32, v2: i64):
-;; @0040                               v3 = icmp_imm ugt v0, 0x0fff_effc
+;; @0040                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0040                               v4 = global_value.i64 gv1
 ;; @0040                               v5 = iadd v4, v0
 ;; @0040                               v6 = iadd_imm v5, 4096
@@ -60,7 +60,7 @@
 ;;     gv1 = load.i64 notrap aligned readonly gv0
 ;;
 ;;                                 block0(v0: i64, v1: i64):
-;; @0049                               v3 = icmp_imm ugt v0, 0x0fff_effc
+;; @0049                               v3 = icmp_imm ugt v0, 0x1000_0000
 ;; @0049                               v4 = global_value.i64 gv1
 ;; @0049                               v5 = iadd v4, v0
 ;; @0049                               v6 = iadd_imm v5, 4096
@@ -28,6 +28,7 @@ mod test_cat;
 mod test_compile;
 mod test_dce;
 mod test_domtree;
+mod test_inline;
 mod test_interpret;
 mod test_legalizer;
 mod test_optimize;
@@ -98,6 +99,7 @@ fn new_subtest(parsed: &TestCommand) -> anyhow::Result<Box<dyn subtest::SubTest>
         "compile" => test_compile::subtest(parsed),
         "dce" => test_dce::subtest(parsed),
         "domtree" => test_domtree::subtest(parsed),
+        "inline" => test_inline::subtest(parsed),
         "interpret" => test_interpret::subtest(parsed),
         "legalizer" => test_legalizer::subtest(parsed),
         "optimize" => test_optimize::subtest(parsed),
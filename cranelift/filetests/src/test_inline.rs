@@ -0,0 +1,124 @@
+//! Test command for testing the inlining pass.
+//!
+//! The `inline` test command runs each function through the inlining pass,
+//! with the other functions of the test file available for inlining by name.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use crate::runone::FileUpdate;
+use crate::subtest::{check_precise_output, run_filecheck, Context, SubTest};
+use anyhow::{Context as _, Result};
+use cranelift_codegen::ir::{FuncRef, Function};
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::settings::Flags;
+use cranelift_codegen::{FunctionStore, InlineOptions};
+use cranelift_reader::{TestCommand, TestFile, TestOption};
+use log::info;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+struct TestInline {
+    /// Flag indicating that the text expectation, comments after the function,
+    /// must be a precise 100% match on the compiled output of the function.
+    /// This test assertion is also automatically-update-able to allow tweaking
+    /// the code generator and easily updating all affected tests.
+    precise_output: bool,
+}
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "inline");
+    let mut test = TestInline {
+        precise_output: false,
+    };
+    for option in parsed.options.iter() {
+        match option {
+            TestOption::Flag("precise-output") => test.precise_output = true,
+            _ => anyhow::bail!("unknown option on {}", parsed),
+        }
+    }
+    Ok(Box::new(test))
+}
+
+/// The functions of a test file, by name.
+struct TestFileFunctions<'a>(HashMap<String, &'a Function>);
+
+impl FunctionStore for TestFileFunctions<'_> {
+    fn get(&self, caller: &Function, func_ref: FuncRef) -> Option<&Function> {
+        let name = caller.dfg.ext_funcs[func_ref]
+            .name
+            .display(Some(&caller.params))
+            .to_string();
+        self.0.get(&name).copied()
+    }
+}
+
+impl SubTest for TestInline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn run_target<'a>(
+        &self,
+        testfile: &TestFile,
+        file_update: &mut FileUpdate,
+        file_path: &'a str,
+        flags: &'a Flags,
+        isa: Option<&'a dyn TargetIsa>,
+    ) -> Result<()> {
+        let store = TestFileFunctions(
+            testfile
+                .functions
+                .iter()
+                .map(|(func, _)| (func.name.to_string(), func))
+                .collect(),
+        );
+
+        for (func, details) in &testfile.functions {
+            info!("Test: {}({})", self.name(), func.name);
+
+            let context = Context {
+                preamble_comments: &testfile.preamble_comments,
+                details,
+                flags,
+                isa,
+                file_path,
+                file_update,
+            };
+            self.run_with_store(func, &store, &context)
+                .context(self.name())?;
+        }
+        Ok(())
+    }
+
+    fn run(&self, _func: Cow<Function>, _context: &Context) -> Result<()> {
+        unreachable!()
+    }
+}
+
+impl TestInline {
+    fn run_with_store(
+        &self,
+        func: &Function,
+        store: &dyn FunctionStore,
+        context: &Context,
+    ) -> Result<()> {
+        let mut comp_ctx = cranelift_codegen::Context::for_function(func.clone());
+
+        comp_ctx
+            .inline(store, &InlineOptions::default(), context.flags_or_isa())
+            .map_err(|e| crate::pretty_anyhow_error(&comp_ctx.func, e))?;
+
+        let clif = format!("{:?}", comp_ctx.func);
+
+        if self.precise_output {
+            let actual: Vec<_> = clif.lines().collect();
+            check_precise_output(&actual, context)
+        } else {
+            run_filecheck(&clif, context)
+        }
+    }
+}
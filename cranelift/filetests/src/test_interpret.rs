@@ -89,8 +89,14 @@ fn run_test(func_store: &FunctionStore, func: &Function, details: &Details) -> a
                     args.extend_from_slice(run_args);
 
                     // Because we have stored function names with a leading %, we need to re-add it.
-                    let func_name = &format!("%{}", func_name);
-                    match Interpreter::new(state).call_by_name(func_name, &args) {
+                    // The legacy `; run` form has no invocation, and always refers to the function
+                    // it is attached to.
+                    let func_name = if func_name == "default" {
+                        func.name.to_string()
+                    } else {
+                        format!("%{}", func_name)
+                    };
+                    match Interpreter::new(state).call_by_name(&func_name, &args) {
                         Ok(ControlFlow::Return(results)) => Ok(results.to_vec()),
                         Ok(e) => {
                             panic!("Unexpected returned control flow: {:?}", e)
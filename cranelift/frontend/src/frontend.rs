@@ -1,4 +1,5 @@
 //! A frontend for building Cranelift IR from other languages.
+use crate::safepoints::SafepointSpiller;
 use crate::ssa::{SSABuilder, SideEffects};
 use crate::variable::Variable;
 use core::fmt::{self, Debug};
@@ -26,6 +27,8 @@ pub struct FunctionBuilderContext {
     ssa: SSABuilder,
     status: SecondaryMap<Block, BlockStatus>,
     types: SecondaryMap<Variable, Type>,
+    stack_map_vars: EntitySet<Variable>,
+    safepoints: SafepointSpiller,
 }
 
 /// Temporary object used to build a single Cranelift IR `Function`.
@@ -63,10 +66,15 @@ impl FunctionBuilderContext {
         self.ssa.clear();
         self.status.clear();
         self.types.clear();
+        self.stack_map_vars.clear();
+        self.safepoints.clear();
     }
 
     fn is_empty(&self) -> bool {
-        self.ssa.is_empty() && self.status.is_empty() && self.types.is_empty()
+        self.ssa.is_empty()
+            && self.status.is_empty()
+            && self.types.is_empty()
+            && self.safepoints.is_empty()
     }
 }
 
@@ -311,6 +319,11 @@ impl<'a> FunctionBuilder<'a> {
         self.srcloc = srcloc;
     }
 
+    /// Get the source location currently assigned to new instructions.
+    pub fn srcloc(&self) -> ir::SourceLoc {
+        self.srcloc
+    }
+
     /// Creates a new `Block` and returns its reference.
     pub fn create_block(&mut self) -> Block {
         let block = self.func.dfg.make_block();
@@ -422,6 +435,9 @@ impl<'a> FunctionBuilder<'a> {
                 .use_var(self.func, var, ty, self.position.unwrap())
         };
         self.handle_ssa_side_effects(side_effects);
+        if self.func_ctx.stack_map_vars.contains(var) {
+            self.func_ctx.safepoints.declare(val);
+        }
         Ok(val)
     }
 
@@ -450,6 +466,9 @@ impl<'a> FunctionBuilder<'a> {
         }
 
         self.func_ctx.ssa.def_var(var, val, self.position.unwrap());
+        if self.func_ctx.stack_map_vars.contains(var) {
+            self.func_ctx.safepoints.declare(val);
+        }
         Ok(())
     }
 
@@ -473,6 +492,27 @@ impl<'a> FunctionBuilder<'a> {
             })
     }
 
+    /// Declare that `val` is a GC root, which must be reported in the stack
+    /// maps of the calls it is live across.
+    ///
+    /// When the function is finalized, a value which needs a stack map is
+    /// spilled to a stack slot after its definition and reloaded before each
+    /// of its uses if it is live across any call, and each such call gets a
+    /// user stack map entry for the slot (see
+    /// `MachBufferFinalized::user_stack_maps`). Block parameters receiving the
+    /// value as an argument need stack maps too.
+    pub fn declare_needs_stack_map(&mut self, val: Value) {
+        self.func_ctx.safepoints.declare(val);
+    }
+
+    /// Declare that all values of `var` are GC roots, as if each value defined
+    /// with [`FunctionBuilder::def_var`] or returned by
+    /// [`FunctionBuilder::use_var`] was declared with
+    /// [`FunctionBuilder::declare_needs_stack_map`].
+    pub fn declare_var_needs_stack_map(&mut self, var: Variable) {
+        self.func_ctx.stack_map_vars.insert(var);
+    }
+
     /// Set label for Value
     ///
     /// This will not do anything unless `func.dfg.collect_debug_info` is called first.
@@ -647,6 +687,9 @@ impl<'a> FunctionBuilder<'a> {
             }
         }
 
+        // Spill the values which need stack maps around safepoints.
+        self.func_ctx.safepoints.run(self.func);
+
         // Clear the state (but preserve the allocated buffers) in preparation
         // for translation another function.
         self.func_ctx.clear();
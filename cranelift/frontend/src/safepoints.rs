@@ -0,0 +1,460 @@
+//! Spilling of GC roots around safepoints, for user stack maps.
+//!
+//! Values declared with [`FunctionBuilder::declare_needs_stack_map`] are GC
+//! roots which the runtime must be able to find, and possibly update, during
+//! every call they are live across. When the function is finalized, each such
+//! value which is live across a call is stored to a stack slot right after its
+//! definition and reloaded from the slot before each of its uses, and the calls
+//! it is live across get a user stack map entry for the slot. This way, a
+//! moving collector which updates the slot during a call is observed by all
+//! later uses of the value.
+//!
+//! A block parameter receiving a value which needs a stack map holds a GC root
+//! as well, so it also needs a stack map.
+//!
+//! [`FunctionBuilder::declare_needs_stack_map`]: crate::FunctionBuilder::declare_needs_stack_map
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::entity::{EntitySet, SecondaryMap};
+use cranelift_codegen::flowgraph::ControlFlowGraph;
+use cranelift_codegen::ir::{
+    Block, Function, Inst, InstBuilder, Opcode, StackSlot, StackSlotData, StackSlotKind, Type,
+    UserStackMapEntry, Value, ValueDef,
+};
+
+/// Spills the values needing stack maps around the safepoints of a function.
+#[derive(Default)]
+pub(crate) struct SafepointSpiller {
+    /// The values declared as needing stack maps.
+    declared: Vec<Value>,
+
+    /// The values needing stack maps, with aliases resolved.
+    needs_stack_map: EntitySet<Value>,
+
+    /// The values needing stack maps which are live into each block.
+    live_ins: SecondaryMap<Block, BTreeSet<Value>>,
+
+    /// The values needing stack maps which are live across each safepoint.
+    safepoints: Vec<(Inst, Vec<Value>)>,
+
+    /// The values each value needing a stack map is live at the same time as.
+    interference: BTreeMap<Value, BTreeSet<Value>>,
+}
+
+impl SafepointSpiller {
+    pub(crate) fn clear(&mut self) {
+        self.declared.clear();
+        self.needs_stack_map.clear();
+        self.live_ins.clear();
+        self.safepoints.clear();
+        self.interference.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.declared.is_empty()
+    }
+
+    /// Declare that `value` needs a stack map.
+    pub(crate) fn declare(&mut self, value: Value) {
+        self.declared.push(value);
+    }
+
+    /// Spill the values needing stack maps in `func` around its safepoints.
+    pub(crate) fn run(&mut self, func: &mut Function) {
+        if self.declared.is_empty() {
+            return;
+        }
+        for &value in self.declared.iter() {
+            self.needs_stack_map.insert(func.dfg.resolve_aliases(value));
+        }
+        self.propagate_to_block_params(func);
+        self.compute_liveness(func);
+
+        let slots = self.assign_stack_slots(func);
+        if slots.is_empty() {
+            return;
+        }
+
+        for (inst, values) in self.safepoints.iter() {
+            for value in values {
+                let entry = UserStackMapEntry {
+                    ty: func.dfg.value_type(*value),
+                    slot: slots[value],
+                    offset: 0,
+                };
+                func.dfg.append_user_stack_map_entry(*inst, entry);
+            }
+        }
+        insert_reloads(func, &slots);
+        insert_spills(func, &slots);
+    }
+
+    /// Mark block parameters receiving values which need stack maps as
+    /// needing stack maps themselves.
+    fn propagate_to_block_params(&mut self, func: &Function) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in func.layout.blocks() {
+                let inst = match func.layout.last_inst(block) {
+                    Some(inst) => inst,
+                    None => continue,
+                };
+                for dest in func.dfg.insts[inst].branch_destination(&func.dfg.jump_tables) {
+                    let params = func.dfg.block_params(dest.block(&func.dfg.value_lists));
+                    let args = dest.args_slice(&func.dfg.value_lists);
+                    for (&param, &arg) in params.iter().zip(args) {
+                        if self.needs_stack_map.contains(func.dfg.resolve_aliases(arg))
+                            && !self.needs_stack_map.contains(param)
+                        {
+                            self.needs_stack_map.insert(param);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Compute which values needing stack maps are live into each block and
+    /// across each safepoint, and which of them are live at the same time.
+    fn compute_liveness(&mut self, func: &Function) {
+        let cfg = ControlFlowGraph::with_function(func);
+        let blocks: Vec<Block> = func.layout.blocks().collect();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in blocks.iter().rev() {
+                let live = self.block_liveness(func, &cfg, block, false);
+                if live != self.live_ins[block] {
+                    self.live_ins[block] = live;
+                    changed = true;
+                }
+            }
+        }
+
+        // With the live-in sets known, record the safepoints and interference.
+        for &block in blocks.iter() {
+            self.block_liveness(func, &cfg, block, true);
+        }
+    }
+
+    /// Compute the values needing stack maps which are live into `block`,
+    /// recording its safepoints and the interference between values if
+    /// `record` is set.
+    fn block_liveness(
+        &mut self,
+        func: &Function,
+        cfg: &ControlFlowGraph,
+        block: Block,
+        record: bool,
+    ) -> BTreeSet<Value> {
+        let mut live = BTreeSet::new();
+        for succ in cfg.succ_iter(block) {
+            live.extend(self.live_ins[succ].iter().copied());
+        }
+
+        for inst in func.layout.block_insts(block).rev() {
+            for &result in func.dfg.inst_results(inst) {
+                live.remove(&result);
+            }
+            if record {
+                for &result in func.dfg.inst_results(inst) {
+                    if self.needs_stack_map.contains(result) {
+                        self.interfere(result, &live);
+                        self.interfere(result, func.dfg.inst_results(inst));
+                    }
+                }
+                if is_safepoint(func, inst) && !live.is_empty() {
+                    self.safepoints.push((inst, live.iter().copied().collect()));
+                }
+            }
+            for arg in func.dfg.inst_values(inst) {
+                let arg = func.dfg.resolve_aliases(arg);
+                if self.needs_stack_map.contains(arg) {
+                    live.insert(arg);
+                }
+            }
+        }
+
+        let params = func.dfg.block_params(block);
+        if record {
+            for &param in params {
+                if self.needs_stack_map.contains(param) {
+                    self.interfere(param, &live);
+                    self.interfere(param, params);
+                }
+            }
+        }
+        for param in params {
+            live.remove(param);
+        }
+        live
+    }
+
+    /// Record that `value` is live at the same time as `others`.
+    fn interfere<'a>(&mut self, value: Value, others: impl IntoIterator<Item = &'a Value>) {
+        for &other in others {
+            if other != value && self.needs_stack_map.contains(other) {
+                self.interference.entry(value).or_default().insert(other);
+                self.interference.entry(other).or_default().insert(value);
+            }
+        }
+    }
+
+    /// Assign a stack slot to each value live across a safepoint, sharing
+    /// slots between values of the same type which are never live at the same
+    /// time.
+    fn assign_stack_slots(&self, func: &mut Function) -> BTreeMap<Value, StackSlot> {
+        let spilled: BTreeSet<Value> = self
+            .safepoints
+            .iter()
+            .flat_map(|(_, values)| values.iter().copied())
+            .collect();
+
+        let mut slots = BTreeMap::new();
+        let mut free_slots: Vec<(Type, StackSlot)> = Vec::new();
+        for value in spilled {
+            let ty = func.dfg.value_type(value);
+            let interference = self.interference.get(&value);
+            let slot = free_slots
+                .iter()
+                .filter(|&&(slot_ty, _)| slot_ty == ty)
+                .map(|&(_, slot)| slot)
+                .find(|&slot| {
+                    interference
+                        .into_iter()
+                        .flatten()
+                        .all(|other| slots.get(other) != Some(&slot))
+                });
+            let slot = slot.unwrap_or_else(|| {
+                let slot = func.create_sized_stack_slot(StackSlotData::new(
+                    StackSlotKind::ExplicitSlot,
+                    ty.bytes(),
+                ));
+                free_slots.push((ty, slot));
+                slot
+            });
+            slots.insert(value, slot);
+        }
+        slots
+    }
+}
+
+/// Is `inst` a safepoint, i.e. a call which returns to this function?
+fn is_safepoint(func: &Function, inst: Inst) -> bool {
+    matches!(
+        func.dfg.insts[inst].opcode(),
+        Opcode::Call | Opcode::CallIndirect
+    )
+}
+
+/// Reload the spilled values from their stack slots before each of their uses.
+fn insert_reloads(func: &mut Function, slots: &BTreeMap<Value, StackSlot>) {
+    let mut pos = FuncCursor::new(func);
+    while pos.next_block().is_some() {
+        while let Some(inst) = pos.next_inst() {
+            let mut reloads: Vec<(Value, Value)> = Vec::new();
+            for arg in pos.func.dfg.inst_values(inst).collect::<Vec<_>>() {
+                let arg = pos.func.dfg.resolve_aliases(arg);
+                let slot = match slots.get(&arg) {
+                    Some(&slot) => slot,
+                    None => continue,
+                };
+                if reloads.iter().any(|&(value, _)| value == arg) {
+                    continue;
+                }
+                let srcloc = pos.func.srcloc(inst);
+                pos.set_srcloc(srcloc);
+                let ty = pos.func.dfg.value_type(arg);
+                let reload = pos.ins().stack_load(ty, slot, 0);
+                reloads.push((arg, reload));
+            }
+            if reloads.is_empty() {
+                continue;
+            }
+            pos.func.dfg.map_inst_values(inst, |dfg, arg| {
+                let arg = dfg.resolve_aliases(arg);
+                reloads
+                    .iter()
+                    .find(|&&(value, _)| value == arg)
+                    .map_or(arg, |&(_, reload)| reload)
+            });
+        }
+    }
+}
+
+/// Store the spilled values to their stack slots right after their
+/// definitions.
+fn insert_spills(func: &mut Function, slots: &BTreeMap<Value, StackSlot>) {
+    for (&value, &slot) in slots {
+        let mut pos = FuncCursor::new(func);
+        match pos.func.dfg.value_def(value) {
+            ValueDef::Result(inst, _) => {
+                let srcloc = pos.func.srcloc(inst);
+                pos = pos.after_inst(inst).with_srcloc(srcloc);
+            }
+            ValueDef::Param(block, _) => {
+                pos = pos.at_first_insertion_point(block);
+                if let Some(inst) = pos.current_inst() {
+                    let srcloc = pos.func.srcloc(inst);
+                    pos.set_srcloc(srcloc);
+                }
+            }
+            ValueDef::Union(..) => unreachable!("the frontend does not create unions"),
+        }
+        pos.ins().stack_store(value, slot, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frontend::{FunctionBuilder, FunctionBuilderContext};
+    use crate::Variable;
+    use alloc::string::ToString;
+    use cranelift_codegen::entity::EntityRef;
+    use cranelift_codegen::ir::{
+        types, AbiParam, ExtFuncData, ExternalName, Function, InstBuilder, Signature, UserFuncName,
+    };
+    use cranelift_codegen::isa::CallConv;
+    use cranelift_codegen::settings;
+    use cranelift_codegen::verifier::verify_function;
+
+    fn declare_callee(builder: &mut FunctionBuilder) -> cranelift_codegen::ir::FuncRef {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I32));
+        let signature = builder.import_signature(sig);
+        builder.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature,
+            colocated: true,
+        })
+    }
+
+    fn check(func: &Function, expected: &str) {
+        let flags = settings::Flags::new(settings::builder());
+        verify_function(func, &flags).unwrap();
+        let actual = func.display().to_string();
+        assert_eq!(actual.trim(), expected.trim(), "actual:\n{actual}");
+    }
+
+    #[test]
+    fn spill_across_calls() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I64));
+        sig.params.push(AbiParam::new(types::I32));
+        sig.returns.push(AbiParam::new(types::I64));
+        let mut func = Function::with_name_signature(UserFuncName::testcase("sample"), sig);
+        let mut ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut ctx);
+        let callee = declare_callee(&mut builder);
+
+        let block0 = builder.create_block();
+        builder.append_block_params_for_function_params(block0);
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+        let v0 = builder.block_params(block0)[0];
+        let v1 = builder.block_params(block0)[1];
+        builder.declare_needs_stack_map(v0);
+        // Not live across any call, so not spilled.
+        let v2 = builder.ins().iconst(types::I64, 42);
+        builder.declare_needs_stack_map(v2);
+        let v3 = builder.ins().iadd(v0, v2);
+        builder.ins().call(callee, &[v1]);
+        builder.ins().call(callee, &[v1]);
+        let v4 = builder.ins().iadd(v0, v3);
+        builder.ins().return_(&[v4]);
+        builder.finalize();
+
+        check(
+            &func,
+            r#"
+function %sample(i64, i32) -> i64 system_v {
+    ss0 = explicit_slot 8
+    sig0 = (i32) system_v
+    fn0 = colocated %callee sig0
+
+block0(v0: i64, v1: i32):
+    stack_store v0, ss0
+    v2 = iconst.i64 42
+    v5 = stack_load.i64 ss0
+    v3 = iadd v5, v2  ; v2 = 42
+    call fn0(v1), stack_map=[i64 @ ss0+0]
+    call fn0(v1), stack_map=[i64 @ ss0+0]
+    v6 = stack_load.i64 ss0
+    v4 = iadd v6, v3
+    return v4
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn spill_variable_across_loop() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I32));
+        sig.params.push(AbiParam::new(types::I32));
+        sig.returns.push(AbiParam::new(types::I32));
+        let mut func = Function::with_name_signature(UserFuncName::testcase("sample"), sig);
+        let mut ctx = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut ctx);
+        let callee = declare_callee(&mut builder);
+
+        let x = Variable::new(0);
+        builder.declare_var(x, types::I32);
+        builder.declare_var_needs_stack_map(x);
+
+        let block0 = builder.create_block();
+        let block1 = builder.create_block();
+        let block2 = builder.create_block();
+        builder.append_block_params_for_function_params(block0);
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+        let v0 = builder.block_params(block0)[0];
+        let v1 = builder.block_params(block0)[1];
+        builder.def_var(x, v0);
+        builder.ins().jump(block1, &[]);
+
+        // The loop header gets a parameter for `x`, which needs a stack map.
+        builder.switch_to_block(block1);
+        builder.ins().call(callee, &[v1]);
+        let v2 = builder.use_var(x);
+        let v3 = builder.ins().iadd_imm(v2, 1);
+        builder.def_var(x, v3);
+        builder.ins().brif(v3, block1, &[], block2, &[]);
+        builder.seal_block(block1);
+
+        builder.switch_to_block(block2);
+        builder.seal_block(block2);
+        let v4 = builder.use_var(x);
+        builder.ins().return_(&[v4]);
+        builder.finalize();
+
+        check(
+            &func,
+            r#"
+function %sample(i32, i32) -> i32 system_v {
+    ss0 = explicit_slot 4
+    sig0 = (i32) system_v
+    fn0 = colocated %callee sig0
+
+block0(v0: i32, v1: i32):
+    jump block1(v0)
+
+block1(v2: i32):
+    stack_store v2, ss0
+    call fn0(v1), stack_map=[i32 @ ss0+0]
+    v4 = stack_load.i32 ss0
+    v3 = iadd_imm v4, 1
+    brif v3, block1(v3), block2
+
+block2:
+    return v3
+}
+"#,
+        );
+    }
+}
@@ -574,6 +574,7 @@ fn valid_for_target(triple: &Triple, op: Opcode, args: &[Type], rets: &[Type]) -
                 (Opcode::Uunarrow),
                 (Opcode::Snarrow | Opcode::Unarrow, &[I64X2, I64X2]),
                 (Opcode::SqmulRoundSat, &[I32X4, I32X4]),
+                (Opcode::AvgRound, &[I32X4 | I64X2, I32X4 | I64X2]),
                 // This Icmp is not implemented: #5529
                 (Opcode::Icmp, &[I64X2, I64X2]),
                 // IaddPairwise is implemented, but only for some types, and with some preceding ops.
@@ -875,7 +876,8 @@ static OPCODE_SIGNATURES: Lazy<Vec<OpcodeSignature>> = Lazy::new(|| {
                 (Opcode::CallIndirect, &[I32]),
                 (Opcode::FuncAddr),
                 (Opcode::X86Pshufb),
-                (Opcode::AvgRound),
+                // No backend lowers scalar `avg_round`.
+                (Opcode::AvgRound, &[I8 | I16 | I32 | I64 | I128, _]),
                 (Opcode::Uload8x8),
                 (Opcode::Sload8x8),
                 (Opcode::Uload16x4),
@@ -894,6 +896,7 @@ static OPCODE_SIGNATURES: Lazy<Vec<OpcodeSignature>> = Lazy::new(|| {
                 (Opcode::GetFramePointer),
                 (Opcode::GetStackPointer),
                 (Opcode::GetReturnAddress),
+                (Opcode::StackSwitch),
                 (Opcode::TableAddr),
                 (Opcode::Null),
                 (Opcode::X86Blendv),
@@ -934,10 +937,6 @@ static OPCODE_SIGNATURES: Lazy<Vec<OpcodeSignature>> = Lazy::new(|| {
                 (Opcode::Isplit, &[I64], &[I32, I32]),
                 (Opcode::Isplit, &[I32], &[I16, I16]),
                 (Opcode::Isplit, &[I16], &[I8, I8]),
-                (Opcode::Fmin, &[F32X4, F32X4], &[F32X4]),
-                (Opcode::Fmin, &[F64X2, F64X2], &[F64X2]),
-                (Opcode::Fmax, &[F32X4, F32X4], &[F32X4]),
-                (Opcode::Fmax, &[F64X2, F64X2], &[F64X2]),
                 (Opcode::FcvtToUintSat, &[F32X4], &[I8]),
                 (Opcode::FcvtToUintSat, &[F64X2], &[I8]),
                 (Opcode::FcvtToUintSat, &[F32X4], &[I16]),
@@ -556,7 +556,7 @@ mod tests {
     use super::*;
     use crate::step::CraneliftTrap;
     use cranelift_codegen::ir::immediates::Ieee32;
-    use cranelift_codegen::ir::TrapCode;
+    use cranelift_codegen::ir::{Opcode, TrapCode};
     use cranelift_reader::parse_functions;
     use smallvec::smallvec;
 
@@ -916,7 +916,7 @@ mod tests {
     }
 
     #[test]
-    fn srem_trap() {
+    fn srem_min_by_neg_one_does_not_trap() {
         let code = "function %test() -> i64 {
         block0:
             v0 = iconst.i64 0x8000_0000_0000_0000
@@ -929,12 +929,9 @@ mod tests {
         let mut env = FunctionStore::default();
         env.add(func.name.to_string(), &func);
         let state = InterpreterState::default().with_function_store(env);
-        let trap = Interpreter::new(state).call_by_name("%test", &[]).unwrap();
+        let result = Interpreter::new(state).call_by_name("%test", &[]).unwrap();
 
-        assert_eq!(
-            trap,
-            ControlFlow::Trap(CraneliftTrap::User(TrapCode::IntegerOverflow))
-        );
+        assert_eq!(result, ControlFlow::Return(smallvec![DataValue::I64(0)]));
     }
 
     #[test]
@@ -1061,4 +1058,45 @@ mod tests {
             ControlFlow::Trap(CraneliftTrap::User(TrapCode::HeapMisaligned))
         );
     }
+
+    #[test]
+    fn stack_switch_is_unsupported() {
+        let code = "function %test(i64, i64, i64) -> i64 {
+        block0(v0: i64, v1: i64, v2: i64):
+            v3 = stack_switch v0, v1, v2
+            return v3
+        }";
+
+        let func = parse_functions(code).unwrap().into_iter().next().unwrap();
+        let mut env = FunctionStore::default();
+        env.add(func.name.to_string(), &func);
+        let state = InterpreterState::default().with_function_store(env);
+        let args = [DataValue::I64(0), DataValue::I64(0), DataValue::I64(0)];
+        let result = Interpreter::new(state).call_by_name("%test", &args);
+
+        match result {
+            Err(InterpreterError::StepError(StepError::Unsupported(Opcode::StackSwitch))) => {}
+            _ => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn get_frame_pointer_is_unsupported() {
+        let code = "function %test() -> i64 {
+        block0:
+            v0 = get_frame_pointer.i64
+            return v0
+        }";
+
+        let func = parse_functions(code).unwrap().into_iter().next().unwrap();
+        let mut env = FunctionStore::default();
+        env.add(func.name.to_string(), &func);
+        let state = InterpreterState::default().with_function_store(env);
+        let result = Interpreter::new(state).call_by_name("%test", &[]);
+
+        match result {
+            Err(InterpreterError::StepError(StepError::Unsupported(Opcode::GetFramePointer))) => {}
+            _ => panic!("Unexpected result: {:?}", result),
+        }
+    }
 }
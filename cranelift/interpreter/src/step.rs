@@ -3,7 +3,7 @@
 use crate::address::{Address, AddressSize};
 use crate::instruction::InstructionContext;
 use crate::state::{InterpreterFunctionRef, MemoryError, State};
-use crate::value::{DataValueExt, ValueConversionKind, ValueError, ValueResult};
+use crate::value::{DataValueExt, ValueConversionKind, ValueError, ValueResult, ValueTypeClass};
 use cranelift_codegen::data_value::DataValue;
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
 use cranelift_codegen::ir::{
@@ -380,7 +380,7 @@ where
                     InterpreterFunctionRef::Function(function)
                 }
                 ExternalName::LibCall(libcall) => InterpreterFunctionRef::LibCall(libcall),
-                ExternalName::KnownSymbol(_) => unimplemented!(),
+                ExternalName::KnownSymbol(_) => return Err(StepError::Unsupported(inst.opcode())),
             };
 
             let make_control_flow = match inst.opcode() {
@@ -455,12 +455,30 @@ where
                 Opcode::Sload16 => (types::I16, Some(ValueConversionKind::SignExtend(ctrl_ty))),
                 Opcode::Uload32 => (types::I32, Some(ValueConversionKind::ZeroExtend(ctrl_ty))),
                 Opcode::Sload32 => (types::I32, Some(ValueConversionKind::SignExtend(ctrl_ty))),
-                Opcode::Uload8x8
-                | Opcode::Sload8x8
-                | Opcode::Uload16x4
-                | Opcode::Sload16x4
-                | Opcode::Uload32x2
-                | Opcode::Sload32x2 => unimplemented!(),
+                Opcode::Uload8x8 => (
+                    types::I8X8,
+                    Some(ValueConversionKind::ZeroExtend(types::I16)),
+                ),
+                Opcode::Sload8x8 => (
+                    types::I8X8,
+                    Some(ValueConversionKind::SignExtend(types::I16)),
+                ),
+                Opcode::Uload16x4 => (
+                    types::I16X4,
+                    Some(ValueConversionKind::ZeroExtend(types::I32)),
+                ),
+                Opcode::Sload16x4 => (
+                    types::I16X4,
+                    Some(ValueConversionKind::SignExtend(types::I32)),
+                ),
+                Opcode::Uload32x2 => (
+                    types::I32X2,
+                    Some(ValueConversionKind::ZeroExtend(types::I64)),
+                ),
+                Opcode::Sload32x2 => (
+                    types::I32X2,
+                    Some(ValueConversionKind::SignExtend(types::I64)),
+                ),
                 _ => unreachable!(),
             };
 
@@ -472,6 +490,15 @@ where
             );
 
             match (loaded, kind) {
+                // The vector loads extend each of the loaded lanes to twice its width. Note that
+                // `ctrl_ty` is the address type for these.
+                (ControlFlow::Assign(ret), Some(c)) if load_ty.is_vector() => {
+                    let lanes = extractlanes(&ret[0], load_ty)?
+                        .into_iter()
+                        .map(|lane| lane.convert(c.clone()))
+                        .collect::<ValueResult<SimdVec<DataValue>>>()?;
+                    assign(vectorizelanes(&lanes, load_ty.double_width().unwrap())?)
+                }
                 (ControlFlow::Assign(ret), Some(c)) => ControlFlow::Assign(
                     ret.into_iter()
                         .map(|loaded| loaded.convert(c.clone()))
@@ -535,9 +562,10 @@ where
                 })
             })
         }
-        Opcode::DynamicStackAddr => unimplemented!("DynamicStackSlot"),
-        Opcode::DynamicStackLoad => unimplemented!("DynamicStackLoad"),
-        Opcode::DynamicStackStore => unimplemented!("DynamicStackStore"),
+        // Dynamic vector types have no `DataValue` representation.
+        Opcode::DynamicStackAddr | Opcode::DynamicStackLoad | Opcode::DynamicStackStore => {
+            return Err(StepError::Unsupported(inst.opcode()))
+        }
         Opcode::GlobalValue | Opcode::SymbolValue | Opcode::TlsValue => {
             if let InstructionData::UnaryGlobalValue { global_value, .. } = inst {
                 assign_or_memtrap(state.resolve_global_value(global_value))
@@ -577,7 +605,8 @@ where
         Opcode::F32const => assign(imm()),
         Opcode::F64const => assign(imm()),
         Opcode::Vconst => assign(imm()),
-        Opcode::Null => unimplemented!("Null"),
+        // Reference types have no `DataValue` representation.
+        Opcode::Null => return Err(StepError::Unsupported(inst.opcode())),
         Opcode::Nop => ControlFlow::Continue,
         Opcode::Select | Opcode::SelectSpectreGuard => choose(arg(0).into_bool()?, arg(1), arg(2)),
         Opcode::Bitselect => assign(bitselect(arg(0), arg(1), arg(2))?),
@@ -621,11 +650,14 @@ where
             }
         }
         Opcode::AvgRound => {
-            let sum = DataValueExt::add(arg(0), arg(1))?;
-            let one = DataValueExt::int(1, arg(0).ty())?;
-            let inc = DataValueExt::add(sum, one)?;
-            let two = DataValueExt::int(2, arg(0).ty())?;
-            binary(DataValueExt::udiv, inc, two)?
+            // `(x + y + 1) / 2`, computed without overflowing the unsigned lanes.
+            let avg_round = |x: DataValue, y: DataValue| -> ValueResult<DataValue> {
+                let x = x.into_int_unsigned()?;
+                let y = y.into_int_unsigned()?;
+                let avg = (x >> 1) + (y >> 1) + ((x | y) & 1);
+                DataValueExt::int(avg as i128, ctrl_ty.lane_type())
+            };
+            assign(binary_arith(arg(0), arg(1), ctrl_ty, avg_round)?)
         }
         Opcode::Iadd => binary(DataValueExt::add, arg(0), arg(1))?,
         Opcode::UaddSat => assign(binary_arith(
@@ -671,13 +703,30 @@ where
             assign(vectorizelanes(&new_vec, ctrl_ty)?)
         }
         Opcode::Imul => binary(DataValueExt::mul, arg(0), arg(1))?,
+        Opcode::Umulhi | Opcode::Smulhi if ctrl_ty == types::I128 => {
+            // There is no wider `DataValue` to multiply in, so take the
+            // upper half of the unsigned product and correct it for the
+            // sign of each operand.
+            let x = arg(0).into_int_unsigned()?;
+            let y = arg(1).into_int_unsigned()?;
+            let mut hi = umulhi_u128(x, y);
+            if inst.opcode() == Opcode::Smulhi {
+                if (x as i128) < 0 {
+                    hi = hi.wrapping_sub(y);
+                }
+                if (y as i128) < 0 {
+                    hi = hi.wrapping_sub(x);
+                }
+            }
+            assign(DataValue::I128(hi as i128))
+        }
         Opcode::Umulhi | Opcode::Smulhi => {
             let double_length = match ctrl_ty.lane_bits() {
                 8 => types::I16,
                 16 => types::I32,
                 32 => types::I64,
                 64 => types::I128,
-                _ => unimplemented!("Unsupported integer length {}", ctrl_ty.bits()),
+                _ => unreachable!(),
             };
             let conv_type = if inst.opcode() == Opcode::Umulhi {
                 ValueConversionKind::ZeroExtend(double_length)
@@ -872,26 +921,29 @@ where
         Opcode::Fneg => unary(DataValueExt::neg, arg(0))?,
         Opcode::Fabs => unary(DataValueExt::abs, arg(0))?,
         Opcode::Fcopysign => binary(DataValueExt::copysign, arg(0), arg(1))?,
-        Opcode::Fmin => assign(match (arg(0), arg(1)) {
-            (a, _) if a.is_nan()? => a,
-            (_, b) if b.is_nan()? => b,
-            (a, b) if a.is_zero()? && b.is_zero()? && a.is_negative()? => a,
-            (a, b) if a.is_zero()? && b.is_zero()? && b.is_negative()? => b,
-            (a, b) => a.smin(b)?,
-        }),
-        Opcode::Fmax => assign(match (arg(0), arg(1)) {
-            (a, _) if a.is_nan()? => a,
-            (_, b) if b.is_nan()? => b,
-            (a, b) if a.is_zero()? && b.is_zero()? && a.is_negative()? => b,
-            (a, b) if a.is_zero()? && b.is_zero()? && b.is_negative()? => a,
-            (a, b) => a.smax(b)?,
-        }),
+        Opcode::Fmin => assign(binary_arith(arg(0), arg(1), ctrl_ty, |a, b| {
+            Ok(match (a, b) {
+                (a, _) if a.is_nan()? => a,
+                (_, b) if b.is_nan()? => b,
+                (a, b) if a.is_zero()? && b.is_zero()? && a.is_negative()? => a,
+                (a, b) if a.is_zero()? && b.is_zero()? && b.is_negative()? => b,
+                (a, b) => a.smin(b)?,
+            })
+        })?),
+        Opcode::Fmax => assign(binary_arith(arg(0), arg(1), ctrl_ty, |a, b| {
+            Ok(match (a, b) {
+                (a, _) if a.is_nan()? => a,
+                (_, b) if b.is_nan()? => b,
+                (a, b) if a.is_zero()? && b.is_zero()? && a.is_negative()? => b,
+                (a, b) if a.is_zero()? && b.is_zero()? && b.is_negative()? => a,
+                (a, b) => a.smax(b)?,
+            })
+        })?),
         Opcode::Ceil => unary(DataValueExt::ceil, arg(0))?,
         Opcode::Floor => unary(DataValueExt::floor, arg(0))?,
         Opcode::Trunc => unary(DataValueExt::trunc, arg(0))?,
         Opcode::Nearest => unary(DataValueExt::nearest, arg(0))?,
-        Opcode::IsNull => unimplemented!("IsNull"),
-        Opcode::IsInvalid => unimplemented!("IsInvalid"),
+        Opcode::IsNull | Opcode::IsInvalid => return Err(StepError::Unsupported(inst.opcode())),
         Opcode::Bitcast | Opcode::ScalarToVector => {
             let input_ty = inst_context.type_of(inst_context.args()[0]).unwrap();
             let lanes = &if input_ty.is_vector() {
@@ -1069,21 +1121,27 @@ where
                     TrapCode::BadConversionToInteger,
                 )));
             }
-            let x = arg(0).into_float()? as i128;
+            // Check the bounds before converting, since `as` would saturate out-of-range values
+            // of the 128-bit types.
+            let x = arg(0).into_float()?.trunc();
             let is_signed = inst.opcode() == Opcode::FcvtToSint;
-            let (min, max) = ctrl_ty.bounds(is_signed);
+            let bits = ctrl_ty.bits() as i32;
             let overflow = if is_signed {
-                x < (min as i128) || x > (max as i128)
+                x < -(2f64.powi(bits - 1)) || x >= 2f64.powi(bits - 1)
             } else {
-                x < 0 || (x as u128) > (max as u128)
+                x < 0.0 || x >= 2f64.powi(bits)
             };
-            // bounds check
             if overflow {
                 return Ok(ControlFlow::Trap(CraneliftTrap::User(
                     TrapCode::IntegerOverflow,
                 )));
             }
             // perform the conversion.
+            let x = if is_signed {
+                x as i128
+            } else {
+                x as u128 as i128
+            };
             assign(DataValueExt::int(x, ctrl_ty)?)
         }
         Opcode::FcvtToUintSat | Opcode::FcvtToSintSat => {
@@ -1095,13 +1153,13 @@ where
                 } else {
                     let is_signed = inst.opcode() == Opcode::FcvtToSintSat;
                     let (min, max) = ctrl_ty.bounds(is_signed);
-                    let x = x.into_float()? as i128;
+                    let x = x.into_float()?;
                     let x = if is_signed {
+                        let x = x as i128;
                         let x = i128::max(x, min as i128);
                         let x = i128::min(x, max as i128);
                         x
                     } else {
-                        let x = if x < 0 { 0 } else { x };
                         let x = u128::min(x as u128, max as u128);
                         x as i128
                     };
@@ -1138,7 +1196,7 @@ where
                         x.into_int_signed()? as f64
                     })
                     .to_bits(),
-                    _ => unimplemented!("unexpected conversion to {:?}", ctrl_ty.lane_type()),
+                    ty => return Err(ValueError::InvalidType(ValueTypeClass::Float, ty)),
                 })
             };
             assign(vectorizelanes(
@@ -1183,10 +1241,13 @@ where
             }
             assign(vectorizelanes(x, out_ty)?)
         }
-        Opcode::Isplit => assign_multiple(&[
-            DataValueExt::convert(arg(0), ValueConversionKind::Truncate(types::I64))?,
-            DataValueExt::convert(arg(0), ValueConversionKind::ExtractUpper(types::I64))?,
-        ]),
+        Opcode::Isplit => {
+            let half_ty = arg(0).ty().half_width().unwrap();
+            assign_multiple(&[
+                DataValueExt::convert(arg(0), ValueConversionKind::Truncate(half_ty))?,
+                DataValueExt::convert(arg(0), ValueConversionKind::ExtractUpper(half_ty))?,
+            ])
+        }
         Opcode::Iconcat => assign(DataValueExt::concat(arg(0), arg(1))?),
         Opcode::AtomicRmw => {
             let op = inst.atomic_rmw_op().unwrap();
@@ -1292,17 +1353,96 @@ where
         Opcode::IaddPairwise => {
             assign(binary_pairwise(arg(0), arg(1), ctrl_ty, DataValueExt::add)?)
         }
-        Opcode::ExtractVector => {
-            unimplemented!("ExtractVector not supported");
-        }
-        Opcode::GetFramePointer => unimplemented!("GetFramePointer"),
-        Opcode::GetStackPointer => unimplemented!("GetStackPointer"),
-        Opcode::GetReturnAddress => unimplemented!("GetReturnAddress"),
-        Opcode::X86Pshufb => unimplemented!("X86Pshufb"),
-        Opcode::X86Blendv => unimplemented!("X86Blendv"),
-        Opcode::X86Pmulhrsw => unimplemented!("X86Pmulhrsw"),
-        Opcode::X86Pmaddubsw => unimplemented!("X86Pmaddubsw"),
-        Opcode::X86Cvtt2dq => unimplemented!("X86Cvtt2dq"),
+        // The interpreter has no machine frames, so there are no frame or
+        // stack pointers and no return addresses to hand out.
+        Opcode::ExtractVector
+        | Opcode::GetFramePointer
+        | Opcode::GetStackPointer
+        | Opcode::GetReturnAddress => return Err(StepError::Unsupported(inst.opcode())),
+        // The interpreter doesn't run on machine stacks, so there are none to
+        // switch between.
+        Opcode::StackSwitch => return Err(StepError::Unsupported(inst.opcode())),
+        Opcode::X86Pshufb => {
+            let x = DataValueExt::into_array(&arg(0))?;
+            let y = DataValueExt::into_array(&arg(1))?;
+            let mut new = [0u8; 16];
+            for i in 0..new.len() {
+                if y[i] & 0x80 == 0 {
+                    new[i] = x[(y[i] & 0xf) as usize];
+                } // else leave as 0
+            }
+            assign(DataValueExt::vector(new, types::I8X16)?)
+        }
+        Opcode::X86Blendv => {
+            // Only the most significant bit of each lane of the condition is used.
+            let c = extractlanes(&arg(0), ctrl_ty.as_int())?;
+            let x = extractlanes(&arg(1), ctrl_ty)?;
+            let y = extractlanes(&arg(2), ctrl_ty)?;
+            let new_vec = c
+                .into_iter()
+                .zip(x)
+                .zip(y)
+                .map(|((c, x), y)| Ok(if c.into_int_signed()? < 0 { x } else { y }))
+                .collect::<ValueResult<SimdVec<DataValue>>>()?;
+            assign(vectorizelanes(&new_vec, ctrl_ty)?)
+        }
+        Opcode::X86Pmulhrsw => {
+            // Like `sqmul_round_sat`, but `MIN * MIN` wraps around to `MIN` instead of
+            // saturating.
+            let lane_type = ctrl_ty.lane_type();
+            let arg0 = extractlanes(&arg(0), ctrl_ty)?;
+            let arg1 = extractlanes(&arg(1), ctrl_ty)?;
+            let new_vec = arg0
+                .into_iter()
+                .zip(arg1)
+                .map(|(x, y)| {
+                    let x = x.into_int_signed()?;
+                    let y = y.into_int_signed()?;
+                    let z = (x * y + (1 << (lane_type.bits() - 2))) >> (lane_type.bits() - 1);
+                    DataValueExt::int(z, lane_type)
+                })
+                .collect::<ValueResult<SimdVec<_>>>()?;
+            assign(vectorizelanes(&new_vec, ctrl_ty)?)
+        }
+        Opcode::X86Pmaddubsw => {
+            // Multiply the signed bytes of `x` by the unsigned bytes of `y`, and add adjacent
+            // pairs of the products with signed saturation.
+            let x = extractlanes(&arg(0), types::I8X16)?;
+            let y = extractlanes(&arg(1), types::I8X16)?;
+            let (min, max) = types::I16.bounds(true);
+            let products = x
+                .into_iter()
+                .zip(y)
+                .map(|(x, y)| Ok(x.into_int_signed()? * (y.into_int_unsigned()? as i128)))
+                .collect::<ValueResult<SmallVec<[i128; 16]>>>()?;
+            let new_vec = products
+                .chunks(2)
+                .map(|pair| {
+                    let sum = (pair[0] + pair[1]).clamp(min as i128, max as i128);
+                    DataValueExt::int(sum, types::I16)
+                })
+                .collect::<ValueResult<SimdVec<_>>>()?;
+            assign(vectorizelanes(&new_vec, types::I16X8)?)
+        }
+        Opcode::X86Cvtt2dq => {
+            // Every lane is converted as by `cvttps2dq`/`cvttpd2dq`: NaN and out-of-bounds lanes
+            // produce `i32::MIN`, which is then sign-extended to the output lane type.
+            let in_ty = inst_context.type_of(inst_context.args()[0]).unwrap();
+            let cvt = |x: DataValue| -> ValueResult<DataValue> {
+                let x = x.into_float()?.trunc();
+                let x = if x.is_nan() || x < i32::MIN as f64 || x > i32::MAX as f64 {
+                    i32::MIN
+                } else {
+                    x as i32
+                };
+                DataValueExt::int(x as i128, ctrl_ty.lane_type())
+            };
+            let new_vec = extractlanes(&arg(0), in_ty)?
+                .into_iter()
+                .map(cvt)
+                .collect::<ValueResult<SimdVec<_>>>()?;
+            assign(vectorizelanes(&new_vec, ctrl_ty)?)
+        }
     })
 }
 
@@ -1316,6 +1456,8 @@ pub enum StepError {
     ValueError(#[from] ValueError),
     #[error("failed to access memory")]
     MemoryError(#[from] MemoryError),
+    #[error("the interpreter doesn't support `{0}`")]
+    Unsupported(Opcode),
 }
 
 /// Enumerate the ways in which the control flow can change based on a single step in a Cranelift
@@ -1446,7 +1588,7 @@ pub(crate) fn extractlanes(
         types::I16 => 2,
         types::I32 | types::F32 => 4,
         types::I64 | types::F64 => 8,
-        _ => unimplemented!("vectors with lanes wider than 64-bits are currently unsupported."),
+        _ => return Err(ValueError::InvalidType(ValueTypeClass::Vector, vector_type)),
     };
 
     let x = x.into_array()?;
@@ -1485,7 +1627,7 @@ fn vectorizelanes_all(x: &[DataValue], vector_type: types::Type) -> ValueResult<
         types::I16 => 2,
         types::I32 | types::F32 => 4,
         types::I64 | types::F64 => 8,
-        _ => unimplemented!("vectors with lanes wider than 64-bits are currently unsupported."),
+        _ => return Err(ValueError::InvalidType(ValueTypeClass::Vector, vector_type)),
     };
     let mut result: [u8; 16] = [0; 16];
     for (i, val) in x.iter().enumerate() {
@@ -1589,3 +1731,15 @@ fn splat(ty: Type, val: DataValue) -> ValueResult<DataValue> {
 fn shift_amt(ty: Type, val: DataValue) -> ValueResult<DataValue> {
     splat(ty, val.convert(ValueConversionKind::Exact(ty.lane_type()))?)
 }
+
+// Computes the upper 128 bits of the 256-bit product of `x` and `y`.
+fn umulhi_u128(x: u128, y: u128) -> u128 {
+    let (x_lo, x_hi) = (x as u64 as u128, x >> 64);
+    let (y_lo, y_hi) = (y as u64 as u128, y >> 64);
+    let lo_lo = x_lo * y_lo;
+    let hi_lo = x_hi * y_lo;
+    let lo_hi = x_lo * y_hi;
+    let hi_hi = x_hi * y_hi;
+    let carry = ((lo_lo >> 64) + (hi_lo as u64 as u128) + (lo_hi as u64 as u128)) >> 64;
+    hi_hi + (hi_lo >> 64) + (lo_hi >> 64) + carry
+}
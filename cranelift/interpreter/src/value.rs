@@ -110,6 +110,12 @@ pub enum ValueError {
     IntegerDivisionByZero,
     #[error("performed a operation that overflowed this integer type")]
     IntegerOverflow,
+    #[error("operation is not supported for type {0}")]
+    UnsupportedType(Type),
+    #[error("operation is not supported for types {0} and {1}")]
+    UnsupportedTypes(Type, Type),
+    #[error("unable to convert type {0} with {1:?}")]
+    UnsupportedConversion(Type, ValueConversionKind),
 }
 
 #[derive(Debug, PartialEq)]
@@ -131,7 +137,7 @@ impl Display for ValueTypeClass {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ValueConversionKind {
     /// Throw a [ValueError] if an exact conversion to [Type] is not possible; e.g. in `i32` to
     /// `i16`, convert `0x00001234` to `0x1234`.
@@ -165,19 +171,19 @@ macro_rules! unary_match {
             $( DataValue::$data_value_ty(a) => {
                 Ok(DataValue::$data_value_ty($return_value_ty::try_from(a.$op()).unwrap()))
             } )*
-            _ => unimplemented!()
+            a => Err(ValueError::UnsupportedType(a.ty()))
         }
     };
     ( $op:ident($arg1:expr); [ $( $data_value_ty:ident ),* ] ) => {
         match $arg1 {
             $( DataValue::$data_value_ty(a) => { Ok(DataValue::$data_value_ty(a.$op())) } )*
-            _ => unimplemented!()
+            a => Err(ValueError::UnsupportedType(a.ty()))
         }
     };
     ( $op:tt($arg1:expr); [ $( $data_value_ty:ident ),* ] ) => {
         match $arg1 {
             $( DataValue::$data_value_ty(a) => { Ok(DataValue::$data_value_ty($op a)) } )*
-            _ => unimplemented!()
+            a => Err(ValueError::UnsupportedType(a.ty()))
         }
     };
 }
@@ -185,19 +191,19 @@ macro_rules! binary_match {
     ( $op:ident($arg1:expr, $arg2:expr); [ $( $data_value_ty:ident ),* ] ) => {
         match ($arg1, $arg2) {
             $( (DataValue::$data_value_ty(a), DataValue::$data_value_ty(b)) => { Ok(DataValue::$data_value_ty(a.$op(*b))) } )*
-            _ => unimplemented!()
+            (a, b) => Err(ValueError::UnsupportedTypes(a.ty(), b.ty()))
         }
     };
     ( $op:ident($arg1:expr, $arg2:expr); [ $( $data_value_ty:ident ),* ]; [ $( $op_type:ty ),* ] ) => {
         match ($arg1, $arg2) {
             $( (DataValue::$data_value_ty(a), DataValue::$data_value_ty(b)) => { Ok(DataValue::$data_value_ty((*a as $op_type).$op(*b as $op_type) as _)) } )*
-            _ => unimplemented!()
+            (a, b) => Err(ValueError::UnsupportedTypes(a.ty(), b.ty()))
         }
     };
     ( option $op:ident($arg1:expr, $arg2:expr); [ $( $data_value_ty:ident ),* ]; [ $( $op_type:ty ),* ] ) => {
         match ($arg1, $arg2) {
             $( (DataValue::$data_value_ty(a), DataValue::$data_value_ty(b)) => { Ok((*a as $op_type).$op(*b as $op_type).map(|v| DataValue::$data_value_ty(v as _))) } )*
-            _ => unimplemented!()
+            (a, b) => Err(ValueError::UnsupportedTypes(a.ty(), b.ty()))
         }
     };
     ( pair $op:ident($arg1:expr, $arg2:expr); [ $( $data_value_ty:ident ),* ]; [ $( $op_type:ty ),* ] ) => {
@@ -206,25 +212,25 @@ macro_rules! binary_match {
                 let (f, s) = (*a as $op_type).$op(*b as $op_type);
                 Ok((DataValue::$data_value_ty(f as _), s))
             } )*
-            _ => unimplemented!()
+            (a, b) => Err(ValueError::UnsupportedTypes(a.ty(), b.ty()))
         }
     };
     ( $op:tt($arg1:expr, $arg2:expr); [ $( $data_value_ty:ident ),* ] ) => {
         match ($arg1, $arg2) {
             $( (DataValue::$data_value_ty(a), DataValue::$data_value_ty(b)) => { Ok(DataValue::$data_value_ty(a $op b)) } )*
-            _ => unimplemented!()
+            (a, b) => Err(ValueError::UnsupportedTypes(a.ty(), b.ty()))
         }
     };
     ( $op:tt($arg1:expr, $arg2:expr); [ $( $data_value_ty:ident ),* ]; [ $( $op_type:ty ),* ] ) => {
         match ($arg1, $arg2) {
             $( (DataValue::$data_value_ty(a), DataValue::$data_value_ty(b)) => { Ok(DataValue::$data_value_ty(((*a as $op_type) $op (*b as $op_type)) as _)) } )*
-            _ => unimplemented!()
+            (a, b) => Err(ValueError::UnsupportedTypes(a.ty(), b.ty()))
         }
     };
     ( $op:tt($arg1:expr, $arg2:expr); [ $( $data_value_ty:ident ),* ]; [ $( $a_type:ty ),* ]; rhs: $rhs:tt,$rhs_type:ty ) => {
         match ($arg1, $arg2) {
             $( (DataValue::$data_value_ty(a), DataValue::$rhs(b)) => { Ok(DataValue::$data_value_ty((*a as $a_type).$op(*b as $rhs_type) as _)) } )*
-            _ => unimplemented!()
+            (a, b) => Err(ValueError::UnsupportedTypes(a.ty(), b.ty()))
         }
     };
     ( $op:ident($arg1:expr, $arg2:expr); unsigned integers ) => {
@@ -256,7 +262,14 @@ macro_rules! bitop {
                 }
                 DataValue::V128(a2)
             }
-            _ => unimplemented!(),
+            (DataValue::V64(a), DataValue::V64(b)) => {
+                let mut a2 = a.clone();
+                for (a, b) in a2.iter_mut().zip(b.iter()) {
+                    *a = *a $op *b;
+                }
+                DataValue::V64(a2)
+            }
+            (a, b) => return Err(ValueError::UnsupportedTypes(a.ty(), b.ty())),
         })
     };
 }
@@ -375,7 +388,7 @@ impl DataValueExt for DataValue {
             let v64: [u8; 8] = v[..8].try_into().unwrap();
             Ok(DataValue::V64(v64))
         } else {
-            unimplemented!()
+            unreachable!()
         }
     }
 
@@ -404,27 +417,21 @@ impl DataValueExt for DataValue {
                 (DataValue::F64(n), types::I64) => DataValue::I64(n.bits() as i64),
                 (DataValue::F32(n), types::F64) => DataValue::F64((n.as_f32() as f64).into()),
                 (dv, t) if (t.is_int() || t.is_float()) && dv.ty() == t => dv,
-                (dv, _) => unimplemented!("conversion: {} -> {:?}", dv.ty(), kind),
+                (dv, _) => return Err(ValueError::UnsupportedConversion(dv.ty(), kind)),
             },
             ValueConversionKind::Truncate(ty) => {
-                assert!(
-                    ty.is_int(),
-                    "unimplemented conversion: {} -> {:?}",
-                    self.ty(),
-                    kind
-                );
+                if !ty.is_int() {
+                    return Err(ValueError::UnsupportedConversion(self.ty(), kind));
+                }
 
                 let mask = (1 << (ty.bytes() * 8)) - 1i128;
                 let truncated = self.into_int_signed()? & mask;
                 Self::from_integer(truncated, ty)?
             }
             ValueConversionKind::ExtractUpper(ty) => {
-                assert!(
-                    ty.is_int(),
-                    "unimplemented conversion: {} -> {:?}",
-                    self.ty(),
-                    kind
-                );
+                if !ty.is_int() {
+                    return Err(ValueError::UnsupportedConversion(self.ty(), kind));
+                }
 
                 let shift_amt = (self.ty().bytes() * 8) - (ty.bytes() * 8);
                 let mask = (1 << (ty.bytes() * 8)) - 1i128;
@@ -444,7 +451,7 @@ impl DataValueExt for DataValue {
                 (DataValue::I32(n), types::I64) => DataValue::I64(n as i64),
                 (DataValue::I32(n), types::I128) => DataValue::I128(n as i128),
                 (DataValue::I64(n), types::I128) => DataValue::I128(n as i128),
-                (dv, _) => unimplemented!("conversion: {} -> {:?}", dv.ty(), kind),
+                (dv, _) => return Err(ValueError::UnsupportedConversion(dv.ty(), kind)),
             },
             ValueConversionKind::ZeroExtend(ty) => match (self, ty) {
                 (DataValue::I8(n), types::I16) => DataValue::I16(n as u8 as i16),
@@ -458,17 +465,18 @@ impl DataValueExt for DataValue {
                 (DataValue::I32(n), types::I128) => DataValue::I128(n as u32 as i128),
                 (DataValue::I64(n), types::I128) => DataValue::I128(n as u64 as i128),
                 (from, to) if from.ty() == to => from,
-                (dv, _) => unimplemented!("conversion: {} -> {:?}", dv.ty(), kind),
+                (dv, _) => return Err(ValueError::UnsupportedConversion(dv.ty(), kind)),
             },
             ValueConversionKind::RoundNearestEven(ty) => match (self, ty) {
                 (DataValue::F64(n), types::F32) => DataValue::F32(Ieee32::from(n.as_f64() as f32)),
-                (s, _) => unimplemented!("conversion: {} -> {:?}", s.ty(), kind),
+                (dv, t) if dv.ty() == t => dv,
+                (s, _) => return Err(ValueError::UnsupportedConversion(s.ty(), kind)),
             },
             ValueConversionKind::ToBoolean => match self.ty() {
                 ty if ty.is_int() => {
                     DataValue::I8(if self.into_int_signed()? != 0 { 1 } else { 0 })
                 }
-                ty => unimplemented!("conversion: {} -> {:?}", ty, kind),
+                ty => return Err(ValueError::UnsupportedConversion(ty, kind)),
             },
             ValueConversionKind::Mask(ty) => {
                 let b = self.into_bool()?;
@@ -478,12 +486,20 @@ impl DataValueExt for DataValue {
     }
 
     fn concat(self, other: Self) -> ValueResult<Self> {
-        match (self, other) {
-            (DataValue::I64(lhs), DataValue::I64(rhs)) => Ok(DataValue::I128(
-                (((lhs as u64) as u128) | (((rhs as u64) as u128) << 64)) as i128,
-            )),
-            (lhs, rhs) => unimplemented!("concat: {} -> {}", lhs.ty(), rhs.ty()),
-        }
+        let ty = self.ty();
+        if other.ty() != ty {
+            return Err(ValueError::InvalidType(ValueTypeClass::Integer, other.ty()));
+        }
+        let double_ty = ty
+            .double_width()
+            .filter(|ty| ty.is_int() && !ty.is_vector())
+            .ok_or(ValueError::InvalidType(ValueTypeClass::Integer, ty))?;
+        let lo = self.into_int_unsigned()?;
+        let hi = other.into_int_unsigned()?;
+        Ok(DataValue::from_integer(
+            (lo | (hi << ty.bits())) as i128,
+            double_ty,
+        )?)
     }
 
     fn is_negative(&self) -> ValueResult<bool> {
@@ -610,17 +626,12 @@ impl DataValueExt for DataValue {
     fn srem(self, other: Self) -> ValueResult<Self> {
         let denominator = other.clone().into_int_signed()?;
 
-        // Check if we are dividing INT_MIN / -1. This causes an integer overflow trap.
-        let min = DataValueExt::int(1i128 << (self.ty().bits() - 1), self.ty())?;
-        if self == min && denominator == -1 {
-            return Err(ValueError::IntegerOverflow);
-        }
-
         if denominator == 0 {
             return Err(ValueError::IntegerDivisionByZero);
         }
 
-        binary_match!(%(&self, &other); [I8, I16, I32, I64, I128])
+        // Unlike `sdiv`, `INT_MIN % -1` does not trap: the remainder is simply zero.
+        binary_match!(wrapping_rem(&self, &other); [I8, I16, I32, I64, I128])
     }
 
     fn urem(self, other: Self) -> ValueResult<Self> {
@@ -800,7 +811,13 @@ impl DataValueExt for DataValue {
                 }
                 DataValue::V128(a2)
             }
-            _ => unimplemented!(),
+            DataValue::V64(a) => {
+                let mut a2 = a.clone();
+                for a in a2.iter_mut() {
+                    *a = !*a;
+                }
+                DataValue::V64(a2)
+            }
         })
     }
 
@@ -0,0 +1,22 @@
+mod coverage;
+
+struct Context;
+impl coverage::Context for Context {}
+
+fn main() {
+    let mut ctx = Context;
+
+    assert_eq!(coverage::constructor_classify(&mut ctx, 0), 10);
+    assert_eq!(coverage::constructor_classify(&mut ctx, 0), 10);
+    assert_eq!(coverage::constructor_classify(&mut ctx, 5), 12);
+
+    let counts: Vec<_> = coverage::rule_coverage().collect();
+    assert_eq!(counts.len(), 3);
+    assert!(counts[0].0.contains("coverage.isle:4:"));
+    assert!(counts[1].0.contains("coverage.isle:5:"));
+    assert!(counts[2].0.contains("coverage.isle:6:"));
+    assert_eq!(
+        counts.iter().map(|&(_, count)| count).collect::<Vec<_>>(),
+        [2, 0, 1]
+    );
+}
@@ -1,9 +1,10 @@
 //! Generate Rust code from a series of Sequences.
 
+use crate::lexer::Pos;
 use crate::sema::{ExternalSig, ReturnKind, Sym, Term, TermEnv, TermId, Type, TypeEnv, TypeId};
 use crate::serialize::{Block, ControlFlow, EvalStep, MatchArm};
 use crate::trie_again::{Binding, BindingId, Constraint, RuleSet};
-use crate::StableSet;
+use crate::{StableMap, StableSet};
 use std::fmt::Write;
 
 /// Options for code generation.
@@ -12,6 +13,11 @@ pub struct CodegenOptions {
     /// Do not include the `#![allow(...)]` pragmas in the generated
     /// source. Useful if it must be include!()'d elsewhere.
     pub exclude_global_allow_pragmas: bool,
+
+    /// Count how many times each rule fires at runtime. The generated code
+    /// then provides a `rule_coverage()` function, which returns the source
+    /// position of every rule as `file:line:column` along with its count.
+    pub rule_coverage: bool,
 }
 
 /// Emit Rust source code for the given type and term environments.
@@ -21,7 +27,7 @@ pub fn codegen(
     terms: &[(TermId, RuleSet)],
     options: &CodegenOptions,
 ) -> String {
-    Codegen::compile(typeenv, termenv, terms).generate_rust(options)
+    Codegen::compile(typeenv, termenv, terms, options).generate_rust(options)
 }
 
 #[derive(Clone, Debug)]
@@ -29,6 +35,9 @@ struct Codegen<'a> {
     typeenv: &'a TypeEnv,
     termenv: &'a TermEnv,
     terms: &'a [(TermId, RuleSet)],
+    /// If we're counting rule firings, the index of each rule's counter,
+    /// keyed by the rule's position.
+    rule_counters: Option<StableMap<Pos, usize>>,
 }
 
 struct BodyContext<'a, W> {
@@ -85,11 +94,21 @@ impl<'a> Codegen<'a> {
         typeenv: &'a TypeEnv,
         termenv: &'a TermEnv,
         terms: &'a [(TermId, RuleSet)],
+        options: &CodegenOptions,
     ) -> Codegen<'a> {
+        let rule_counters = options.rule_coverage.then(|| {
+            let mut counters = StableMap::new();
+            for rule in termenv.rules.iter() {
+                let index = counters.len();
+                counters.insert(rule.pos, index);
+            }
+            counters
+        });
         Codegen {
             typeenv,
             termenv,
             terms,
+            rule_counters,
         }
     }
 
@@ -100,6 +119,9 @@ impl<'a> Codegen<'a> {
         self.generate_ctx_trait(&mut code);
         self.generate_internal_types(&mut code);
         self.generate_internal_term_constructors(&mut code).unwrap();
+        if self.rule_counters.is_some() {
+            self.generate_rule_coverage(&mut code);
+        }
 
         code
     }
@@ -138,6 +160,43 @@ impl<'a> Codegen<'a> {
         writeln!(code, "use std::marker::PhantomData;").unwrap();
     }
 
+    fn generate_rule_coverage(&self, code: &mut String) {
+        let num_rules = self.termenv.rules.len();
+        writeln!(
+            code,
+            "\n/// The source position of each rule, as `file:line:column`."
+        )
+        .unwrap();
+        writeln!(code, "pub const RULE_SPANS: [&str; {}] = [", num_rules).unwrap();
+        for rule in self.termenv.rules.iter() {
+            let span = format!(
+                "{}:{}:{}",
+                self.typeenv.filenames[rule.pos.file],
+                rule.pos.line,
+                rule.pos.col + 1
+            );
+            writeln!(code, "    {:?},", span).unwrap();
+        }
+        writeln!(code, "];").unwrap();
+        writeln!(
+            code,
+            r#"
+#[allow(clippy::declare_interior_mutable_const)]
+const RULE_COUNT_ZERO: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+/// How many times each rule in `RULE_SPANS` has fired.
+pub static RULE_COUNTS: [core::sync::atomic::AtomicUsize; {num_rules}] = [RULE_COUNT_ZERO; {num_rules}];
+
+/// The source position of each rule and how many times it has fired.
+pub fn rule_coverage() -> impl Iterator<Item = (&'static str, usize)> {{
+    RULE_SPANS.iter().zip(RULE_COUNTS.iter()).map(|(span, count)| {{
+        (*span, count.load(core::sync::atomic::Ordering::Relaxed))
+    }})
+}}"#,
+        )
+        .unwrap();
+    }
+
     fn generate_trait_sig(&self, code: &mut String, indent: &str, sig: &ExternalSig) {
         let ret_tuple = format!(
             "{open_paren}{rets}{close_paren}",
@@ -526,6 +585,13 @@ impl<'a> Codegen<'a> {
                         &ctx.indent,
                         pos.pretty_print_line(&self.typeenv.filenames)
                     )?;
+                    if let Some(counters) = &self.rule_counters {
+                        writeln!(
+                            ctx.out,
+                            "{}RULE_COUNTS[{}].fetch_add(1, core::sync::atomic::Ordering::Relaxed);",
+                            &ctx.indent, counters[&pos]
+                        )?;
+                    }
                     write!(ctx.out, "{}", &ctx.indent)?;
                     match ret_kind {
                         ReturnKind::Plain => write!(ctx.out, "return ")?,
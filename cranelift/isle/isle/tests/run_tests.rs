@@ -1,11 +1,16 @@
 //! Helper for autogenerated unit tests.
 
+use cranelift_isle::codegen::CodegenOptions;
 use cranelift_isle::compile;
 use cranelift_isle::error::Errors;
 use std::default::Default;
 
 fn build(filename: &str) -> Result<String, Errors> {
-    compile::from_files(&[filename], &Default::default())
+    build_with_options(filename, &Default::default())
+}
+
+fn build_with_options(filename: &str, options: &CodegenOptions) -> Result<String, Errors> {
+    compile::from_files([filename], options)
 }
 
 pub fn run_pass(filename: &str) {
@@ -24,9 +29,12 @@ pub fn run_fail(filename: &str) {
     }
 }
 
-fn build_and_link_isle(isle_filename: &str) -> (tempfile::TempDir, std::path::PathBuf) {
+fn build_and_link_isle(
+    isle_filename: &str,
+    options: &CodegenOptions,
+) -> (tempfile::TempDir, std::path::PathBuf) {
     let tempdir = tempfile::tempdir().unwrap();
-    let code = build(isle_filename).unwrap();
+    let code = build_with_options(isle_filename, options).unwrap();
 
     let isle_filename_base = std::path::Path::new(isle_filename)
         .file_stem()
@@ -60,11 +68,11 @@ fn build_and_link_isle(isle_filename: &str) -> (tempfile::TempDir, std::path::Pa
 }
 
 pub fn run_link(isle_filename: &str) {
-    build_and_link_isle(isle_filename);
+    build_and_link_isle(isle_filename, &Default::default());
 }
 
-pub fn run_run(isle_filename: &str) {
-    let (_tempdir, exe) = build_and_link_isle(isle_filename);
+fn build_and_run_isle(isle_filename: &str, options: &CodegenOptions) {
+    let (_tempdir, exe) = build_and_link_isle(isle_filename, options);
 
     assert!(std::process::Command::new(exe)
         .spawn()
@@ -74,5 +82,17 @@ pub fn run_run(isle_filename: &str) {
         .success());
 }
 
+pub fn run_run(isle_filename: &str) {
+    build_and_run_isle(isle_filename, &Default::default());
+}
+
+pub fn run_coverage(isle_filename: &str) {
+    let options = CodegenOptions {
+        rule_coverage: true,
+        ..Default::default()
+    };
+    build_and_run_isle(isle_filename, &options);
+}
+
 // Generated by build.rs.
 include!(concat!(env!("OUT_DIR"), "/isle_tests.rs"));
@@ -1,4 +1,5 @@
 use clap::Parser;
+use cranelift_isle::codegen::CodegenOptions;
 use cranelift_isle::compile;
 use cranelift_isle::error::Errors;
 use std::{
@@ -15,6 +16,11 @@ struct Opts {
     #[clap(short, long)]
     output: Option<PathBuf>,
 
+    /// Count how many times each rule fires at runtime, so that rules which
+    /// are never used can be found.
+    #[clap(long)]
+    rule_coverage: bool,
+
     /// The input ISLE DSL source files.
     #[clap(required = true)]
     inputs: Vec<PathBuf>,
@@ -24,7 +30,11 @@ fn main() -> Result<(), Errors> {
     let _ = env_logger::try_init();
 
     let opts = Opts::parse();
-    let code = compile::from_files(opts.inputs, &Default::default())?;
+    let options = CodegenOptions {
+        rule_coverage: opts.rule_coverage,
+        ..Default::default()
+    };
+    let code = compile::from_files(opts.inputs, &options)?;
 
     let stdout = io::stdout();
     let (mut output, output_name): (Box<dyn Write>, _) = match &opts.output {
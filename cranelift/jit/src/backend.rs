@@ -1,7 +1,13 @@
 //! Defines `JITModule`.
 
+#[cfg(feature = "gdb-jit")]
+use crate::gdb_jit::GdbJitRegistry;
+#[cfg(target_arch = "x86_64")]
+use crate::tls::TlsDescriptor;
+use crate::unwind::UnwindRegistry;
 use crate::{compiled_blob::CompiledBlob, memory::BranchProtection, memory::Memory};
 use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::isa::unwind::UnwindInfo;
 use cranelift_codegen::isa::{OwnedTargetIsa, TargetIsa};
 use cranelift_codegen::settings::Configurable;
 use cranelift_codegen::{self, ir, settings, FinalizedMachReloc};
@@ -67,6 +73,12 @@ impl JITBuilder {
         // we require long-range relocation types.
         flag_builder.set("use_colocated_libcalls", "false").unwrap();
         flag_builder.set("is_pic", "true").unwrap();
+        // Thread-local variables are accessed through the JIT's own
+        // implementation of `__tls_get_addr`, whatever the host platform is.
+        // A caller choosing another model gets an error on declaring TLS data.
+        if !flags.iter().any(|(name, _)| *name == "tls_model") {
+            flag_builder.set("tls_model", "elf_gd").unwrap();
+        }
         let isa_builder = cranelift_native::builder().unwrap_or_else(|msg| {
             panic!("host machine is not supported: {}", msg);
         });
@@ -166,6 +178,22 @@ struct GotUpdate {
     ptr: *const u8,
 }
 
+/// A function awaiting registration with the unwinder and debuggers.
+#[cfg_attr(not(feature = "gdb-jit"), allow(dead_code))]
+struct PendingFunction {
+    /// The id of the function.
+    id: FuncId,
+
+    /// The address of the code of the function.
+    ptr: *const u8,
+
+    /// The size of the code of the function.
+    size: usize,
+
+    /// The unwind information of the function, if any.
+    unwind_info: Option<UnwindInfo>,
+}
+
 /// A `JITModule` implements `Module` and emits code and data into memory where it can be
 /// directly called and accessed.
 ///
@@ -181,6 +209,8 @@ pub struct JITModule {
     function_got_entries: SecondaryMap<FuncId, Option<NonNull<AtomicPtr<u8>>>>,
     function_plt_entries: SecondaryMap<FuncId, Option<NonNull<[u8; 16]>>>,
     data_object_got_entries: SecondaryMap<DataId, Option<NonNull<AtomicPtr<u8>>>>,
+    #[cfg(target_arch = "x86_64")]
+    tls_descriptors: SecondaryMap<DataId, Option<NonNull<TlsDescriptor>>>,
     libcall_got_entries: HashMap<ir::LibCall, NonNull<AtomicPtr<u8>>>,
     libcall_plt_entries: HashMap<ir::LibCall, NonNull<[u8; 16]>>,
     compiled_functions: SecondaryMap<FuncId, Option<CompiledBlob>>,
@@ -190,6 +220,16 @@ pub struct JITModule {
 
     /// Updates to the GOT awaiting relocations to be made and region protections to be set
     pending_got_updates: Vec<GotUpdate>,
+
+    /// Functions awaiting registration once they are finalized
+    functions_to_register: Vec<PendingFunction>,
+
+    /// The registered unwind information of finalized functions
+    unwind_registry: UnwindRegistry,
+
+    /// The images published for finalized functions through the GDB JIT interface
+    #[cfg(feature = "gdb-jit")]
+    gdb_jit_registry: GdbJitRegistry,
 }
 
 /// A handle to allow freeing memory allocated by the `Module`.
@@ -209,6 +249,13 @@ impl JITModule {
     /// from that module are currently executing and none of the `fn` pointers
     /// are called afterwards.
     pub unsafe fn free_memory(mut self) {
+        self.unwind_registry.deregister();
+        #[cfg(feature = "gdb-jit")]
+        self.gdb_jit_registry.deregister();
+        #[cfg(target_arch = "x86_64")]
+        for desc in self.tls_descriptors.values().flatten() {
+            desc.as_ref().release();
+        }
         self.memory.code.free_memory();
         self.memory.readonly.free_memory();
         self.memory.writable.free_memory();
@@ -231,6 +278,16 @@ impl JITModule {
         }
     }
 
+    fn lookup_libcall(&self, libcall: ir::LibCall) -> Option<*const u8> {
+        match libcall {
+            // The JIT's thread-local variables are unknown to the dynamic
+            // linker's `__tls_get_addr`.
+            #[cfg(target_arch = "x86_64")]
+            ir::LibCall::ElfTlsGetAddr => Some(crate::tls::tls_get_addr as *const u8),
+            _ => self.lookup_symbol(&(self.libcall_names)(libcall)),
+        }
+    }
+
     fn new_got_entry(&mut self, val: *const u8) -> NonNull<AtomicPtr<u8>> {
         let got_entry = self
             .memory
@@ -285,6 +342,42 @@ impl JITModule {
         self.data_object_got_entries[id] = Some(got_entry);
     }
 
+    #[cfg(not(target_arch = "x86_64"))]
+    fn new_tls_descriptor(&mut self, _id: DataId) -> ModuleResult<()> {
+        Err(ModuleError::Backend(anyhow::anyhow!(
+            "JIT doesn't yet support TLS on this architecture"
+        )))
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn new_tls_descriptor(&mut self, id: DataId) -> ModuleResult<()> {
+        if self.isa.flags().tls_model() != settings::TlsModel::ElfGd || !self.isa.flags().is_pic() {
+            return Err(ModuleError::Backend(anyhow::anyhow!(
+                "JIT requires `tls_model=elf_gd` and `is_pic` for TLS"
+            )));
+        }
+        if self.tls_descriptors[id].is_some() {
+            return Ok(());
+        }
+        let desc = self
+            .memory
+            .writable
+            .allocate(
+                std::mem::size_of::<TlsDescriptor>(),
+                std::mem::align_of::<TlsDescriptor>().try_into().unwrap(),
+            )
+            .map_err(|e| ModuleError::Allocation {
+                message: "unable to alloc TLS descriptor",
+                err: e,
+            })?
+            .cast::<TlsDescriptor>();
+        unsafe {
+            std::ptr::write(desc, TlsDescriptor::new());
+        }
+        self.tls_descriptors[id] = NonNull::new(desc);
+        Ok(())
+    }
+
     unsafe fn write_plt_entry_bytes(plt_ptr: *mut [u8; 16], got_ptr: NonNull<AtomicPtr<u8>>) {
         assert!(
             cfg!(target_arch = "x86_64"),
@@ -318,6 +411,11 @@ impl JITModule {
                     }
                 } else {
                     let data_id = DataId::from_name(name);
+                    // TLS relocations refer to the descriptor of the variable.
+                    #[cfg(target_arch = "x86_64")]
+                    if let Some(desc) = self.tls_descriptors[data_id] {
+                        return desc.as_ptr().cast();
+                    }
                     match &self.compiled_data_objects[data_id] {
                         Some(compiled) => return compiled.ptr,
                         None => {
@@ -338,9 +436,9 @@ impl JITModule {
                 }
             }
             ModuleRelocTarget::LibCall(ref libcall) => {
-                let sym = (self.libcall_names)(*libcall);
-                self.lookup_symbol(&sym)
-                    .unwrap_or_else(|| panic!("can't resolve libcall {}", sym))
+                self.lookup_libcall(*libcall).unwrap_or_else(|| {
+                    panic!("can't resolve libcall {}", (self.libcall_names)(*libcall))
+                })
             }
             _ => panic!("invalid name"),
         }
@@ -487,6 +585,23 @@ impl JITModule {
         for update in self.pending_got_updates.drain(..) {
             unsafe { update.entry.as_ref() }.store(update.ptr as *mut _, Ordering::SeqCst);
         }
+
+        for func in std::mem::take(&mut self.functions_to_register) {
+            if let Some(unwind_info) = &func.unwind_info {
+                self.unwind_registry
+                    .register(&*self.isa, func.ptr, unwind_info)?;
+            }
+            #[cfg(feature = "gdb-jit")]
+            {
+                let decl = self.declarations.get_function_decl(func.id);
+                self.gdb_jit_registry.register(
+                    &*self.isa,
+                    &decl.linkage_name(func.id),
+                    func.ptr,
+                    func.size,
+                )?;
+            }
+        }
         Ok(())
     }
 
@@ -521,6 +636,8 @@ impl JITModule {
             function_got_entries: SecondaryMap::new(),
             function_plt_entries: SecondaryMap::new(),
             data_object_got_entries: SecondaryMap::new(),
+            #[cfg(target_arch = "x86_64")]
+            tls_descriptors: SecondaryMap::new(),
             libcall_got_entries: HashMap::new(),
             libcall_plt_entries: HashMap::new(),
             compiled_functions: SecondaryMap::new(),
@@ -528,6 +645,10 @@ impl JITModule {
             functions_to_finalize: Vec::new(),
             data_objects_to_finalize: Vec::new(),
             pending_got_updates: Vec::new(),
+            functions_to_register: Vec::new(),
+            unwind_registry: UnwindRegistry::new(),
+            #[cfg(feature = "gdb-jit")]
+            gdb_jit_registry: GdbJitRegistry::new(),
         };
 
         // Pre-create a GOT and PLT entry for each libcall.
@@ -537,8 +658,7 @@ impl JITModule {
             &[] // Not PIC, so no GOT and PLT entries necessary
         };
         for &libcall in all_libcalls {
-            let sym = (module.libcall_names)(libcall);
-            let addr = if let Some(addr) = module.lookup_symbol(&sym) {
+            let addr = if let Some(addr) = module.lookup_libcall(libcall) {
                 addr
             } else {
                 continue;
@@ -625,11 +745,18 @@ impl Module for JITModule {
         writable: bool,
         tls: bool,
     ) -> ModuleResult<DataId> {
-        assert!(!tls, "JIT doesn't yet support TLS");
+        if tls && linkage == Linkage::Import {
+            return Err(ModuleError::Backend(anyhow::anyhow!(
+                "JIT doesn't support importing TLS data: {}",
+                name
+            )));
+        }
         let (id, linkage) = self
             .declarations
             .declare_data(name, linkage, writable, tls)?;
-        if self.data_object_got_entries[id].is_none() && self.isa.flags().is_pic() {
+        if tls {
+            self.new_tls_descriptor(id)?;
+        } else if self.data_object_got_entries[id].is_none() && self.isa.flags().is_pic() {
             // FIXME populate got entries with a null pointer when defined
             let val = if linkage == Linkage::Import {
                 self.lookup_symbol(name).unwrap_or(std::ptr::null())
@@ -642,9 +769,10 @@ impl Module for JITModule {
     }
 
     fn declare_anonymous_data(&mut self, writable: bool, tls: bool) -> ModuleResult<DataId> {
-        assert!(!tls, "JIT doesn't yet support TLS");
         let id = self.declarations.declare_anonymous_data(writable, tls)?;
-        if self.isa.flags().is_pic() {
+        if tls {
+            self.new_tls_descriptor(id)?;
+        } else if self.isa.flags().is_pic() {
             self.new_data_got_entry(id, std::ptr::null());
         }
         Ok(id)
@@ -714,9 +842,16 @@ impl Module for JITModule {
             .iter()
             .map(|reloc| ModuleReloc::from_mach_reloc(reloc, &ctx.func))
             .collect();
+        let unwind_info = compiled_code.create_unwind_info(self.isa())?;
 
         self.record_function_for_perf(ptr, size, &decl.linkage_name(id));
         self.compiled_functions[id] = Some(CompiledBlob { ptr, size, relocs });
+        self.functions_to_register.push(PendingFunction {
+            id,
+            ptr,
+            size,
+            unwind_info,
+        });
 
         if self.isa.flags().is_pic() {
             self.pending_got_updates.push(GotUpdate {
@@ -731,6 +866,12 @@ impl Module for JITModule {
                 .unwrap()
                 .perform_relocations(
                     |name| match *name {
+                        ModuleRelocTarget::User { .. }
+                            if !ModuleDeclarations::is_function(name)
+                                && self.declarations.get_data_decl(DataId::from_name(name)).tls =>
+                        {
+                            self.get_address(name)
+                        }
                         ModuleRelocTarget::User { .. } => {
                             unreachable!("non GOT or PLT relocation in function {} to {}", id, name)
                         }
@@ -800,6 +941,12 @@ impl Module for JITModule {
                 .map(|reloc| ModuleReloc::from_mach_reloc(reloc, func))
                 .collect(),
         });
+        self.functions_to_register.push(PendingFunction {
+            id,
+            ptr,
+            size,
+            unwind_info: None,
+        });
 
         if self.isa.flags().is_pic() {
             self.pending_got_updates.push(GotUpdate {
@@ -838,8 +985,6 @@ impl Module for JITModule {
             ));
         }
 
-        assert!(!decl.tls, "JIT doesn't yet support TLS");
-
         let &DataDescription {
             ref init,
             function_decls: _,
@@ -851,7 +996,9 @@ impl Module for JITModule {
         } = data;
 
         let size = init.size();
-        let ptr = if decl.writable {
+        // The definition of a TLS variable is the template each thread's copy
+        // is initialized from; the copies themselves are what is written to.
+        let ptr = if decl.writable && !decl.tls {
             self.memory
                 .writable
                 .allocate(size, align.unwrap_or(WRITABLE_DATA_ALIGNMENT))
@@ -891,7 +1038,22 @@ impl Module for JITModule {
 
         self.compiled_data_objects[id] = Some(CompiledBlob { ptr, size, relocs });
         self.data_objects_to_finalize.push(id);
-        if self.isa.flags().is_pic() {
+        if decl.tls {
+            #[cfg(target_arch = "x86_64")]
+            {
+                let init = match *init {
+                    Init::Bytes { .. } => ptr.cast_const(),
+                    _ => ptr::null(),
+                };
+                let align = align.unwrap_or(if decl.writable {
+                    WRITABLE_DATA_ALIGNMENT
+                } else {
+                    READONLY_DATA_ALIGNMENT
+                });
+                let mut desc = self.tls_descriptors[id].unwrap();
+                unsafe { desc.as_mut() }.define(init, size, align);
+            }
+        } else if self.isa.flags().is_pic() {
             self.pending_got_updates.push(GotUpdate {
                 entry: self.data_object_got_entries[id].unwrap(),
                 ptr,
@@ -0,0 +1,160 @@
+//! The GDB JIT compilation interface, which tells debuggers about the symbols
+//! of JIT-compiled functions.
+//!
+//! Each finalized function is published as an in-memory ELF image holding a
+//! single symbol for it. See
+//! <https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html>.
+//!
+//! The images are registered through `wasmtime-jit-debug`, which owns the
+//! debugger's descriptor and the lock guarding it, so that JIT modules and
+//! Wasmtime can publish images in the same process.
+
+use cranelift_codegen::ir::Endianness;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_module::{ModuleError, ModuleResult};
+use object::elf;
+use object::write::elf::{FileHeader, ProgramHeader, SectionHeader, Sym, Writer};
+use std::mem;
+use target_lexicon::{Architecture, PointerWidth};
+use wasmtime_jit_debug::gdb_jit_int::GdbJitImageRegistration;
+
+/// The images published for the functions of a `JITModule`.
+pub(crate) struct GdbJitRegistry {
+    registrations: Vec<GdbJitImageRegistration>,
+}
+
+impl GdbJitRegistry {
+    pub(crate) fn new() -> Self {
+        Self {
+            registrations: Vec::new(),
+        }
+    }
+
+    /// Publishes the function `name` of `size` bytes at `code`.
+    pub(crate) fn register(
+        &mut self,
+        isa: &dyn TargetIsa,
+        name: &str,
+        code: *const u8,
+        size: usize,
+    ) -> ModuleResult<()> {
+        let image = match symbol_image(isa, name, code as u64, size as u64)? {
+            Some(image) => image,
+            None => return Ok(()),
+        };
+        self.registrations
+            .push(GdbJitImageRegistration::register(image));
+        Ok(())
+    }
+
+    /// Unpublishes all images.
+    pub(crate) fn deregister(&mut self) {
+        self.registrations.clear();
+    }
+}
+
+impl Drop for GdbJitRegistry {
+    fn drop(&mut self) {
+        // Leak the registrations, as the code they describe is leaked too
+        // unless `JITModule::free_memory` is called.
+        mem::take(&mut self.registrations)
+            .into_iter()
+            .for_each(mem::forget);
+    }
+}
+
+/// Creates an ELF image with a symbol for the function `name` of `size` bytes
+/// at `address`, or returns `None` if the target has no ELF machine number.
+fn symbol_image(
+    isa: &dyn TargetIsa,
+    name: &str,
+    address: u64,
+    size: u64,
+) -> ModuleResult<Option<Vec<u8>>> {
+    let triple = isa.triple();
+    let e_machine = match triple.architecture {
+        Architecture::X86_64 => elf::EM_X86_64,
+        Architecture::X86_32(_) => elf::EM_386,
+        Architecture::Aarch64(_) => elf::EM_AARCH64,
+        Architecture::S390x => elf::EM_S390,
+        Architecture::Riscv64(_) => elf::EM_RISCV,
+        _ => return Ok(None),
+    };
+    let is_64 = triple.pointer_width() == Ok(PointerWidth::U64);
+    let endian = match isa.endianness() {
+        Endianness::Little => object::Endianness::Little,
+        Endianness::Big => object::Endianness::Big,
+    };
+
+    // The image only describes the code, which is already in memory, so
+    // `.text` has no contents in the file.
+    let mut image = Vec::new();
+    let mut writer = Writer::new(endian, is_64, &mut image);
+    let text_name = writer.add_section_name(b".text");
+    let symbol_name = writer.add_string(name.as_bytes());
+    writer.reserve_file_header();
+    writer.reserve_program_headers(1);
+    writer.reserve_null_section_index();
+    let text = writer.reserve_section_index();
+    writer.reserve_null_symbol_index();
+    writer.reserve_symbol_index(Some(text));
+    writer.reserve_symtab_section_index();
+    writer.reserve_strtab_section_index();
+    writer.reserve_shstrtab_section_index();
+    writer.reserve_symtab();
+    writer.reserve_strtab();
+    writer.reserve_shstrtab();
+    writer.reserve_section_headers();
+
+    writer
+        .write_file_header(&FileHeader {
+            os_abi: elf::ELFOSABI_NONE,
+            abi_version: 0,
+            e_type: elf::ET_DYN,
+            e_machine,
+            e_entry: 0,
+            e_flags: 0,
+        })
+        .map_err(|e| ModuleError::Backend(e.into()))?;
+    writer.write_align_program_headers();
+    writer.write_program_header(&ProgramHeader {
+        p_type: elf::PT_LOAD,
+        p_flags: elf::PF_R | elf::PF_X,
+        p_offset: 0,
+        p_vaddr: address,
+        p_paddr: address,
+        p_filesz: 0,
+        p_memsz: size,
+        p_align: 1,
+    });
+    writer.write_null_symbol();
+    writer.write_symbol(&Sym {
+        name: Some(symbol_name),
+        section: Some(text),
+        st_info: (elf::STB_GLOBAL << 4) | elf::STT_FUNC,
+        st_other: elf::STV_DEFAULT,
+        st_shndx: 0,
+        st_value: address,
+        st_size: size,
+    });
+    writer.write_strtab();
+    writer.write_shstrtab();
+    writer.write_null_section_header();
+    writer.write_section_header(&SectionHeader {
+        name: Some(text_name),
+        sh_type: elf::SHT_NOBITS,
+        sh_flags: u64::from(elf::SHF_ALLOC | elf::SHF_EXECINSTR),
+        sh_addr: address,
+        sh_offset: 0,
+        sh_size: size,
+        sh_link: 0,
+        sh_info: 0,
+        sh_addralign: 1,
+        sh_entsize: 0,
+    });
+    writer.write_symtab_section_header(1);
+    writer.write_strtab_section_header();
+    writer.write_shstrtab_section_header();
+    debug_assert_eq!(writer.reserved_len(), writer.len());
+    Ok(Some(image))
+}
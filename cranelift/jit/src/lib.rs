@@ -2,6 +2,15 @@
 //!
 //! There is an [example project](https://github.com/bytecodealliance/cranelift-jit-demo/)
 //! which shows how to use some of the features of `cranelift_jit`.
+//!
+//! Unwind information of finalized functions is registered with the system
+//! unwinder on Unix platforms. With the `gdb-jit` feature enabled, finalized
+//! functions are also published through the
+//! [GDB JIT interface](https://sourceware.org/gdb/onlinedocs/gdb/JIT-Interface.html),
+//! so that debuggers and profilers can show their names.
+//!
+//! On x86-64, data objects may be declared thread-local. Each thread gets its
+//! own copy of such an object, allocated the first time the thread accesses it.
 
 #![deny(
     missing_docs,
@@ -14,7 +23,12 @@
 
 mod backend;
 mod compiled_blob;
+#[cfg(feature = "gdb-jit")]
+mod gdb_jit;
 mod memory;
+#[cfg(target_arch = "x86_64")]
+mod tls;
+mod unwind;
 
 pub use crate::backend::{JITBuilder, JITModule};
 
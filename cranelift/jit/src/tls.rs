@@ -0,0 +1,152 @@
+//! Thread-local storage for JIT-compiled code.
+//!
+//! Code compiled with `tls_model=elf_gd` computes the address of a
+//! thread-local variable by calling `__tls_get_addr` with a pointer to a
+//! descriptor of the variable. In a `JITModule` that descriptor is a
+//! [`TlsDescriptor`] allocated by the JIT, and the call goes to
+//! [`tls_get_addr`] instead of the dynamic linker's implementation.
+//!
+//! Each thread gets its own copy of a variable the first time it looks up the
+//! variable's address. Copies are freed when the thread exits, or once the
+//! variable's `JITModule` is freed: right away for the thread freeing it, and
+//! for other threads when they next look up a variable reusing its index.
+
+use std::alloc::{self, Layout};
+use std::cell::RefCell;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Index of the next thread-local variable, unique across all live
+/// `JITModule`s.
+static NEXT_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// Indices of the variables of freed `JITModule`s, to be handed out again.
+static FREE_INDICES: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// Generation of the next thread-local variable, telling apart the variables
+/// that share an index.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The copies of the thread-local variables owned by the current thread,
+    /// indexed by `TlsDescriptor::index`.
+    static BLOCKS: RefCell<Vec<Option<TlsBlock>>> = RefCell::new(Vec::new());
+}
+
+/// Describes a thread-local variable to `tls_get_addr`.
+///
+/// The address of the descriptor takes the place of the `tls_index` entry in
+/// the GOT that the ELF general-dynamic model passes to `__tls_get_addr`.
+#[repr(C)]
+pub(crate) struct TlsDescriptor {
+    /// Index of the variable in each thread's table of copies.
+    index: usize,
+    /// Generation of the variable, to detect copies left behind by a previous
+    /// variable with the same index.
+    generation: u64,
+    /// The initial contents of each copy, or null if it is zero-initialized.
+    init: *const u8,
+    /// The size of the variable.
+    size: usize,
+    /// The alignment of the variable.
+    align: usize,
+}
+
+impl TlsDescriptor {
+    /// Creates the descriptor of a newly declared thread-local variable.
+    ///
+    /// The variable's contents are filled in by `define` once it is defined.
+    pub(crate) fn new() -> Self {
+        let index = FREE_INDICES
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| NEXT_INDEX.fetch_add(1, Ordering::Relaxed));
+        Self {
+            index,
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+            init: ptr::null(),
+            size: 0,
+            align: 1,
+        }
+    }
+
+    /// Sets the initial contents of the variable. `init` is null for a
+    /// zero-initialized variable, and must otherwise point to `size` bytes
+    /// that stay valid as long as the JIT-compiled code can run.
+    pub(crate) fn define(&mut self, init: *const u8, size: usize, align: u64) {
+        self.init = init;
+        self.size = size;
+        self.align = usize::try_from(align).unwrap();
+    }
+
+    /// Frees the current thread's copy of the variable and makes its index
+    /// available to new variables. Other threads free their copies when they
+    /// next use the index, or when they exit.
+    ///
+    /// The variable must not be accessed afterwards.
+    pub(crate) fn release(&self) {
+        // Nothing to free if the thread is already tearing down its copies.
+        let _ = BLOCKS.try_with(|blocks| {
+            if let Some(block) = blocks.borrow_mut().get_mut(self.index) {
+                *block = None;
+            }
+        });
+        FREE_INDICES.lock().unwrap().push(self.index);
+    }
+}
+
+/// The current thread's copy of a thread-local variable.
+struct TlsBlock {
+    ptr: *mut u8,
+    layout: Layout,
+    generation: u64,
+}
+
+impl Drop for TlsBlock {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr, self.layout) }
+    }
+}
+
+/// Returns the address of the current thread's copy of the thread-local
+/// variable described by `desc`, allocating and initializing it on first use.
+///
+/// This is called by JIT-compiled code in place of `__tls_get_addr`, so it
+/// follows the System V calling convention the x64 backend assumes for it,
+/// and must not unwind.
+pub(crate) extern "sysv64" fn tls_get_addr(desc: &TlsDescriptor) -> *mut u8 {
+    BLOCKS.with(|blocks| {
+        let mut blocks = blocks.borrow_mut();
+        if let Some(Some(block)) = blocks.get(desc.index) {
+            if block.generation == desc.generation {
+                return block.ptr;
+            }
+        }
+
+        let layout = Layout::from_size_align(desc.size.max(1), desc.align).unwrap();
+        let ptr = unsafe { alloc::alloc(layout) };
+        if ptr.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        unsafe {
+            if desc.init.is_null() {
+                ptr::write_bytes(ptr, 0, desc.size);
+            } else {
+                ptr::copy_nonoverlapping(desc.init, ptr, desc.size);
+            }
+        }
+
+        if blocks.len() <= desc.index {
+            blocks.resize_with(desc.index + 1, || None);
+        }
+        // This drops any copy of a released variable that had the same index.
+        blocks[desc.index] = Some(TlsBlock {
+            ptr,
+            layout,
+            generation: desc.generation,
+        });
+        ptr
+    })
+}
@@ -0,0 +1,151 @@
+//! Registration of the unwind information of JIT-compiled functions with the
+//! system unwinder, so that panics, C++ exceptions and backtraces can unwind
+//! through their frames.
+
+use cranelift_codegen::ir::Endianness;
+use cranelift_codegen::isa::unwind::UnwindInfo;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_module::{ModuleError, ModuleResult};
+use gimli::write::{Address, EhFrame, EndianVec, FrameTable, Writer};
+use gimli::RunTimeEndian;
+use std::mem;
+
+#[cfg(unix)]
+extern "C" {
+    // libunwind import
+    fn __register_frame(fde: *const u8);
+    fn __deregister_frame(fde: *const u8);
+}
+
+/// The `.eh_frame` sections registered for the functions of a `JITModule`.
+pub(crate) struct UnwindRegistry {
+    registrations: Vec<UnwindRegistration>,
+}
+
+/// A `.eh_frame` section describing a single function, along with the
+/// entries of it which were passed to `__register_frame`.
+struct UnwindRegistration {
+    eh_frame: Box<[u8]>,
+    frames: Vec<*const u8>,
+}
+
+impl UnwindRegistry {
+    pub(crate) fn new() -> Self {
+        Self {
+            registrations: Vec::new(),
+        }
+    }
+
+    /// Registers `unwind_info` for the function at `code`.
+    ///
+    /// Only System V unwind information is registered, which is what Cranelift
+    /// produces for every target but Windows.
+    pub(crate) fn register(
+        &mut self,
+        isa: &dyn TargetIsa,
+        code: *const u8,
+        unwind_info: &UnwindInfo,
+    ) -> ModuleResult<()> {
+        let info = match unwind_info {
+            UnwindInfo::SystemV(info) => info,
+            _ => return Ok(()),
+        };
+        let cie = match isa.create_systemv_cie() {
+            Some(cie) => cie,
+            None => return Ok(()),
+        };
+
+        let mut table = FrameTable::default();
+        let cie_id = table.add_cie(cie);
+        table.add_fde(cie_id, info.to_fde(Address::Constant(code as u64)));
+        let endian = match isa.endianness() {
+            Endianness::Little => RunTimeEndian::Little,
+            Endianness::Big => RunTimeEndian::Big,
+        };
+        let mut eh_frame = EhFrame(EndianVec::new(endian));
+        table
+            .write_eh_frame(&mut eh_frame)
+            .map_err(|e| ModuleError::Backend(e.into()))?;
+        // libgcc walks the entries until one of length 0.
+        eh_frame
+            .write_u32(0)
+            .map_err(|e| ModuleError::Backend(e.into()))?;
+
+        let mut registration = UnwindRegistration {
+            eh_frame: eh_frame.0.into_vec().into_boxed_slice(),
+            frames: Vec::new(),
+        };
+        // SAFETY: the registration keeps the `.eh_frame` alive until it is
+        // deregistered.
+        unsafe { registration.register() };
+        self.registrations.push(registration);
+        Ok(())
+    }
+
+    /// Deregisters all unwind information.
+    ///
+    /// # Safety
+    ///
+    /// None of the registered functions may be executing.
+    pub(crate) unsafe fn deregister(&mut self) {
+        // libgcc stores the frame entries as a linked list in decreasing sort
+        // order based on the PC value of the registered entry, which makes
+        // deregistering the most recent registrations first the cheapest.
+        for registration in self.registrations.drain(..).rev() {
+            registration.deregister();
+        }
+    }
+}
+
+impl Drop for UnwindRegistry {
+    fn drop(&mut self) {
+        // Leak the registrations, as the code they describe is leaked too
+        // unless `JITModule::free_memory` is called.
+        mem::take(&mut self.registrations)
+            .into_iter()
+            .for_each(mem::forget);
+    }
+}
+
+impl UnwindRegistration {
+    #[cfg(unix)]
+    unsafe fn register(&mut self) {
+        let start = self.eh_frame.as_ptr();
+        if cfg!(any(
+            all(target_os = "linux", target_env = "gnu"),
+            target_os = "freebsd"
+        )) {
+            // On gnu (libgcc), `__register_frame` will walk the FDEs until an
+            // entry of length 0.
+            __register_frame(start);
+            self.frames.push(start);
+        } else {
+            // For libunwind, `__register_frame` takes a pointer to a single
+            // FDE, skipping over the CIE at the start and the terminator at
+            // the end.
+            let end = start.add(self.eh_frame.len() - 4);
+            let mut current = start;
+            while current < end {
+                let len = std::ptr::read_unaligned::<u32>(current as *const u32) as usize;
+                if current != start {
+                    __register_frame(current);
+                    self.frames.push(current);
+                }
+                current = current.add(len + 4);
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    unsafe fn register(&mut self) {}
+
+    #[cfg(unix)]
+    unsafe fn deregister(self) {
+        for frame in self.frames.iter().rev() {
+            __deregister_frame(*frame);
+        }
+    }
+
+    #[cfg(not(unix))]
+    unsafe fn deregister(self) {}
+}
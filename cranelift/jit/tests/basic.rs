@@ -213,3 +213,383 @@ fn libcall_function() {
 
     module.finalize_definitions().unwrap();
 }
+
+#[test]
+#[cfg(all(target_arch = "x86_64", unix))]
+fn stack_switch() {
+    let isa_builder = cranelift_native::builder().unwrap_or_else(|msg| {
+        panic!("host machine is not supported: {}", msg);
+    });
+    let isa = isa_builder
+        .finish(settings::Flags::new(settings::builder()))
+        .unwrap();
+    let mut module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+    let int = module.target_config().pointer_type();
+
+    // `contexts` points to the control context of the caller of `resume`
+    // followed by that of the generator.
+    let define = |module: &mut JITModule,
+                  name: &str,
+                  returns,
+                  body: &dyn Fn(&mut FunctionBuilder, &[Value])| {
+        let mut sig = module.make_signature();
+        sig.params.push(AbiParam::new(int));
+        if returns {
+            sig.params.push(AbiParam::new(int));
+            sig.returns.push(AbiParam::new(int));
+        }
+        let func_id = module.declare_function(name, Linkage::Local, &sig).unwrap();
+        let mut ctx = Context::new();
+        ctx.func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+        let mut func_ctx = FunctionBuilderContext::new();
+        {
+            let mut bcx = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+            let block = bcx.create_block();
+            bcx.append_block_params_for_function_params(block);
+            bcx.switch_to_block(block);
+            let params = bcx.block_params(block).to_vec();
+            body(&mut bcx, &params);
+            bcx.seal_all_blocks();
+            bcx.finalize();
+        }
+        module.define_function(func_id, &mut ctx).unwrap();
+        func_id
+    };
+
+    // `generator(contexts)` yields the running sum of the values it's
+    // resumed with, starting at zero.
+    let generator = define(&mut module, "generator", false, &|bcx, params| {
+        let contexts = params[0];
+        let sum = Variable::new(0);
+        bcx.declare_var(sum, int);
+        let zero = bcx.ins().iconst(int, 0);
+        bcx.def_var(sum, zero);
+        let body = bcx.create_block();
+        bcx.ins().jump(body, &[]);
+        bcx.switch_to_block(body);
+        let own = bcx.ins().iadd_imm(contexts, 24);
+        let current = bcx.use_var(sum);
+        let value = bcx.ins().stack_switch(own, contexts, current);
+        let next = bcx.ins().iadd(current, value);
+        bcx.def_var(sum, next);
+        bcx.ins().jump(body, &[]);
+    });
+
+    // `resume(contexts, value)` resumes the generator with `value` and
+    // returns what it yields.
+    let resume = define(&mut module, "resume", true, &|bcx, params| {
+        let (contexts, value) = (params[0], params[1]);
+        let generator = bcx.ins().iadd_imm(contexts, 24);
+        let result = bcx.ins().stack_switch(contexts, generator, value);
+        bcx.ins().return_(&[result]);
+    });
+
+    module.finalize_definitions().unwrap();
+    let generator = module.get_finalized_function(generator);
+    let resume = module.get_finalized_function(resume);
+    let resume = unsafe { std::mem::transmute::<_, extern "C" fn(*mut u64, u64) -> u64>(resume) };
+
+    // The generator starts on a fresh stack at its entry, where a (never
+    // used) return address is expected on top of a 16-byte aligned stack.
+    let mut stack = vec![0u8; 64 * 1024];
+    let top = (stack.as_mut_ptr_range().end as u64 & !15) - 8;
+    let mut contexts = [0, 0, 0, top, 0, generator as u64];
+
+    // The first value the generator is resumed with is its argument.
+    let contexts_ptr = contexts.as_mut_ptr();
+    assert_eq!(resume(contexts_ptr, contexts_ptr as u64), 0);
+    assert_eq!(resume(contexts_ptr, 5), 5);
+    assert_eq!(resume(contexts_ptr, 7), 12);
+    assert_eq!(resume(contexts_ptr, 30), 42);
+}
+
+#[test]
+#[cfg(unix)]
+fn unwind_through_jit_frames() {
+    use std::backtrace::Backtrace;
+
+    let isa_builder = cranelift_native::builder().unwrap_or_else(|msg| {
+        panic!("host machine is not supported: {}", msg);
+    });
+    let isa = isa_builder
+        .finish(settings::Flags::new(settings::builder()))
+        .unwrap();
+    let mut module = JITModule::new(JITBuilder::with_isa(isa, default_libcall_names()));
+    let int = module.target_config().pointer_type();
+
+    // `trampoline(callback)` calls `callback` and returns its result.
+    let mut callback_sig = module.make_signature();
+    callback_sig.returns.push(AbiParam::new(int));
+    let mut sig = module.make_signature();
+    sig.params.push(AbiParam::new(int));
+    sig.returns.push(AbiParam::new(int));
+    let func_id = module
+        .declare_function("trampoline", Linkage::Local, &sig)
+        .unwrap();
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.append_block_params_for_function_params(block);
+        bcx.switch_to_block(block);
+        let callback = bcx.block_params(block)[0];
+        let callback_sig = bcx.import_signature(callback_sig);
+        let call = bcx.ins().call_indirect(callback_sig, callback, &[]);
+        let result = bcx.inst_results(call)[0];
+        bcx.ins().return_(&[result]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+    module.finalize_definitions().unwrap();
+    let trampoline = module.get_finalized_function(func_id);
+    let trampoline = unsafe { std::mem::transmute::<_, extern "C" fn(usize) -> usize>(trampoline) };
+
+    // The backtrace taken by the callback only reaches its caller's caller
+    // if the unwinder can step through the frame of `trampoline`.
+    extern "C" fn callback() -> usize {
+        let backtrace = Backtrace::force_capture().to_string();
+        backtrace.contains("call_through_jit_code") as usize
+    }
+    #[inline(never)]
+    fn call_through_jit_code(trampoline: extern "C" fn(usize) -> usize) -> usize {
+        trampoline(callback as extern "C" fn() -> usize as usize)
+    }
+    assert_eq!(call_through_jit_code(trampoline), 1);
+
+    unsafe { module.free_memory() };
+}
+
+#[test]
+#[cfg(feature = "gdb-jit")]
+fn gdb_jit_interface() {
+    #[repr(C)]
+    struct JITCodeEntry {
+        next_entry: *mut JITCodeEntry,
+        prev_entry: *mut JITCodeEntry,
+        symfile_addr: *const u8,
+        symfile_size: u64,
+    }
+
+    #[repr(C)]
+    struct JITDescriptor {
+        version: u32,
+        action_flag: u32,
+        relevant_entry: *mut JITCodeEntry,
+        first_entry: *mut JITCodeEntry,
+    }
+
+    extern "C" {
+        static __jit_debug_descriptor: JITDescriptor;
+    }
+
+    // Other tests may register images concurrently, so this only looks for
+    // the image of its own function.
+    fn published(name: &[u8]) -> bool {
+        unsafe {
+            let mut entry = std::ptr::addr_of!(__jit_debug_descriptor)
+                .read_volatile()
+                .first_entry;
+            while !entry.is_null() {
+                let image = std::slice::from_raw_parts(
+                    (*entry).symfile_addr,
+                    (*entry).symfile_size as usize,
+                );
+                if image.starts_with(b"\x7fELF") && image.windows(name.len()).any(|w| w == name) {
+                    return true;
+                }
+                entry = (*entry).next_entry;
+            }
+            false
+        }
+    }
+
+    let mut module = JITModule::new(JITBuilder::new(default_libcall_names()).unwrap());
+    let name = "gdb_jit_interface_function";
+    let sig = module.make_signature();
+    let func_id = module.declare_function(name, Linkage::Local, &sig).unwrap();
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.switch_to_block(block);
+        bcx.ins().return_(&[]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+
+    assert!(!published(name.as_bytes()));
+    module.finalize_definitions().unwrap();
+    assert!(published(name.as_bytes()));
+    unsafe { module.free_memory() };
+    assert!(!published(name.as_bytes()));
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn thread_local_data() {
+    let mut module = JITModule::new(JITBuilder::new(default_libcall_names()).unwrap());
+    let int = module.target_config().pointer_type();
+
+    // `counter` starts at 100 and `zeroed` at 0 in every thread.
+    let counter_id = module
+        .declare_data("counter", Linkage::Local, true, true)
+        .unwrap();
+    let mut data = DataDescription::new();
+    data.define(100i64.to_le_bytes().to_vec().into_boxed_slice());
+    module.define_data(counter_id, &data).unwrap();
+    let zeroed_id = module
+        .declare_data("zeroed", Linkage::Local, true, true)
+        .unwrap();
+    let mut data = DataDescription::new();
+    data.define_zeroinit(8);
+    module.define_data(zeroed_id, &data).unwrap();
+
+    // `bump(n)` adds `n` to `counter` and 1 to `zeroed`, and returns the sum of
+    // both.
+    let mut sig = module.make_signature();
+    sig.params.push(AbiParam::new(types::I64));
+    sig.returns.push(AbiParam::new(types::I64));
+    let func_id = module
+        .declare_function("bump", Linkage::Local, &sig)
+        .unwrap();
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.append_block_params_for_function_params(block);
+        bcx.switch_to_block(block);
+        let n = bcx.block_params(block)[0];
+
+        let counter = module.declare_data_in_func(counter_id, bcx.func);
+        let counter = bcx.ins().tls_value(int, counter);
+        let old = bcx.ins().load(types::I64, MemFlags::trusted(), counter, 0);
+        let new_counter = bcx.ins().iadd(old, n);
+        bcx.ins()
+            .store(MemFlags::trusted(), new_counter, counter, 0);
+
+        let zeroed = module.declare_data_in_func(zeroed_id, bcx.func);
+        let zeroed = bcx.ins().tls_value(int, zeroed);
+        let old = bcx.ins().load(types::I64, MemFlags::trusted(), zeroed, 0);
+        let new_zeroed = bcx.ins().iadd_imm(old, 1);
+        bcx.ins().store(MemFlags::trusted(), new_zeroed, zeroed, 0);
+
+        let sum = bcx.ins().iadd(new_counter, new_zeroed);
+        bcx.ins().return_(&[sum]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+    module.finalize_definitions().unwrap();
+    let bump = module.get_finalized_function(func_id);
+    let bump = unsafe { std::mem::transmute::<_, extern "C" fn(i64) -> i64>(bump) };
+
+    // Each thread only sees its own updates.
+    std::thread::scope(|scope| {
+        let threads = (1..=4)
+            .map(|n| {
+                scope.spawn(move || {
+                    let mut sum = 0;
+                    for _ in 0..1000 {
+                        sum = bump(n);
+                    }
+                    sum
+                })
+            })
+            .collect::<Vec<_>>();
+        for (n, thread) in (1..=4).zip(threads) {
+            assert_eq!(thread.join().unwrap(), 100 + 1000 * n + 1000);
+        }
+    });
+    assert_eq!(bump(5), 100 + 5 + 1);
+    assert_eq!(bump(5), 100 + 10 + 2);
+
+    unsafe { module.free_memory() };
+}
+
+/// Defines a thread-local `i64` initialized to `init` and a function
+/// `bump(n)` adding `n` to it and returning the new value.
+#[cfg(target_arch = "x86_64")]
+fn thread_local_counter(init: i64) -> (JITModule, extern "C" fn(i64) -> i64) {
+    let mut module = JITModule::new(JITBuilder::new(default_libcall_names()).unwrap());
+    let int = module.target_config().pointer_type();
+
+    let counter_id = module
+        .declare_data("counter", Linkage::Local, true, true)
+        .unwrap();
+    let mut data = DataDescription::new();
+    data.define(init.to_le_bytes().to_vec().into_boxed_slice());
+    module.define_data(counter_id, &data).unwrap();
+
+    let mut sig = module.make_signature();
+    sig.params.push(AbiParam::new(types::I64));
+    sig.returns.push(AbiParam::new(types::I64));
+    let func_id = module
+        .declare_function("bump", Linkage::Local, &sig)
+        .unwrap();
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.append_block_params_for_function_params(block);
+        bcx.switch_to_block(block);
+        let n = bcx.block_params(block)[0];
+        let counter = module.declare_data_in_func(counter_id, bcx.func);
+        let counter = bcx.ins().tls_value(int, counter);
+        let old = bcx.ins().load(types::I64, MemFlags::trusted(), counter, 0);
+        let new = bcx.ins().iadd(old, n);
+        bcx.ins().store(MemFlags::trusted(), new, counter, 0);
+        bcx.ins().return_(&[new]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+    module.finalize_definitions().unwrap();
+    let bump = module.get_finalized_function(func_id);
+    let bump = unsafe { std::mem::transmute::<_, extern "C" fn(i64) -> i64>(bump) };
+    (module, bump)
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn thread_local_data_of_freed_module() {
+    // Variables of later modules may reuse the freed variable's index, but
+    // never see its contents.
+    for init in [100, 200, 300] {
+        let (module, bump) = thread_local_counter(init);
+        let other_thread = std::thread::spawn(move || bump(1)).join().unwrap();
+        assert_eq!(other_thread, init + 1);
+        assert_eq!(bump(2), init + 2);
+        assert_eq!(bump(2), init + 4);
+        unsafe { module.free_memory() };
+    }
+}
+
+#[test]
+fn error_on_thread_local_data_with_other_tls_model() {
+    let mut module = JITModule::new(
+        JITBuilder::with_flags(&[("tls_model", "none")], default_libcall_names()).unwrap(),
+    );
+    module
+        .declare_data("counter", Linkage::Local, true, true)
+        .err()
+        .unwrap();
+}
+
+#[test]
+fn error_on_imported_thread_local_data() {
+    let mut module = JITModule::new(JITBuilder::new(default_libcall_names()).unwrap());
+    module
+        .declare_data("imported", Linkage::Import, true, true)
+        .err()
+        .unwrap();
+}
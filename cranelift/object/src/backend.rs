@@ -1,5 +1,6 @@
 //! Defines `ObjectModule`.
 
+use crate::debug::{self, DebugInfoBuilder, DebugInfoOptions, FunctionDebugInfo};
 use anyhow::anyhow;
 use cranelift_codegen::binemit::{Addend, CodeOffset, Reloc};
 use cranelift_codegen::entity::SecondaryMap;
@@ -33,6 +34,7 @@ pub struct ObjectBuilder {
     name: Vec<u8>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String + Send + Sync>,
     per_function_section: bool,
+    debug_info: Option<DebugInfoOptions>,
 }
 
 impl ObjectBuilder {
@@ -109,6 +111,7 @@ impl ObjectBuilder {
             name: name.into(),
             libcall_names,
             per_function_section: false,
+            debug_info: None,
         })
     }
 
@@ -117,6 +120,22 @@ impl ObjectBuilder {
         self.per_function_section = per_function_section;
         self
     }
+
+    /// Emit DWARF debug information for the defined functions.
+    ///
+    /// Line tables are built from the `SourceLoc`s of the compiled functions,
+    /// and the locations of the variables described with
+    /// [`ObjectModule::set_function_debug_info`] from the ranges of their value
+    /// labels.
+    ///
+    /// The debug information is written by
+    /// [`ObjectModule::finish_with_debug_info`]. Only ELF objects can carry
+    /// it: this returns an error for Mach-O, COFF and other binary formats.
+    pub fn debug_info(&mut self, options: DebugInfoOptions) -> ModuleResult<&mut Self> {
+        debug::check_binary_format(self.binary_format)?;
+        self.debug_info = Some(options);
+        Ok(self)
+    }
 }
 
 /// An `ObjectModule` implements `Module` and emits ".o" files using the `object` library.
@@ -134,6 +153,7 @@ pub struct ObjectModule {
     known_symbols: HashMap<ir::KnownSymbol, SymbolId>,
     known_labels: HashMap<(UserFuncName, CodeOffset), SymbolId>,
     per_function_section: bool,
+    debug_info: Option<DebugInfoBuilder>,
 }
 
 impl ObjectModule {
@@ -154,6 +174,17 @@ impl ObjectModule {
             known_symbols: HashMap::new(),
             known_labels: HashMap::new(),
             per_function_section: builder.per_function_section,
+            debug_info: builder.debug_info.map(DebugInfoBuilder::new),
+        }
+    }
+
+    /// Describe the function `func_id` in the debug information.
+    ///
+    /// This must be called before the function is defined, and has no effect
+    /// unless debug information was enabled with [`ObjectBuilder::debug_info`].
+    pub fn set_function_debug_info(&mut self, func_id: FuncId, info: FunctionDebugInfo) {
+        if let Some(debug_info) = &mut self.debug_info {
+            debug_info.set_function_debug_info(func_id, info);
         }
     }
 }
@@ -328,7 +359,23 @@ impl Module for ObjectModule {
             alignment,
             &code,
             ctx.compiled_code().unwrap().buffer.relocs(),
-        )
+        )?;
+
+        if let Some(debug_info) = &mut self.debug_info {
+            let symbol = self.functions[func_id].unwrap().0;
+            let name = self
+                .declarations
+                .get_function_decl(func_id)
+                .linkage_name(func_id);
+            debug_info.add_function(
+                &*self.isa,
+                func_id,
+                symbol,
+                &name,
+                ctx.compiled_code().unwrap(),
+            )?;
+        }
+        Ok(())
     }
 
     fn define_function_bytes(
@@ -482,6 +529,10 @@ impl Module for ObjectModule {
 
 impl ObjectModule {
     /// Finalize all relocations and output an object.
+    ///
+    /// This doesn't write debug information, even if it was enabled with
+    /// [`ObjectBuilder::debug_info`]; use
+    /// [`ObjectModule::finish_with_debug_info`] for that.
     pub fn finish(mut self) -> ObjectProduct {
         let symbol_relocs = mem::take(&mut self.relocs);
         for symbol in symbol_relocs {
@@ -527,6 +578,20 @@ impl ObjectModule {
         }
     }
 
+    /// Finalize all relocations and output an object, including the debug
+    /// information enabled with [`ObjectBuilder::debug_info`].
+    ///
+    /// Fails if the debug information can't be written, for example because
+    /// the variable locations of a function are malformed.
+    pub fn finish_with_debug_info(mut self) -> ModuleResult<ObjectProduct> {
+        if let Some(debug_info) = self.debug_info.take() {
+            debug_info.emit(&*self.isa, &mut self.object).map_err(|e| {
+                ModuleError::Backend(anyhow!("failed to write DWARF debug information: {e}"))
+            })?;
+        }
+        Ok(self.finish())
+    }
+
     /// This should only be called during finish because it creates
     /// symbols for missing libcalls.
     fn get_symbol(&mut self, name: &ModuleRelocTarget) -> SymbolId {
@@ -0,0 +1,568 @@
+//! DWARF debug information for the functions of an `ObjectModule`.
+//!
+//! Line tables are built from the `SourceLoc`s of the compiled instructions,
+//! which the embedder maps to source positions, and the locations of
+//! variables from the ranges of their value labels. Call frame information is
+//! emitted in `.debug_frame`, as variables in memory are described relative
+//! to the canonical frame address.
+
+use anyhow::anyhow;
+use cranelift_codegen::binemit::CodeOffset;
+use cranelift_codegen::ir::{Endianness, SourceLoc, ValueLabel};
+use cranelift_codegen::isa::unwind::{CfaUnwindInfo, UnwindInfo};
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::{CompiledCode, LabelValueLoc};
+use cranelift_module::{FuncId, ModuleError, ModuleResult};
+use gimli::write::{
+    Address, AttributeValue, DwarfUnit, EndianVec, Expression, FileId, FrameTable, LineProgram,
+    LineString, Location, LocationList, Range, RangeList, Sections, UnitEntryId, Writer,
+};
+use gimli::{Encoding, Format, LineEncoding, Register, RunTimeEndian, SectionId};
+use object::write::{Object, Relocation, SymbolId};
+use object::{RelocationEncoding, RelocationKind, SectionKind};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Options for the DWARF debug information of an `ObjectModule`, see
+/// [`ObjectBuilder::debug_info`](crate::ObjectBuilder::debug_info).
+pub struct DebugInfoOptions {
+    /// The name of the compilation unit, usually the path of its main source
+    /// file.
+    pub name: String,
+
+    /// The directory relative paths are relative to.
+    pub comp_dir: String,
+
+    /// The name of the compiler, recorded as the producer of the compilation
+    /// unit.
+    pub producer: String,
+
+    /// The paths of the source files, which `SourcePosition`s index into.
+    pub files: Vec<String>,
+
+    /// Maps the `SourceLoc` of an instruction to its position in the source,
+    /// or `None` if it doesn't correspond to any.
+    pub source_map: Box<dyn Fn(SourceLoc) -> Option<SourcePosition> + Send + Sync>,
+}
+
+/// A position in a source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourcePosition {
+    /// The index of the file in [`DebugInfoOptions::files`].
+    pub file: usize,
+    /// The line number, starting at 1.
+    pub line: u64,
+    /// The column number, starting at 1, or 0 if unknown.
+    pub column: u64,
+}
+
+/// Debug information about a function, see
+/// [`ObjectModule::set_function_debug_info`](crate::ObjectModule::set_function_debug_info).
+#[derive(Clone, Debug, Default)]
+pub struct FunctionDebugInfo {
+    /// The name of the function in the source, if different from its symbol
+    /// name.
+    pub name: Option<String>,
+
+    /// The position of the function's declaration.
+    pub position: Option<SourcePosition>,
+
+    /// The parameters and local variables of the function.
+    pub variables: Vec<DebugVariable>,
+}
+
+/// A variable of a function, whose values are labeled in the CLIF with
+/// `FunctionBuilder::set_val_label`.
+#[derive(Clone, Debug)]
+pub struct DebugVariable {
+    /// The name of the variable.
+    pub name: String,
+    /// The label of the values of the variable.
+    pub label: ValueLabel,
+    /// The type of the variable.
+    pub ty: DebugType,
+    /// Whether the variable is a parameter of the function.
+    pub parameter: bool,
+}
+
+/// The base type of a variable.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DebugType {
+    /// The name of the type.
+    pub name: String,
+    /// The size of the type in bytes.
+    pub size: u8,
+    /// How values of the type are encoded.
+    pub encoding: DebugTypeEncoding,
+}
+
+/// How values of a [`DebugType`] are encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DebugTypeEncoding {
+    /// A signed integer.
+    Signed,
+    /// An unsigned integer.
+    Unsigned,
+    /// A floating-point number.
+    Float,
+    /// A boolean, which is zero when false.
+    Boolean,
+    /// A machine address.
+    Address,
+}
+
+/// The location of a variable from a start offset to an end offset.
+type VariableLocation = (CodeOffset, CodeOffset, Expression);
+
+/// Collects the debug information of the functions of an `ObjectModule`.
+pub(crate) struct DebugInfoBuilder {
+    options: DebugInfoOptions,
+    /// Debug information about functions which haven't been defined yet.
+    pending: HashMap<FuncId, FunctionDebugInfo>,
+    functions: Vec<FunctionRecord>,
+}
+
+/// The debug information of a defined function.
+struct FunctionRecord {
+    symbol: SymbolId,
+    name: String,
+    size: CodeOffset,
+    position: Option<SourcePosition>,
+    /// The source position of the code from each offset on.
+    lines: Vec<(CodeOffset, Option<SourcePosition>)>,
+    /// The variables, with the location of their values in each code range.
+    variables: Vec<(DebugVariable, Vec<VariableLocation>)>,
+    unwind_info: Option<CfaUnwindInfo>,
+}
+
+impl DebugInfoBuilder {
+    pub(crate) fn new(options: DebugInfoOptions) -> Self {
+        Self {
+            options,
+            pending: HashMap::new(),
+            functions: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_function_debug_info(&mut self, func_id: FuncId, info: FunctionDebugInfo) {
+        self.pending.insert(func_id, info);
+    }
+
+    /// Records the debug information of the function `func_id`, which was
+    /// compiled to `compiled_code` and defined as `symbol`.
+    pub(crate) fn add_function(
+        &mut self,
+        isa: &dyn TargetIsa,
+        func_id: FuncId,
+        symbol: SymbolId,
+        symbol_name: &str,
+        compiled_code: &CompiledCode,
+    ) -> ModuleResult<()> {
+        let info = self.pending.remove(&func_id).unwrap_or_default();
+        let check = |position: Option<SourcePosition>| match position {
+            Some(position) if position.file >= self.options.files.len() => {
+                Err(ModuleError::Backend(anyhow!(
+                    "source position in function {symbol_name} refers to unknown file {}",
+                    position.file
+                )))
+            }
+            _ => Ok(position),
+        };
+
+        let mut lines = Vec::new();
+        let mut end = 0;
+        for srcloc in compiled_code.buffer.get_srclocs_sorted() {
+            if srcloc.start != end {
+                lines.push((end, None));
+            }
+            let position = if srcloc.loc.is_default() {
+                None
+            } else {
+                check((self.options.source_map)(srcloc.loc))?
+            };
+            lines.push((srcloc.start, position));
+            end = srcloc.end;
+        }
+        lines.dedup_by_key(|&mut (_, position)| position);
+
+        let variables = info
+            .variables
+            .into_iter()
+            .map(|variable| {
+                let ranges = compiled_code
+                    .value_labels_ranges
+                    .get(&variable.label)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|range| {
+                        let mut expr = Expression::new();
+                        match range.loc {
+                            LabelValueLoc::Reg(reg) => {
+                                let reg = isa.map_regalloc_reg_to_dwarf(reg).ok()?;
+                                expr.op_reg(Register(reg));
+                            }
+                            LabelValueLoc::CFAOffset(offset) => expr.op_fbreg(offset),
+                        }
+                        Some((range.start, range.end, expr))
+                    })
+                    .collect();
+                (variable, ranges)
+            })
+            .collect();
+
+        let unwind_info = match compiled_code
+            .create_unwind_info(isa)
+            .map_err(ModuleError::Compilation)?
+        {
+            Some(UnwindInfo::SystemV(info)) => Some(info),
+            _ => None,
+        };
+
+        self.functions.push(FunctionRecord {
+            symbol,
+            name: info.name.unwrap_or_else(|| symbol_name.to_string()),
+            size: compiled_code.buffer.total_size(),
+            position: check(info.position)?,
+            lines,
+            variables,
+            unwind_info,
+        });
+        Ok(())
+    }
+
+    /// Writes the debug sections to `object`.
+    pub(crate) fn emit(self, isa: &dyn TargetIsa, object: &mut Object) -> gimli::write::Result<()> {
+        let encoding = Encoding {
+            format: Format::Dwarf32,
+            version: 4,
+            address_size: isa.pointer_bytes(),
+        };
+        let mut dwarf = DwarfUnit::new(encoding);
+        dwarf.unit.line_program = LineProgram::new(
+            encoding,
+            LineEncoding::default(),
+            LineString::String(self.options.comp_dir.clone().into_bytes()),
+            LineString::String(self.options.name.clone().into_bytes()),
+            None,
+        );
+        let files = self
+            .options
+            .files
+            .iter()
+            .map(|path| add_file(&mut dwarf.unit.line_program, path))
+            .collect::<Vec<_>>();
+
+        let root = dwarf.unit.root();
+        let mut ranges = Vec::new();
+        let mut types = HashMap::new();
+        for (index, func) in self.functions.iter().enumerate() {
+            let address = Address::Symbol {
+                symbol: index,
+                addend: 0,
+            };
+            ranges.push(Range::StartLength {
+                begin: address,
+                length: func.size.into(),
+            });
+
+            let program = &mut dwarf.unit.line_program;
+            program.begin_sequence(Some(address));
+            for &(offset, position) in func.lines.iter() {
+                let row = program.row();
+                row.address_offset = offset.into();
+                match position {
+                    Some(position) => {
+                        row.file = files[position.file];
+                        row.line = position.line;
+                        row.column = position.column;
+                    }
+                    None => {
+                        row.line = 0;
+                        row.column = 0;
+                    }
+                }
+                program.generate_row();
+            }
+            program.end_sequence(func.size.into());
+
+            let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+            let name = dwarf.strings.add(func.name.as_str());
+            let entry = dwarf.unit.get_mut(subprogram);
+            entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+            entry.set(gimli::DW_AT_external, AttributeValue::Flag(true));
+            entry.set(gimli::DW_AT_low_pc, AttributeValue::Address(address));
+            entry.set(
+                gimli::DW_AT_high_pc,
+                AttributeValue::Udata(func.size.into()),
+            );
+            let mut frame_base = Expression::new();
+            frame_base.op(gimli::DW_OP_call_frame_cfa);
+            entry.set(gimli::DW_AT_frame_base, AttributeValue::Exprloc(frame_base));
+            if let Some(position) = func.position {
+                entry.set(
+                    gimli::DW_AT_decl_file,
+                    AttributeValue::FileIndex(Some(files[position.file])),
+                );
+                entry.set(gimli::DW_AT_decl_line, AttributeValue::Udata(position.line));
+            }
+
+            for (variable, locations) in func.variables.iter() {
+                let ty = match types.get(&variable.ty) {
+                    Some(&ty) => ty,
+                    None => {
+                        let ty = add_base_type(&mut dwarf, root, &variable.ty);
+                        types.insert(variable.ty.clone(), ty);
+                        ty
+                    }
+                };
+                let tag = if variable.parameter {
+                    gimli::DW_TAG_formal_parameter
+                } else {
+                    gimli::DW_TAG_variable
+                };
+                let locations = LocationList(
+                    locations
+                        .iter()
+                        .map(|(start, end, expr)| Location::StartEnd {
+                            begin: Address::Symbol {
+                                symbol: index,
+                                addend: (*start).into(),
+                            },
+                            end: Address::Symbol {
+                                symbol: index,
+                                addend: (*end).into(),
+                            },
+                            data: expr.clone(),
+                        })
+                        .collect(),
+                );
+                let locations = dwarf.unit.locations.add(locations);
+                let name = dwarf.strings.add(variable.name.as_str());
+                let var = dwarf.unit.add(subprogram, tag);
+                let entry = dwarf.unit.get_mut(var);
+                entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+                entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(ty));
+                entry.set(
+                    gimli::DW_AT_location,
+                    AttributeValue::LocationListRef(locations),
+                );
+            }
+        }
+
+        let ranges = dwarf.unit.ranges.add(RangeList(ranges));
+        let producer = dwarf.strings.add(self.options.producer.as_str());
+        let name = dwarf.strings.add(self.options.name.as_str());
+        let comp_dir = dwarf.strings.add(self.options.comp_dir.as_str());
+        let entry = dwarf.unit.get_mut(root);
+        entry.set(gimli::DW_AT_producer, AttributeValue::StringRef(producer));
+        entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+        entry.set(gimli::DW_AT_comp_dir, AttributeValue::StringRef(comp_dir));
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(0)),
+        );
+        entry.set(gimli::DW_AT_ranges, AttributeValue::RangeListRef(ranges));
+
+        let endian = match isa.endianness() {
+            Endianness::Little => RunTimeEndian::Little,
+            Endianness::Big => RunTimeEndian::Big,
+        };
+        let mut sections = Sections::new(WriterRelocate::new(endian));
+        dwarf.write(&mut sections)?;
+        if let Some(cie) = isa.create_systemv_cie() {
+            let mut frames = FrameTable::default();
+            let cie = frames.add_cie(cie);
+            for (index, func) in self.functions.iter().enumerate() {
+                if let Some(info) = &func.unwind_info {
+                    let address = Address::Symbol {
+                        symbol: index,
+                        addend: 0,
+                    };
+                    frames.add_fde(cie, info.to_fde(address));
+                }
+            }
+            frames.write_debug_frame(&mut sections.debug_frame)?;
+        }
+
+        // Sections may refer to sections which are written after them, so
+        // all sections are added before the relocations.
+        let mut section_ids = HashMap::new();
+        sections.for_each(|id, section| -> gimli::write::Result<()> {
+            if !section.writer.slice().is_empty() {
+                let section_id = object.add_section(
+                    Vec::new(),
+                    id.name().as_bytes().to_vec(),
+                    SectionKind::Debug,
+                );
+                object.append_section_data(section_id, section.writer.slice(), 1);
+                section_ids.insert(id, section_id);
+            }
+            Ok(())
+        })?;
+        sections.for_each(|id, section| -> gimli::write::Result<()> {
+            for reloc in section.relocs.iter() {
+                let symbol = match reloc.target {
+                    RelocTarget::Function(index) => self.functions[index].symbol,
+                    RelocTarget::Section(id) => object.section_symbol(section_ids[&id]),
+                };
+                object
+                    .add_relocation(
+                        section_ids[&id],
+                        Relocation {
+                            offset: reloc.offset.into(),
+                            size: reloc.size * 8,
+                            kind: RelocationKind::Absolute,
+                            encoding: RelocationEncoding::Generic,
+                            symbol,
+                            addend: reloc.addend,
+                        },
+                    )
+                    .unwrap();
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Adds the source file at `path` to the line program.
+fn add_file(program: &mut LineProgram, path: &str) -> FileId {
+    let path = Path::new(path);
+    let directory = match path.parent().and_then(Path::to_str) {
+        Some(dir) if !dir.is_empty() => {
+            program.add_directory(LineString::String(dir.as_bytes().to_vec()))
+        }
+        _ => program.default_directory(),
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string_lossy().into_owned());
+    program.add_file(LineString::String(name.into_bytes()), directory, None)
+}
+
+fn add_base_type(dwarf: &mut DwarfUnit, root: UnitEntryId, ty: &DebugType) -> UnitEntryId {
+    let id = dwarf.unit.add(root, gimli::DW_TAG_base_type);
+    let name = dwarf.strings.add(ty.name.as_str());
+    let entry = dwarf.unit.get_mut(id);
+    entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+    entry.set(gimli::DW_AT_byte_size, AttributeValue::Data1(ty.size));
+    let encoding = match ty.encoding {
+        DebugTypeEncoding::Signed => gimli::DW_ATE_signed,
+        DebugTypeEncoding::Unsigned => gimli::DW_ATE_unsigned,
+        DebugTypeEncoding::Float => gimli::DW_ATE_float,
+        DebugTypeEncoding::Boolean => gimli::DW_ATE_boolean,
+        DebugTypeEncoding::Address => gimli::DW_ATE_address,
+    };
+    entry.set(gimli::DW_AT_encoding, AttributeValue::Encoding(encoding));
+    id
+}
+
+/// Returns an error if DWARF debug information can't be emitted for
+/// `binary_format`.
+pub(crate) fn check_binary_format(binary_format: object::BinaryFormat) -> ModuleResult<()> {
+    if binary_format != object::BinaryFormat::Elf {
+        return Err(ModuleError::Backend(anyhow!(
+            "DWARF debug information is not supported for binary format {:?}",
+            binary_format
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+enum RelocTarget {
+    /// The function at this index of `DebugInfoBuilder::functions`.
+    Function(usize),
+    Section(SectionId),
+}
+
+#[derive(Clone)]
+struct DebugReloc {
+    target: RelocTarget,
+    offset: u32,
+    size: u8,
+    addend: i64,
+}
+
+/// A `Writer` which records relocations for addresses of functions and
+/// offsets into other sections.
+#[derive(Clone)]
+struct WriterRelocate {
+    relocs: Vec<DebugReloc>,
+    writer: EndianVec<RunTimeEndian>,
+}
+
+impl WriterRelocate {
+    fn new(endian: RunTimeEndian) -> Self {
+        Self {
+            relocs: Vec::new(),
+            writer: EndianVec::new(endian),
+        }
+    }
+}
+
+impl Writer for WriterRelocate {
+    type Endian = RunTimeEndian;
+
+    fn endian(&self) -> Self::Endian {
+        self.writer.endian()
+    }
+
+    fn len(&self) -> usize {
+        self.writer.len()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> gimli::write::Result<()> {
+        self.writer.write(bytes)
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> gimli::write::Result<()> {
+        self.writer.write_at(offset, bytes)
+    }
+
+    fn write_address(&mut self, address: Address, size: u8) -> gimli::write::Result<()> {
+        match address {
+            Address::Constant(val) => self.write_udata(val, size),
+            Address::Symbol { symbol, addend } => {
+                self.relocs.push(DebugReloc {
+                    target: RelocTarget::Function(symbol),
+                    offset: self.len() as u32,
+                    size,
+                    addend,
+                });
+                self.write_udata(addend as u64, size)
+            }
+        }
+    }
+
+    fn write_offset(
+        &mut self,
+        val: usize,
+        section: SectionId,
+        size: u8,
+    ) -> gimli::write::Result<()> {
+        self.relocs.push(DebugReloc {
+            target: RelocTarget::Section(section),
+            offset: self.len() as u32,
+            size,
+            addend: val as i64,
+        });
+        self.write_udata(val as u64, size)
+    }
+
+    fn write_offset_at(
+        &mut self,
+        offset: usize,
+        val: usize,
+        section: SectionId,
+        size: u8,
+    ) -> gimli::write::Result<()> {
+        self.relocs.push(DebugReloc {
+            target: RelocTarget::Section(section),
+            offset: offset as u32,
+            size,
+            addend: val as i64,
+        });
+        self.write_udata_at(offset, val as u64, size)
+    }
+}
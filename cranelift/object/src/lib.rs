@@ -11,8 +11,13 @@
 #![warn(unused_import_braces)]
 
 mod backend;
+mod debug;
 
 pub use crate::backend::{ObjectBuilder, ObjectModule, ObjectProduct};
+pub use crate::debug::{
+    DebugInfoOptions, DebugType, DebugTypeEncoding, DebugVariable, FunctionDebugInfo,
+    SourcePosition,
+};
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -239,3 +239,168 @@ fn reject_nul_byte_symbol_for_data() {
         )
         .unwrap();
 }
+
+#[test]
+fn debug_info() {
+    use gimli::{EndianSlice, LittleEndian};
+    use object::{Object, ObjectSection};
+
+    let flag_builder = settings::builder();
+    let isa_builder = cranelift_codegen::isa::lookup_by_name("x86_64-unknown-linux-gnu").unwrap();
+    let isa = isa_builder
+        .finish(settings::Flags::new(flag_builder))
+        .unwrap();
+    let mut builder = ObjectBuilder::new(isa, "foo", default_libcall_names()).unwrap();
+    builder
+        .debug_info(DebugInfoOptions {
+            name: "foo.c".into(),
+            comp_dir: "/tmp".into(),
+            producer: "test".into(),
+            files: vec!["src/foo.c".into()],
+            source_map: Box::new(|loc| {
+                Some(SourcePosition {
+                    file: 0,
+                    line: loc.bits().into(),
+                    column: 1,
+                })
+            }),
+        })
+        .unwrap();
+    let mut module = ObjectModule::new(builder);
+
+    let sig = Signature {
+        params: vec![AbiParam::new(types::I32), AbiParam::new(types::I32)],
+        returns: vec![AbiParam::new(types::I32)],
+        call_conv: CallConv::SystemV,
+    };
+    let func_id = module
+        .declare_function("add", Linkage::Export, &sig)
+        .unwrap();
+    let int = DebugType {
+        name: "int".into(),
+        size: 4,
+        encoding: DebugTypeEncoding::Signed,
+    };
+    module.set_function_debug_info(
+        func_id,
+        FunctionDebugInfo {
+            name: None,
+            position: Some(SourcePosition {
+                file: 0,
+                line: 3,
+                column: 1,
+            }),
+            variables: vec![
+                DebugVariable {
+                    name: "x".into(),
+                    label: ValueLabel::new(0),
+                    ty: int.clone(),
+                    parameter: true,
+                },
+                DebugVariable {
+                    name: "y".into(),
+                    label: ValueLabel::new(1),
+                    ty: int,
+                    parameter: true,
+                },
+            ],
+        },
+    );
+
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+    ctx.func.dfg.collect_debug_info();
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.append_block_params_for_function_params(block);
+        bcx.switch_to_block(block);
+        bcx.set_srcloc(SourceLoc::new(4));
+        let x = bcx.block_params(block)[0];
+        let y = bcx.block_params(block)[1];
+        bcx.set_val_label(x, ValueLabel::new(0));
+        bcx.set_val_label(y, ValueLabel::new(1));
+        let sum = bcx.ins().iadd(x, y);
+        bcx.set_srcloc(SourceLoc::new(5));
+        bcx.ins().return_(&[sum]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(func_id, &mut ctx).unwrap();
+
+    let bytes = module.finish_with_debug_info().unwrap().emit().unwrap();
+    let file = object::File::parse(&*bytes).unwrap();
+    let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+        let data = file
+            .section_by_name(id.name())
+            .map(|section| section.data().unwrap())
+            .unwrap_or(&[]);
+        Ok(EndianSlice::new(data, LittleEndian))
+    })
+    .unwrap();
+    assert!(file.section_by_name(".debug_frame").is_some());
+
+    let header = dwarf.units().next().unwrap().unwrap();
+    let unit = dwarf.unit(header).unwrap();
+
+    let program = unit.line_program.clone().unwrap();
+    let mut rows = program.rows();
+    let mut lines = Vec::new();
+    while let Some((header, row)) = rows.next_row().unwrap() {
+        if row.end_sequence() {
+            continue;
+        }
+        let file = row.file(header).unwrap();
+        let name = dwarf.attr_string(&unit, file.path_name()).unwrap();
+        assert_eq!(name.slice(), b"foo.c");
+        lines.push(row.line().map_or(0, |line| line.get()));
+    }
+    assert!(lines.contains(&4), "{lines:?}");
+    assert!(lines.contains(&5), "{lines:?}");
+
+    let mut names = Vec::new();
+    let mut entries = unit.entries();
+    while let Some((_, entry)) = entries.next_dfs().unwrap() {
+        let name = match entry.attr_value(gimli::DW_AT_name).unwrap() {
+            Some(name) => dwarf.attr_string(&unit, name).unwrap(),
+            None => continue,
+        };
+        let name = String::from_utf8(name.slice().to_vec()).unwrap();
+        if entry.tag() == gimli::DW_TAG_formal_parameter {
+            assert!(entry.attr(gimli::DW_AT_location).unwrap().is_some());
+        }
+        names.push((entry.tag(), name));
+    }
+    assert_eq!(
+        names,
+        [
+            (gimli::DW_TAG_compile_unit, "foo.c".to_string()),
+            (gimli::DW_TAG_base_type, "int".to_string()),
+            (gimli::DW_TAG_subprogram, "add".to_string()),
+            (gimli::DW_TAG_formal_parameter, "x".to_string()),
+            (gimli::DW_TAG_formal_parameter, "y".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn debug_info_requires_elf() {
+    let flag_builder = settings::builder();
+    let isa_builder = cranelift_codegen::isa::lookup_by_name("x86_64-apple-darwin").unwrap();
+    let isa = isa_builder
+        .finish(settings::Flags::new(flag_builder))
+        .unwrap();
+    let mut builder = ObjectBuilder::new(isa, "foo", default_libcall_names()).unwrap();
+    let err = builder
+        .debug_info(DebugInfoOptions {
+            name: "foo.c".into(),
+            comp_dir: "/tmp".into(),
+            producer: "test".into(),
+            files: vec![],
+            source_map: Box::new(|_| None),
+        })
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("MachO"), "{err}");
+}
@@ -28,6 +28,7 @@ pub enum Token<'a> {
     Dot,                   // '.'
     Colon,                 // ':'
     Equal,                 // '='
+    At,                    // '@'
     Not,                   // '!'
     Arrow,                 // '->'
     Float(&'a str),        // Floating point immediate
@@ -452,6 +453,10 @@ impl<'a> Lexer<'a> {
         }
 
         let end = self.pos;
+        if begin == end {
+            // A lone `@` separates the type and location of a stack map entry.
+            return token(Token::At, loc);
+        }
         token(Token::SourceLoc(&self.source[begin..end]), loc)
     }
 
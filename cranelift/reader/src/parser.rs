@@ -20,7 +20,7 @@ use cranelift_codegen::ir::{
     AbiParam, ArgumentExtension, ArgumentPurpose, Block, Constant, ConstantData, DynamicStackSlot,
     DynamicStackSlotData, DynamicTypeData, ExtFuncData, ExternalName, FuncRef, Function,
     GlobalValue, GlobalValueData, JumpTableData, MemFlags, Opcode, SigRef, Signature, StackSlot,
-    StackSlotData, StackSlotKind, Table, TableData, Type, UserFuncName, Value,
+    StackSlotData, StackSlotKind, Table, TableData, Type, UserFuncName, UserStackMapEntry, Value,
 };
 use cranelift_codegen::isa::{self, CallConv};
 use cranelift_codegen::packed_option::ReservedValue;
@@ -2158,6 +2158,11 @@ impl<'a> Parser<'a> {
             );
         }
 
+        // instruction ::=  [inst-results "="] Opcode(opc) ["." Type] ... * ["," stack-map]
+        if self.optional(Token::Comma) {
+            self.parse_user_stack_map(ctx, inst, opcode)?;
+        }
+
         // Collect any trailing comments.
         self.token();
         self.claim_gathered_comments(inst);
@@ -2165,6 +2170,50 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
+    // Parse the user stack map of a call instruction.
+    //
+    // stack-map ::= "stack_map" "=" "[" [stack-map-entry {"," stack-map-entry}] "]"
+    // stack-map-entry ::= Type "@" StackSlot(ss) "+" Integer(offset)
+    fn parse_user_stack_map(
+        &mut self,
+        ctx: &mut Context,
+        inst: ir::Inst,
+        opcode: Opcode,
+    ) -> ParseResult<()> {
+        self.match_identifier("stack_map", "expected 'stack_map'")?;
+        if !matches!(opcode, Opcode::Call | Opcode::CallIndirect) {
+            return err!(self.loc, "only non-tail calls can have stack maps");
+        }
+        self.match_token(Token::Equal, "expected '=' after 'stack_map'")?;
+        self.match_token(Token::LBracket, "expected '[' to begin stack map")?;
+        if self.optional(Token::RBracket) {
+            return Ok(());
+        }
+        loop {
+            let ty = self.match_type("expected stack map entry type")?;
+            self.match_token(Token::At, "expected '@' after stack map entry type")?;
+            let slot = self.match_ss("expected stack slot number: ss«n»")?;
+            ctx.check_ss(slot, self.loc)?;
+            let offset = match self.token() {
+                Some(Token::Integer(text)) if text.starts_with('+') => {
+                    self.consume();
+                    text[1..]
+                        .parse()
+                        .map_err(|_| self.error("invalid stack map entry offset"))?
+                }
+                _ => return err!(self.loc, "expected '+' offset after stack slot"),
+            };
+            ctx.function
+                .dfg
+                .append_user_stack_map_entry(inst, UserStackMapEntry { ty, slot, offset });
+            if !self.optional(Token::Comma) {
+                break;
+            }
+        }
+        self.match_token(Token::RBracket, "expected ']' to end stack map")?;
+        Ok(())
+    }
+
     // Type inference for polymorphic instructions.
     //
     // The controlling type variable can be specified explicitly as 'splat.i32x4 v5', or it can be
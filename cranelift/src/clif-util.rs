@@ -9,6 +9,7 @@ mod cat;
 mod compile;
 mod disasm;
 mod interpret;
+mod isle_coverage;
 mod print_cfg;
 mod run;
 mod utils;
@@ -30,6 +31,7 @@ enum Commands {
     Compile(compile::Options),
     Pass(PassOptions),
     Bugpoint(bugpoint::Options),
+    IsleCoverage(isle_coverage::Options),
 
     #[cfg(feature = "wasm")]
     Wasm(wasm::Options),
@@ -53,6 +55,10 @@ struct TestOptions {
     #[clap(short = 'T')]
     time_passes: bool,
 
+    /// Write how many times each ISLE rule fired to this file
+    #[clap(long, value_name = "FILE")]
+    isle_coverage: Option<PathBuf>,
+
     /// Specify an input file to be used. Use '-' for stdin.
     #[clap(required = true)]
     files: Vec<PathBuf>,
@@ -94,6 +100,7 @@ fn main() -> anyhow::Result<()> {
         Commands::PrintCfg(p) => print_cfg::run(&p)?,
         Commands::Compile(c) => compile::run(&c)?,
         Commands::Bugpoint(b) => bugpoint::run(&b)?,
+        Commands::IsleCoverage(c) => isle_coverage::run(&c)?,
 
         #[cfg(feature = "wasm")]
         Commands::Wasm(w) => wasm::run(&w)?,
@@ -109,14 +116,18 @@ fn main() -> anyhow::Result<()> {
         ),
 
         Commands::Test(t) => {
-            cranelift_filetests::run(
+            let result = cranelift_filetests::run(
                 t.verbose,
                 t.time_passes,
                 &t.files
                     .iter()
                     .map(|f| f.display().to_string())
                     .collect::<Vec<_>>(),
-            )?;
+            );
+            if let Some(path) = &t.isle_coverage {
+                isle_coverage::write_rule_counts(path)?;
+            }
+            result?;
         }
         Commands::Pass(p) => {
             cranelift_filetests::run_passes(
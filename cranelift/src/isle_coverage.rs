@@ -0,0 +1,92 @@
+//! The `isle-coverage` sub-command.
+//!
+//! Merge the ISLE rule counts written by `clif-util test --isle-coverage`, and
+//! report the rules which never fired.
+//!
+//! Coverage files have one line per rule, holding the number of times the rule
+//! fired and the rule's source position as `file:line:column`, separated by a
+//! space.
+
+use crate::utils::read_to_string;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Merge ISLE rule coverage files and report the rules that never fired
+#[derive(Parser)]
+pub struct Options {
+    /// Coverage files written by `clif-util test --isle-coverage`.
+    #[clap(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Write the merged counts to this file.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub fn run(options: &Options) -> Result<()> {
+    let mut counts = BTreeMap::new();
+    for path in &options.files {
+        read_counts(path, &mut counts)?;
+    }
+
+    if let Some(output) = &options.output {
+        write_counts(output, &counts)?;
+    }
+
+    let never_fired: Vec<_> = counts
+        .iter()
+        .filter(|&(_, &count)| count == 0)
+        .map(|(span, _)| span)
+        .collect();
+    for span in &never_fired {
+        println!("{}", span);
+    }
+    println!(
+        "{} of {} ISLE rules never fired",
+        never_fired.len(),
+        counts.len()
+    );
+    Ok(())
+}
+
+/// Write how many times each ISLE rule has fired in this process to `path`.
+#[cfg(feature = "isle-coverage")]
+pub fn write_rule_counts(path: &Path) -> Result<()> {
+    let mut counts = BTreeMap::new();
+    for (span, count) in cranelift_codegen::isle_coverage::rule_counts() {
+        *counts.entry(span.to_string()).or_insert(0) += count as u64;
+    }
+    write_counts(path, &counts)
+}
+
+#[cfg(not(feature = "isle-coverage"))]
+pub fn write_rule_counts(_path: &Path) -> Result<()> {
+    bail!("Error: clif-util was compiled without support for ISLE rule coverage.")
+}
+
+fn read_counts(path: &Path, counts: &mut BTreeMap<String, u64>) -> Result<()> {
+    let buffer = read_to_string(path)?;
+    for (i, line) in buffer.lines().enumerate() {
+        let (count, span) = match line.split_once(' ') {
+            Some((count, span)) => (count.parse::<u64>().ok(), span),
+            None => (None, line),
+        };
+        let count = match count {
+            Some(count) => count,
+            None => bail!("{}:{}: malformed coverage line", path.display(), i + 1),
+        };
+        *counts.entry(span.to_string()).or_insert(0) += count;
+    }
+    Ok(())
+}
+
+fn write_counts(path: &Path, counts: &BTreeMap<String, u64>) -> Result<()> {
+    let mut buffer = String::new();
+    for (span, count) in counts {
+        writeln!(buffer, "{} {}", count, span).unwrap();
+    }
+    std::fs::write(path, buffer).with_context(|| format!("failed to write {}", path.display()))
+}
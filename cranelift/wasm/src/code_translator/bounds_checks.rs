@@ -257,7 +257,40 @@ where
             ))
         }
 
-        // 3. General case for static memories.
+        // 3. Third special case for when we know that there are enough guard
+        //    pages to cover the offset and access size, but the index can't
+        //    be proven to always land within the heap plus its guard region.
+        //    This is the common case for 64-bit static memories.
+        //
+        //    Just like the second special case for dynamic memories, instead
+        //    of the precise condition
+        //
+        //        index + offset + access_size > bound
+        //
+        //    we check only the partial condition
+        //
+        //        index > bound
+        //
+        //    and rely on the guard region to catch the accesses which are at
+        //    most `offset + access_size` bytes beyond the bound. This lets
+        //    accesses using the same index but different offsets share a
+        //    single check.
+        HeapStyle::Static { bound } if offset_and_size <= heap.offset_guard_size => {
+            let oob = builder
+                .ins()
+                .icmp_imm(IntCC::UnsignedGreaterThan, index, bound as i64);
+            Reachable(explicit_check_oob_condition_and_compute_addr(
+                &mut builder.cursor(),
+                heap,
+                env.pointer_type(),
+                index,
+                offset,
+                spectre_mitigations_enabled,
+                oob,
+            ))
+        }
+
+        // 4. General case for static memories.
         //
         //    We have to explicitly test whether
         //
@@ -110,6 +110,16 @@ wasmtime_option_group! {
         pub log_to_files: Option<bool>,
         /// Enable coredump generation to this file after a WebAssembly trap.
         pub coredump: Option<String>,
+        /// Wait for a GDB remote protocol debugger, such as LLDB, to connect
+        /// to this local TCP port before running, and let it debug the guest.
+        pub gdbstub: Option<u16>,
+        /// Record the host calls made by the guest into this file, to replay
+        /// the run later with `-D replay`. Components using WASI preview2
+        /// can't be recorded, since their host calls pass resources.
+        pub record: Option<String>,
+        /// Replay the host calls recorded into this file with `-D record`
+        /// instead of calling the host.
+        pub replay: Option<String>,
     }
 
     enum Debug {
@@ -239,6 +249,19 @@ wasmtime_option_group! {
         /// available is dependent on the backends implemented in the
         /// `wasmtime_wasi_nn` crate.
         pub nn_graph: Vec<WasiNnGraph>,
+        /// Load machine learning graphs for wasi-nn on demand from a directory.
+        ///
+        /// Each subdirectory of the host directory holds a graph, named after
+        /// the subdirectory, in the given model encoding: e.g., with
+        /// `--wasi nn-graph-dir=onnx::/models`, loading the graph `foo` by
+        /// name loads `/models/foo`. Graphs are only loaded when first used
+        /// and are shared by all instances; this cannot be combined with
+        /// `nn-graph`.
+        pub nn_graph_dir: Option<WasiNnGraph>,
+        /// The maximum size, in bytes, of the graphs loaded from
+        /// `nn-graph-dir` at any one time, after which the least recently
+        /// used graphs are unloaded. Unlimited by default.
+        pub nn_graph_budget: Option<usize>,
         /// Flag for WASI preview2 to inherit the host's network within the
         /// guest so it has full access to all addresses/ports/etc.
         pub inherit_network: Option<bool>,
@@ -355,6 +378,14 @@ impl CommonOptions {
         if self.debug.coredump.is_some() {
             config.coredump_on_trap(true);
         }
+        if self.debug.gdbstub.is_some() {
+            config.guest_debug(true);
+        }
+        if self.debug.record.is_some() || self.debug.replay.is_some() {
+            // Host calls are then the only source of nondeterminism.
+            config.cranelift_nan_canonicalization(true);
+            config.relaxed_simd_deterministic(true);
+        }
         if let Some(level) = self.opts.opt_level {
             config.cranelift_opt_level(level);
         }
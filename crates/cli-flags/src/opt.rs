@@ -295,6 +295,14 @@ impl WasmtimeOptionValue for String {
     }
 }
 
+impl WasmtimeOptionValue for u16 {
+    const VAL_HELP: &'static str = "=N";
+    fn parse(val: Option<&str>) -> Result<Self> {
+        let val = String::parse(val)?;
+        Ok(val.parse()?)
+    }
+}
+
 impl WasmtimeOptionValue for u32 {
     const VAL_HELP: &'static str = "=N";
     fn parse(val: Option<&str>) -> Result<Self> {
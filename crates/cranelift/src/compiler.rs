@@ -3,6 +3,7 @@ use crate::func_environ::FuncEnvironment;
 use crate::{array_call_signature, native_call_signature, DEBUG_ASSERT_TRAP_CODE};
 use crate::{builder::LinkOptions, value_type, wasm_call_signature};
 use anyhow::{Context as _, Result};
+use cranelift_codegen::flowgraph::ControlFlowGraph;
 use cranelift_codegen::ir::{
     self, InstBuilder, MemFlags, UserExternalName, UserExternalNameRef, UserFuncName, Value,
 };
@@ -23,8 +24,8 @@ use object::write::{Object, StandardSegment, SymbolId};
 use object::{RelocationEncoding, RelocationKind, SectionKind};
 use std::any::Any;
 use std::cmp;
-use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryFrom;
 use std::mem;
 use std::path;
@@ -33,8 +34,8 @@ use wasmparser::{FuncValidatorAllocations, FunctionBody};
 use wasmtime_cranelift_shared::{CompiledFunction, ModuleTextBuilder};
 use wasmtime_environ::{
     AddressMapSection, CacheStore, CompileError, FlagValue, FunctionBodyData, FunctionLoc,
-    ModuleTranslation, ModuleTypes, PtrSize, StackMapInformation, TrapEncodingBuilder, Tunables,
-    VMOffsets, WasmFunctionInfo,
+    ModuleProfile, ModuleTranslation, ModuleTypes, PtrSize, StackMapInformation,
+    TrapEncodingBuilder, Tunables, TypeConvert, VMOffsets, WasmError, WasmFunctionInfo,
 };
 
 #[cfg(feature = "component-model")]
@@ -209,6 +210,16 @@ impl wasmtime_environ::Compiler for Compiler {
         });
         context.func.stack_limit = Some(stack_limit);
         let FunctionBodyData { validator, body } = input;
+        if self.tunables.guest_debug {
+            let mut locals = wasm_func_ty.params().to_vec();
+            for local in body.get_locals_reader().map_err(WasmError::from)? {
+                let (count, ty) = local.map_err(WasmError::from)?;
+                let ty = func_env.convert_valtype(ty);
+                locals.extend((0..count).map(|_| ty));
+            }
+            let func_start = body.get_binary_reader().original_position();
+            func_env.enable_guest_debug(u32::try_from(func_start).unwrap(), locals);
+        }
         let mut validator =
             validator.into_validator(mem::take(&mut compiler.cx.validator_allocations));
         compiler.cx.func_translator.translate_body(
@@ -218,6 +229,11 @@ impl wasmtime_environ::Compiler for Compiler {
             &mut func_env,
         )?;
 
+        if !translation.profile.is_empty() {
+            let profile = function_profile(&context.func, &translation.profile);
+            context.set_profile(profile);
+        }
+
         if let Some(path) = &self.clif_dir {
             use std::io::Write;
 
@@ -1125,6 +1141,80 @@ fn mach_stack_maps_to_stack_maps(mach_stack_maps: &[MachStackMap]) -> Vec<StackM
     stack_maps
 }
 
+/// Converts the branch counts of a module's `profile` into the edge and block
+/// counts of `func`, the translation of one of its functions.
+///
+/// The branches that the translator emits for `if`, `br_if` and `br_table`
+/// have the offsets of those instructions as source locations. Counts that
+/// don't match the branch at their offset are ignored.
+fn function_profile(func: &ir::Function, profile: &ModuleProfile) -> ir::FunctionProfile {
+    let pool = &func.dfg.value_lists;
+    let cfg = ControlFlowGraph::with_function(func);
+    let mut func_profile = ir::FunctionProfile::new();
+    for block in func.layout.blocks() {
+        let inst = match func.layout.last_inst(block) {
+            Some(inst) => inst,
+            None => continue,
+        };
+        let counts = match profile.branch_counts(func.srcloc(inst).bits()) {
+            Some(counts) => counts,
+            None => continue,
+        };
+        let succs: Vec<ir::Block> = match func.dfg.insts[inst] {
+            ir::InstructionData::Brif {
+                blocks: [then_block, else_block],
+                ..
+            } => vec![then_block.block(pool), else_block.block(pool)],
+            ir::InstructionData::BranchTable { table, .. } => {
+                let table = &func.stencil.dfg.jump_tables[table];
+                table
+                    .as_slice()
+                    .iter()
+                    .chain(Some(&table.default_block()))
+                    .map(|call| call.block(pool))
+                    .collect()
+            }
+            _ => continue,
+        };
+        if succs.len() != counts.len() {
+            continue;
+        }
+
+        let mut edge_counts = BTreeMap::new();
+        for (&succ, &count) in succs.iter().zip(counts) {
+            *edge_counts.entry(succ).or_insert(0) += count;
+        }
+        for (succ, count) in edge_counts {
+            func_profile.set_edge_count(block, succ, count);
+            // Targets of a `br_table` with arguments are reached through a
+            // block which only jumps to them.
+            let jump = func.layout.first_inst(succ).unwrap();
+            if let ir::InstructionData::Jump { destination, .. } = func.dfg.insts[jump] {
+                if cfg.pred_iter(succ).count() == 1 {
+                    func_profile.set_edge_count(succ, destination.block(pool), count);
+                }
+            }
+        }
+    }
+
+    // A block was executed as often as the edges into it were taken, if the
+    // counts of all of them are known.
+    for block in func.layout.blocks() {
+        let preds: BTreeSet<_> = cfg.pred_iter(block).map(|pred| pred.block).collect();
+        if preds.is_empty() {
+            continue;
+        }
+        let count = preds
+            .into_iter()
+            .map(|pred| func_profile.edge_count(pred, block))
+            .sum::<Option<u64>>();
+        if let Some(count) = count {
+            func_profile.set_block_count(block, count);
+        }
+    }
+    func_profile
+}
+
 fn declare_and_call(
     builder: &mut FunctionBuilder,
     signature: ir::Signature,
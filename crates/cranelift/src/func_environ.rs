@@ -20,8 +20,9 @@ use std::convert::TryFrom;
 use std::mem;
 use wasmparser::Operator;
 use wasmtime_environ::{
-    BuiltinFunctionIndex, MemoryPlan, MemoryStyle, Module, ModuleTranslation, ModuleTypes, PtrSize,
-    TableStyle, Tunables, TypeConvert, VMOffsets, WASM_PAGE_SIZE,
+    BuiltinFunctionIndex, DebugLocalKind, MemoryPlan, MemoryStyle, Module, ModuleTranslation,
+    ModuleTypes, PtrSize, TableStyle, Tunables, TypeConvert, VMOffsets, DEBUG_LOCAL_SLOT_SIZE,
+    WASM_PAGE_SIZE,
 };
 use wasmtime_environ::{FUNCREF_INIT_BIT, FUNCREF_MASK};
 
@@ -156,10 +157,26 @@ pub struct FuncEnvironment<'module_environment> {
 
     fuel_consumed: i64,
 
+    /// The state of the instrumentation for `Tunables::guest_debug`, if it's
+    /// enabled for this function.
+    guest_debug: Option<GuestDebug>,
+
     #[cfg(feature = "wmemcheck")]
     wmemcheck: bool,
 }
 
+/// Per-function state of the instrumentation for guest debugging.
+struct GuestDebug {
+    /// The offset of the function's body within the module, which instruction
+    /// offsets reported to the runtime are relative to.
+    func_start: u32,
+    /// The types of all of the function's locals, parameters included.
+    locals: Vec<WasmType>,
+    /// The stack slot that locals are spilled to when execution pauses,
+    /// created lazily.
+    slot: Option<ir::StackSlot>,
+}
+
 impl<'module_environment> FuncEnvironment<'module_environment> {
     pub fn new(
         isa: &'module_environment (dyn TargetIsa + 'module_environment),
@@ -200,11 +217,23 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             // Start with at least one fuel being consumed because even empty
             // functions should consume at least some fuel.
             fuel_consumed: 1,
+            guest_debug: None,
             #[cfg(feature = "wmemcheck")]
             wmemcheck,
         }
     }
 
+    /// Enables the instrumentation for guest debugging of the function being
+    /// translated, whose body starts at `func_start` in the module and whose
+    /// locals, parameters included, have the types `locals`.
+    pub fn enable_guest_debug(&mut self, func_start: u32, locals: Vec<WasmType>) {
+        self.guest_debug = Some(GuestDebug {
+            func_start,
+            locals,
+            slot: None,
+        });
+    }
+
     fn pointer_type(&self) -> ir::Type {
         self.isa.pointer_type()
     }
@@ -903,13 +932,101 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             .call_indirect(free_start_sig, free_start, &[vmctx]);
     }
 
-    fn current_func_name(&self, builder: &mut FunctionBuilder) -> Option<&str> {
-        let func_index = match &builder.func.name {
+    /// Emits a check of whether execution should pause for the debugger
+    /// before the current instruction, and the pause itself on a cold path.
+    ///
+    /// While paused the runtime is handed the locals of this frame, spilled
+    /// to a stack slot, and they're reloaded afterwards to pick up any writes.
+    /// See `wasmtime_environ::DEBUG_LOCAL_SLOT_SIZE` for the slot's layout.
+    fn debug_check(&mut self, builder: &mut FunctionBuilder<'_>) {
+        let func_index = self.current_func_index(builder);
+        let debug = self.guest_debug.as_mut().unwrap();
+        let offset = builder.srcloc().bits() - debug.func_start;
+        let num_locals = u32::try_from(debug.locals.len()).unwrap();
+        let slot = *debug.slot.get_or_insert_with(|| {
+            builder.create_sized_stack_slot(ir::StackSlotData::new(
+                ir::StackSlotKind::ExplicitSlot,
+                num_locals * (DEBUG_LOCAL_SLOT_SIZE + 1),
+            ))
+        });
+
+        let pause_block = builder.create_block();
+        let continuation_block = builder.create_block();
+        builder.set_cold_block(pause_block);
+
+        let func_index = builder.ins().iconst(I32, i64::from(func_index.as_u32()));
+        let offset = builder.ins().iconst(I32, i64::from(offset));
+        let check_sig = self.builtin_function_signatures.debug_check(builder.func);
+        let (vmctx, check) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::debug_check(),
+        );
+        let call = builder
+            .ins()
+            .call_indirect(check_sig, check, &[vmctx, func_index, offset]);
+        let pause = builder.func.dfg.first_result(call);
+        builder
+            .ins()
+            .brif(pause, pause_block, &[], continuation_block, &[]);
+        builder.seal_block(pause_block);
+
+        builder.switch_to_block(pause_block);
+        let debug = self.guest_debug.as_ref().unwrap();
+        let kinds_offset = num_locals * DEBUG_LOCAL_SLOT_SIZE;
+        let mut spilled = Vec::new();
+        for (i, ty) in debug.locals.iter().enumerate() {
+            let var = Variable::new(i);
+            let (kind, ty) = match ty {
+                WasmType::I32 => (DebugLocalKind::I32, I32),
+                WasmType::I64 => (DebugLocalKind::I64, I64),
+                WasmType::F32 => (DebugLocalKind::F32, F32),
+                WasmType::F64 => (DebugLocalKind::F64, F64),
+                WasmType::V128 => (DebugLocalKind::V128, I8X16),
+                WasmType::Ref(_) => (DebugLocalKind::Unavailable, INVALID),
+            };
+            let i = u32::try_from(i).unwrap();
+            let kind = builder.ins().iconst(I8, kind as i64);
+            let kind_offset = i32::try_from(kinds_offset + i).unwrap();
+            builder.ins().stack_store(kind, slot, kind_offset);
+            if ty != INVALID {
+                let value = builder.use_var(var);
+                let value_offset = i32::try_from(i * DEBUG_LOCAL_SLOT_SIZE).unwrap();
+                builder.ins().stack_store(value, slot, value_offset);
+                spilled.push((var, ty, value_offset));
+            }
+        }
+        let pointer_type = self.pointer_type();
+        let locals = builder.ins().stack_addr(pointer_type, slot, 0);
+        let count = builder.ins().iconst(I32, i64::from(num_locals));
+        let break_sig = self.builtin_function_signatures.debug_break(builder.func);
+        let (vmctx, brk) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::debug_break(),
+        );
+        builder
+            .ins()
+            .call_indirect(break_sig, brk, &[vmctx, func_index, offset, locals, count]);
+        for (var, ty, value_offset) in spilled {
+            let value = builder.ins().stack_load(ty, slot, value_offset);
+            builder.def_var(var, value);
+        }
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(continuation_block);
+
+        builder.switch_to_block(continuation_block);
+    }
+
+    fn current_func_index(&self, builder: &FunctionBuilder) -> FuncIndex {
+        match &builder.func.name {
             UserFuncName::User(user) => FuncIndex::from_u32(user.index),
             _ => {
                 panic!("function name not a UserFuncName::User as expected")
             }
-        };
+        }
+    }
+
+    fn current_func_name(&self, builder: &mut FunctionBuilder) -> Option<&str> {
+        let func_index = self.current_func_index(builder);
         self.translation
             .debuginfo
             .name_section
@@ -2391,6 +2508,9 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         builder: &mut FunctionBuilder,
         state: &FuncTranslationState,
     ) -> WasmResult<()> {
+        if self.guest_debug.is_some() && state.reachable() {
+            self.debug_check(builder);
+        }
         if self.tunables.consume_fuel {
             self.fuel_before_op(op, builder, state.reachable());
         }
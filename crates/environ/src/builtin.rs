@@ -67,6 +67,12 @@ macro_rules! foreach_builtin_function {
             update_stack_pointer(vmctx: vmctx, value: i32);
             /// Invoked before memory.grow is called.
             update_mem_size(vmctx: vmctx, num_bytes: i32);
+            /// Invoked before each instruction when guest debugging is
+            /// enabled, returning whether execution should pause there.
+            debug_check(vmctx: vmctx, func: i32, offset: i32) -> i32;
+            /// Invoked when execution pauses for guest debugging, with the
+            /// locals of the paused frame.
+            debug_break(vmctx: vmctx, func: i32, offset: i32, locals: pointer, count: i32);
         }
     };
 }
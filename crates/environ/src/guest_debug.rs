@@ -0,0 +1,45 @@
+//! Layout of the frames which code compiled with `Tunables::guest_debug`
+//! hands to the runtime when it pauses.
+
+/// The size, in bytes, of each local's slot in the locals area of a paused
+/// frame.
+///
+/// The locals area of a frame with `n` locals is made of `n` slots holding the
+/// values of the locals in native byte order, followed by `n` bytes each
+/// holding the `DebugLocalKind` of the corresponding local. Code resumes with
+/// whatever values are in the slots once the runtime returns.
+pub const DEBUG_LOCAL_SLOT_SIZE: u32 = 16;
+
+/// The kind of value held by a local of a paused frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DebugLocalKind {
+    /// An `i32` local.
+    I32,
+    /// An `i64` local.
+    I64,
+    /// An `f32` local.
+    F32,
+    /// An `f64` local.
+    F64,
+    /// A `v128` local.
+    V128,
+    /// A reference local; these aren't spilled since they must stay visible
+    /// to stack maps, so their slots hold nothing.
+    Unavailable,
+}
+
+impl DebugLocalKind {
+    /// Decodes a kind byte from a frame's locals area.
+    pub fn from_u8(byte: u8) -> Option<DebugLocalKind> {
+        Some(match byte {
+            0 => DebugLocalKind::I32,
+            1 => DebugLocalKind::I64,
+            2 => DebugLocalKind::F32,
+            3 => DebugLocalKind::F64,
+            4 => DebugLocalKind::V128,
+            5 => DebugLocalKind::Unavailable,
+            _ => return None,
+        })
+    }
+}
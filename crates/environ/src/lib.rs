@@ -9,10 +9,12 @@
 mod address_map;
 mod builtin;
 mod compilation;
+mod guest_debug;
 mod module;
 mod module_environ;
 mod module_types;
 pub mod obj;
+mod profile;
 mod ref_bits;
 mod scopevec;
 mod stack_map;
@@ -23,9 +25,11 @@ mod vmoffsets;
 pub use crate::address_map::*;
 pub use crate::builtin::*;
 pub use crate::compilation::*;
+pub use crate::guest_debug::*;
 pub use crate::module::*;
 pub use crate::module_environ::*;
 pub use crate::module_types::*;
+pub use crate::profile::ModuleProfile;
 pub use crate::ref_bits::*;
 pub use crate::scopevec::ScopeVec;
 pub use crate::stack_map::StackMap;
@@ -4,9 +4,9 @@ use crate::module::{
 };
 use crate::{
     DataIndex, DefinedFuncIndex, ElemIndex, EntityIndex, EntityType, FuncIndex, GlobalIndex,
-    GlobalInit, MemoryIndex, ModuleTypesBuilder, PrimaryMap, SignatureIndex, TableIndex,
-    TableInitialValue, Tunables, TypeConvert, TypeIndex, WasmError, WasmFuncType, WasmHeapType,
-    WasmResult, WasmType,
+    GlobalInit, MemoryIndex, ModuleProfile, ModuleTypesBuilder, PrimaryMap, SignatureIndex,
+    TableIndex, TableInitialValue, Tunables, TypeConvert, TypeIndex, WasmError, WasmFuncType,
+    WasmHeapType, WasmResult, WasmType,
 };
 use cranelift_entity::packed_option::ReservedValue;
 use std::borrow::Cow;
@@ -63,6 +63,10 @@ pub struct ModuleTranslation<'data> {
     /// configuration.
     pub has_unparsed_debuginfo: bool,
 
+    /// Execution profile of the module guiding the layout of compiled code,
+    /// or an empty profile if there is none.
+    pub profile: ModuleProfile,
+
     /// List of data segments found in this module which should be concatenated
     /// together for the final compiled artifact.
     ///
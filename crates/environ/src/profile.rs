@@ -0,0 +1,54 @@
+//! Execution profiles of WebAssembly modules, used to lay out compiled code.
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How often the branches of a WebAssembly module went each way, as measured
+/// by running the module.
+///
+/// Branches are identified by the offset of their `if`, `br_if` or
+/// `br_table` instruction in the module's binary encoding. Compilers which
+/// support profiles place the most frequently taken successor of a branch
+/// right after it, and move code that was never reached out of the way. A
+/// profile only affects code layout, so a profile which doesn't match the
+/// module at all makes code slower, not incorrect.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleProfile {
+    branches: BTreeMap<u32, Vec<u64>>,
+}
+
+impl ModuleProfile {
+    /// Creates an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the `if` or `br_if` instruction at `offset` was executed
+    /// with a non-zero condition `taken` times and with a zero condition
+    /// `not_taken` times.
+    pub fn set_branch_counts(&mut self, offset: u32, taken: u64, not_taken: u64) {
+        self.branches.insert(offset, vec![taken, not_taken]);
+    }
+
+    /// Records how many times the `br_table` instruction at `offset` branched
+    /// to each of its `targets`, in order, and to its `default` target.
+    pub fn set_br_table_counts(&mut self, offset: u32, targets: &[u64], default: u64) {
+        let mut counts = targets.to_vec();
+        counts.push(default);
+        self.branches.insert(offset, counts);
+    }
+
+    /// The counts recorded for the branch at `offset`, if any.
+    ///
+    /// These are the taken and not-taken counts of an `if` or `br_if`, or
+    /// the counts of each target of a `br_table` followed by the count of its
+    /// default target.
+    pub fn branch_counts(&self, offset: u32) -> Option<&[u64]> {
+        self.branches.get(&offset).map(|counts| &counts[..])
+    }
+
+    /// Is this profile empty?
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+}
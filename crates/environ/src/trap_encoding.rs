@@ -1,6 +1,7 @@
 use crate::obj::ELF_WASMTIME_TRAPS;
 use object::write::{Object, StandardSegment};
 use object::{Bytes, LittleEndian, SectionKind, U32Bytes};
+use serde_derive::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::fmt;
 use std::ops::Range;
@@ -38,7 +39,7 @@ pub struct TrapInformation {
 //
 // These need to be kept in sync.
 #[non_exhaustive]
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub enum Trap {
     /// The current stack space was exhausted.
@@ -54,6 +54,10 @@ pub struct Tunables {
 
     /// Whether or not Wasm functions can be tail-called or not.
     pub tail_callable: bool,
+
+    /// Whether or not code is instrumented for in-process guest debugging,
+    /// pausing at breakpoints and single steps.
+    pub guest_debug: bool,
 }
 
 impl Default for Tunables {
@@ -112,6 +116,7 @@ impl Default for Tunables {
             debug_adapter_modules: false,
             relaxed_simd_deterministic: false,
             tail_callable: false,
+            guest_debug: false,
         }
     }
 }
@@ -43,6 +43,14 @@ impl FiberStack {
         Ok(Self(imp::FiberStack::from_raw_parts(bottom, len)?))
     }
 
+    /// Creates a new fiber stack from a custom stack implementation, which is
+    /// dropped along with the returned stack.
+    ///
+    /// Returns an error on platforms which don't support custom stacks.
+    pub fn from_custom(custom: Box<dyn RuntimeFiberStack>) -> io::Result<Self> {
+        Ok(Self(imp::FiberStack::from_custom(custom)?))
+    }
+
     /// Gets the top of the stack.
     ///
     /// Returns `None` if the platform does not support getting the top of the
@@ -58,6 +66,23 @@ impl FiberStack {
     }
 }
 
+/// A fiber stack whose memory is managed by the embedder rather than by this
+/// crate.
+///
+/// # Safety
+///
+/// The memory in `range` must be readable and writable for as long as the
+/// stack exists, `top` must be the end of `range` and suitably aligned for the
+/// platform's stack pointer, and the page beneath `range` must be unmapped as a
+/// guard page.
+pub unsafe trait RuntimeFiberStack: Send + Sync {
+    /// The top of the stack, i.e. the end of [`RuntimeFiberStack::range`].
+    fn top(&self) -> *mut u8;
+
+    /// The range of addresses which make up the usable stack.
+    fn range(&self) -> Range<usize>;
+}
+
 pub struct Fiber<'a, Resume, Yield, Return> {
     stack: FiberStack,
     inner: imp::Fiber,
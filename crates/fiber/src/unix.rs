@@ -31,21 +31,45 @@
 
 #![allow(unused_macros)]
 
-use crate::RunResult;
+use crate::{RunResult, RuntimeFiberStack};
 use std::cell::Cell;
+use std::fmt;
 use std::io;
 use std::ops::Range;
 use std::ptr;
 
-#[derive(Debug)]
 pub struct FiberStack {
     // The top of the stack; for stacks allocated by the fiber implementation itself,
     // the base address of the allocation will be `top.sub(len.unwrap())`
     top: *mut u8,
     // The length of the stack
     len: usize,
-    // whether or not this stack was mmap'd
-    mmap: bool,
+    // Who owns the memory of the stack
+    storage: FiberStackStorage,
+}
+
+enum FiberStackStorage {
+    // The stack was mmap'd by `FiberStack::new` and is unmapped on drop.
+    Mmap,
+    // The stack is owned by whoever called `FiberStack::from_raw_parts`.
+    Unmanaged,
+    // The stack is owned by a custom implementation, dropped along with it.
+    Custom(Box<dyn RuntimeFiberStack>),
+}
+
+impl fmt::Debug for FiberStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let storage = match self.storage {
+            FiberStackStorage::Mmap => "mmap",
+            FiberStackStorage::Unmanaged => "unmanaged",
+            FiberStackStorage::Custom(_) => "custom",
+        };
+        f.debug_struct("FiberStack")
+            .field("top", &self.top)
+            .field("len", &self.len)
+            .field("storage", &storage)
+            .finish()
+    }
 }
 
 impl FiberStack {
@@ -78,7 +102,7 @@ impl FiberStack {
             Ok(Self {
                 top: mmap.cast::<u8>().add(mmap_len),
                 len: mmap_len,
-                mmap: true,
+                storage: FiberStackStorage::Mmap,
             })
         }
     }
@@ -87,7 +111,23 @@ impl FiberStack {
         Ok(Self {
             top: base.add(len),
             len,
-            mmap: false,
+            storage: FiberStackStorage::Unmanaged,
+        })
+    }
+
+    pub fn from_custom(custom: Box<dyn RuntimeFiberStack>) -> io::Result<Self> {
+        let range = custom.range();
+        let top = custom.top();
+        if top as usize != range.end {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the top of a custom fiber stack must be the end of its range",
+            ));
+        }
+        Ok(Self {
+            top,
+            len: range.len(),
+            storage: FiberStackStorage::Custom(custom),
         })
     }
 
@@ -104,7 +144,7 @@ impl FiberStack {
 impl Drop for FiberStack {
     fn drop(&mut self) {
         unsafe {
-            if self.mmap {
+            if let FiberStackStorage::Mmap = self.storage {
                 let ret = rustix::mm::munmap(self.top.sub(self.len) as _, self.len);
                 debug_assert!(ret.is_ok());
             }
@@ -0,0 +1,17 @@
+fn main() {
+    #[cfg(feature = "gdb_jit_int")]
+    {
+        use std::env;
+        use wasmtime_versioned_export_macros::versioned_suffix;
+
+        let mut build = cc::Build::new();
+        build.warnings(true);
+        let os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+        build.define(&format!("CFG_TARGET_OS_{}", os), None);
+        build.define("VERSIONED_SUFFIX", Some(versioned_suffix!()));
+        println!("cargo:rerun-if-changed=src/gdb_jit_int.c");
+        build.file("src/gdb_jit_int.c");
+        build.compile("wasmtime-jit-debug");
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
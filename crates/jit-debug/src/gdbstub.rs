@@ -0,0 +1,552 @@
+//! A server for the GDB remote serial protocol which describes the state of a
+//! paused WebAssembly guest, rather than that of the native code it's
+//! compiled to.
+//!
+//! Addresses follow the convention of LLDB's support for wasm targets: the top
+//! two bits of a 64-bit address select its space, the next 30 bits a module
+//! and the low 32 bits an offset within it. See [`WasmAddr`] for the spaces.
+//! The locals of the paused frame are exposed as registers `local0`,
+//! `local1`, ... following the program counter, sized according to their
+//! type, so `qRegisterInfo` describes the function which is currently paused.
+//! The `qWasmCallStack`, `qWasmLocal`, `qWasmGlobal` and
+//! `qWasmMem` extensions LLDB uses for wasm are supported as well.
+//!
+//! Only a single thread of execution, with id 1, is described.
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+
+/// The maximum size of the packets the debugger is allowed to send.
+const PACKET_SIZE: usize = 0x4000;
+
+const SIGTRAP: u8 = 5;
+
+/// The size reported for locals whose value isn't available, such as those of
+/// reference types.
+const UNAVAILABLE_LOCAL_SIZE: usize = 8;
+
+/// The space an address of a [`WasmAddr`] refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WasmAddrSpace {
+    /// Offsets within the first linear memory of the module's instance.
+    Memory,
+    /// Offsets within the module's binary, which is also how code is
+    /// addressed.
+    Code,
+}
+
+/// An address in the wasm address space a [`GdbStub`] describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WasmAddr {
+    /// The space of this address.
+    pub space: WasmAddrSpace,
+    /// The id of the module this address belongs to, which is its index in
+    /// [`Target::modules`].
+    pub module: u32,
+    /// The offset of this address within its space.
+    pub offset: u32,
+}
+
+impl WasmAddr {
+    /// Returns the address of the byte at `offset` in the binary of `module`.
+    pub fn code(module: u32, offset: u32) -> WasmAddr {
+        WasmAddr {
+            space: WasmAddrSpace::Code,
+            module,
+            offset,
+        }
+    }
+
+    /// Returns the address of the byte at `offset` in the memory of the
+    /// instance of `module`.
+    pub fn memory(module: u32, offset: u32) -> WasmAddr {
+        WasmAddr {
+            space: WasmAddrSpace::Memory,
+            module,
+            offset,
+        }
+    }
+
+    /// Encodes this address as the debugger sees it.
+    pub fn encode(&self) -> u64 {
+        let space = match self.space {
+            WasmAddrSpace::Memory => 0,
+            WasmAddrSpace::Code => 1,
+        };
+        (space << 62) | (u64::from(self.module & 0x3fff_ffff) << 32) | u64::from(self.offset)
+    }
+
+    /// Decodes an address sent by the debugger.
+    pub fn decode(addr: u64) -> Option<WasmAddr> {
+        let space = match addr >> 62 {
+            0 => WasmAddrSpace::Memory,
+            1 => WasmAddrSpace::Code,
+            _ => return None,
+        };
+        Some(WasmAddr {
+            space,
+            module: ((addr >> 32) & 0x3fff_ffff) as u32,
+            offset: addr as u32,
+        })
+    }
+}
+
+/// The paused guest a [`GdbStub`] answers the debugger's requests about.
+///
+/// Frames are numbered from the innermost one, which is the paused frame.
+/// Values are all exchanged as little-endian bytes.
+pub trait Target {
+    /// Returns the name of each module, indexed by module id.
+    fn modules(&mut self) -> Vec<String>;
+
+    /// Returns the code address of each frame on the stack, innermost first.
+    fn call_stack(&mut self) -> Vec<WasmAddr>;
+
+    /// Returns the number of locals of the frame `frame`.
+    fn num_locals(&mut self, frame: usize) -> usize;
+
+    /// Returns the value of the local `index` of the frame `frame`, or
+    /// `None` if it isn't available.
+    fn local(&mut self, frame: usize, index: usize) -> Option<Vec<u8>>;
+
+    /// Sets the value of the local `index` of the frame `frame`, returning
+    /// whether it was possible.
+    fn set_local(&mut self, frame: usize, index: usize, value: &[u8]) -> bool;
+
+    /// Returns the value of the global `index` of the instance of the frame
+    /// `frame`, or `None` if it isn't available.
+    fn global(&mut self, frame: usize, index: usize) -> Option<Vec<u8>>;
+
+    /// Reads up to `len` bytes at `addr`, returning `None` if nothing can be
+    /// read there.
+    fn read(&mut self, addr: WasmAddr, len: usize) -> Option<Vec<u8>>;
+
+    /// Writes `data` at `addr`, returning whether it was possible.
+    fn write(&mut self, addr: WasmAddr, data: &[u8]) -> bool;
+
+    /// Adds a breakpoint before the instruction at the code address `addr`,
+    /// returning whether it was possible.
+    fn add_breakpoint(&mut self, addr: WasmAddr) -> bool;
+
+    /// Removes a breakpoint added with [`Target::add_breakpoint`], returning
+    /// whether it existed.
+    fn remove_breakpoint(&mut self, addr: WasmAddr) -> bool;
+}
+
+/// How the debugger asked for execution to resume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resume {
+    /// Run until the next breakpoint.
+    Continue,
+    /// Pause again before the next instruction.
+    Step,
+    /// Run to completion, the debugger is gone.
+    Detach,
+    /// Terminate the guest.
+    Kill,
+}
+
+/// A GDB remote serial protocol server talking over `C`, typically a TCP
+/// stream.
+pub struct GdbStub<C> {
+    conn: C,
+    buf: Vec<u8>,
+    pos: usize,
+    no_ack: bool,
+    resumed: bool,
+}
+
+impl<C: Read + Write> GdbStub<C> {
+    /// Creates a server for a debugger which just connected over `conn`.
+    ///
+    /// The guest is reported as paused the first time [`GdbStub::stopped`]
+    /// is called.
+    pub fn new(conn: C) -> GdbStub<C> {
+        GdbStub {
+            conn,
+            buf: Vec::new(),
+            pos: 0,
+            no_ack: false,
+            resumed: false,
+        }
+    }
+
+    /// Reports that the guest paused and serves the debugger's requests about
+    /// `target` until it asks for execution to resume.
+    ///
+    /// A debugger which disconnects is treated as having detached.
+    pub fn stopped(&mut self, target: &mut dyn Target) -> io::Result<Resume> {
+        match self.serve(target) {
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::UnexpectedEof
+                        | io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::BrokenPipe
+                ) =>
+            {
+                Ok(Resume::Detach)
+            }
+            result => result,
+        }
+    }
+
+    fn serve(&mut self, target: &mut dyn Target) -> io::Result<Resume> {
+        if self.resumed {
+            self.resumed = false;
+            let reply = self.stop_reply(target);
+            self.send(&reply)?;
+        }
+        loop {
+            let packet = self.recv()?;
+            let resume = match packet.as_slice() {
+                b"c" => Resume::Continue,
+                b"s" => Resume::Step,
+                b"k" => return Ok(Resume::Kill),
+                b"D" => {
+                    self.send(b"OK")?;
+                    return Ok(Resume::Detach);
+                }
+                p if p.starts_with(b"vCont;c") || p.starts_with(b"vCont;C") => Resume::Continue,
+                p if p.starts_with(b"vCont;s") || p.starts_with(b"vCont;S") => Resume::Step,
+                _ => {
+                    let reply = self.handle(&packet, target);
+                    self.send(&reply)?;
+                    if packet == b"QStartNoAckMode" {
+                        self.no_ack = true;
+                    }
+                    continue;
+                }
+            };
+            self.resumed = true;
+            return Ok(resume);
+        }
+    }
+
+    /// Reports that the guest exited with `code`.
+    pub fn exited(&mut self, code: u8) -> io::Result<()> {
+        self.send(format!("W{code:02x}").as_bytes())
+    }
+
+    /// Reports that the guest was terminated, typically by a trap, as if by
+    /// `signal`.
+    pub fn terminated(&mut self, signal: u8) -> io::Result<()> {
+        self.send(format!("X{signal:02x}").as_bytes())
+    }
+
+    fn stop_reply(&mut self, target: &mut dyn Target) -> Vec<u8> {
+        let mut reply = format!("T{SIGTRAP:02x}");
+        if let Some(pc) = target.call_stack().first() {
+            write!(reply, "00:{};", hex(&pc.encode().to_le_bytes())).unwrap();
+        }
+        reply.push_str("thread:1;");
+        reply.into_bytes()
+    }
+
+    /// Computes the reply to a packet which doesn't resume execution. Empty
+    /// replies tell the debugger a packet isn't supported.
+    fn handle(&mut self, packet: &[u8], target: &mut dyn Target) -> Vec<u8> {
+        let packet = match std::str::from_utf8(packet) {
+            Ok(packet) => packet,
+            Err(_) => return Vec::new(),
+        };
+        let reply = match packet {
+            "?" => return self.stop_reply(target),
+            "QStartNoAckMode" => "OK".to_string(),
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "vCont?" => "vCont;c;C;s;S".to_string(),
+            "qHostInfo" => format!(
+                "triple:{};ptrsize:4;endian:little;",
+                hex(b"wasm32-unknown-unknown-wasm")
+            ),
+            "qProcessInfo" => format!(
+                "pid:1;parent-pid:1;triple:{};ptrsize:4;endian:little;",
+                hex(b"wasm32-unknown-unknown-wasm")
+            ),
+            "g" => {
+                let mut reply = hex(&self.pc(target).to_le_bytes());
+                for i in 0..target.num_locals(0) {
+                    match target.local(0, i) {
+                        Some(value) => reply.push_str(&hex(&value)),
+                        None => reply.push_str(&"xx".repeat(UNAVAILABLE_LOCAL_SIZE)),
+                    }
+                }
+                reply
+            }
+            p if p.starts_with("qSupported") => {
+                format!("PacketSize={PACKET_SIZE:x};QStartNoAckMode+;qXfer:libraries:read+")
+            }
+            p if p.starts_with('H') || p.starts_with('T') => "OK".to_string(),
+            p => match self.handle_with_args(p, target) {
+                Some(reply) => reply,
+                None => "E01".to_string(),
+            },
+        };
+        reply.into_bytes()
+    }
+
+    /// Handles packets with arguments, returning `None` on errors.
+    fn handle_with_args(&mut self, packet: &str, target: &mut dyn Target) -> Option<String> {
+        if let Some(n) = packet.strip_prefix("qRegisterInfo") {
+            let n = usize::from_str_radix(n, 16).ok()?;
+            return Some(match n.checked_sub(1) {
+                None => "name:pc;alt-name:pc;bitsize:64;offset:0;encoding:uint;format:hex;\
+                         set:General Purpose Registers;gcc:16;dwarf:16;generic:pc;"
+                    .to_string(),
+                Some(i) if i < target.num_locals(0) => {
+                    // Registers are laid out in the `g` reply one after the
+                    // other, starting with the 8 bytes of the `pc`.
+                    let offset = 8 + (0..i).map(|j| local_size(target, j)).sum::<usize>();
+                    let size = local_size(target, i);
+                    let encoding = if size > 8 {
+                        "encoding:vector;format:vector-uint8"
+                    } else {
+                        "encoding:uint;format:hex"
+                    };
+                    format!(
+                        "name:local{i};bitsize:{};offset:{offset};{encoding};set:Locals;",
+                        size * 8
+                    )
+                }
+                Some(_) => "E45".to_string(),
+            });
+        }
+        if let Some(n) = packet.strip_prefix('p') {
+            let n = usize::from_str_radix(n, 16).ok()?;
+            return match n.checked_sub(1) {
+                None => Some(hex(&self.pc(target).to_le_bytes())),
+                Some(i) => target.local(0, i).map(|v| hex(&v)),
+            };
+        }
+        if let Some(args) = packet.strip_prefix('P') {
+            let (n, value) = args.split_once('=')?;
+            let i = usize::from_str_radix(n, 16).ok()?.checked_sub(1)?;
+            return ok(target.set_local(0, i, &unhex(value)?));
+        }
+        if let Some(args) = packet.strip_prefix('m') {
+            let (addr, len) = args.split_once(',')?;
+            let addr = WasmAddr::decode(u64::from_str_radix(addr, 16).ok()?)?;
+            let len = usize::from_str_radix(len, 16).ok()?.min(PACKET_SIZE / 2);
+            return target.read(addr, len).map(|data| hex(&data));
+        }
+        if let Some(args) = packet.strip_prefix('M') {
+            let (addr, data) = args.split_once(':')?;
+            let (addr, _len) = addr.split_once(',')?;
+            let addr = WasmAddr::decode(u64::from_str_radix(addr, 16).ok()?)?;
+            return ok(target.write(addr, &unhex(data)?));
+        }
+        if let Some(args) = packet.strip_prefix("Z0,") {
+            return ok(target.add_breakpoint(breakpoint_addr(args)?));
+        }
+        if let Some(args) = packet.strip_prefix("z0,") {
+            return ok(target.remove_breakpoint(breakpoint_addr(args)?));
+        }
+        if packet.starts_with('Z') || packet.starts_with('z') {
+            return Some(String::new());
+        }
+        if let Some(args) = packet.strip_prefix("qXfer:libraries:read::") {
+            let (offset, len) = args.split_once(',')?;
+            let offset = usize::from_str_radix(offset, 16).ok()?;
+            let len = usize::from_str_radix(len, 16).ok()?.min(PACKET_SIZE / 2);
+            let xml = libraries(&target.modules());
+            let chunk = xml.get(offset..)?;
+            return Some(if chunk.len() > len {
+                format!("m{}", &chunk[..len])
+            } else {
+                format!("l{chunk}")
+            });
+        }
+        if packet.starts_with("qWasmCallStack") {
+            let mut reply = String::new();
+            for pc in target.call_stack() {
+                reply.push_str(&hex(&pc.encode().to_le_bytes()));
+            }
+            return Some(reply);
+        }
+        if let Some(args) = packet.strip_prefix("qWasmLocal:") {
+            let (frame, index) = frame_and_index(args)?;
+            return target.local(frame, index).map(|v| hex(&v));
+        }
+        if let Some(args) = packet.strip_prefix("qWasmGlobal:") {
+            let (frame, index) = frame_and_index(args)?;
+            return target.global(frame, index).map(|v| hex(&v));
+        }
+        if let Some(args) = packet.strip_prefix("qWasmMem:") {
+            let mut args = args.split(';');
+            let frame: usize = args.next()?.parse().ok()?;
+            let offset = u32::from_str_radix(args.next()?, 16).ok()?;
+            let len = usize::from_str_radix(args.next()?, 16)
+                .ok()?
+                .min(PACKET_SIZE / 2);
+            let module = target.call_stack().get(frame)?.module;
+            return target
+                .read(WasmAddr::memory(module, offset), len)
+                .map(|data| hex(&data));
+        }
+        Some(String::new())
+    }
+
+    fn pc(&mut self, target: &mut dyn Target) -> u64 {
+        target.call_stack().first().map_or(0, |pc| pc.encode())
+    }
+
+    /// Receives the next packet, acknowledging it unless acknowledgements
+    /// were turned off.
+    fn recv(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            // Skip anything before the start of a packet, notably
+            // acknowledgements and interrupt requests.
+            while self.byte()? != b'$' {}
+            let mut packet = Vec::new();
+            let mut checksum = 0u8;
+            loop {
+                let byte = self.byte()?;
+                if byte == b'#' {
+                    break;
+                }
+                checksum = checksum.wrapping_add(byte);
+                if byte == b'}' {
+                    let escaped = self.byte()?;
+                    checksum = checksum.wrapping_add(escaped);
+                    packet.push(escaped ^ 0x20);
+                } else {
+                    packet.push(byte);
+                }
+            }
+            let expected = [self.byte()?, self.byte()?];
+            let valid = std::str::from_utf8(&expected)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+                == Some(checksum);
+            if self.no_ack {
+                return Ok(packet);
+            }
+            if valid {
+                self.conn.write_all(b"+")?;
+                return Ok(packet);
+            }
+            self.conn.write_all(b"-")?;
+        }
+    }
+
+    /// Sends a packet, waiting for it to be acknowledged unless
+    /// acknowledgements were turned off.
+    fn send(&mut self, data: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        let mut checksum = 0u8;
+        for &byte in data {
+            if matches!(byte, b'$' | b'#' | b'}' | b'*') {
+                packet.push(b'}');
+                checksum = checksum.wrapping_add(b'}');
+                packet.push(byte ^ 0x20);
+                checksum = checksum.wrapping_add(byte ^ 0x20);
+            } else {
+                packet.push(byte);
+                checksum = checksum.wrapping_add(byte);
+            }
+        }
+        write!(packet, "#{checksum:02x}")?;
+        loop {
+            self.conn.write_all(&packet)?;
+            self.conn.flush()?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.byte()? {
+                b'-' => continue,
+                // Treat a packet sent without acknowledging ours as an
+                // acknowledgement, leaving the packet to be received.
+                b'$' => {
+                    self.pos -= 1;
+                    return Ok(());
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        if self.pos == self.buf.len() {
+            self.buf.resize(PACKET_SIZE, 0);
+            let n = self.conn.read(&mut self.buf)?;
+            self.buf.truncate(n);
+            self.pos = 0;
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+        self.pos += 1;
+        Ok(self.buf[self.pos - 1])
+    }
+}
+
+fn ok(success: bool) -> Option<String> {
+    success.then(|| "OK".to_string())
+}
+
+fn breakpoint_addr(args: &str) -> Option<WasmAddr> {
+    let (addr, _kind) = args.split_once(',')?;
+    WasmAddr::decode(u64::from_str_radix(addr, 16).ok()?)
+}
+
+fn frame_and_index(args: &str) -> Option<(usize, usize)> {
+    let (frame, index) = args.split_once(';')?;
+    Some((frame.parse().ok()?, index.parse().ok()?))
+}
+
+fn libraries(modules: &[String]) -> String {
+    let mut xml = String::from("<library-list>");
+    for (i, name) in modules.iter().enumerate() {
+        let mut escaped = String::new();
+        for c in name.chars() {
+            match c {
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '&' => escaped.push_str("&amp;"),
+                '"' => escaped.push_str("&quot;"),
+                // Keep the document ASCII so that it can be split anywhere
+                // when it's read in chunks.
+                c if !c.is_ascii() => write!(escaped, "&#x{:x};", u32::from(c)).unwrap(),
+                c => escaped.push(c),
+            }
+        }
+        let addr = WasmAddr::code(i as u32, 0).encode();
+        write!(
+            xml,
+            "<library name=\"{escaped}\"><section address=\"0x{addr:x}\"/></library>"
+        )
+        .unwrap();
+    }
+    xml.push_str("</library-list>");
+    xml
+}
+
+/// Returns the size in bytes of the register for the local `index` of the
+/// innermost frame.
+fn local_size(target: &mut dyn Target, index: usize) -> usize {
+    target
+        .local(0, index)
+        .map_or(UNAVAILABLE_LOCAL_SIZE, |value| value.len())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(s, "{byte:02x}").unwrap();
+    }
+    s
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
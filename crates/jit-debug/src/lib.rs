@@ -1,5 +1,8 @@
 #[cfg(feature = "gdb_jit_int")]
 pub mod gdb_jit_int;
 
+#[cfg(feature = "gdbstub")]
+pub mod gdbstub;
+
 #[cfg(all(feature = "perf_jitdump", target_os = "linux"))]
 pub mod perf_jitdump;
@@ -203,6 +203,27 @@ impl MemoryImage {
         }
     }
 
+    /// Creates an image of the current `contents` of a linear memory, for
+    /// example to create other memories starting from the same contents.
+    ///
+    /// Returns `None` if images aren't supported on this platform.
+    pub fn from_contents(contents: &[u8]) -> Result<Option<MemoryImage>> {
+        // Leading and trailing zero pages are left out of the image, they're
+        // zero anyway in the memory it's mapped into.
+        let page_size = crate::page_size();
+        assert_eq!(contents.len() % page_size, 0);
+        let (start, end) = match contents.iter().position(|b| *b != 0) {
+            Some(first) => {
+                let last = contents.iter().rposition(|b| *b != 0).unwrap();
+                let start = first - first % page_size;
+                let end = (last / page_size + 1) * page_size;
+                (start, end)
+            }
+            None => (0, 0),
+        };
+        MemoryImage::new(page_size as u32, start as u64, &contents[start..end], None)
+    }
+
     unsafe fn map_at(&self, base: *mut u8) -> Result<()> {
         cfg_if::cfg_if! {
             if #[cfg(all(unix, not(miri)))] {
@@ -591,6 +612,16 @@ impl MemoryImageSlot {
         Ok(())
     }
 
+    /// Returns an upper bound on how many bytes of this slot remain resident
+    /// after `clear_and_remain_ready` was called with `keep_resident`.
+    pub(crate) fn resident_bytes(&self, keep_resident: usize) -> usize {
+        if !cfg!(target_os = "linux") || cfg!(miri) {
+            // See `reset_all_memory_contents`: memory is entirely replaced.
+            return 0;
+        }
+        self.accessible.min(keep_resident)
+    }
+
     #[allow(dead_code)] // ignore warnings as this is only used in some cfgs
     unsafe fn reset_all_memory_contents(&mut self, keep_resident: usize) -> Result<()> {
         if !cfg!(target_os = "linux") || cfg!(miri) {
@@ -12,8 +12,8 @@ use crate::vmcontext::{
     VMTableDefinition, VMTableImport,
 };
 use crate::{
-    ExportFunction, ExportGlobal, ExportMemory, ExportTable, Imports, ModuleRuntimeInfo,
-    SendSyncPtr, Store, VMFunctionBody, VMSharedSignatureIndex, WasmFault,
+    CompiledModuleId, ExportFunction, ExportGlobal, ExportMemory, ExportTable, Imports,
+    ModuleRuntimeInfo, SendSyncPtr, Store, VMFunctionBody, VMSharedSignatureIndex, WasmFault,
 };
 use anyhow::Error;
 use anyhow::Result;
@@ -266,6 +266,12 @@ impl Instance {
         self.runtime_info.module()
     }
 
+    /// Returns the identifier of the compiled module this instance was created
+    /// from, if any.
+    pub(crate) fn compiled_module_id(&self) -> Option<CompiledModuleId> {
+        self.runtime_info.unique_id()
+    }
+
     #[inline]
     fn offsets(&self) -> &VMOffsets<HostPtr> {
         self.runtime_info.offsets()
@@ -1419,6 +1425,16 @@ impl InstanceHandle {
         self.instance_mut().defined_globals()
     }
 
+    /// Returns whether the passive element segment `index` was dropped.
+    pub fn elem_dropped(&self, index: ElemIndex) -> bool {
+        self.instance().dropped_elements.contains(index)
+    }
+
+    /// Returns whether the passive data segment `index` was dropped.
+    pub fn data_dropped(&self, index: DataIndex) -> bool {
+        self.instance().dropped_data.contains(index)
+    }
+
     /// Return a reference to the contained `Instance`.
     #[inline]
     pub(crate) fn instance(&self) -> &Instance {
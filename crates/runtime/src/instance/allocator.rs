@@ -24,7 +24,24 @@ pub use self::on_demand::OnDemandInstanceAllocator;
 #[cfg(feature = "pooling-allocator")]
 mod pooling;
 #[cfg(feature = "pooling-allocator")]
-pub use self::pooling::{InstanceLimits, PoolingInstanceAllocator, PoolingInstanceAllocatorConfig};
+pub use self::pooling::{
+    InstanceLimits, PoolStats, PoolingAllocatorStats, PoolingInstanceAllocator,
+    PoolingInstanceAllocatorConfig,
+};
+
+/// A creator of fiber stacks for async calls into wasm.
+#[cfg(feature = "async")]
+pub trait RuntimeFiberStackCreator: Send + Sync {
+    /// Creates a fiber stack with at least `size` bytes of usable stack.
+    fn new_stack(&self, size: usize) -> Result<wasmtime_fiber::FiberStack>;
+}
+
+#[cfg(feature = "async")]
+impl std::fmt::Debug for dyn RuntimeFiberStackCreator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RuntimeFiberStackCreator")
+    }
+}
 
 /// Represents a request for a new runtime instance.
 pub struct InstanceAllocationRequest<'a> {
@@ -290,6 +307,13 @@ pub unsafe trait InstanceAllocatorImpl {
 
     /// Allow access to memory regions protected by any protection key.
     fn allow_all_pkeys(&self);
+
+    /// Returns a snapshot of this allocator's pools if it is a pooling
+    /// allocator.
+    #[cfg(feature = "pooling-allocator")]
+    fn pooling_stats(&self) -> Option<PoolingAllocatorStats> {
+        None
+    }
 }
 
 /// A thing that can allocate instances.
@@ -12,6 +12,9 @@ use wasmtime_environ::{
     DefinedMemoryIndex, DefinedTableIndex, HostPtr, MemoryPlan, Module, TablePlan, VMOffsets,
 };
 
+#[cfg(feature = "async")]
+use super::RuntimeFiberStackCreator;
+
 #[cfg(feature = "component-model")]
 use wasmtime_environ::{
     component::{Component, VMComponentOffsets},
@@ -23,6 +26,8 @@ use wasmtime_environ::{
 pub struct OnDemandInstanceAllocator {
     mem_creator: Option<Arc<dyn RuntimeMemoryCreator>>,
     #[cfg(feature = "async")]
+    stack_creator: Option<Arc<dyn RuntimeFiberStackCreator>>,
+    #[cfg(feature = "async")]
     stack_size: usize,
 }
 
@@ -33,9 +38,18 @@ impl OnDemandInstanceAllocator {
         Self {
             mem_creator,
             #[cfg(feature = "async")]
+            stack_creator: None,
+            #[cfg(feature = "async")]
             stack_size,
         }
     }
+
+    /// Sets the creator of this allocator's fiber stacks, replacing the
+    /// default of mapping them with `wasmtime-fiber`.
+    #[cfg(feature = "async")]
+    pub fn set_stack_creator(&mut self, stack_creator: Arc<dyn RuntimeFiberStackCreator>) {
+        self.stack_creator = Some(stack_creator);
+    }
 }
 
 impl Default for OnDemandInstanceAllocator {
@@ -43,6 +57,8 @@ impl Default for OnDemandInstanceAllocator {
         Self {
             mem_creator: None,
             #[cfg(feature = "async")]
+            stack_creator: None,
+            #[cfg(feature = "async")]
             stack_size: 0,
         }
     }
@@ -142,7 +158,10 @@ unsafe impl InstanceAllocatorImpl for OnDemandInstanceAllocator {
             anyhow::bail!("fiber stacks are not supported by the allocator")
         }
 
-        let stack = wasmtime_fiber::FiberStack::new(self.stack_size)?;
+        let stack = match &self.stack_creator {
+            Some(creator) => creator.new_stack(self.stack_size)?,
+            None => wasmtime_fiber::FiberStack::new(self.stack_size)?,
+        };
         Ok(stack)
     }
 
@@ -89,6 +89,11 @@ use std::{
     mem,
     sync::atomic::{AtomicU64, Ordering},
 };
+
+#[cfg(feature = "async")]
+use super::RuntimeFiberStackCreator;
+#[cfg(feature = "async")]
+use std::sync::Arc;
 use table_pool::TablePool;
 use wasmtime_environ::{
     DefinedMemoryIndex, DefinedTableIndex, HostPtr, MemoryPlan, Module, TablePlan, Tunables,
@@ -236,6 +241,83 @@ impl Default for PoolingInstanceAllocatorConfig {
     }
 }
 
+/// Occupancy and reuse statistics of one of the pools of a
+/// [`PoolingInstanceAllocator`].
+///
+/// A slot is "warm" when it's unused but has been used before, in which case
+/// it may still hold the state of, and be affine to, the module which last
+/// used it. A slot is "cold" when it has never been used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The total number of slots in the pool.
+    pub capacity: u32,
+    /// The number of slots currently in use.
+    pub in_use: u32,
+    /// The number of unused warm slots.
+    pub unused_warm: u32,
+    /// The number of unused cold slots.
+    pub unused_cold: u32,
+    /// The number of allocations which asked for a slot affine to a
+    /// particular module's memory.
+    pub affinity_requests: u64,
+    /// The number of `affinity_requests` which were given such a slot.
+    pub affinity_hits: u64,
+    /// The number of allocations which failed because every slot was in use.
+    pub exhausted: u64,
+    /// An upper bound on the number of bytes that unused warm slots keep
+    /// resident, as configured by the various `keep_resident` options.
+    pub resident_bytes: usize,
+}
+
+impl PoolStats {
+    /// The fraction of `affinity_requests` which were given an affine slot,
+    /// or `None` if there were no such requests.
+    pub fn affinity_hit_rate(&self) -> Option<f64> {
+        if self.affinity_requests == 0 {
+            None
+        } else {
+            Some(self.affinity_hits as f64 / self.affinity_requests as f64)
+        }
+    }
+
+    fn add(&mut self, other: &PoolStats) {
+        self.capacity += other.capacity;
+        self.in_use += other.in_use;
+        self.unused_warm += other.unused_warm;
+        self.unused_cold += other.unused_cold;
+        self.affinity_requests += other.affinity_requests;
+        self.affinity_hits += other.affinity_hits;
+        self.exhausted += other.exhausted;
+        self.resident_bytes += other.resident_bytes;
+    }
+}
+
+/// A snapshot of the state of a [`PoolingInstanceAllocator`].
+#[derive(Clone, Debug, Default)]
+pub struct PoolingAllocatorStats {
+    /// The number of core instances currently allocated.
+    pub core_instances: u64,
+    /// The number of core instance allocations which failed because the
+    /// `total_core_instances` limit was reached.
+    pub core_instances_exhausted: u64,
+    /// The number of component instances currently allocated.
+    pub component_instances: u64,
+    /// The number of component instance allocations which failed because the
+    /// `total_component_instances` limit was reached.
+    pub component_instances_exhausted: u64,
+    /// The statistics of the memory pool as a whole.
+    pub memories: PoolStats,
+    /// The statistics of each stripe of the memory pool. There is more than
+    /// one stripe only when memory protection keys are in use, in which case
+    /// each stripe is protected by a different key.
+    pub memory_stripes: Vec<PoolStats>,
+    /// The statistics of the table pool.
+    pub tables: PoolStats,
+    /// The statistics of the stack pool. When stacks don't come from a pool,
+    /// only the capacity, the number in use and exhaustion are reported.
+    pub stacks: PoolStats,
+}
+
 /// Implements the pooling instance allocator.
 ///
 /// This allocator internally maintains pools of instances, memories, tables,
@@ -256,6 +338,8 @@ pub struct PoolingInstanceAllocator {
     // the increment trait methods for more details.
     live_core_instances: AtomicU64,
     live_component_instances: AtomicU64,
+    core_instances_exhausted: AtomicU64,
+    component_instances_exhausted: AtomicU64,
 
     memories: MemoryPool,
     tables: TablePool,
@@ -263,10 +347,17 @@ pub struct PoolingInstanceAllocator {
     #[cfg(all(feature = "async", unix, not(miri)))]
     stacks: StackPool,
 
-    #[cfg(all(feature = "async", windows))]
+    // Stacks which don't come from `stacks`, either because the platform
+    // doesn't use a stack pool or because a custom stack creator was
+    // configured, are counted in `live_stacks` to enforce `total_stacks`.
+    #[cfg(feature = "async")]
     stack_size: usize,
-    #[cfg(all(feature = "async", windows))]
+    #[cfg(feature = "async")]
     live_stacks: AtomicU64,
+    #[cfg(feature = "async")]
+    stacks_exhausted: AtomicU64,
+    #[cfg(feature = "async")]
+    stack_creator: Option<Arc<dyn RuntimeFiberStackCreator>>,
 }
 
 impl Drop for PoolingInstanceAllocator {
@@ -279,7 +370,7 @@ impl Drop for PoolingInstanceAllocator {
 
         #[cfg(all(feature = "async", unix, not(miri)))]
         debug_assert!(self.stacks.is_empty());
-        #[cfg(all(feature = "async", windows))]
+        #[cfg(feature = "async")]
         debug_assert_eq!(self.live_stacks.load(Ordering::Acquire), 0);
     }
 }
@@ -291,17 +382,114 @@ impl PoolingInstanceAllocator {
             limits: config.limits,
             live_component_instances: AtomicU64::new(0),
             live_core_instances: AtomicU64::new(0),
+            core_instances_exhausted: AtomicU64::new(0),
+            component_instances_exhausted: AtomicU64::new(0),
             memories: MemoryPool::new(config, tunables)?,
             tables: TablePool::new(config)?,
             #[cfg(all(feature = "async", unix, not(miri)))]
             stacks: StackPool::new(config)?,
-            #[cfg(all(feature = "async", windows))]
+            #[cfg(feature = "async")]
             stack_size: config.stack_size,
-            #[cfg(all(feature = "async", windows))]
+            #[cfg(feature = "async")]
             live_stacks: AtomicU64::new(0),
+            #[cfg(feature = "async")]
+            stacks_exhausted: AtomicU64::new(0),
+            #[cfg(feature = "async")]
+            stack_creator: None,
         })
     }
 
+    /// Creates a new pooling instance allocator which gets its fiber stacks
+    /// from `stack_creator` instead of from a pool.
+    ///
+    /// The `total_stacks` limit still applies to the stacks created.
+    #[cfg(feature = "async")]
+    pub fn with_stack_creator(
+        config: &PoolingInstanceAllocatorConfig,
+        tunables: &Tunables,
+        stack_creator: Arc<dyn RuntimeFiberStackCreator>,
+    ) -> Result<Self> {
+        // Don't reserve any address space for a stack pool which won't be used.
+        let mut pool_config = *config;
+        pool_config.limits.total_stacks = 0;
+        let mut allocator = Self::new(&pool_config, tunables)?;
+        allocator.limits.total_stacks = config.limits.total_stacks;
+        allocator.stack_creator = Some(stack_creator);
+        Ok(allocator)
+    }
+
+    /// Allocates a stack with `allocate`, counting it towards the
+    /// `total_stacks` limit.
+    #[cfg(feature = "async")]
+    fn allocate_counted_stack(
+        &self,
+        allocate: impl FnOnce() -> Result<wasmtime_fiber::FiberStack>,
+    ) -> Result<wasmtime_fiber::FiberStack> {
+        if self.stack_size == 0 {
+            bail!("fiber stack allocation not supported")
+        }
+
+        let old_count = self.live_stacks.fetch_add(1, Ordering::AcqRel);
+        if old_count >= u64::from(self.limits.total_stacks) {
+            self.live_stacks.fetch_sub(1, Ordering::AcqRel);
+            self.stacks_exhausted.fetch_add(1, Ordering::Relaxed);
+            bail!(
+                "maximum concurrent fiber limit of {} reached",
+                self.limits.total_stacks
+            );
+        }
+
+        match allocate() {
+            Ok(stack) => Ok(stack),
+            Err(e) => {
+                self.live_stacks.fetch_sub(1, Ordering::AcqRel);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns a snapshot of the occupancy and reuse of this allocator's
+    /// pools.
+    pub fn stats(&self) -> PoolingAllocatorStats {
+        let memory_stripes = self.memories.stripe_stats();
+        let mut memories = PoolStats::default();
+        for stripe in memory_stripes.iter() {
+            memories.add(stripe);
+        }
+        PoolingAllocatorStats {
+            core_instances: self.live_core_instances.load(Ordering::Acquire),
+            core_instances_exhausted: self.core_instances_exhausted.load(Ordering::Relaxed),
+            component_instances: self.live_component_instances.load(Ordering::Acquire),
+            component_instances_exhausted: self
+                .component_instances_exhausted
+                .load(Ordering::Relaxed),
+            memories,
+            memory_stripes,
+            tables: self.tables.stats(),
+            stacks: self.stack_stats(),
+        }
+    }
+
+    fn stack_stats(&self) -> PoolStats {
+        #[cfg(feature = "async")]
+        {
+            #[cfg(all(unix, not(miri)))]
+            if self.stack_creator.is_none() {
+                return self.stacks.stats();
+            }
+            let in_use = u32::try_from(self.live_stacks.load(Ordering::Acquire)).unwrap();
+            PoolStats {
+                capacity: self.limits.total_stacks,
+                in_use,
+                unused_cold: self.limits.total_stacks.saturating_sub(in_use),
+                exhausted: self.stacks_exhausted.load(Ordering::Relaxed),
+                ..PoolStats::default()
+            }
+        }
+        #[cfg(not(feature = "async"))]
+        PoolStats::default()
+    }
+
     fn core_instance_size(&self) -> usize {
         round_up_to_pow2(self.limits.core_instance_size, mem::align_of::<Instance>())
     }
@@ -470,6 +658,8 @@ unsafe impl InstanceAllocatorImpl for PoolingInstanceAllocator {
         let old_count = self.live_component_instances.fetch_add(1, Ordering::AcqRel);
         if old_count >= u64::from(self.limits.total_component_instances) {
             self.decrement_component_instance_count();
+            self.component_instances_exhausted
+                .fetch_add(1, Ordering::Relaxed);
             bail!(
                 "maximum concurrent component instance limit of {} reached",
                 self.limits.total_component_instances
@@ -486,6 +676,8 @@ unsafe impl InstanceAllocatorImpl for PoolingInstanceAllocator {
         let old_count = self.live_core_instances.fetch_add(1, Ordering::AcqRel);
         if old_count >= u64::from(self.limits.total_core_instances) {
             self.decrement_core_instance_count();
+            self.core_instances_exhausted
+                .fetch_add(1, Ordering::Relaxed);
             bail!(
                 "maximum concurrent core instance limit of {} reached",
                 self.limits.total_core_instances
@@ -536,36 +728,22 @@ unsafe impl InstanceAllocatorImpl for PoolingInstanceAllocator {
 
     #[cfg(feature = "async")]
     fn allocate_fiber_stack(&self) -> Result<wasmtime_fiber::FiberStack> {
+        if let Some(creator) = &self.stack_creator {
+            return self.allocate_counted_stack(|| creator.new_stack(self.stack_size));
+        }
+
         cfg_if::cfg_if! {
             if #[cfg(miri)] {
                 unimplemented!()
             } else if #[cfg(unix)] {
                 self.stacks.allocate()
             } else if #[cfg(windows)] {
-                if self.stack_size == 0 {
-                    bail!("fiber stack allocation not supported")
-                }
-
                 // On windows, we don't use a stack pool as we use the native
                 // fiber implementation. We do still enforce the `total_stacks`
                 // limit, however.
-
-                let old_count = self.live_stacks.fetch_add(1, Ordering::AcqRel);
-                if old_count >= u64::from(self.limits.total_stacks) {
-                    self.live_stacks.fetch_sub(1, Ordering::AcqRel);
-                    bail!(
-                        "maximum concurrent fiber limit of {} reached",
-                        self.limits.total_stacks
-                    );
-                }
-
-                match wasmtime_fiber::FiberStack::new(self.stack_size) {
-                    Ok(stack) => Ok(stack),
-                    Err(e) => {
-                        self.live_stacks.fetch_sub(1, Ordering::AcqRel);
-                        Err(anyhow::Error::from(e))
-                    }
-                }
+                self.allocate_counted_stack(|| {
+                    Ok(wasmtime_fiber::FiberStack::new(self.stack_size)?)
+                })
             } else {
                 compile_error!("not implemented");
             }
@@ -574,6 +752,12 @@ unsafe impl InstanceAllocatorImpl for PoolingInstanceAllocator {
 
     #[cfg(feature = "async")]
     unsafe fn deallocate_fiber_stack(&self, stack: &wasmtime_fiber::FiberStack) {
+        if self.stack_creator.is_some() {
+            // The stack's memory is released when it is dropped.
+            self.live_stacks.fetch_sub(1, Ordering::AcqRel);
+            return;
+        }
+
         cfg_if::cfg_if! {
             if #[cfg(miri)] {
                 let _ = stack;
@@ -598,6 +782,10 @@ unsafe impl InstanceAllocatorImpl for PoolingInstanceAllocator {
         self.memories.next_available_pkey()
     }
 
+    fn pooling_stats(&self) -> Option<PoolingAllocatorStats> {
+        Some(self.stats())
+    }
+
     fn restrict_to_pkey(&self, pkey: ProtectionKey) {
         mpk::allow(ProtectionMask::zero().or(pkey));
     }
@@ -616,7 +804,7 @@ mod test {
         let config = PoolingInstanceAllocatorConfig {
             limits: InstanceLimits {
                 total_memories: 1,
-                memory_pages: 0x10001,
+                memory_pages: (1 << 48) + 1,
                 ..Default::default()
             },
             ..PoolingInstanceAllocatorConfig::default()
@@ -631,7 +819,7 @@ mod test {
             )
             .map_err(|e| e.to_string())
             .expect_err("expected a failure constructing instance allocator"),
-            "module memory page limit of 65537 exceeds the maximum of 65536"
+            "module memory page limit of 281474976710657 exceeds the maximum of 281474976710656"
         );
     }
 
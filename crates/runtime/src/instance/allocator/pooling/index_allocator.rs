@@ -1,5 +1,6 @@
 //! Index/slot allocator policies for the pooling allocator.
 
+use super::PoolStats;
 use crate::CompiledModuleId;
 use std::collections::hash_map::{Entry, HashMap};
 use std::mem;
@@ -42,6 +43,10 @@ impl SimpleIndexAllocator {
         self.0.free(index);
     }
 
+    pub fn stats(&self) -> PoolStats {
+        self.0.stats()
+    }
+
     #[cfg(test)]
     #[allow(unused)]
     pub(crate) fn testing_freelist(&self) -> Vec<SlotId> {
@@ -96,6 +101,14 @@ struct Inner {
     /// The `List` here is appended to during deallocation and removal happens
     /// from the tail during allocation.
     module_affine: HashMap<MemoryInModule, List>,
+
+    /// The number of allocations which asked for a slot affine to a module's
+    /// memory, and how many of those got one.
+    affinity_requests: u64,
+    affinity_hits: u64,
+
+    /// The number of allocations which failed because all slots were in use.
+    exhausted: u64,
 }
 
 /// A helper "linked list" data structure which is based on indices.
@@ -164,6 +177,9 @@ impl ModuleAffinityIndexAllocator {
             module_affine: HashMap::new(),
             slot_state: (0..capacity).map(|_| SlotState::UnusedCold).collect(),
             warm: List::default(),
+            affinity_requests: 0,
+            affinity_hits: 0,
+            exhausted: 0,
         }))
     }
 
@@ -215,7 +231,12 @@ impl ModuleAffinityIndexAllocator {
         // As a first-pass always attempt an affine allocation. This will
         // succeed if any slots are considered affine to `module_id` (if it's
         // specified). Failing that something else is attempted to be chosen.
-        let slot_id = inner.pick_affine(for_memory).or_else(|| {
+        let affine = inner.pick_affine(for_memory);
+        if let (AllocMode::AnySlot, Some(_)) = (&mode, for_memory) {
+            inner.affinity_requests += 1;
+            inner.affinity_hits += u64::from(affine.is_some());
+        }
+        let slot_id = affine.or_else(|| {
             match mode {
                 // If any slot is requested then this is a normal instantiation
                 // looking for an index. Without any affine candidates there are
@@ -254,7 +275,16 @@ impl ModuleAffinityIndexAllocator {
                 // no consulting non-affine slots in this path.
                 AllocMode::ForceAffineAndClear => None,
             }
-        })?;
+        });
+        let slot_id = match slot_id {
+            Some(slot_id) => slot_id,
+            None => {
+                if let AllocMode::AnySlot = mode {
+                    inner.exhausted += 1;
+                }
+                return None;
+            }
+        };
 
         inner.slot_state[slot_id.index()] = SlotState::Used(match mode {
             AllocMode::ForceAffineAndClear => None,
@@ -305,6 +335,25 @@ impl ModuleAffinityIndexAllocator {
         });
     }
 
+    /// Returns the occupancy and reuse statistics of this allocator.
+    ///
+    /// The `resident_bytes` of the result are left for the pool to fill in.
+    pub fn stats(&self) -> PoolStats {
+        let inner = self.0.lock().unwrap();
+        let capacity = u32::try_from(inner.slot_state.len()).unwrap();
+        let unused_cold = capacity - inner.last_cold;
+        PoolStats {
+            capacity,
+            in_use: capacity - unused_cold - inner.unused_warm_slots,
+            unused_warm: inner.unused_warm_slots,
+            unused_cold,
+            affinity_requests: inner.affinity_requests,
+            affinity_hits: inner.affinity_hits,
+            exhausted: inner.exhausted,
+            resident_bytes: 0,
+        }
+    }
+
     /// Return the number of empty slots available in this allocator.
     #[cfg(test)]
     pub fn num_empty_slots(&self) -> usize {
@@ -60,7 +60,7 @@
 
 use super::{
     index_allocator::{MemoryInModule, ModuleAffinityIndexAllocator, SlotId},
-    MemoryAllocationIndex,
+    MemoryAllocationIndex, PoolStats,
 };
 use crate::mpk::{self, ProtectionKey, ProtectionMask};
 use crate::{
@@ -72,7 +72,7 @@ use libc::c_void;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 use wasmtime_environ::{
-    DefinedMemoryIndex, MemoryPlan, MemoryStyle, Module, Tunables, WASM_PAGE_SIZE,
+    DefinedMemoryIndex, MemoryPlan, MemoryStyle, Module, Tunables, WASM64_MAX_PAGES, WASM_PAGE_SIZE,
 };
 
 /// A set of allocator slots.
@@ -145,11 +145,14 @@ pub struct MemoryPool {
 impl MemoryPool {
     /// Create a new `MemoryPool`.
     pub fn new(config: &PoolingInstanceAllocatorConfig, tunables: &Tunables) -> Result<Self> {
-        // The maximum module memory page count cannot exceed 65536 pages
-        if config.limits.memory_pages > 0x10000 {
+        // The maximum module memory page count cannot exceed what a 64-bit
+        // memory can address; 32-bit memories are additionally limited by their
+        // own type to 65536 pages.
+        if config.limits.memory_pages > WASM64_MAX_PAGES {
             bail!(
-                "module memory page limit of {} exceeds the maximum of 65536",
-                config.limits.memory_pages
+                "module memory page limit of {} exceeds the maximum of {}",
+                config.limits.memory_pages,
+                WASM64_MAX_PAGES,
             );
         }
 
@@ -301,6 +304,27 @@ impl MemoryPool {
         self.stripes.iter().all(|s| s.allocator.is_empty())
     }
 
+    /// Returns the statistics of each stripe of this pool.
+    pub fn stripe_stats(&self) -> Vec<PoolStats> {
+        let mut stats = self
+            .stripes
+            .iter()
+            .map(|s| s.allocator.stats())
+            .collect::<Vec<_>>();
+
+        // Slots which are in use, or have never been used, have no image slot
+        // here, so this only counts unused warm slots.
+        for (i, slot) in self.image_slots.iter().enumerate() {
+            if let Some(slot) = &*slot.lock().unwrap() {
+                let index = MemoryAllocationIndex(u32::try_from(i).unwrap());
+                let (stripe, _) =
+                    StripedAllocationIndex::from_unstriped_slot_index(index, self.stripes.len());
+                stats[stripe].resident_bytes += slot.resident_bytes(self.keep_resident);
+            }
+        }
+        stats
+    }
+
     /// Allocate a single memory for the given instance allocation request.
     pub fn allocate(
         &self,
@@ -551,7 +575,10 @@ impl SlabConstraints {
         num_pkeys_available: usize,
     ) -> Result<Self> {
         // The maximum size a memory can grow to in this pool.
-        let max_memory_bytes = limits.memory_pages * u64::from(WASM_PAGE_SIZE);
+        let max_memory_bytes = limits
+            .memory_pages
+            .checked_mul(u64::from(WASM_PAGE_SIZE))
+            .context("max memory is too large")?;
 
         // `static_memory_bound` is the configured number of Wasm pages for a
         // static memory slot (see `Config::static_memory_maximum_size`); even
@@ -561,7 +588,10 @@ impl SlabConstraints {
         // most bounds checks. `MemoryPool` must respect this bound, though not
         // explicitly: if we can achieve the same effect via MPK-protected
         // stripes, the slot size can be lower than the `static_memory_bound`.
-        let expected_slot_bytes = tunables.static_memory_bound * u64::from(WASM_PAGE_SIZE);
+        let expected_slot_bytes = tunables
+            .static_memory_bound
+            .checked_mul(u64::from(WASM_PAGE_SIZE))
+            .context("static memory bound is too large")?;
 
         let constraints = SlabConstraints {
             max_memory_bytes: max_memory_bytes
@@ -1,7 +1,7 @@
 use super::{
     imp::{commit_stack_pages, reset_stack_pages_to_zero},
     index_allocator::{SimpleIndexAllocator, SlotId},
-    round_up_to_pow2,
+    round_up_to_pow2, PoolStats,
 };
 use crate::{Mmap, PoolingInstanceAllocatorConfig};
 use anyhow::{anyhow, bail, Context, Result};
@@ -79,6 +79,21 @@ impl StackPool {
         self.index_allocator.is_empty()
     }
 
+    /// Returns the statistics of this pool.
+    pub fn stats(&self) -> PoolStats {
+        let mut stats = self.index_allocator.stats();
+        // Without zeroing stacks are left as they are, otherwise at most
+        // `async_stack_keep_resident` bytes are kept; see `zero_stack`.
+        let stack_size = self.stack_size.saturating_sub(self.page_size);
+        let per_stack = if self.async_stack_zeroing {
+            stack_size.min(self.async_stack_keep_resident)
+        } else {
+            stack_size
+        };
+        stats.resident_bytes = stats.unused_warm as usize * per_stack;
+        stats
+    }
+
     /// Allocate a new fiber.
     pub fn allocate(&self) -> Result<wasmtime_fiber::FiberStack> {
         if self.stack_size == 0 {
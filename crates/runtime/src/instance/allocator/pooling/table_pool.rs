@@ -1,7 +1,7 @@
 use super::{
     imp::{commit_table_pages, decommit_table_pages},
     index_allocator::{SimpleIndexAllocator, SlotId},
-    round_up_to_pow2, TableAllocationIndex,
+    round_up_to_pow2, PoolStats, TableAllocationIndex,
 };
 use crate::{InstanceAllocationRequest, Mmap, PoolingInstanceAllocatorConfig, Table};
 use anyhow::{anyhow, bail, Context, Result};
@@ -108,6 +108,15 @@ impl TablePool {
         }
     }
 
+    /// Returns the statistics of this pool.
+    pub fn stats(&self) -> PoolStats {
+        let mut stats = self.index_allocator.stats();
+        // Unused warm slots keep at most `keep_resident` bytes resident; see
+        // `reset_table_pages_to_zero`.
+        stats.resident_bytes = stats.unused_warm as usize * self.table_size.min(self.keep_resident);
+        stats
+    }
+
     /// Allocate a single table for the given instance allocation request.
     pub fn allocate(
         &self,
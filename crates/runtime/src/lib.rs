@@ -8,7 +8,7 @@ use std::fmt;
 use std::ptr::NonNull;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use wasmtime_environ::{DefinedFuncIndex, DefinedMemoryIndex, HostPtr, VMOffsets};
+use wasmtime_environ::{DefinedFuncIndex, DefinedMemoryIndex, FuncIndex, HostPtr, VMOffsets};
 
 #[macro_use]
 mod trampolines;
@@ -38,13 +38,16 @@ pub use wasmtime_jit_debug::gdb_jit_int::GdbJitImageRegistration;
 pub use crate::export::*;
 pub use crate::externref::*;
 pub use crate::imports::Imports;
+#[cfg(feature = "async")]
+pub use crate::instance::RuntimeFiberStackCreator;
 pub use crate::instance::{
     Instance, InstanceAllocationRequest, InstanceAllocator, InstanceAllocatorImpl, InstanceHandle,
     MemoryAllocationIndex, OnDemandInstanceAllocator, StorePtr, TableAllocationIndex,
 };
 #[cfg(feature = "pooling-allocator")]
 pub use crate::instance::{
-    InstanceLimits, PoolingInstanceAllocator, PoolingInstanceAllocatorConfig,
+    InstanceLimits, PoolStats, PoolingAllocatorStats, PoolingInstanceAllocator,
+    PoolingInstanceAllocatorConfig,
 };
 pub use crate::memory::{
     DefaultMemoryCreator, Memory, RuntimeLinearMemory, RuntimeMemoryCreator, SharedMemory,
@@ -142,6 +145,33 @@ pub unsafe trait Store {
     /// completely semantically transparent. Returns the new deadline.
     fn new_epoch(&mut self) -> Result<u64, Error>;
 
+    /// Callback invoked before each instruction of code compiled with guest
+    /// debugging, returning whether execution should pause there.
+    ///
+    /// The instruction is identified by the module it belongs to, its function
+    /// and its offset from the start of the function's body.
+    fn debug_check(
+        &mut self,
+        module: Option<CompiledModuleId>,
+        func: FuncIndex,
+        offset: u32,
+    ) -> bool;
+    /// Callback invoked when code compiled with guest debugging pauses before
+    /// an instruction of the instance `vmctx`. If an error is returned that's
+    /// raised as a trap.
+    ///
+    /// `locals` points to the `count` locals of the paused frame, laid out as
+    /// described by `wasmtime_environ::DEBUG_LOCAL_SLOT_SIZE`, and execution
+    /// resumes with the values found there on return.
+    fn debug_break(
+        &mut self,
+        vmctx: *mut VMContext,
+        func: FuncIndex,
+        offset: u32,
+        locals: *mut u8,
+        count: u32,
+    ) -> Result<(), Error>;
+
     /// Metadata required for resources for the component model.
     #[cfg(feature = "component-model")]
     fn component_calls(&mut self) -> &mut component::CallContexts;
@@ -183,6 +183,13 @@ pub mod trampolines {
         }
     }
 
+    impl LibcallResult for u32 {
+        type Abi = u32;
+        unsafe fn convert(self) -> u32 {
+            self
+        }
+    }
+
     impl LibcallResult for *mut u8 {
         type Abi = *mut u8;
         unsafe fn convert(self) -> *mut u8 {
@@ -495,6 +502,27 @@ unsafe fn new_epoch(instance: &mut Instance) -> Result<u64> {
     (*instance.store()).new_epoch()
 }
 
+// Hook for guest debugging, checking whether execution should pause before an
+// instruction.
+unsafe fn debug_check(instance: &mut Instance, func: u32, offset: u32) -> u32 {
+    let module = instance.compiled_module_id();
+    let pause = (*instance.store()).debug_check(module, FuncIndex::from_u32(func), offset);
+    u32::from(pause)
+}
+
+// Hook for guest debugging, invoked when execution pauses before an
+// instruction.
+unsafe fn debug_break(
+    instance: &mut Instance,
+    func: u32,
+    offset: u32,
+    locals: *mut u8,
+    count: u32,
+) -> Result<()> {
+    let vmctx = instance.vmctx();
+    (*instance.store()).debug_break(vmctx, FuncIndex::from_u32(func), offset, locals, count)
+}
+
 cfg_if! {
     if #[cfg(feature = "wmemcheck")] {
         // Hook for validating malloc using wmemcheck_state.
@@ -0,0 +1,206 @@
+//! Checkpointing the descriptor table of a [`WasiCtx`].
+//!
+//! Open files and directories are host resources which can't be serialized
+//! themselves. What can be is where each one came from: the preopened
+//! directory, the path the guest opened within it, and the state the guest
+//! has since changed (file position and fd flags). [`WasiCtx::checkpoint`]
+//! records that, and [`WasiCtx::restore`] reopens each descriptor at the same
+//! number in a context built with the same preopens.
+
+use crate::dir::{DirEntry, OpenResult, TableDirExt};
+use crate::file::{FdFlags, FileAccessMode, FileEntry, OFlags};
+use crate::{Error, ErrorExt, WasiCtx};
+use serde_derive::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Where a descriptor was opened from by the guest.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Origin {
+    /// The host path of the preopened directory it was opened relative to.
+    pub preopen: PathBuf,
+    /// The path within that preopen.
+    pub path: String,
+    pub symlink_follow: bool,
+}
+
+/// The serializable state of a [`WasiCtx`]'s descriptor table, created by
+/// [`WasiCtx::checkpoint`].
+///
+/// Arguments, environment variables, stdio and preopens are configured by the
+/// embedder when building a context and aren't captured. Preopens are only
+/// recorded so [`WasiCtx::restore`] can check the context matches.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WasiCheckpoint {
+    preopens: Vec<(u32, PathBuf)>,
+    opened: Vec<(u32, Opened)>,
+    next_fd: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Opened {
+    File {
+        origin: Origin,
+        access_mode: u32,
+        fdflags: u32,
+        position: u64,
+    },
+    Dir {
+        origin: Origin,
+    },
+}
+
+impl WasiCtx {
+    /// Records the descriptors this context has open.
+    ///
+    /// Fails if a descriptor other than stdio or a preopen wasn't opened by
+    /// path from a preopen, e.g. a socket or a file inserted by the embedder,
+    /// since there's no way to reopen it.
+    pub async fn checkpoint(&self) -> Result<WasiCheckpoint, Error> {
+        let mut preopens = Vec::new();
+        let mut opened = Vec::new();
+        for (fd, entry) in self.table().entries() {
+            if let Ok(dir) = entry.clone().downcast::<DirEntry>() {
+                match (dir.preopen_path(), &dir.origin) {
+                    (Some(path), _) => preopens.push((fd, path.clone())),
+                    (None, Some(origin)) => opened.push((
+                        fd,
+                        Opened::Dir {
+                            origin: origin.clone(),
+                        },
+                    )),
+                    (None, None) => return Err(not_checkpointable(fd)),
+                }
+            } else if let Ok(file) = entry.downcast::<FileEntry>() {
+                match &file.origin {
+                    Some(origin) => opened.push((
+                        fd,
+                        Opened::File {
+                            origin: origin.clone(),
+                            access_mode: file.access_mode.bits(),
+                            fdflags: file.file.get_fdflags().await?.bits(),
+                            position: file.file.seek(SeekFrom::Current(0)).await?,
+                        },
+                    )),
+                    // Stdio is set up by the embedder.
+                    None if fd <= 2 => {}
+                    None => return Err(not_checkpointable(fd)),
+                }
+            } else {
+                return Err(not_checkpointable(fd));
+            }
+        }
+        Ok(WasiCheckpoint {
+            preopens,
+            opened,
+            next_fd: self.table().next_key(),
+        })
+    }
+
+    /// Reopens the descriptors recorded in `checkpoint`.
+    ///
+    /// This context must have the same directories preopened at the same
+    /// descriptors as the one the checkpoint was taken from, and the opened
+    /// paths must still exist. Nothing is changed if any of them fails to
+    /// open.
+    pub async fn restore(&self, checkpoint: &WasiCheckpoint) -> Result<(), Error> {
+        let table = self.table();
+        for (fd, path) in &checkpoint.preopens {
+            let dir = table.get_dir(*fd).map_err(|e| {
+                e.context(format!(
+                    "preopen {} isn't at descriptor {fd}",
+                    path.display()
+                ))
+            })?;
+            if dir.preopen_path().as_ref() != Some(path) {
+                return Err(Error::not_dir().context(format!(
+                    "descriptor {fd} isn't the preopen {}",
+                    path.display()
+                )));
+            }
+        }
+
+        // Files are `Ok` and directories `Err`, inserted once all have opened.
+        let mut entries: Vec<(u32, Result<FileEntry, DirEntry>)> = Vec::new();
+        for (fd, opened) in &checkpoint.opened {
+            let origin = match opened {
+                Opened::File { origin, .. } | Opened::Dir { origin } => origin,
+            };
+            let preopen = checkpoint
+                .preopens
+                .iter()
+                .find(|(_, path)| *path == origin.preopen)
+                .ok_or_else(|| {
+                    Error::not_found().context(format!(
+                        "descriptor {fd} was opened from the missing preopen {}",
+                        origin.preopen.display()
+                    ))
+                })?;
+            let preopen = table.get_dir(preopen.0)?;
+            let entry = match opened {
+                Opened::File {
+                    access_mode,
+                    fdflags,
+                    position,
+                    ..
+                } => {
+                    let access_mode = FileAccessMode::from_bits_truncate(*access_mode);
+                    let file = preopen
+                        .dir
+                        .open_file(
+                            origin.symlink_follow,
+                            &origin.path,
+                            OFlags::empty(),
+                            access_mode.contains(FileAccessMode::READ),
+                            access_mode.contains(FileAccessMode::WRITE),
+                            FdFlags::from_bits_truncate(*fdflags),
+                        )
+                        .await?;
+                    let OpenResult::File(file) = file else {
+                        return Err(Error::not_supported()
+                            .context(format!("{} is no longer a file", origin.path)));
+                    };
+                    file.seek(SeekFrom::Start(*position)).await?;
+                    let mut entry = FileEntry::new(file, access_mode);
+                    entry.origin = Some(origin.clone());
+                    Ok(entry)
+                }
+                Opened::Dir { .. } => {
+                    let dir = preopen
+                        .dir
+                        .open_file(
+                            origin.symlink_follow,
+                            &origin.path,
+                            OFlags::DIRECTORY,
+                            true,
+                            false,
+                            FdFlags::empty(),
+                        )
+                        .await?;
+                    let OpenResult::Dir(dir) = dir else {
+                        return Err(Error::not_dir()
+                            .context(format!("{} is no longer a directory", origin.path)));
+                    };
+                    let mut entry = DirEntry::new(None, dir);
+                    entry.origin = Some(origin.clone());
+                    Err(entry)
+                }
+            };
+            entries.push((*fd, entry));
+        }
+
+        for (fd, entry) in entries {
+            match entry {
+                Ok(file) => table.insert_at(fd, Arc::new(file)),
+                Err(dir) => table.insert_at(fd, Arc::new(dir)),
+            }
+        }
+        table.set_next_key(checkpoint.next_fd);
+        Ok(())
+    }
+}
+
+fn not_checkpointable(fd: u32) -> Error {
+    Error::not_supported().context(format!("descriptor {fd} cannot be checkpointed"))
+}
@@ -1,3 +1,4 @@
+use crate::checkpoint::Origin;
 use crate::file::{FdFlags, FileType, Filestat, OFlags, WasiFile};
 use crate::{Error, ErrorExt, SystemTimeSpec};
 use std::any::Any;
@@ -97,15 +98,35 @@ pub trait WasiDir: Send + Sync {
 pub(crate) struct DirEntry {
     preopen_path: Option<PathBuf>, // precondition: PathBuf is valid unicode
     pub dir: Box<dyn WasiDir>,
+    /// Where the guest opened this directory from, if it did.
+    pub origin: Option<Origin>,
 }
 
 impl DirEntry {
     pub fn new(preopen_path: Option<PathBuf>, dir: Box<dyn WasiDir>) -> Self {
-        DirEntry { preopen_path, dir }
+        DirEntry {
+            preopen_path,
+            dir,
+            origin: None,
+        }
     }
     pub fn preopen_path(&self) -> &Option<PathBuf> {
         &self.preopen_path
     }
+    /// Where the file or directory at `path` within this directory is opened
+    /// from, if this directory is a preopen or was itself opened by path.
+    pub fn child_origin(&self, path: &str, symlink_follow: bool) -> Option<Origin> {
+        let (preopen, path) = match (&self.preopen_path, &self.origin) {
+            (Some(preopen), _) => (preopen.clone(), path.to_string()),
+            (None, Some(origin)) => (origin.preopen.clone(), format!("{}/{path}", origin.path)),
+            (None, None) => return None,
+        };
+        Some(Origin {
+            preopen,
+            path,
+            symlink_follow,
+        })
+    }
 }
 
 pub(crate) trait TableDirExt {
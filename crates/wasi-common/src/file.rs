@@ -1,3 +1,4 @@
+use crate::checkpoint::Origin;
 use crate::{Error, ErrorExt, SystemTimeSpec};
 use bitflags::bitflags;
 use std::any::Any;
@@ -227,6 +228,8 @@ impl TableFileExt for crate::table::Table {
 pub(crate) struct FileEntry {
     pub file: Box<dyn WasiFile>,
     pub access_mode: FileAccessMode,
+    /// Where the guest opened this file from, if it did.
+    pub origin: Option<Origin>,
 }
 
 bitflags! {
@@ -239,7 +242,11 @@ bitflags! {
 
 impl FileEntry {
     pub fn new(file: Box<dyn WasiFile>, access_mode: FileAccessMode) -> Self {
-        FileEntry { file, access_mode }
+        FileEntry {
+            file,
+            access_mode,
+            origin: None,
+        }
     }
 
     pub async fn get_fdstat(&self) -> Result<FdStat, Error> {
@@ -50,6 +50,7 @@
 //! `WasiCtx::builder(...)` function. The
 //! `wasi_cap_std_sync::WasiCtxBuilder::new()` function uses this public
 //! interface to plug in its own implementations of each of these resources.
+mod checkpoint;
 pub mod clocks;
 mod ctx;
 pub mod dir;
@@ -63,6 +64,7 @@ mod string_array;
 pub mod table;
 
 pub use cap_rand::RngCore;
+pub use checkpoint::WasiCheckpoint;
 pub use clocks::{SystemTimeSpec, WasiClocks, WasiMonotonicClock, WasiSystemClock};
 pub use ctx::WasiCtx;
 pub use dir::WasiDir;
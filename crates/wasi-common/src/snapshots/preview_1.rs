@@ -758,11 +758,20 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             .dir
             .open_file(symlink_follow, path.deref(), oflags, read, write, fdflags)
             .await?;
+        let origin = dir_entry.child_origin(path.deref(), symlink_follow);
         drop(dir_entry);
 
         let fd = match file {
-            OpenResult::File(file) => table.push(Arc::new(FileEntry::new(file, access_mode)))?,
-            OpenResult::Dir(child_dir) => table.push(Arc::new(DirEntry::new(None, child_dir)))?,
+            OpenResult::File(file) => {
+                let mut entry = FileEntry::new(file, access_mode);
+                entry.origin = origin;
+                table.push(Arc::new(entry))?
+            }
+            OpenResult::Dir(child_dir) => {
+                let mut entry = DirEntry::new(None, child_dir);
+                entry.origin = origin;
+                table.push(Arc::new(entry))?
+            }
         };
         Ok(types::Fd::from(fd))
     }
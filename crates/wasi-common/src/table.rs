@@ -103,6 +103,31 @@ impl Table {
             .map(|r| r.downcast::<T>().unwrap())
     }
 
+    /// Get all resources in the table along with their indices, in order.
+    pub(crate) fn entries(&self) -> Vec<(u32, Arc<dyn Any + Send + Sync>)> {
+        let mut entries = self
+            .0
+            .read()
+            .unwrap()
+            .map
+            .iter()
+            .map(|(key, entry)| (*key, entry.clone()))
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| *key);
+        entries
+    }
+
+    /// Get the index at which the next pushed resource is inserted, unless it's
+    /// taken by then.
+    pub(crate) fn next_key(&self) -> u32 {
+        self.0.read().unwrap().next_key
+    }
+
+    /// Set the index at which the next pushed resource is inserted.
+    pub(crate) fn set_next_key(&self, key: u32) {
+        self.0.write().unwrap().next_key = key;
+    }
+
     /// Remove a resource at a given index from the table. Returns the resource
     /// if it was present.
     pub fn renumber(&self, from: u32, to: u32) -> Result<(), Error> {
@@ -2,6 +2,8 @@
 //! this crate. The `Box<dyn ...>` types returned by these interfaces allow
 //! implementations to maintain backend-specific state between calls.
 
+#[cfg(feature = "onnx")]
+pub mod onnx;
 pub mod openvino;
 
 use self::openvino::OpenvinoBackend;
@@ -13,7 +15,11 @@ use wiggle::GuestError;
 
 /// Return a list of all available backend frameworks.
 pub fn list() -> Vec<crate::Backend> {
-    vec![Backend::from(OpenvinoBackend::default())]
+    vec![
+        Backend::from(OpenvinoBackend::default()),
+        #[cfg(feature = "onnx")]
+        Backend::from(self::onnx::OnnxBackend::default()),
+    ]
 }
 
 /// A [Backend] contains the necessary state to load [Graph]s.
@@ -0,0 +1,250 @@
+//! Implements a `wasi-nn` [`BackendInner`] for ONNX models using `tract`, a
+//! pure-Rust inference engine which runs on the CPU.
+
+use super::{BackendError, BackendExecutionContext, BackendFromDir, BackendGraph, BackendInner};
+use crate::wit::types::{ExecutionTarget, GraphEncoding, Tensor, TensorType};
+use crate::{ExecutionContext, Graph};
+use anyhow::{anyhow, bail};
+use std::path::Path;
+use std::sync::Arc;
+use tract_onnx::prelude::{
+    DatumType, Framework, InferenceModelExt, IntoArcTensor, TValue, TVec, TypedModel,
+    TypedRunnableModel,
+};
+
+#[derive(Default)]
+pub struct OnnxBackend;
+
+impl BackendInner for OnnxBackend {
+    fn encoding(&self) -> GraphEncoding {
+        GraphEncoding::Onnx
+    }
+
+    fn load(&mut self, builders: &[&[u8]], target: ExecutionTarget) -> Result<Graph, BackendError> {
+        if builders.len() != 1 {
+            return Err(BackendError::InvalidNumberOfBuilders(1, builders.len()).into());
+        }
+        if target != ExecutionTarget::Cpu {
+            return Err(anyhow!("the ONNX backend only supports CPU execution targets").into());
+        }
+
+        let model = tract_onnx::onnx()
+            .model_for_read(&mut &builders[0][..])
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| anyhow!(e).context("failed to load ONNX model"))?;
+        let box_: Box<dyn BackendGraph> = Box::new(OnnxGraph(Arc::new(model)));
+        Ok(box_.into())
+    }
+
+    fn as_dir_loadable(&mut self) -> Option<&mut dyn BackendFromDir> {
+        Some(self)
+    }
+}
+
+impl BackendFromDir for OnnxBackend {
+    fn load_from_dir(
+        &mut self,
+        path: &Path,
+        target: ExecutionTarget,
+    ) -> Result<Graph, BackendError> {
+        let model = std::fs::read(path.join("model.onnx"))
+            .map_err(|e| anyhow!(e).context("failed to read `model.onnx`"))?;
+        self.load(&[&model], target)
+    }
+}
+
+/// A loaded model; execution contexts share it, and its weights, through the
+/// `Arc`.
+struct OnnxGraph(Arc<TypedRunnableModel<TypedModel>>);
+
+impl BackendGraph for OnnxGraph {
+    fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
+        let inputs = self.0.model().inputs.len();
+        let box_: Box<dyn BackendExecutionContext> = Box::new(OnnxExecutionContext {
+            model: self.0.clone(),
+            inputs: vec![None; inputs],
+            outputs: Vec::new(),
+        });
+        Ok(box_.into())
+    }
+}
+
+struct OnnxExecutionContext {
+    model: Arc<TypedRunnableModel<TypedModel>>,
+    inputs: Vec<Option<Arc<tract_onnx::prelude::Tensor>>>,
+    outputs: Vec<Arc<tract_onnx::prelude::Tensor>>,
+}
+
+impl BackendExecutionContext for OnnxExecutionContext {
+    fn set_input(&mut self, index: u32, tensor: &Tensor) -> Result<(), BackendError> {
+        let slot = match self.inputs.get_mut(index as usize) {
+            Some(slot) => slot,
+            None => return Err(anyhow!("no input at index {index}").into()),
+        };
+        let datum_type = map_tensor_type_to_datum_type(tensor.tensor_type)?;
+        let dimensions = tensor
+            .dimensions
+            .iter()
+            .map(|&d| d as usize)
+            .collect::<Vec<_>>();
+        let expected = dimensions.iter().product::<usize>() * datum_type.size_of();
+        if tensor.data.len() != expected {
+            return Err(anyhow!(
+                "tensor data is {} bytes but its dimensions require {expected}",
+                tensor.data.len()
+            )
+            .into());
+        }
+
+        // SAFETY: the length of the data was checked above and any bit
+        // pattern is a valid value of the numeric types which
+        // `map_tensor_type_to_datum_type` returns.
+        let value = unsafe {
+            tract_onnx::prelude::Tensor::from_raw_dt(datum_type, &dimensions, &tensor.data)
+        }
+        .map_err(|e| anyhow!(e))?;
+        *slot = Some(Arc::new(value));
+        Ok(())
+    }
+
+    fn compute(&mut self) -> Result<(), BackendError> {
+        let mut inputs = TVec::with_capacity(self.inputs.len());
+        for (i, input) in self.inputs.iter().enumerate() {
+            match input {
+                Some(input) => inputs.push(TValue::Const(input.clone())),
+                None => return Err(anyhow!("input {i} has not been set").into()),
+            }
+        }
+        let outputs = self.model.run(inputs).map_err(|e| anyhow!(e))?;
+        self.outputs = outputs.into_iter().map(|o| o.into_arc_tensor()).collect();
+        Ok(())
+    }
+
+    fn get_output(&mut self, index: u32, destination: &mut [u8]) -> Result<u32, BackendError> {
+        let output = match self.outputs.get(index as usize) {
+            Some(output) => output,
+            None => {
+                return Err(anyhow!("no output at index {index}; has compute been called?").into())
+            }
+        };
+        if !output.datum_type().is_copy() {
+            return Err(anyhow!("unsupported output type: {:?}", output.datum_type()).into());
+        }
+
+        // SAFETY: the output is a plain-old-data tensor, as checked above.
+        let bytes = unsafe { output.as_bytes() };
+        if bytes.len() > destination.len() {
+            return Err(BackendError::NotEnoughMemory(bytes.len()));
+        }
+
+        // Copy the tensor data into the destination buffer.
+        destination[..bytes.len()].copy_from_slice(bytes);
+        Ok(bytes.len() as u32)
+    }
+}
+
+/// Return `tract`'s datum type for the `TensorType` enum provided by wasi-nn.
+fn map_tensor_type_to_datum_type(tensor_type: TensorType) -> anyhow::Result<DatumType> {
+    Ok(match tensor_type {
+        TensorType::Fp16 => DatumType::F16,
+        TensorType::Fp32 => DatumType::F32,
+        TensorType::U8 => DatumType::U8,
+        TensorType::I32 => DatumType::I32,
+        TensorType::Bf16 => bail!("the ONNX backend does not support bf16 tensors"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost::Message;
+    use tract_onnx::pb;
+
+    /// Build an ONNX model which applies `Relu` to a vector of four floats.
+    fn relu_model() -> Vec<u8> {
+        let vector = || pb::TypeProto {
+            value: Some(pb::type_proto::Value::TensorType(pb::type_proto::Tensor {
+                elem_type: pb::tensor_proto::DataType::Float as i32,
+                shape: Some(pb::TensorShapeProto {
+                    dim: vec![pb::tensor_shape_proto::Dimension {
+                        value: Some(pb::tensor_shape_proto::dimension::Value::DimValue(4)),
+                        ..Default::default()
+                    }],
+                }),
+            })),
+            ..Default::default()
+        };
+        pb::ModelProto {
+            ir_version: 7,
+            opset_import: vec![pb::OperatorSetIdProto {
+                domain: String::new(),
+                version: 13,
+            }],
+            graph: Some(pb::GraphProto {
+                node: vec![pb::NodeProto {
+                    input: vec!["x".to_string()],
+                    output: vec!["y".to_string()],
+                    op_type: "Relu".to_string(),
+                    ..Default::default()
+                }],
+                input: vec![pb::ValueInfoProto {
+                    name: "x".to_string(),
+                    r#type: Some(vector()),
+                    ..Default::default()
+                }],
+                output: vec![pb::ValueInfoProto {
+                    name: "y".to_string(),
+                    r#type: Some(vector()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+        .encode_to_vec()
+    }
+
+    #[test]
+    fn relu() {
+        let mut backend = OnnxBackend;
+        let graph = backend
+            .load(&[&relu_model()], ExecutionTarget::Cpu)
+            .unwrap();
+        let mut context = graph.init_execution_context().unwrap();
+
+        let input = [-1.0f32, 2.0, -3.0, 4.0];
+        let tensor = Tensor {
+            dimensions: vec![4],
+            tensor_type: TensorType::Fp32,
+            data: input.iter().flat_map(|f| f.to_le_bytes()).collect(),
+        };
+        context.set_input(0, &tensor).unwrap();
+        context.compute().unwrap();
+
+        let mut output = [0u8; 16];
+        assert_eq!(context.get_output(0, &mut output).unwrap(), 16);
+        let output = output
+            .chunks(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(output, [0.0, 2.0, 0.0, 4.0]);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut backend = OnnxBackend;
+        let graph = backend
+            .load(&[&relu_model()], ExecutionTarget::Cpu)
+            .unwrap();
+        let mut context = graph.init_execution_context().unwrap();
+
+        let tensor = Tensor {
+            dimensions: vec![4],
+            tensor_type: TensorType::Fp32,
+            data: vec![0; 3],
+        };
+        assert!(context.set_input(0, &tensor).is_err());
+        assert!(context.compute().is_err());
+    }
+}
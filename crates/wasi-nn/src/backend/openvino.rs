@@ -3,6 +3,7 @@
 use super::{BackendError, BackendExecutionContext, BackendFromDir, BackendGraph, BackendInner};
 use crate::wit::types::{ExecutionTarget, GraphEncoding, Tensor, TensorType};
 use crate::{ExecutionContext, Graph};
+use anyhow::bail;
 use openvino::{InferenceError, Layout, Precision, SetupError, TensorDesc};
 use std::sync::{Arc, Mutex};
 use std::{fs::File, io::Read, path::Path};
@@ -102,7 +103,7 @@ impl BackendExecutionContext for OpenvinoExecutionContext {
 
         // Construct the blob structure. TODO: there must be some good way to
         // discover the layout here; `desc` should not have to default to NHWC.
-        let precision = map_tensor_type_to_precision(tensor.tensor_type);
+        let precision = map_tensor_type_to_precision(tensor.tensor_type)?;
         let dimensions = tensor
             .dimensions
             .iter()
@@ -159,14 +160,14 @@ fn map_execution_target_to_string(target: ExecutionTarget) -> &'static str {
 
 /// Return OpenVINO's precision type for the `TensorType` enum provided by
 /// wasi-nn.
-fn map_tensor_type_to_precision(tensor_type: TensorType) -> openvino::Precision {
-    match tensor_type {
+fn map_tensor_type_to_precision(tensor_type: TensorType) -> anyhow::Result<openvino::Precision> {
+    Ok(match tensor_type {
         TensorType::Fp16 => Precision::FP16,
         TensorType::Fp32 => Precision::FP32,
         TensorType::U8 => Precision::U8,
         TensorType::I32 => Precision::I32,
-        TensorType::Bf16 => todo!("not yet supported in `openvino` bindings"),
-    }
+        TensorType::Bf16 => bail!("bf16 tensors are not yet supported in `openvino` bindings"),
+    })
 }
 
 /// Read a file into a byte vector.
@@ -2,7 +2,7 @@
 
 use crate::backend::{self, BackendError};
 use crate::wit::types::GraphEncoding;
-use crate::{Backend, ExecutionContext, Graph, InMemoryRegistry, Registry};
+use crate::{Backend, ExecutionContext, Graph, InMemoryRegistry, LazyRegistry, Registry};
 use anyhow::anyhow;
 use std::{collections::HashMap, hash::Hash, path::Path};
 use thiserror::Error;
@@ -17,9 +17,12 @@ type GraphDirectory = String;
 /// `(<backend name>, <graph directory>)`. This assumes graphs can be loaded
 /// from a local directory, which is a safe assumption currently for the current
 /// model types.
+///
+/// Clones of the returned registry share its graphs, so one registry can be
+/// used for any number of [WasiNnCtx]s.
 pub fn preload(
     preload_graphs: &[(BackendName, GraphDirectory)],
-) -> anyhow::Result<(impl IntoIterator<Item = Backend>, Registry)> {
+) -> anyhow::Result<(impl IntoIterator<Item = Backend>, InMemoryRegistry)> {
     let mut backends = backend::list();
     let mut registry = InMemoryRegistry::new();
     for (kind, path) in preload_graphs {
@@ -32,7 +35,25 @@ pub fn preload(
             .ok_or(anyhow!("{} does not support directory loading", kind))?;
         registry.load(backend, Path::new(path))?;
     }
-    Ok((backends, Registry::from(registry)))
+    Ok((backends, registry))
+}
+
+/// Construct a [LazyRegistry] which loads the graphs in the subdirectories of
+/// `path` with the `kind` backend when they are first used, keeping at most
+/// `budget` bytes of graphs loaded.
+pub fn lazy(kind: &str, path: &str, budget: usize) -> anyhow::Result<LazyRegistry> {
+    let kind_ = kind.parse()?;
+    let mut backend = backend::list()
+        .into_iter()
+        .find(|b| b.encoding() == kind_)
+        .ok_or(anyhow!("unsupported backend: {}", kind))?;
+    if backend.as_dir_loadable().is_none() {
+        return Err(anyhow!("{} does not support directory loading", kind));
+    }
+    if !Path::new(path).is_dir() {
+        return Err(anyhow!("graph directory does not exist: {}", path));
+    }
+    Ok(LazyRegistry::new(backend, path, budget))
 }
 
 /// Capture the state necessary for calling into the backend ML libraries.
@@ -45,11 +66,11 @@ pub struct WasiNnCtx {
 
 impl WasiNnCtx {
     /// Make a new context from the default state.
-    pub fn new(backends: impl IntoIterator<Item = Backend>, registry: Registry) -> Self {
+    pub fn new(backends: impl IntoIterator<Item = Backend>, registry: impl Into<Registry>) -> Self {
         let backends = backends.into_iter().map(|b| (b.encoding(), b)).collect();
         Self {
             backends,
-            registry,
+            registry: registry.into(),
             graphs: Table::default(),
             executions: Table::default(),
         }
@@ -71,7 +92,7 @@ pub enum WasiNnError {
 pub enum UsageError {
     #[error("Invalid context; has the load function been called?")]
     InvalidContext,
-    #[error("Only OpenVINO's IR is currently supported, passed encoding: {0:?}")]
+    #[error("No backend is available for the graph encoding: {0:?}")]
     InvalidEncoding(GraphEncoding),
     #[error("OpenVINO expects only two buffers (i.e. [ir, weights]), passed: {0}")]
     InvalidNumberOfBuilders(u32),
@@ -136,8 +157,8 @@ mod test {
     fn example() {
         struct FakeRegistry;
         impl GraphRegistry for FakeRegistry {
-            fn get_mut(&mut self, _: &str) -> Option<&mut Graph> {
-                None
+            fn get(&mut self, _: &str) -> Result<Option<Graph>, BackendError> {
+                Ok(None)
             }
         }
 
@@ -2,8 +2,8 @@ mod ctx;
 mod registry;
 
 pub mod backend;
-pub use ctx::{preload, WasiNnCtx};
-pub use registry::{GraphRegistry, InMemoryRegistry};
+pub use ctx::{lazy, preload, WasiNnCtx};
+pub use registry::{GraphRegistry, InMemoryRegistry, LazyRegistry};
 pub mod wit;
 pub mod witx;
 
@@ -1,11 +1,14 @@
 //! Implement a [`GraphRegistry`] with a hash map.
 
 use super::{Graph, GraphRegistry};
-use crate::backend::BackendFromDir;
+use crate::backend::{BackendError, BackendFromDir};
 use crate::wit::types::ExecutionTarget;
 use anyhow::{anyhow, bail};
 use std::{collections::HashMap, path::Path};
 
+/// A registry of graphs loaded ahead of time; clones of it share the same
+/// graphs.
+#[derive(Clone)]
 pub struct InMemoryRegistry(HashMap<String, Graph>);
 impl InMemoryRegistry {
     pub fn new() -> Self {
@@ -37,7 +40,7 @@ impl InMemoryRegistry {
 }
 
 impl GraphRegistry for InMemoryRegistry {
-    fn get_mut(&mut self, name: &str) -> Option<&mut Graph> {
-        self.0.get_mut(name)
+    fn get(&mut self, name: &str) -> Result<Option<Graph>, BackendError> {
+        Ok(self.0.get(name).cloned())
     }
 }
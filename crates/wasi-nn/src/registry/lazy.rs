@@ -0,0 +1,279 @@
+//! Implement a [`GraphRegistry`] which loads graphs from a directory on
+//! demand and shares them between contexts.
+
+use super::{Graph, GraphRegistry};
+use crate::backend::BackendError;
+use crate::wit::types::ExecutionTarget;
+use crate::Backend;
+use anyhow::anyhow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A registry of the graphs found in a directory, each of which is stored in a
+/// subdirectory named after the graph.
+///
+/// A graph is only loaded the first time it is requested by name, after which
+/// it is kept for later requests. All clones of a [`LazyRegistry`] share the
+/// same loaded graphs, so one registry can be cloned into the [`WasiNnCtx`] of
+/// every store without loading any model more than once. Since a [`Graph`] is
+/// reference-counted, execution contexts created from it in different stores
+/// share its weights as well.
+///
+/// The size of a graph is estimated from the size of the files in its
+/// directory. When the graphs loaded exceed the registry's budget, the least
+/// recently used ones are evicted; they are freed once no store is using them
+/// any more and loaded again if requested later.
+///
+/// [`WasiNnCtx`]: crate::WasiNnCtx
+#[derive(Clone)]
+pub struct LazyRegistry(Arc<Shared>);
+
+struct Shared {
+    dir: PathBuf,
+    budget: usize,
+    backend: Mutex<Backend>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    graphs: HashMap<String, Entry>,
+    size: usize,
+    clock: u64,
+}
+
+struct Entry {
+    graph: Graph,
+    size: usize,
+    last_used: u64,
+}
+
+impl LazyRegistry {
+    /// Create a registry which loads graphs from the subdirectories of `dir`
+    /// using `backend`, keeping at most `budget` bytes of graphs loaded.
+    ///
+    /// The backend must be able to load graphs from a directory (see
+    /// [`BackendFromDir`](crate::backend::BackendFromDir)).
+    pub fn new(backend: Backend, dir: impl Into<PathBuf>, budget: usize) -> Self {
+        Self(Arc::new(Shared {
+            dir: dir.into(),
+            budget,
+            backend: Mutex::new(backend),
+            state: Mutex::default(),
+        }))
+    }
+
+    /// The estimated size, in bytes, of all graphs currently loaded.
+    pub fn loaded_size(&self) -> usize {
+        self.0.state.lock().unwrap().size
+    }
+
+    /// Return the graph named `name` if it is already loaded.
+    fn lookup(&self, name: &str) -> Option<Graph> {
+        let mut state = self.0.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let entry = state.graphs.get_mut(name)?;
+        entry.last_used = clock;
+        Some(entry.graph.clone())
+    }
+
+    fn load(&self, name: &str) -> Result<Option<Graph>, BackendError> {
+        if !is_valid_name(name) {
+            return Ok(None);
+        }
+        if let Some(graph) = self.lookup(name) {
+            return Ok(Some(graph));
+        }
+
+        // Only one graph is loaded at a time; check again in case it was
+        // loaded while waiting on another load to finish.
+        let mut backend = self.0.backend.lock().unwrap();
+        if let Some(graph) = self.lookup(name) {
+            return Ok(Some(graph));
+        }
+        let path = self.0.dir.join(name);
+        if !path.is_dir() {
+            return Ok(None);
+        }
+        let size = dir_size(&path)?;
+        let encoding = backend.encoding();
+        let graph = backend
+            .as_dir_loadable()
+            .ok_or_else(|| anyhow!("{encoding:?} does not support directory loading"))?
+            .load_from_dir(&path, ExecutionTarget::Cpu)?;
+        drop(backend);
+
+        let mut state = self.0.state.lock().unwrap();
+        state.clock += 1;
+        let entry = Entry {
+            graph: graph.clone(),
+            size,
+            last_used: state.clock,
+        };
+        state.graphs.insert(name.to_string(), entry);
+        state.size += size;
+        while state.size > self.0.budget {
+            let lru = state
+                .graphs
+                .iter()
+                .filter(|(n, _)| n.as_str() != name)
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(n, _)| n.clone());
+            match lru {
+                Some(lru) => {
+                    let evicted = state.graphs.remove(&lru).unwrap();
+                    state.size -= evicted.size;
+                    tracing::debug!("evicted graph {lru} from the wasi-nn registry");
+                }
+                None => break,
+            }
+        }
+        Ok(Some(graph))
+    }
+}
+
+impl GraphRegistry for LazyRegistry {
+    fn get(&mut self, name: &str) -> Result<Option<Graph>, BackendError> {
+        self.load(name)
+    }
+}
+
+/// Graph names come from the guest, so only allow names which refer to a
+/// direct subdirectory of the registry's directory.
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(|c| c == '/' || c == '\\' || c == '\0')
+}
+
+/// Sum the sizes of the files in `path`.
+fn dir_size(path: &Path) -> anyhow::Result<usize> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size += metadata.len() as usize;
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::backend::{BackendFromDir, BackendGraph, BackendInner};
+    use crate::wit::types::GraphEncoding;
+    use crate::ExecutionContext;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A backend which counts how many graphs it has loaded.
+    struct CountingBackend(Arc<AtomicUsize>);
+
+    struct FakeGraph;
+
+    impl BackendGraph for FakeGraph {
+        fn init_execution_context(&self) -> Result<ExecutionContext, BackendError> {
+            Err(BackendError::BackendAccess(anyhow::anyhow!(
+                "fake graphs cannot be executed"
+            )))
+        }
+    }
+
+    impl BackendInner for CountingBackend {
+        fn encoding(&self) -> GraphEncoding {
+            GraphEncoding::Autodetect
+        }
+
+        fn load(&mut self, _: &[&[u8]], _: ExecutionTarget) -> Result<Graph, BackendError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let graph: Box<dyn BackendGraph> = Box::new(FakeGraph);
+            Ok(graph.into())
+        }
+
+        fn as_dir_loadable(&mut self) -> Option<&mut dyn BackendFromDir> {
+            Some(self)
+        }
+    }
+
+    impl BackendFromDir for CountingBackend {
+        fn load_from_dir(
+            &mut self,
+            _: &Path,
+            target: ExecutionTarget,
+        ) -> Result<Graph, BackendError> {
+            self.load(&[], target)
+        }
+    }
+
+    fn registry(
+        budget: usize,
+        graphs: &[(&str, usize)],
+    ) -> (tempfile::TempDir, LazyRegistry, Arc<AtomicUsize>) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, size) in graphs {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+            std::fs::write(dir.path().join(name).join("model"), vec![0; *size]).unwrap();
+        }
+        let loads = Arc::new(AtomicUsize::new(0));
+        let backend = Backend::from(CountingBackend(loads.clone()));
+        let registry = LazyRegistry::new(backend, dir.path(), budget);
+        (dir, registry, loads)
+    }
+
+    #[test]
+    fn loads_once_across_clones() {
+        let (_dir, mut registry, loads) = registry(usize::MAX, &[("a", 10)]);
+        assert_eq!(loads.load(Ordering::SeqCst), 0);
+
+        assert!(registry.get("a").unwrap().is_some());
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        let mut clone = registry.clone();
+        assert!(clone.get("a").unwrap().is_some());
+        assert!(registry.get("a").unwrap().is_some());
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(registry.loaded_size(), 10);
+    }
+
+    #[test]
+    fn missing_and_invalid_names() {
+        let (_dir, mut registry, loads) = registry(usize::MAX, &[("a", 10)]);
+        assert!(registry.get("b").unwrap().is_none());
+        assert!(registry.get("").unwrap().is_none());
+        assert!(registry.get(".").unwrap().is_none());
+        assert!(registry.get("..").unwrap().is_none());
+        assert!(registry.get("../a").unwrap().is_none());
+        assert!(registry.get("a/").unwrap().is_none());
+        assert_eq!(loads.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let (_dir, mut registry, loads) = registry(25, &[("a", 10), ("b", 10), ("c", 10)]);
+        registry.get("a").unwrap().unwrap();
+        registry.get("b").unwrap().unwrap();
+        registry.get("a").unwrap().unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+        // Loading `c` exceeds the budget, evicting `b`.
+        registry.get("c").unwrap().unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+        assert_eq!(registry.loaded_size(), 20);
+
+        registry.get("a").unwrap().unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+        registry.get("b").unwrap().unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn keeps_graph_larger_than_budget() {
+        let (_dir, mut registry, loads) = registry(5, &[("a", 10)]);
+        registry.get("a").unwrap().unwrap();
+        registry.get("a").unwrap().unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+}
@@ -4,13 +4,18 @@
 //! by name. This API does not mandate how a graph is loaded or how it must be
 //! stored--it could be stored remotely and rematerialized when needed, e.g. A
 //! naive in-memory implementation, [`InMemoryRegistry`] is provided for use
-//! with the Wasmtime CLI.
+//! with the Wasmtime CLI, as is [`LazyRegistry`], which loads graphs from a
+//! directory on demand and shares them between contexts.
 
 mod in_memory;
+mod lazy;
 
+use crate::backend::BackendError;
 use crate::Graph;
 pub use in_memory::InMemoryRegistry;
+pub use lazy::LazyRegistry;
 
 pub trait GraphRegistry: Send + Sync {
-    fn get_mut(&mut self, name: &str) -> Option<&mut Graph>;
+    /// Retrieve the graph named `name`, or `None` if there is no such graph.
+    fn get(&mut self, name: &str) -> Result<Option<Graph>, BackendError>;
 }
@@ -10,7 +10,7 @@
 #[cfg(feature = "preview2")]
 pub mod preview2;
 
-pub use wasi_common::{Error, I32Exit, WasiCtx, WasiDir, WasiFile};
+pub use wasi_common::{Error, I32Exit, WasiCheckpoint, WasiCtx, WasiDir, WasiFile};
 
 /// Re-export the commonly used wasi-cap-std-sync crate here. This saves
 /// consumers of this library from having to keep additional dependencies
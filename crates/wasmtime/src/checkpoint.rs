@@ -0,0 +1,502 @@
+//! Checkpoints of the state of a store.
+//!
+//! A checkpoint records the state of every instance in a store, including the
+//! memories, tables and globals created by the host, along with the store's
+//! fuel and epoch deadline. It contains no pointers or compiled code, so it
+//! can be serialized, moved to another process or machine, and restored into a
+//! store there which was set up in the same way, i.e. which has instantiated
+//! the same modules and created the same host objects in the same order.
+
+use crate::store::InstanceId;
+use crate::{Func, Global, Memory, Mutability, StoreContextMut, Table, Val, ValType};
+use anyhow::{bail, Context, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use wasmtime_environ::{EntityRef, FuncIndex, TableIndex};
+
+/// The header at the start of every serialized checkpoint.
+const MAGIC: &[u8] = b"\0wasmtime-checkpoint";
+
+/// The version of the format which follows [`MAGIC`], bumped whenever the
+/// layout of [`Checkpoint`] changes.
+const VERSION: u32 = 1;
+
+/// The size of the chunks in which memory is captured; chunks which are
+/// entirely zero are omitted.
+const CHUNK_SIZE: usize = 4096;
+
+/// The captured state of a [`Store`](crate::Store).
+///
+/// Checkpoints are created with [`Store::checkpoint`](crate::Store::checkpoint)
+/// and applied with [`Store::restore`](crate::Store::restore). They can be
+/// converted to and from bytes with [`Checkpoint::serialize`] and
+/// [`Checkpoint::deserialize`] to move them between processes or machines.
+///
+/// A checkpoint captures the linear memories, tables and mutable globals of
+/// every instance in the store, as well as those created with
+/// [`Memory::new`], [`Table::new`] and [`Global::new`], the fuel remaining and
+/// the epoch deadline. Tables and globals may refer to any function defined or
+/// imported by an instance in the store, but not to other host functions or
+/// to non-null `externref`s.
+///
+/// The store's `T` is not captured since Wasmtime doesn't know how to
+/// serialize it. Embedders which can serialize the parts of their state which
+/// matter can attach them to a checkpoint with [`Checkpoint::set_host_state`].
+///
+/// # WASI state
+///
+/// The state of a WASI context lives in the store's data rather than in wasm
+/// instances, so it isn't captured here. For preview1 contexts the
+/// `wasi-common` crate can capture the serializable part of it with
+/// `WasiCtx::checkpoint`: which files and directories the guest opened from
+/// its preopened directories, and their positions and flags. That
+/// `WasiCheckpoint` can be serialized and attached with
+/// [`Checkpoint::set_host_state`], and `WasiCtx::restore` reopens the
+/// descriptors in a new context built with the same arguments, environment
+/// and preopened directories as the original one. Descriptors which can't be
+/// reopened by path, such as sockets, can't be checkpointed.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The state of every instance in the store, including the instances
+    /// which hold the memories and tables created by the host.
+    instances: Vec<InstanceState>,
+    /// The values of the globals created by the host, which aren't part of
+    /// any instance.
+    host_globals: Vec<Option<Value>>,
+    fuel: Option<u64>,
+    epoch_deadline: Option<u64>,
+    host_state: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstanceState {
+    functions: u32,
+    memories: Vec<MemoryState>,
+    tables: Vec<Vec<Reference>>,
+    /// The values of the instance's defined globals; immutable globals are
+    /// `None` since they can't have changed.
+    globals: Vec<Option<Value>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MemoryState {
+    pages: u64,
+    chunks: Vec<(u64, Vec<u8>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Value {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+    V128(u128),
+    Ref(Reference),
+}
+
+/// A reference, which is either null or a function of one of the store's
+/// instances.
+#[derive(Serialize, Deserialize)]
+enum Reference {
+    Null,
+    Func { instance: u32, func: u32 },
+}
+
+impl Checkpoint {
+    /// Captures the state of all instances in `store`.
+    pub(crate) fn capture<T>(store: &mut StoreContextMut<'_, T>) -> Result<Checkpoint> {
+        let instances = store.0.all_instance_ids().collect::<Vec<_>>();
+        let funcs = FuncMap::new(store, &instances);
+
+        let mut states = Vec::with_capacity(instances.len());
+        for &id in instances.iter() {
+            let env = store.0.instance(id).module().clone();
+
+            let mut memories = Vec::new();
+            for memory in defined_memories(store, id) {
+                memories.push(capture_memory(store, &memory)?);
+            }
+
+            let mut tables = Vec::new();
+            for index in (env.num_imported_tables..env.table_plans.len()).map(TableIndex::new) {
+                let table = table(store, id, index);
+                let mut elements = Vec::new();
+                for i in 0..table.size(&*store) {
+                    let val = table.get(&mut *store, i).unwrap();
+                    let element = funcs.reference(store, val).with_context(|| {
+                        format!("cannot checkpoint element {i} of table {}", index.index())
+                    })?;
+                    elements.push(element);
+                }
+                tables.push(elements);
+            }
+
+            let mut globals = Vec::new();
+            for (i, global) in defined_globals(store, id).into_iter().enumerate() {
+                let value = capture_global(store, &funcs, &global).with_context(|| {
+                    let index = env.num_imported_globals + i;
+                    format!("cannot checkpoint global {index}")
+                })?;
+                globals.push(value);
+            }
+
+            states.push(InstanceState {
+                functions: env.functions.len() as u32,
+                memories,
+                tables,
+                globals,
+            });
+        }
+
+        let mut globals = Vec::new();
+        for (i, global) in host_globals(store).into_iter().enumerate() {
+            let value = capture_global(store, &funcs, &global)
+                .with_context(|| format!("cannot checkpoint host global {i}"))?;
+            globals.push(value);
+        }
+
+        let epoch_deadline = if store.engine().config().tunables.epoch_interruption {
+            let current = store.engine().current_epoch();
+            Some(store.0.get_epoch_deadline().saturating_sub(current))
+        } else {
+            None
+        };
+
+        Ok(Checkpoint {
+            instances: states,
+            host_globals: globals,
+            fuel: store.fuel_remaining(),
+            epoch_deadline,
+            host_state: Vec::new(),
+        })
+    }
+
+    /// Restores this checkpoint into `store`.
+    pub(crate) fn restore<T>(&self, store: &mut StoreContextMut<'_, T>) -> Result<()> {
+        let instances = store.0.all_instance_ids().collect::<Vec<_>>();
+        if instances.len() != self.instances.len() {
+            bail!(
+                "the checkpoint has {} instances but the store has {}",
+                self.instances.len(),
+                instances.len()
+            );
+        }
+        let host_globals = host_globals(store);
+        if host_globals.len() != self.host_globals.len() {
+            bail!(
+                "the checkpoint has {} host globals but the store has {}",
+                self.host_globals.len(),
+                host_globals.len()
+            );
+        }
+
+        // Check everything which can be checked up front so that a mismatched
+        // checkpoint doesn't leave the store half-restored.
+        for (i, (&id, state)) in instances.iter().zip(&self.instances).enumerate() {
+            let env = store.0.instance(id).module();
+            if env.functions.len() != state.functions as usize
+                || env.memory_plans.len() - env.num_imported_memories != state.memories.len()
+                || env.table_plans.len() - env.num_imported_tables != state.tables.len()
+                || env.globals.len() - env.num_imported_globals != state.globals.len()
+            {
+                bail!("instance {i} of the store does not match the checkpoint");
+            }
+        }
+        if self.fuel.is_some() && store.fuel_remaining().is_none() {
+            bail!("the checkpoint has fuel but fuel is not enabled for the store");
+        }
+
+        for (&id, state) in instances.iter().zip(&self.instances) {
+            let env = store.0.instance(id).module().clone();
+
+            for (memory, state) in defined_memories(store, id).iter().zip(&state.memories) {
+                restore_memory(store, memory, state)?;
+            }
+
+            let indices = (env.num_imported_tables..env.table_plans.len()).map(TableIndex::new);
+            for (index, elements) in indices.zip(&state.tables) {
+                let table = table(store, id, index);
+                let null = null(table.ty(&*store).element().clone());
+                let size = table.size(&*store);
+                let len = u32::try_from(elements.len()).unwrap();
+                if size > len {
+                    bail!("table {} is larger than in the checkpoint", index.index());
+                }
+                table.grow(&mut *store, len - size, null.clone())?;
+                for (i, element) in elements.iter().enumerate() {
+                    let val = resolve(store, &instances, element, &null)?;
+                    table.set(&mut *store, i as u32, val)?;
+                }
+            }
+
+            for (global, value) in defined_globals(store, id).iter().zip(&state.globals) {
+                restore_global(store, &instances, global, value)?;
+            }
+        }
+        for (global, value) in host_globals.iter().zip(&self.host_globals) {
+            restore_global(store, &instances, global, value)?;
+        }
+
+        if let Some(fuel) = self.fuel {
+            let remaining = store.fuel_remaining().unwrap();
+            if remaining < fuel {
+                store.add_fuel(fuel - remaining)?;
+            } else {
+                store.consume_fuel(remaining - fuel)?;
+            }
+        }
+        if let Some(deadline) = self.epoch_deadline {
+            store.set_epoch_deadline(deadline);
+        }
+        Ok(())
+    }
+
+    /// Returns the state attached to this checkpoint by the embedder.
+    pub fn host_state(&self) -> &[u8] {
+        &self.host_state
+    }
+
+    /// Attaches state to this checkpoint on behalf of the embedder, such as a
+    /// serialized form of the store's `T`.
+    pub fn set_host_state(&mut self, state: Vec<u8>) {
+        self.host_state = state;
+    }
+
+    /// Serializes this checkpoint into bytes which can later be passed to
+    /// [`Checkpoint::deserialize`].
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self)?;
+        Ok(bytes)
+    }
+
+    /// Deserializes a checkpoint produced by [`Checkpoint::serialize`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` isn't a checkpoint or was produced by an
+    /// incompatible version of Wasmtime.
+    pub fn deserialize(bytes: &[u8]) -> Result<Checkpoint> {
+        let bytes = bytes
+            .strip_prefix(MAGIC)
+            .context("bytes are not a serialized checkpoint")?;
+        if bytes.get(..4) != Some(&VERSION.to_le_bytes()[..]) {
+            bail!("the checkpoint was serialized by an incompatible version of Wasmtime");
+        }
+        bincode::deserialize(&bytes[4..]).context("failed to deserialize checkpoint")
+    }
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("instances", &self.instances.len())
+            .field("host_globals", &self.host_globals.len())
+            .field("fuel", &self.fuel)
+            .field("epoch_deadline", &self.epoch_deadline)
+            .field("host_state", &self.host_state.len())
+            .finish()
+    }
+}
+
+/// Maps the functions of all instances in a store, by the address of their
+/// `VMFuncRef`, to their instance and function index.
+struct FuncMap(HashMap<usize, (u32, u32)>);
+
+impl FuncMap {
+    fn new<T>(store: &mut StoreContextMut<'_, T>, instances: &[InstanceId]) -> FuncMap {
+        let mut map = HashMap::new();
+        for (i, &id) in instances.iter().enumerate() {
+            let env = store.0.instance(id).module().clone();
+            // Only escaping functions can be referenced, and only they have a
+            // `VMFuncRef` to begin with.
+            for (func, _) in env.functions.iter().filter(|(_, f)| f.is_escaping()) {
+                let export = store.0.instance_mut(id).get_exported_func(func);
+                map.entry(export.func_ref.as_ptr() as usize)
+                    .or_insert((i as u32, func.as_u32()));
+            }
+        }
+        FuncMap(map)
+    }
+
+    fn reference<T>(&self, store: &mut StoreContextMut<'_, T>, val: Val) -> Result<Reference> {
+        match val {
+            Val::FuncRef(None) | Val::ExternRef(None) => Ok(Reference::Null),
+            Val::FuncRef(Some(func)) => {
+                let func_ref = func.vm_func_ref(store.0).as_ptr() as usize;
+                match self.0.get(&func_ref) {
+                    Some(&(instance, func)) => Ok(Reference::Func { instance, func }),
+                    None => bail!("references to host functions cannot be checkpointed"),
+                }
+            }
+            Val::ExternRef(Some(_)) => bail!("non-null externrefs cannot be checkpointed"),
+            Val::I32(_) | Val::I64(_) | Val::F32(_) | Val::F64(_) | Val::V128(_) => {
+                unreachable!()
+            }
+        }
+    }
+}
+
+/// Returns the value of `reference`, with `null` as its null value.
+fn resolve<T>(
+    store: &mut StoreContextMut<'_, T>,
+    instances: &[InstanceId],
+    reference: &Reference,
+    null: &Val,
+) -> Result<Val> {
+    match *reference {
+        Reference::Null => Ok(null.clone()),
+        Reference::Func { instance, func } => {
+            let id = *instances
+                .get(instance as usize)
+                .context("the checkpoint refers to an instance which does not exist")?;
+            let escaping = store
+                .0
+                .instance(id)
+                .module()
+                .functions
+                .get(FuncIndex::from_u32(func))
+                .map_or(false, |f| f.is_escaping());
+            if !escaping {
+                bail!("the checkpoint refers to a function which does not exist");
+            }
+            let export = store
+                .0
+                .instance_mut(id)
+                .get_exported_func(FuncIndex::from_u32(func));
+            // SAFETY: the function belongs to an instance of this store.
+            let func = unsafe { Func::from_wasmtime_function(export, store.0) };
+            Ok(Val::FuncRef(Some(func)))
+        }
+    }
+}
+
+fn defined_memories<T>(store: &mut StoreContextMut<'_, T>, id: InstanceId) -> Vec<Memory> {
+    let exports = store
+        .0
+        .instance_mut(id)
+        .defined_memories()
+        .collect::<Vec<_>>();
+    exports
+        .into_iter()
+        // SAFETY: the memories belong to an instance of this store.
+        .map(|export| unsafe { Memory::from_wasmtime_memory(export, store.0) })
+        .collect()
+}
+
+fn defined_globals<T>(store: &mut StoreContextMut<'_, T>, id: InstanceId) -> Vec<Global> {
+    let exports = store
+        .0
+        .instance_mut(id)
+        .defined_globals()
+        .map(|(_, export)| export)
+        .collect::<Vec<_>>();
+    exports
+        .into_iter()
+        // SAFETY: the globals belong to an instance of this store.
+        .map(|export| unsafe { Global::from_wasmtime_global(export, store.0) })
+        .collect()
+}
+
+/// Returns the globals created with [`Global::new`], which don't belong to
+/// any instance.
+fn host_globals<T>(store: &mut StoreContextMut<'_, T>) -> Vec<Global> {
+    let count = store.0.host_globals().len();
+    store.0.all_globals().take(count).collect()
+}
+
+fn table<T>(store: &mut StoreContextMut<'_, T>, id: InstanceId, index: TableIndex) -> Table {
+    let export = store.0.instance_mut(id).get_exported_table(index);
+    // SAFETY: the table belongs to an instance of this store.
+    unsafe { Table::from_wasmtime_table(export, store.0) }
+}
+
+fn null(ty: ValType) -> Val {
+    match ty {
+        ValType::ExternRef => Val::ExternRef(None),
+        _ => Val::FuncRef(None),
+    }
+}
+
+fn capture_memory<T>(store: &StoreContextMut<'_, T>, memory: &Memory) -> Result<MemoryState> {
+    if memory.ty(store).is_shared() {
+        bail!("shared memories cannot be checkpointed");
+    }
+    let mut chunks = Vec::new();
+    for (i, chunk) in memory.data(store).chunks(CHUNK_SIZE).enumerate() {
+        if chunk.iter().any(|byte| *byte != 0) {
+            chunks.push(((i * CHUNK_SIZE) as u64, chunk.to_vec()));
+        }
+    }
+    Ok(MemoryState {
+        pages: memory.size(store),
+        chunks,
+    })
+}
+
+fn restore_memory<T>(
+    store: &mut StoreContextMut<'_, T>,
+    memory: &Memory,
+    state: &MemoryState,
+) -> Result<()> {
+    if memory.ty(&*store).is_shared() {
+        bail!("shared memories cannot be restored");
+    }
+    let pages = memory.size(&*store);
+    if pages > state.pages {
+        bail!("a memory is larger than in the checkpoint");
+    }
+    memory.grow(&mut *store, state.pages - pages)?;
+    let data = memory.data_mut(&mut *store);
+    data.fill(0);
+    for (offset, chunk) in state.chunks.iter() {
+        usize::try_from(*offset)
+            .ok()
+            .and_then(|offset| data.get_mut(offset..))
+            .and_then(|data| data.get_mut(..chunk.len()))
+            .context("the checkpoint's memory contents are out of bounds")?
+            .copy_from_slice(chunk);
+    }
+    Ok(())
+}
+
+fn capture_global<T>(
+    store: &mut StoreContextMut<'_, T>,
+    funcs: &FuncMap,
+    global: &Global,
+) -> Result<Option<Value>> {
+    if let Mutability::Const = global.ty(&*store).mutability() {
+        return Ok(None);
+    }
+    Ok(Some(match global.get(&mut *store) {
+        Val::I32(x) => Value::I32(x),
+        Val::I64(x) => Value::I64(x),
+        Val::F32(x) => Value::F32(x),
+        Val::F64(x) => Value::F64(x),
+        Val::V128(x) => Value::V128(x.as_u128()),
+        val => Value::Ref(funcs.reference(store, val)?),
+    }))
+}
+
+fn restore_global<T>(
+    store: &mut StoreContextMut<'_, T>,
+    instances: &[InstanceId],
+    global: &Global,
+    value: &Option<Value>,
+) -> Result<()> {
+    let val = match value {
+        None => return Ok(()),
+        Some(Value::I32(x)) => Val::I32(*x),
+        Some(Value::I64(x)) => Val::I64(*x),
+        Some(Value::F32(x)) => Val::F32(*x),
+        Some(Value::F64(x)) => Val::F64(*x),
+        Some(Value::V128(x)) => Val::V128((*x).into()),
+        Some(Value::Ref(r)) => {
+            let null = null(global.ty(&*store).content().clone());
+            resolve(store, instances, r, &null)?
+        }
+    };
+    global.set(&mut *store, val)
+}
@@ -2,6 +2,7 @@ use crate::component::func::{LiftContext, LowerContext, Options};
 use crate::component::matching::InstanceType;
 use crate::component::storage::slice_to_storage_mut;
 use crate::component::{ComponentNamedList, ComponentType, Lift, Lower, Type, Val};
+use crate::store::StoreOpaque;
 use crate::{AsContextMut, StoreContextMut, ValRaw};
 use anyhow::{anyhow, bail, Context, Result};
 use std::any::Any;
@@ -56,17 +57,21 @@ impl HostFunc {
     {
         let data = data as *const F;
         unsafe {
+            let storage = std::slice::from_raw_parts_mut(storage, storage_len);
             handle_result(|| {
-                call_host::<_, _, _, _>(
-                    cx,
-                    ty,
-                    flags,
-                    memory,
-                    realloc,
-                    string_encoding,
-                    std::slice::from_raw_parts_mut(storage, storage_len),
-                    |store, args| (*data)(store, args),
-                )
+                let options = (memory, realloc, string_encoding);
+                record_or_replay::<T>(cx, ty, options, storage, |storage| {
+                    call_host::<_, _, _, _>(
+                        cx,
+                        ty,
+                        flags,
+                        memory,
+                        realloc,
+                        string_encoding,
+                        storage,
+                        |store, args| (*data)(store, args),
+                    )
+                })
             })
         }
     }
@@ -273,11 +278,13 @@ where
                     ret.lower(cx, ty, map_maybe_uninit!(storage.ret))
                 }
                 Storage::ResultsIndirect(storage) => {
-                    let ptr = validate_inbounds::<R>(cx.as_slice_mut(), &storage.retptr)?;
+                    let ptr =
+                        validate_inbounds::<R>(cx.options.memory(cx.store.0), &storage.retptr)?;
                     ret.store(cx, ty, ptr)
                 }
                 Storage::Indirect(storage) => {
-                    let ptr = validate_inbounds::<R>(cx.as_slice_mut(), &storage.retptr)?;
+                    let ptr =
+                        validate_inbounds::<R>(cx.options.memory(cx.store.0), &storage.retptr)?;
                     ret.store(cx, ty, ptr)
                 }
             }
@@ -301,6 +308,179 @@ fn validate_inbounds<T: ComponentType>(memory: &[u8], ptr: &ValRaw) -> Result<us
     Ok(ptr)
 }
 
+/// Calls `call` to make a host call with `storage`, unless the store is
+/// recording or replaying host calls.
+///
+/// Host calls are logged in terms of the flat arguments and results in
+/// `storage`; those which don't fit there are passed through memory, where
+/// only their pointer is compared and results are logged along with the rest
+/// of the call's changes to memory. Calls passing resources can't be logged:
+/// the resources a call creates or takes from the guest live in tables outside
+/// of the guest's memory.
+unsafe fn record_or_replay<T>(
+    cx: *mut VMOpaqueContext,
+    ty: TypeFuncIndex,
+    (memory, realloc, string_encoding): (*mut VMMemoryDefinition, *mut VMFuncRef, StringEncoding),
+    storage: &mut [MaybeUninit<ValRaw>],
+    call: impl FnOnce(&mut [MaybeUninit<ValRaw>]) -> Result<()>,
+) -> Result<()> {
+    let instance = (*VMComponentContext::from_opaque(cx)).instance();
+    let store = StoreContextMut::<T>::from_raw((*instance).store());
+    if store.0.host_calls_mut().is_off() {
+        return call(storage);
+    }
+
+    let types = (*instance).component_types();
+    let param_tys = &types[types[ty].params];
+    let result_tys = &types[types[ty].results];
+    let mut params = Vec::new();
+    let mut results = Vec::new();
+    for ty in param_tys.types.iter() {
+        flatten(types, ty, &mut params)?;
+    }
+    for ty in result_tys.types.iter() {
+        flatten(types, ty, &mut results)?;
+    }
+    if params.len() > MAX_FLAT_PARAMS {
+        params = vec![crate::ValType::I32];
+    }
+    if results.len() > MAX_FLAT_RESULTS {
+        // The results are written to memory through a pointer passed after
+        // the arguments.
+        params.push(crate::ValType::I32);
+        results.clear();
+    }
+
+    let options = Options::new(
+        store.0.id(),
+        NonNull::new(memory),
+        NonNull::new(realloc),
+        string_encoding,
+    );
+    let mut realloc = |store: &mut StoreOpaque, [old, old_size, old_align, new_size]: [u32; 4]| {
+        let store = StoreContextMut::<T>::from_raw(store.traitobj());
+        let mut cx = LowerContext::new(store, &options, types, instance);
+        let ptr = cx.realloc(
+            old as usize,
+            old_size as usize,
+            old_align,
+            new_size as usize,
+        )?;
+        Ok(u32::try_from(ptr)?)
+    };
+
+    // NB: the results are only read once they've been written by the call.
+    let values = |storage: &mut [MaybeUninit<ValRaw>]| {
+        &mut *(storage as *mut [MaybeUninit<ValRaw>] as *mut [ValRaw])
+    };
+    match crate::replay::start(
+        store.0,
+        &params,
+        &results,
+        values(storage),
+        Some(&mut realloc),
+    )? {
+        crate::replay::Start::Call => call(storage),
+        crate::replay::Start::Replayed => Ok(()),
+        crate::replay::Start::Record(recording) => {
+            let result = call(storage);
+            let error = result.as_ref().err();
+            crate::replay::finish(store.0, recording, &results, values(storage), error);
+            result
+        }
+    }
+}
+
+/// Pushes the core wasm types `ty` is flattened to in the canonical ABI onto
+/// `dst`, failing if it contains resources, whose host calls can't be logged.
+fn flatten(
+    types: &ComponentTypes,
+    ty: &InterfaceType,
+    dst: &mut Vec<crate::ValType>,
+) -> Result<()> {
+    use crate::ValType;
+
+    match ty {
+        InterfaceType::Bool
+        | InterfaceType::S8
+        | InterfaceType::U8
+        | InterfaceType::S16
+        | InterfaceType::U16
+        | InterfaceType::S32
+        | InterfaceType::U32
+        | InterfaceType::Char
+        | InterfaceType::Enum(_) => dst.push(ValType::I32),
+        InterfaceType::S64 | InterfaceType::U64 => dst.push(ValType::I64),
+        InterfaceType::Float32 => dst.push(ValType::F32),
+        InterfaceType::Float64 => dst.push(ValType::F64),
+        InterfaceType::String => dst.extend([ValType::I32, ValType::I32]),
+        InterfaceType::List(i) => {
+            // The elements live in memory, but they may still be resources.
+            flatten(types, &types[*i].element, &mut Vec::new())?;
+            dst.extend([ValType::I32, ValType::I32]);
+        }
+        InterfaceType::Record(i) => {
+            for field in types[*i].fields.iter() {
+                flatten(types, &field.ty, dst)?;
+            }
+        }
+        InterfaceType::Tuple(i) => {
+            for ty in types[*i].types.iter() {
+                flatten(types, ty, dst)?;
+            }
+        }
+        InterfaceType::Flags(i) => {
+            let count = (types[*i].names.len() + 31) / 32;
+            dst.extend(std::iter::repeat(ValType::I32).take(count));
+        }
+        InterfaceType::Variant(i) => {
+            let cases = types[*i].cases.iter().map(|case| case.ty.as_ref());
+            flatten_variant(types, cases, dst)?;
+        }
+        InterfaceType::Option(i) => {
+            flatten_variant(types, [None, Some(&types[*i].ty)].into_iter(), dst)?;
+        }
+        InterfaceType::Result(i) => {
+            let ty = &types[*i];
+            flatten_variant(types, [ty.ok.as_ref(), ty.err.as_ref()].into_iter(), dst)?;
+        }
+        InterfaceType::Own(_) | InterfaceType::Borrow(_) => {
+            bail!("host functions taking or returning resources can't be recorded or replayed")
+        }
+    }
+    Ok(())
+}
+
+/// Flattens a variant with the payloads `cases`: a discriminant followed by
+/// the payloads' flat types joined together.
+fn flatten_variant<'a>(
+    types: &ComponentTypes,
+    cases: impl Iterator<Item = Option<&'a InterfaceType>>,
+    dst: &mut Vec<crate::ValType>,
+) -> Result<()> {
+    use crate::ValType;
+
+    dst.push(ValType::I32);
+    let start = dst.len();
+    for ty in cases.flatten() {
+        let mut flat = Vec::new();
+        flatten(types, ty, &mut flat)?;
+        for (i, ty) in flat.into_iter().enumerate() {
+            match dst.get_mut(start + i) {
+                Some(prev) if *prev == ty => {}
+                Some(prev) => {
+                    *prev = match (&*prev, &ty) {
+                        (ValType::I32, ValType::F32) | (ValType::F32, ValType::I32) => ValType::I32,
+                        _ => ValType::I64,
+                    }
+                }
+                None => dst.push(ty),
+            }
+        }
+    }
+    Ok(())
+}
+
 unsafe fn handle_result(func: impl FnOnce() -> Result<()>) {
     match panic::catch_unwind(AssertUnwindSafe(func)) {
         Ok(Ok(())) => {}
@@ -395,7 +575,8 @@ where
         assert!(dst.next().is_none());
     } else {
         let ret_ptr = storage[ret_index].assume_init_ref();
-        let mut ptr = validate_inbounds_dynamic(&result_tys.abi, cx.as_slice_mut(), ret_ptr)?;
+        let memory = cx.options.memory(cx.store.0);
+        let mut ptr = validate_inbounds_dynamic(&result_tys.abi, memory, ret_ptr)?;
         for (val, ty) in result_vals.iter().zip(result_tys.types.iter()) {
             let offset = types.canonical_abi(ty).next_field32_size(&mut ptr);
             val.store(&mut cx, *ty, offset)?;
@@ -440,17 +621,21 @@ extern "C" fn dynamic_entrypoint<T, F>(
 {
     let data = data as *const F;
     unsafe {
+        let storage = std::slice::from_raw_parts_mut(storage, storage_len);
         handle_result(|| {
-            call_host_dynamic::<T, _>(
-                cx,
-                ty,
-                flags,
-                memory,
-                realloc,
-                string_encoding,
-                std::slice::from_raw_parts_mut(storage, storage_len),
-                |store, params, results| (*data)(store, params, results),
-            )
+            let options = (memory, realloc, string_encoding);
+            record_or_replay::<T>(cx, ty, options, storage, |storage| {
+                call_host_dynamic::<T, _>(
+                    cx,
+                    ty,
+                    flags,
+                    memory,
+                    realloc,
+                    string_encoding,
+                    storage,
+                    |store, params, results| (*data)(store, params, results),
+                )
+            })
         })
     }
 }
@@ -88,18 +88,21 @@ impl Options {
 
         // Invoke the wasm malloc function using its raw and statically known
         // signature.
+        let params = (
+            u32::try_from(old)?,
+            u32::try_from(old_size)?,
+            old_align,
+            u32::try_from(new_size)?,
+        );
+        let logged =
+            crate::replay::start_realloc(store.0, [params.0, params.1, params.2, params.3]);
         let result = unsafe {
-            crate::TypedFunc::<(u32, u32, u32, u32), u32>::call_raw(
-                store,
-                realloc,
-                (
-                    u32::try_from(old)?,
-                    u32::try_from(old_size)?,
-                    old_align,
-                    u32::try_from(new_size)?,
-                ),
-            )?
+            crate::TypedFunc::<(u32, u32, u32, u32), u32>::call_raw(store, realloc, params)
         };
+        if logged {
+            crate::replay::finish_realloc(store.0, result.as_ref().ok().copied());
+        }
+        let result = result?;
 
         if result % old_align != 0 {
             bail!("realloc return: result not aligned");
@@ -220,14 +223,37 @@ impl<'a, T> LowerContext<'a, T> {
 
     /// Returns a view into memory as a mutable slice of bytes.
     ///
+    /// Prefer [`LowerContext::slice_mut`] to write to memory, which notes what
+    /// it hands out while host calls are being recorded rather than having to
+    /// copy the whole memory.
+    ///
     /// # Panics
     ///
     /// This will panic if memory has not been configured for this lowering
     /// (e.g. it wasn't present during the specification of canonical options).
     pub fn as_slice_mut(&mut self) -> &mut [u8] {
+        let memory = self.options.memory.unwrap().as_ptr();
+        if let Some(call) = self.store.0.host_calls_mut().call_mut() {
+            unsafe { call.accessed(memory) };
+        }
         self.options.memory_mut(self.store.0)
     }
 
+    /// Returns the `len` bytes of memory starting at `offset` for them to be
+    /// written, panicking on out-of-bounds.
+    ///
+    /// # Panics
+    ///
+    /// This will panic if memory has not been configured for this lowering
+    /// (e.g. it wasn't present during the specification of canonical options).
+    pub fn slice_mut(&mut self, offset: usize, len: usize) -> &mut [u8] {
+        let memory = self.options.memory.unwrap().as_ptr();
+        if let Some(call) = self.store.0.host_calls_mut().call_mut() {
+            call.wrote(memory, offset, len);
+        }
+        &mut self.options.memory_mut(self.store.0)[offset..][..len]
+    }
+
     /// Invokes the memory allocation function (which is style after `realloc`)
     /// with the specified parameters.
     ///
@@ -268,9 +294,7 @@ impl<'a, T> LowerContext<'a, T> {
         // For now I figure we can leave in this bounds check and if it becomes
         // an issue we can optimize further later, probably with judicious use
         // of `unsafe`.
-        (&mut self.as_slice_mut()[offset..][..N])
-            .try_into()
-            .unwrap()
+        self.slice_mut(offset, N).try_into().unwrap()
     }
 
     /// Lowers an `own` resource into the guest, converting the `rep` specified
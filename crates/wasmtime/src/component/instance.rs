@@ -5,8 +5,9 @@ use crate::instance::OwnedImports;
 use crate::linker::DefinitionType;
 use crate::store::{StoreOpaque, Stored};
 use crate::{AsContextMut, Module, StoreContextMut};
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use indexmap::IndexMap;
+use std::collections::HashMap;
 use std::marker;
 use std::ptr::NonNull;
 use std::sync::Arc;
@@ -143,6 +144,65 @@ impl Instance {
     pub fn get_resource(&self, mut store: impl AsContextMut, name: &str) -> Option<ResourceType> {
         self.exports(store.as_context_mut()).root().resource(name)
     }
+
+    /// Captures the current state of this instance in a new component.
+    ///
+    /// `wasm` must be the original binary of this instance's component. Each
+    /// core module within it which was instantiated by this instance is
+    /// replaced with a snapshot of that core instance, as described by
+    /// [`crate::Instance::snapshot`], so that instantiating the returned
+    /// component starts from the state this instance is in now. Core modules
+    /// which were not instantiated are left unchanged.
+    ///
+    /// Only the state of core instances is captured; component-level state
+    /// such as resource handles owned by this instance is not, so this should
+    /// be used when no resources are live.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a core module was instantiated more than once by
+    /// this instance or if the state of one of its instances can't be
+    /// captured.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn snapshot(&self, mut store: impl AsContextMut, wasm: &[u8]) -> Result<Vec<u8>> {
+        let mut store = store.as_context_mut();
+
+        // Instances are created in the order of the component's initializers,
+        // so this maps each of the component's core modules to its instances.
+        let data = store.0[self.0].as_ref().unwrap();
+        let mut instances = HashMap::<StaticModuleIndex, Vec<crate::Instance>>::new();
+        let mut runtime_instances = data.instances.values();
+        for initializer in data.component.env_component().initializers.iter() {
+            if let GlobalInitializer::InstantiateModule(m) = initializer {
+                let instance = *runtime_instances.next().unwrap();
+                if let InstantiateModule::Static(idx, _) = m {
+                    instances.entry(*idx).or_default().push(instance);
+                }
+            }
+        }
+
+        // Core modules are numbered in the order they appear in the binary,
+        // including those nested within other components.
+        let mut next_module = 0;
+        crate::snapshot::rewrite_component(wasm, &mut |module| {
+            let idx = StaticModuleIndex::from_u32(next_module);
+            next_module += 1;
+            match instances.get(&idx).map(|i| &i[..]) {
+                None | Some([]) => Ok(module.to_vec()),
+                Some([instance]) => {
+                    crate::snapshot::snapshot_module(&mut store, instance, module, true)
+                        .with_context(|| format!("failed to snapshot core module {}", idx.as_u32()))
+                }
+                Some(_) => bail!(
+                    "cannot snapshot core module {} which was instantiated more than once",
+                    idx.as_u32()
+                ),
+            }
+        })
+    }
 }
 
 impl InstanceData {
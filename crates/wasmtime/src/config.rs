@@ -1,4 +1,6 @@
 use crate::memory::MemoryCreator;
+#[cfg(feature = "async")]
+use crate::stack::{StackCreator, StackCreatorProxy};
 use crate::trampoline::MemoryCreatorProxy;
 use anyhow::{bail, ensure, Result};
 use serde_derive::{Deserialize, Serialize};
@@ -16,8 +18,13 @@ use wasmtime_environ::Tunables;
 use wasmtime_jit::profiling::{self, ProfilingAgent};
 use wasmtime_runtime::{mpk, InstanceAllocator, OnDemandInstanceAllocator, RuntimeMemoryCreator};
 
+#[cfg(feature = "async")]
+use wasmtime_runtime::RuntimeFiberStackCreator;
+
 pub use wasmtime_environ::CacheStore;
 pub use wasmtime_runtime::MpkEnabled;
+#[cfg(feature = "pooling-allocator")]
+pub use wasmtime_runtime::{PoolStats, PoolingAllocatorStats};
 
 /// Represents the module instance allocation strategy to use.
 #[derive(Clone)]
@@ -97,6 +104,8 @@ pub struct Config {
     #[cfg(feature = "cache")]
     pub(crate) cache_config: CacheConfig,
     pub(crate) mem_creator: Option<Arc<dyn RuntimeMemoryCreator>>,
+    #[cfg(feature = "async")]
+    pub(crate) stack_creator: Option<Arc<dyn RuntimeFiberStackCreator>>,
     pub(crate) allocation_strategy: InstanceAllocationStrategy,
     pub(crate) max_wasm_stack: usize,
     pub(crate) features: WasmFeatures,
@@ -183,6 +192,8 @@ impl Config {
             cache_config: CacheConfig::new_cache_disabled(),
             profiling_strategy: ProfilingStrategy::None,
             mem_creator: None,
+            #[cfg(feature = "async")]
+            stack_creator: None,
             allocation_strategy: InstanceAllocationStrategy::OnDemand,
             // 512k of stack -- note that this is chosen currently to not be too
             // big, not be too small, and be a good default for most platforms.
@@ -371,6 +382,27 @@ impl Config {
         self
     }
 
+    /// Configures whether compiled code is instrumented for in-process
+    /// debugging of guests.
+    ///
+    /// When enabled, execution can be paused before any WebAssembly
+    /// instruction, either at breakpoints or while single-stepping, to inspect
+    /// and modify the paused frame's locals along with the instance's globals
+    /// and memories. See [`Store::debug_handler`](crate::Store::debug_handler)
+    /// for how to drive this.
+    ///
+    /// The instrumentation checks whether to pause before every instruction so
+    /// code runs considerably slower with this enabled. Note that locals of
+    /// reference types can't be inspected.
+    ///
+    /// This is only supported by the Cranelift compilation strategy.
+    ///
+    /// By default this option is `false`.
+    pub fn guest_debug(&mut self, enable: bool) -> &mut Self {
+        self.tunables.guest_debug = enable;
+        self
+    }
+
     /// Configures whether [`WasmBacktrace`] will be present in the context of
     /// errors returned from Wasmtime.
     ///
@@ -1088,6 +1120,23 @@ impl Config {
         self
     }
 
+    /// Sets a custom stack creator.
+    ///
+    /// Custom stack creators are used to create the stacks on which async
+    /// calls into WebAssembly execute, both for the on-demand instance
+    /// allocation strategy and, in place of its pool of stacks, for the
+    /// pooling instance allocation strategy. In the latter case the
+    /// [`PoolingAllocationConfig::total_stacks`] limit still applies.
+    ///
+    /// Stacks are requested with the size configured by
+    /// [`Config::async_stack_size`].
+    #[cfg(feature = "async")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    pub fn with_host_stack(&mut self, stack_creator: Arc<dyn StackCreator>) -> &mut Self {
+        self.stack_creator = Some(Arc::new(StackCreatorProxy(stack_creator)));
+        self
+    }
+
     /// Sets the instance allocation strategy to use.
     ///
     /// When using the pooling instance allocation strategy, all linear memories
@@ -1168,6 +1217,12 @@ impl Config {
     /// all. Smaller static memories will use similar bounds checks as dynamic
     /// memories.
     ///
+    /// 64-bit memories are static only if they declare a maximum size no
+    /// larger than `max_size`, or if [`Config::static_memory_forced`] is
+    /// enabled. Their bounds checks can't be elided, but accesses whose offset
+    /// fits within [`Config::static_memory_guard_size`] are checked with a
+    /// single comparison against the static bound.
+    ///
     /// ## Default
     ///
     /// The default value for this property depends on the host platform. For
@@ -1577,14 +1632,30 @@ impl Config {
         let stack_size = 0;
 
         match &self.allocation_strategy {
-            InstanceAllocationStrategy::OnDemand => Ok(Box::new(OnDemandInstanceAllocator::new(
-                self.mem_creator.clone(),
-                stack_size,
-            ))),
+            InstanceAllocationStrategy::OnDemand => {
+                #[allow(unused_mut)]
+                let mut allocator =
+                    OnDemandInstanceAllocator::new(self.mem_creator.clone(), stack_size);
+                #[cfg(feature = "async")]
+                if let Some(stack_creator) = &self.stack_creator {
+                    allocator.set_stack_creator(stack_creator.clone());
+                }
+                Ok(Box::new(allocator))
+            }
             #[cfg(feature = "pooling-allocator")]
             InstanceAllocationStrategy::Pooling(config) => {
                 let mut config = config.config;
                 config.stack_size = stack_size;
+                #[cfg(feature = "async")]
+                if let Some(stack_creator) = &self.stack_creator {
+                    return Ok(Box::new(
+                        wasmtime_runtime::PoolingInstanceAllocator::with_stack_creator(
+                            &config,
+                            &self.tunables,
+                            stack_creator.clone(),
+                        )?,
+                    ));
+                }
                 Ok(Box::new(wasmtime_runtime::PoolingInstanceAllocator::new(
                     &config,
                     &self.tunables,
@@ -1619,6 +1690,13 @@ impl Config {
             Strategy::Winch => bail!("winch support not compiled in"),
         };
 
+        #[cfg(feature = "winch")]
+        if let Strategy::Winch = self.compiler_config.strategy {
+            if self.tunables.guest_debug {
+                bail!("guest debugging is not supported by Winch");
+            }
+        }
+
         if let Some(target) = &self.compiler_config.target {
             compiler.target(target.clone())?;
         }
@@ -1813,6 +1891,7 @@ impl fmt::Debug for Config {
                 "guard_before_linear_memory",
                 &self.tunables.guard_before_linear_memory,
             )
+            .field("guest_debug", &self.tunables.guest_debug)
             .field("parallel_compilation", &self.parallel_compilation);
         #[cfg(any(feature = "cranelift", feature = "winch"))]
         {
@@ -2308,11 +2387,24 @@ impl PoolingAllocationConfig {
     /// The reservation size of each linear memory is controlled by the
     /// `static_memory_maximum_size` setting and this value cannot exceed the
     /// configured static memory maximum size.
+    ///
+    /// This may exceed 65536 pages (4GiB) for the benefit of 64-bit memories;
+    /// 32-bit memories remain limited to 4GiB by their type.
     pub fn memory_pages(&mut self, pages: u64) -> &mut Self {
         self.config.limits.memory_pages = pages;
         self
     }
 
+    /// The maximum size, in bytes, of any linear memory defined in a module.
+    ///
+    /// This is the same as [`PoolingAllocationConfig::memory_pages`] but
+    /// configured in bytes, which is more convenient for the large slots of
+    /// 64-bit memories. The size is rounded down to a whole number of Wasm
+    /// pages.
+    pub fn max_memory_size(&mut self, bytes: u64) -> &mut Self {
+        self.memory_pages(bytes / u64::from(wasmtime_environ::WASM_PAGE_SIZE))
+    }
+
     /// Configures whether memory protection keys (MPK) should be used for more
     /// efficient layout of pool-allocated memories.
     ///
@@ -0,0 +1,218 @@
+//! In-process debugging of guests, see [`Config::guest_debug`].
+//!
+//! [`Config::guest_debug`]: crate::Config::guest_debug
+
+use crate::{AsContextMut, Global, Instance, Memory, Val};
+use anyhow::{bail, Result};
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::slice;
+use wasmtime_environ::{DebugLocalKind, EntityRef, FuncIndex, DEBUG_LOCAL_SLOT_SIZE};
+use wasmtime_runtime::CompiledModuleId;
+
+const SLOT_SIZE: usize = DEBUG_LOCAL_SLOT_SIZE as usize;
+
+/// How execution resumes once a store's debug handler returns.
+///
+/// See [`Store::debug_handler`](crate::Store::debug_handler).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugStep {
+    /// Run until the next breakpoint is reached.
+    Continue,
+    /// Pause again before the next WebAssembly instruction is executed,
+    /// whichever function it's in.
+    Step,
+}
+
+/// The breakpoints and stepping state of a store.
+#[derive(Default)]
+pub(crate) struct DebugState {
+    breakpoints: HashSet<(CompiledModuleId, FuncIndex, u32)>,
+    single_step: bool,
+}
+
+impl DebugState {
+    pub(crate) fn add_breakpoint(
+        &mut self,
+        module: CompiledModuleId,
+        func: FuncIndex,
+        offset: u32,
+    ) {
+        self.breakpoints.insert((module, func, offset));
+    }
+
+    pub(crate) fn remove_breakpoint(
+        &mut self,
+        module: CompiledModuleId,
+        func: FuncIndex,
+        offset: u32,
+    ) -> bool {
+        self.breakpoints.remove(&(module, func, offset))
+    }
+
+    pub(crate) fn set_single_step(&mut self, enable: bool) {
+        self.single_step = enable;
+    }
+
+    /// Returns whether execution should pause before the instruction at
+    /// `offset` in the function `func` of `module`.
+    pub(crate) fn should_pause(
+        &self,
+        module: Option<CompiledModuleId>,
+        func: FuncIndex,
+        offset: u32,
+    ) -> bool {
+        self.single_step
+            || module.map_or(false, |module| {
+                self.breakpoints.contains(&(module, func, offset))
+            })
+    }
+}
+
+/// A WebAssembly frame paused by the debugger.
+///
+/// This is handed to the handler configured with
+/// [`Store::debug_handler`](crate::Store::debug_handler) and gives access to
+/// the locals of the frame as well as the globals and memories of its
+/// instance. The other frames on the stack can be walked with
+/// [`WasmBacktrace::force_capture`](crate::WasmBacktrace::force_capture)
+/// from within the handler, the first of which is this frame.
+pub struct DebugFrame<'a> {
+    instance: Instance,
+    func_index: u32,
+    func_offset: usize,
+    values: &'a mut [u8],
+    kinds: &'a [u8],
+}
+
+impl<'a> DebugFrame<'a> {
+    /// Creates a frame from the locals area handed to the runtime by code
+    /// compiled with guest debugging.
+    ///
+    /// # Safety
+    ///
+    /// `locals` must point to a locals area of `count` locals which is valid
+    /// for reads and writes for `'a`.
+    pub(crate) unsafe fn new(
+        instance: Instance,
+        func: FuncIndex,
+        offset: u32,
+        locals: *mut u8,
+        count: u32,
+    ) -> DebugFrame<'a> {
+        let count = count as usize;
+        let values = slice::from_raw_parts_mut(locals, count * SLOT_SIZE);
+        let kinds = slice::from_raw_parts(locals.add(count * SLOT_SIZE), count);
+        DebugFrame {
+            instance,
+            func_index: func.index() as u32,
+            func_offset: offset as usize,
+            values,
+            kinds,
+        }
+    }
+
+    /// Returns the instance this frame is executing in.
+    pub fn instance(&self) -> Instance {
+        self.instance
+    }
+
+    /// Returns the index, within its module, of the function this frame is
+    /// executing.
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    /// Returns the offset of the instruction this frame is paused before from
+    /// the start of its function, in the same terms as
+    /// [`FrameInfo::func_offset`](crate::FrameInfo::func_offset).
+    pub fn func_offset(&self) -> usize {
+        self.func_offset
+    }
+
+    /// Returns the number of locals of this frame, parameters included.
+    pub fn num_locals(&self) -> usize {
+        self.kinds.len()
+    }
+
+    /// Returns the current value of the local `index` of this frame.
+    ///
+    /// Returns `None` if there's no such local or if it's of a reference type,
+    /// which can't be inspected.
+    pub fn local(&self, index: usize) -> Option<Val> {
+        let kind = self.kind(index)?;
+        let bytes = &self.values[index * SLOT_SIZE..][..SLOT_SIZE];
+        Some(match kind {
+            DebugLocalKind::I32 => Val::I32(i32::from_ne_bytes(bytes[..4].try_into().unwrap())),
+            DebugLocalKind::I64 => Val::I64(i64::from_ne_bytes(bytes[..8].try_into().unwrap())),
+            DebugLocalKind::F32 => Val::F32(u32::from_ne_bytes(bytes[..4].try_into().unwrap())),
+            DebugLocalKind::F64 => Val::F64(u64::from_ne_bytes(bytes[..8].try_into().unwrap())),
+            DebugLocalKind::V128 => {
+                Val::V128(u128::from_ne_bytes(bytes.try_into().unwrap()).into())
+            }
+            DebugLocalKind::Unavailable => return None,
+        })
+    }
+
+    /// Sets the local `index` of this frame to `val`; execution resumes with
+    /// the new value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there's no such local, if it's of a reference type
+    /// or if `val` is not of the local's type.
+    pub fn set_local(&mut self, index: usize, val: Val) -> Result<()> {
+        let kind = match self.kind(index) {
+            Some(kind) => kind,
+            None => bail!("frame has no local {index}"),
+        };
+        let bytes = &mut self.values[index * SLOT_SIZE..][..SLOT_SIZE];
+        match (kind, val) {
+            (DebugLocalKind::I32, Val::I32(v)) => bytes[..4].copy_from_slice(&v.to_ne_bytes()),
+            (DebugLocalKind::I64, Val::I64(v)) => bytes[..8].copy_from_slice(&v.to_ne_bytes()),
+            (DebugLocalKind::F32, Val::F32(v)) => bytes[..4].copy_from_slice(&v.to_ne_bytes()),
+            (DebugLocalKind::F64, Val::F64(v)) => bytes[..8].copy_from_slice(&v.to_ne_bytes()),
+            (DebugLocalKind::V128, Val::V128(v)) => {
+                bytes.copy_from_slice(&v.as_u128().to_ne_bytes())
+            }
+            (DebugLocalKind::Unavailable, _) => {
+                bail!("local {index} is of a reference type and can't be modified")
+            }
+            (_, val) => bail!(
+                "value of type {} doesn't match the type of local {index}",
+                val.ty()
+            ),
+        }
+        Ok(())
+    }
+
+    /// Returns the global `index` of this frame's instance, whether it's
+    /// exported or not.
+    pub fn global(&self, mut store: impl AsContextMut, index: u32) -> Option<Global> {
+        let store = store.as_context_mut().0;
+        let mut globals = self.instance.all_globals(store);
+        globals.nth(index as usize).map(|(_, global)| global)
+    }
+
+    /// Returns the memory `index` of this frame's instance, whether it's
+    /// exported or not.
+    pub fn memory(&self, mut store: impl AsContextMut, index: u32) -> Option<Memory> {
+        let store = store.as_context_mut().0;
+        let mut memories = self.instance.all_memories(store);
+        memories.nth(index as usize).map(|(_, memory)| memory)
+    }
+
+    fn kind(&self, index: usize) -> Option<DebugLocalKind> {
+        DebugLocalKind::from_u8(*self.kinds.get(index)?)
+    }
+}
+
+impl std::fmt::Debug for DebugFrame<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugFrame")
+            .field("func_index", &self.func_index)
+            .field("func_offset", &self.func_offset)
+            .field("num_locals", &self.num_locals())
+            .finish()
+    }
+}
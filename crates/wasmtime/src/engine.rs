@@ -155,6 +155,21 @@ impl Engine {
         Arc::ptr_eq(&a.inner, &b.inner)
     }
 
+    /// Returns a snapshot of the occupancy and reuse statistics of this
+    /// engine's pooling allocator.
+    ///
+    /// This includes the number of slots of each pool in use, warm and cold,
+    /// how often module affinity was honored, how many bytes are kept
+    /// resident, the usage of each memory stripe and the number of
+    /// allocations which failed because a limit was reached.
+    ///
+    /// Returns `None` if this engine doesn't use
+    /// [`InstanceAllocationStrategy::Pooling`](crate::InstanceAllocationStrategy::Pooling).
+    #[cfg(feature = "pooling-allocator")]
+    pub fn pooling_allocator_stats(&self) -> Option<crate::PoolingAllocatorStats> {
+        self.allocator().pooling_stats()
+    }
+
     pub(crate) fn signatures(&self) -> &SignatureRegistry {
         &self.inner.signatures
     }
@@ -221,7 +236,7 @@ impl Engine {
     pub fn precompile_module(&self, bytes: &[u8]) -> Result<Vec<u8>> {
         #[cfg(feature = "wat")]
         let bytes = wat::parse_bytes(&bytes)?;
-        let (mmap, _) = crate::Module::build_artifacts(self, &bytes)?;
+        let (mmap, _) = crate::Module::build_artifacts(self, &bytes, None)?;
         Ok(mmap.to_vec())
     }
 
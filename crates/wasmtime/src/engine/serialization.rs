@@ -345,6 +345,7 @@ impl Metadata {
             guard_before_linear_memory,
             relaxed_simd_deterministic,
             tail_callable,
+            guest_debug,
 
             // This doesn't affect compilation, it's just a runtime setting.
             dynamic_memory_growth_reserve: _,
@@ -406,6 +407,7 @@ impl Metadata {
             "relaxed simd deterministic semantics",
         )?;
         Self::check_bool(tail_callable, other.tail_callable, "WebAssembly tail calls")?;
+        Self::check_bool(guest_debug, other.guest_debug, "guest debugging")?;
 
         Ok(())
     }
@@ -0,0 +1,206 @@
+//! Forking of instances, see [`InstancePre::fork`](crate::InstancePre::fork).
+//!
+//! A fork is instantiated from a copy of the module's type information whose
+//! initializers are replaced with the state of the forked instance: the
+//! minimum sizes of memories and tables are their current sizes, tables are
+//! initialized from a precomputed image of their current elements and
+//! mutable globals from their current values, and passive segments which were
+//! dropped are left out. Memories are initialized from copy-on-write images of
+//! their current contents, or by copying the contents where such images aren't
+//! available.
+
+use crate::instance::Instance;
+use crate::store::StoreOpaque;
+use crate::{Module, StoreContextMut};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::Arc;
+use wasmtime_environ::packed_option::ReservedValue;
+use wasmtime_environ::{
+    DefinedMemoryIndex, DefinedTableIndex, EntityRef, FuncIndex, GlobalInit, MemoryInitialization,
+    PrimaryMap, TableInitialValue, WasmHeapType, WasmType, WASM_PAGE_SIZE,
+};
+use wasmtime_runtime::{Imports, MemoryImage, ModuleRuntimeInfo, TableElement, VMFuncRef};
+
+/// The state of an instance, captured to instantiate forks of it.
+pub(crate) struct Fork {
+    /// Describes instances starting from the captured state.
+    runtime_info: Arc<dyn ModuleRuntimeInfo>,
+    /// The contents of the memories which aren't initialized from a
+    /// copy-on-write image.
+    memories: Vec<(DefinedMemoryIndex, Vec<u8>)>,
+}
+
+impl Fork {
+    /// Captures the current state of `instance`, an instance of `module`.
+    pub(crate) fn capture(
+        store: &mut StoreOpaque,
+        module: &Module,
+        instance: &Instance,
+    ) -> Result<Fork> {
+        if !Module::same(instance._module(store), module) {
+            bail!("the instance to fork is not an instance of this module");
+        }
+        let config = store.engine().config();
+        let cow = config.memory_init_cow && config.mem_creator.is_none();
+        let id = instance.id(store);
+        let handle = store.instance_mut(id);
+
+        // Forks of forks start from the already modified type information.
+        let mut env = handle.module().as_ref().clone();
+        env.start_func = None;
+
+        // Passive segments dropped by the instance are left out of the fork,
+        // which treats them as empty like the runtime does for dropped ones.
+        env.passive_data_map
+            .retain(|index, _| !handle.data_dropped(*index));
+        env.passive_elements_map
+            .retain(|index, _| !handle.elem_dropped(*index));
+
+        // Function references are captured as the index of the function,
+        // which resolves to the same function of the fork.
+        let mut funcs = HashMap::new();
+        for (index, func) in env.functions.iter() {
+            if func.is_escaping() {
+                let func_ref = handle.get_exported_func(index).func_ref;
+                funcs.entry(func_ref.as_ptr() as usize).or_insert(index);
+            }
+        }
+        let func_index = |func_ref: *mut VMFuncRef| -> Result<FuncIndex> {
+            if func_ref.is_null() {
+                return Ok(FuncIndex::reserved_value());
+            }
+            funcs
+                .get(&(func_ref as usize))
+                .copied()
+                .context("references to functions of other instances cannot be forked")
+        };
+
+        // Segments for imported memories and tables were applied when the
+        // instance was created and aren't applied again.
+        env.memory_initialization = MemoryInitialization::Static {
+            map: PrimaryMap::new(),
+        };
+        env.table_initialization.segments.clear();
+
+        let mut images = PrimaryMap::new();
+        let mut memories = Vec::new();
+        for memory in handle.defined_memories() {
+            if memory.memory.memory.shared {
+                bail!("shared memories cannot be forked");
+            }
+            // SAFETY: the memory belongs to a live instance.
+            let contents = unsafe {
+                let definition = &*memory.definition;
+                std::slice::from_raw_parts(definition.base, definition.current_length())
+            };
+            let index = env.memory_index(memory.index);
+            env.memory_plans[index].memory.minimum =
+                (contents.len() / WASM_PAGE_SIZE as usize) as u64;
+            let image = if cow {
+                MemoryImage::from_contents(contents)?
+            } else {
+                None
+            };
+            if image.is_none() {
+                memories.push((memory.index, contents.to_vec()));
+            }
+            images.push(image.map(Arc::new));
+        }
+
+        for i in 0..env.table_plans.len() - env.num_imported_tables {
+            let defined = DefinedTableIndex::new(i);
+            let size = unsafe { (*handle.get_defined_table(defined)).size() };
+            let table = unsafe { &*handle.get_defined_table_with_lazy_init(defined, 0..size) };
+            let mut precomputed = Vec::new();
+            for i in 0..size {
+                let element = match table.get(i).unwrap() {
+                    TableElement::FuncRef(func_ref) => func_index(func_ref)
+                        .with_context(|| format!("cannot fork element {i} of a table"))?,
+                    TableElement::ExternRef(None) => FuncIndex::reserved_value(),
+                    TableElement::ExternRef(Some(_)) => {
+                        bail!("tables holding non-null externrefs cannot be forked")
+                    }
+                    TableElement::UninitFunc => unreachable!(),
+                };
+                precomputed.push(element);
+            }
+            let index = env.table_index(defined);
+            env.table_plans[index].table.minimum = size;
+            env.table_initialization.initial_values[defined] =
+                TableInitialValue::Null { precomputed };
+        }
+
+        // Immutable globals keep their initializers, which evaluate to the
+        // same values as before given the same imports.
+        for (defined, global) in handle.defined_globals() {
+            if !global.global.mutability {
+                continue;
+            }
+            // SAFETY: the global belongs to a live instance and its value is
+            // read according to its type.
+            let value = unsafe { &*global.definition };
+            let init = match global.global.wasm_ty {
+                WasmType::I32 => GlobalInit::I32Const(unsafe { *value.as_i32() }),
+                WasmType::I64 => GlobalInit::I64Const(unsafe { *value.as_i64() }),
+                WasmType::F32 => GlobalInit::F32Const(unsafe { *value.as_f32_bits() }),
+                WasmType::F64 => GlobalInit::F64Const(unsafe { *value.as_f64_bits() }),
+                WasmType::V128 => GlobalInit::V128Const(unsafe { *value.as_u128() }),
+                WasmType::Ref(ty) => match ty.heap_type {
+                    WasmHeapType::Extern => {
+                        if unsafe { value.as_externref() }.is_some() {
+                            bail!("globals holding non-null externrefs cannot be forked");
+                        }
+                        GlobalInit::RefNullConst
+                    }
+                    _ => {
+                        let index =
+                            func_index(unsafe { value.as_func_ref() }).with_context(|| {
+                                let index = env.global_index(defined).index();
+                                format!("cannot fork global {index}")
+                            })?;
+                        if index.is_reserved_value() {
+                            GlobalInit::RefNullConst
+                        } else {
+                            GlobalInit::RefFunc(index)
+                        }
+                    }
+                },
+            };
+            env.global_initializers[defined] = init;
+        }
+
+        Ok(Fork {
+            runtime_info: module.runtime_info_with(env, images),
+            memories,
+        })
+    }
+
+    /// Creates a fork within `store`.
+    ///
+    /// # Unsafety
+    ///
+    /// The `imports` must be suitable for `module`, the module of the forked
+    /// instance.
+    pub(crate) unsafe fn instantiate<T>(
+        &self,
+        store: &mut StoreContextMut<'_, T>,
+        module: &Module,
+        imports: Imports<'_>,
+    ) -> Result<Instance> {
+        let (instance, start) =
+            Instance::new_raw_with(store.0, module, &self.runtime_info, imports)?;
+        debug_assert!(start.is_none());
+
+        let id = instance.id(store.0);
+        let handle = store.0.instance_mut(id);
+        for (index, contents) in self.memories.iter() {
+            let index = self.runtime_info.module().memory_index(*index);
+            let definition = &*handle.get_exported_memory(index).definition;
+            assert_eq!(definition.current_length(), contents.len());
+            ptr::copy_nonoverlapping(contents.as_ptr(), definition.base, contents.len());
+        }
+        Ok(instance)
+    }
+}
@@ -1363,6 +1363,7 @@ pub(crate) fn invoke_wasm_and_catch_traps<T>(
 ) -> Result<()> {
     unsafe {
         let exit = enter_wasm(store);
+        crate::replay::entering_wasm(store.0);
 
         if let Err(trap) = store.0.call_hook(CallHook::CallingWasm) {
             exit_wasm(store, exit);
@@ -1531,6 +1532,13 @@ pub unsafe trait WasmRet {
     #[doc(hidden)]
     unsafe fn wrap_trampoline(ptr: *mut ValRaw, f: impl FnOnce(Self::Retptr) -> Self::Abi);
 
+    // Conversions between the raw values returned to Wasm and an array of
+    // `ValRaw`, used when recording and replaying host calls.
+    #[doc(hidden)]
+    unsafe fn abi_into_raw_for_ret(abi: Self::Abi, retptr: Self::Retptr, ptr: *mut ValRaw);
+    #[doc(hidden)]
+    unsafe fn abi_from_raw_for_ret(ptr: *mut ValRaw, retptr: Self::Retptr) -> Self::Abi;
+
     // Utilities used to convert an instance of this type to a `Result`
     // explicitly, used when wrapping async functions which always bottom-out
     // in a function that returns a trap because futures can be cancelled.
@@ -1566,6 +1574,14 @@ where
         T::abi_into_raw(f(()), ptr);
     }
 
+    unsafe fn abi_into_raw_for_ret(abi: Self::Abi, _retptr: (), ptr: *mut ValRaw) {
+        T::abi_into_raw(abi, ptr);
+    }
+
+    unsafe fn abi_from_raw_for_ret(ptr: *mut ValRaw, _retptr: ()) -> Self::Abi {
+        T::abi_from_raw(ptr)
+    }
+
     fn into_fallible(self) -> Result<T> {
         Ok(self)
     }
@@ -1606,6 +1622,14 @@ where
         T::wrap_trampoline(ptr, f)
     }
 
+    unsafe fn abi_into_raw_for_ret(abi: Self::Abi, retptr: Self::Retptr, ptr: *mut ValRaw) {
+        T::abi_into_raw_for_ret(abi, retptr, ptr)
+    }
+
+    unsafe fn abi_from_raw_for_ret(ptr: *mut ValRaw, retptr: Self::Retptr) -> Self::Abi {
+        T::abi_from_raw_for_ret(ptr, retptr)
+    }
+
     fn into_fallible(self) -> Result<T> {
         self
     }
@@ -1656,6 +1680,24 @@ macro_rules! impl_wasm_host_results {
                 )*
             }
 
+            #[allow(unused_assignments)]
+            unsafe fn abi_into_raw_for_ret(abi: Self::Abi, retptr: Self::Retptr, mut _ptr: *mut ValRaw) {
+                let ($($t,)*) = <($($t::Abi,)*) as HostAbi>::from_abi(abi, retptr);
+                $(
+                    $t::abi_into_raw($t, _ptr);
+                    _ptr = _ptr.add(1);
+                )*
+            }
+
+            #[allow(unused_assignments)]
+            unsafe fn abi_from_raw_for_ret(mut _ptr: *mut ValRaw, retptr: Self::Retptr) -> Self::Abi {
+                $(
+                    let $t = $t::abi_from_raw(_ptr);
+                    _ptr = _ptr.add(1);
+                )*
+                <($($t::Abi,)*) as HostAbi>::into_abi(($($t,)*), retptr)
+            }
+
             #[inline]
             fn into_fallible(self) -> Result<Self> {
                 Ok(self)
@@ -1692,6 +1734,10 @@ pub trait HostAbi {
     // function.
     unsafe fn into_abi(self, ptr: Self::Retptr) -> Self::Abi;
 
+    // The inverse of `into_abi`, reassembling `Self` from the value returned
+    // and the values stored into `ptr`.
+    unsafe fn from_abi(abi: Self::Abi, ptr: Self::Retptr) -> Self;
+
     // Calls `f` with a suitably sized return area and requires `f` to return
     // the raw abi value of the first element of our tuple. This will then
     // unpack the `Retptr` and assemble it with `Self::Abi` to return an
@@ -1709,6 +1755,9 @@ macro_rules! impl_host_abi {
             #[inline]
             unsafe fn into_abi(self, _ptr: Self::Retptr) -> Self::Abi {}
 
+            #[inline]
+            unsafe fn from_abi(_abi: Self::Abi, _ptr: Self::Retptr) -> Self {}
+
             #[inline]
             unsafe fn call(f: impl FnOnce(Self::Retptr) -> Self::Abi) -> Self {
                 f(())
@@ -1726,6 +1775,10 @@ macro_rules! impl_host_abi {
                 self.0
             }
 
+            unsafe fn from_abi(abi: Self::Abi, _ptr: Self::Retptr) -> Self {
+                (abi,)
+            }
+
             unsafe fn call(f: impl FnOnce(Self::Retptr) -> Self::Abi) -> Self {
                 (f(()),)
             }
@@ -1761,6 +1814,10 @@ macro_rules! impl_host_abi {
                 $t
             }
 
+            unsafe fn from_abi(abi: Self::Abi, ptr: Self::Retptr) -> Self {
+                (abi, $((*ptr).$u,)*)
+            }
+
             unsafe fn call(f: impl FnOnce(Self::Retptr) -> Self::Abi) -> Self {
                 // Create space to store all the return values and then invoke
                 // the function.
@@ -2019,45 +2076,89 @@ macro_rules! impl_into_func {
                         debug_assert!(state.is::<F>());
                         let func = &*(state as *const _ as *const F);
 
-                        let ret = {
-                            panic::catch_unwind(AssertUnwindSafe(|| {
-                                if let Err(trap) = caller.store.0.call_hook(CallHook::CallingHost) {
-                                    return R::fallible_from_error(trap);
-                                }
-                                $(let $args = $args::from_abi($args, caller.store.0);)*
-                                let r = func(
-                                    caller.sub_caller(),
-                                    $( $args, )*
-                                );
-                                if let Err(trap) = caller.store.0.call_hook(CallHook::ReturningFromHost) {
-                                    return R::fallible_from_error(trap);
+                        let call = |caller: &mut Caller<'_, T>| {
+                            let ret = {
+                                panic::catch_unwind(AssertUnwindSafe(|| {
+                                    if let Err(trap) = caller.store.0.call_hook(CallHook::CallingHost) {
+                                        return R::fallible_from_error(trap);
+                                    }
+                                    $(let $args = $args::from_abi($args, caller.store.0);)*
+                                    let r = func(
+                                        caller.sub_caller(),
+                                        $( $args, )*
+                                    );
+                                    if let Err(trap) = caller.store.0.call_hook(CallHook::ReturningFromHost) {
+                                        return R::fallible_from_error(trap);
+                                    }
+                                    r.into_fallible()
+                                }))
+                            };
+
+                            // Note that we need to be careful when dealing with traps
+                            // here. Traps are implemented with longjmp/setjmp meaning
+                            // that it's not unwinding and consequently no Rust
+                            // destructors are run. We need to be careful to ensure that
+                            // nothing on the stack needs a destructor when we exit
+                            // abnormally from this `match`, e.g. on `Err`, on
+                            // cross-store-issues, or if `Ok(Err)` is raised.
+                            match ret {
+                                Err(panic) => CallResult::Panic(panic),
+                                Ok(ret) => {
+                                    // Because the wrapped function is not `unsafe`, we
+                                    // can't assume it returned a value that is
+                                    // compatible with this store.
+                                    if !ret.compatible_with_store(caller.store.0) {
+                                        CallResult::Trap(anyhow::anyhow!("host function attempted to return cross-`Store` value to Wasm"))
+                                    } else {
+                                        match ret.into_abi_for_ret(caller.store.0, retptr) {
+                                            Ok(val) => CallResult::Ok(val),
+                                            Err(trap) => CallResult::Trap(trap.into()),
+                                        }
+                                    }
+
                                 }
-                                r.into_fallible()
-                            }))
+                            }
                         };
 
-                        // Note that we need to be careful when dealing with traps
-                        // here. Traps are implemented with longjmp/setjmp meaning
-                        // that it's not unwinding and consequently no Rust
-                        // destructors are run. We need to be careful to ensure that
-                        // nothing on the stack needs a destructor when we exit
-                        // abnormally from this `match`, e.g. on `Err`, on
-                        // cross-store-issues, or if `Ok(Err)` is raised.
-                        match ret {
-                            Err(panic) => CallResult::Panic(panic),
-                            Ok(ret) => {
-                                // Because the wrapped function is not `unsafe`, we
-                                // can't assume it returned a value that is
-                                // compatible with this store.
-                                if !ret.compatible_with_store(caller.store.0) {
-                                    CallResult::Trap(anyhow::anyhow!("host function attempted to return cross-`Store` value to Wasm"))
-                                } else {
-                                    match ret.into_abi_for_ret(caller.store.0, retptr) {
-                                        Ok(val) => CallResult::Ok(val),
-                                        Err(trap) => CallResult::Trap(trap.into()),
-                                    }
-                                }
+                        if caller.store.0.host_calls_mut().is_off() {
+                            return call(&mut caller);
+                        }
 
+                        // Host calls are being recorded or replayed, which
+                        // works in terms of `ValRaw`s like `Func::new`.
+                        let ty = R::func_type(
+                            None::<ValType>.into_iter()
+                                $(.chain(Some($args::valtype())))*
+                        );
+                        let params = ty.params().collect::<Vec<_>>();
+                        let results = ty.results().collect::<Vec<_>>();
+                        let mut values = vec![ValRaw::u64(0); params.len().max(results.len())];
+                        let mut _n = 0;
+                        $(
+                            $args::abi_into_raw($args, values.as_mut_ptr().add(_n));
+                            _n += 1;
+                        )*
+                        match crate::replay::start(caller.store.0, &params, &results, &mut values, None) {
+                            Err(trap) => CallResult::Trap(trap),
+                            Ok(crate::replay::Start::Call) => call(&mut caller),
+                            Ok(crate::replay::Start::Replayed) => {
+                                CallResult::Ok(R::abi_from_raw_for_ret(values.as_mut_ptr(), retptr))
+                            }
+                            Ok(crate::replay::Start::Record(recording)) => {
+                                let result = call(&mut caller);
+                                let error = match &result {
+                                    CallResult::Ok(abi) => {
+                                        R::abi_into_raw_for_ret(*abi, retptr, values.as_mut_ptr());
+                                        None
+                                    }
+                                    CallResult::Trap(trap) => Some(trap),
+                                    CallResult::Panic(_) => {
+                                        crate::replay::abandon(caller.store.0, recording);
+                                        return result;
+                                    }
+                                };
+                                crate::replay::finish(caller.store.0, recording, &results, &values, error);
+                                result
                             }
                         }
                     });
@@ -2189,12 +2290,26 @@ impl HostFunc {
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, &mut [ValRaw]) -> Result<()> + Send + Sync + 'static,
     ) -> Self {
+        let params = ty.params().collect::<Vec<_>>();
+        let results = ty.results().collect::<Vec<_>>();
         let func = move |caller_vmctx, values: &mut [ValRaw]| {
             Caller::<T>::with(caller_vmctx, |mut caller| {
-                caller.store.0.call_hook(CallHook::CallingHost)?;
-                let result = func(caller.sub_caller(), values)?;
-                caller.store.0.call_hook(CallHook::ReturningFromHost)?;
-                Ok(result)
+                let call = |caller: &mut Caller<'_, T>, values: &mut [ValRaw]| {
+                    caller.store.0.call_hook(CallHook::CallingHost)?;
+                    let result = func(caller.sub_caller(), values)?;
+                    caller.store.0.call_hook(CallHook::ReturningFromHost)?;
+                    Ok(result)
+                };
+                match crate::replay::start(caller.store.0, &params, &results, values, None)? {
+                    crate::replay::Start::Call => call(&mut caller, values),
+                    crate::replay::Start::Replayed => Ok(()),
+                    crate::replay::Start::Record(recording) => {
+                        let result = call(&mut caller, values);
+                        let error = result.as_ref().err();
+                        crate::replay::finish(caller.store.0, recording, &results, values, error);
+                        result
+                    }
+                }
             })
         };
         let ctx = crate::trampoline::create_array_call_function(&ty, func, engine)
@@ -1,3 +1,4 @@
+use crate::fork::Fork;
 use crate::linker::{Definition, DefinitionType};
 use crate::store::{InstanceId, StoreOpaque, Stored};
 use crate::types::matching;
@@ -11,8 +12,9 @@ use std::ptr::NonNull;
 use std::sync::Arc;
 use wasmtime_environ::{EntityType, FuncIndex, GlobalIndex, MemoryIndex, PrimaryMap, TableIndex};
 use wasmtime_runtime::{
-    Imports, InstanceAllocationRequest, StorePtr, VMContext, VMFuncRef, VMFunctionImport,
-    VMGlobalImport, VMMemoryImport, VMNativeCallFunction, VMOpaqueContext, VMTableImport,
+    Imports, InstanceAllocationRequest, ModuleRuntimeInfo, StorePtr, VMContext, VMFuncRef,
+    VMFunctionImport, VMGlobalImport, VMMemoryImport, VMNativeCallFunction, VMOpaqueContext,
+    VMTableImport,
 };
 
 /// An instantiated WebAssembly module.
@@ -250,6 +252,22 @@ impl Instance {
         store: &mut StoreOpaque,
         module: &Module,
         imports: Imports<'_>,
+    ) -> Result<(Instance, Option<FuncIndex>)> {
+        Instance::new_raw_with(store, module, &module.runtime_info(), imports)
+    }
+
+    /// Same as `Instance::new_raw`, except that the instance is initialized
+    /// as described by `runtime_info` rather than by `module`.
+    ///
+    /// # Unsafety
+    ///
+    /// In addition to the unsafety of `Instance::new_raw` this requires
+    /// `runtime_info` to describe an instance of `module`.
+    pub(crate) unsafe fn new_raw_with(
+        store: &mut StoreOpaque,
+        module: &Module,
+        runtime_info: &Arc<dyn ModuleRuntimeInfo>,
+        imports: Imports<'_>,
     ) -> Result<(Instance, Option<FuncIndex>)> {
         println!("instance new_raw");
         if !Engine::same(store.engine(), module.engine()) {
@@ -280,7 +298,7 @@ impl Instance {
                 .engine()
                 .allocator()
                 .allocate_module(InstanceAllocationRequest {
-                    runtime_info: &module.runtime_info(),
+                    runtime_info,
                     imports,
                     host_state: Box::new(Instance(instance_to_be)),
                     store: StorePtr::new(store.traitobj()),
@@ -334,12 +352,10 @@ impl Instance {
         // items from this instance into other instances should be ok when
         // those items are loaded and run we'll have all the metadata to
         // look at them.
-        instance_handle.initialize(
-            compiled_module.module(),
-            store.engine().config().features.bulk_memory,
-        )?;
+        let env_module = runtime_info.module();
+        instance_handle.initialize(env_module, store.engine().config().features.bulk_memory)?;
 
-        Ok((instance, compiled_module.module().start_func))
+        Ok((instance, env_module.start_func))
     }
 
     pub(crate) fn from_wasmtime(handle: InstanceData, store: &mut StoreOpaque) -> Instance {
@@ -370,7 +386,7 @@ impl Instance {
         self._module(store.into().0)
     }
 
-    fn _module<'a>(&self, store: &'a StoreOpaque) -> &'a Module {
+    pub(crate) fn _module<'a>(&self, store: &'a StoreOpaque) -> &'a Module {
         let InstanceData { id, .. } = store[self.0];
         store.module_for_instance(id).unwrap()
     }
@@ -544,7 +560,34 @@ impl Instance {
         self.get_export(store, name)?.into_global()
     }
 
-    #[cfg(feature = "component-model")]
+    /// Captures the current state of this instance in a new module.
+    ///
+    /// `wasm` must be the original binary of this instance's module. The
+    /// returned binary is the same module except that instantiating it starts
+    /// from the state this instance is in now: its linear memories hold their
+    /// current contents, its mutable globals hold their current values and its
+    /// start function, which has already run, is removed. This is typically
+    /// used after calling an initialization function to pre-initialize a
+    /// module ahead of time, as the Wizer tool does.
+    ///
+    /// Tables are not captured and are initialized by the module's element
+    /// segments as before. Exports are left unchanged, so calling an
+    /// initialization function again runs it again on the captured state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state of this instance can't be captured: if
+    /// its module imports a memory or a mutable global, defines a shared
+    /// memory, or if a mutable global holds a non-null reference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn snapshot(&self, mut store: impl AsContextMut, wasm: &[u8]) -> Result<Vec<u8>> {
+        let mut store = store.as_context_mut();
+        crate::snapshot::snapshot_module(&mut store, self, wasm, false)
+    }
+
     pub(crate) fn id(&self, store: &StoreOpaque) -> InstanceId {
         store[self.0].id
     }
@@ -730,6 +773,10 @@ pub struct InstancePre<T> {
     /// This is an `Arc<[T]>` for the same reason as `items`.
     func_refs: Arc<[VMFuncRef]>,
 
+    /// The state of the instance that this `InstancePre` creates forks of, if
+    /// it was created with [`InstancePre::fork`].
+    fork: Option<Arc<Fork>>,
+
     _marker: std::marker::PhantomData<fn() -> T>,
 }
 
@@ -741,6 +788,7 @@ impl<T> Clone for InstancePre<T> {
             items: self.items.clone(),
             host_funcs: self.host_funcs,
             func_refs: self.func_refs.clone(),
+            fork: self.fork.clone(),
             _marker: self._marker,
         }
     }
@@ -785,6 +833,7 @@ impl<T> InstancePre<T> {
             items: items.into(),
             host_funcs,
             func_refs: func_refs.into(),
+            fork: None,
             _marker: std::marker::PhantomData,
         })
     }
@@ -823,7 +872,18 @@ impl<T> InstancePre<T> {
         // This unsafety should be handled by the type-checking performed by the
         // constructor of `InstancePre` to assert that all the imports we're passing
         // in match the module we're instantiating.
-        unsafe { Instance::new_started(&mut store, &self.module, imports.as_ref()) }
+        unsafe {
+            match &self.fork {
+                Some(fork) => {
+                    assert!(
+                        !store.0.async_support(),
+                        "must use async instantiation when async support is enabled",
+                    );
+                    fork.instantiate(&mut store, &self.module, imports.as_ref())
+                }
+                None => Instance::new_started(&mut store, &self.module, imports.as_ref()),
+            }
+        }
     }
 
     /// Creates a new instance, running the start function asynchronously
@@ -857,7 +917,76 @@ impl<T> InstancePre<T> {
         // This unsafety should be handled by the type-checking performed by the
         // constructor of `InstancePre` to assert that all the imports we're passing
         // in match the module we're instantiating.
-        unsafe { Instance::new_started_async(&mut store, &self.module, imports.as_ref()).await }
+        unsafe {
+            match &self.fork {
+                Some(fork) => {
+                    assert!(
+                        store.0.async_support(),
+                        "must use sync instantiation when async support is disabled",
+                    );
+                    store
+                        .on_fiber(|store| fork.instantiate(store, &self.module, imports.as_ref()))
+                        .await?
+                }
+                None => {
+                    Instance::new_started_async(&mut store, &self.module, imports.as_ref()).await
+                }
+            }
+        }
+    }
+
+    /// Creates an [`InstancePre`] which instantiates forks of `instance`.
+    ///
+    /// The `instance` must be an instance of this [`InstancePre`]'s module
+    /// and its current state is captured by this method. Each instance
+    /// created by [`InstancePre::instantiate`] on the returned value starts
+    /// out with the captured state rather than the module's initial state:
+    ///
+    /// * Defined memories have the size and contents that `instance`'s
+    ///   memories had. When copy-on-write memory initialization is enabled
+    ///   (see [`Config::memory_init_cow`]) the contents are mapped
+    ///   copy-on-write from an image created here, making instantiation of a
+    ///   fork about as cheap as instantiating a module without data segments.
+    ///   Otherwise the contents are copied into each fork.
+    /// * Defined tables have the size and elements that `instance`'s tables
+    ///   had, with references to `instance`'s functions referring to the
+    ///   fork's functions instead.
+    /// * Defined mutable globals have the values that `instance`'s globals
+    ///   had.
+    ///
+    /// The start function of the module is not run for forks and element and
+    /// data segments are not applied again, even to imported tables and
+    /// memories. Forks are instantiated with the imports of this
+    /// [`InstancePre`], whose state isn't captured. Changes made to
+    /// `instance` after this method returns are not visible in forks, and
+    /// forks are independent of each other.
+    ///
+    /// This is intended for use cases such as serving each request with a
+    /// fresh copy of an instance that has already been initialized.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `instance` isn't an instance of this
+    /// [`InstancePre`]'s module, or if its state can't be captured: shared
+    /// memories, non-null `externref` values in tables and globals, and
+    /// references to functions of other instances are not supported.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `instance` isn't owned by `store`.
+    ///
+    /// [`Config::memory_init_cow`]: crate::Config::memory_init_cow
+    pub fn fork(
+        &self,
+        mut store: impl AsContextMut,
+        instance: &Instance,
+    ) -> Result<InstancePre<T>> {
+        let store = store.as_context_mut();
+        let fork = Fork::capture(store.0, &self.module, instance)?;
+        Ok(InstancePre {
+            fork: Some(Arc::new(fork)),
+            ..self.clone()
+        })
     }
 }
 
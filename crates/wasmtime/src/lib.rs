@@ -393,11 +393,14 @@ mod func;
 #[cfg(any(feature = "cranelift", feature = "winch"))]
 mod compiler;
 
+mod checkpoint;
 mod code;
 mod config;
 mod coredump;
+mod debug;
 mod engine;
 mod externals;
+mod fork;
 mod instance;
 mod limits;
 mod linker;
@@ -405,8 +408,12 @@ mod memory;
 mod module;
 mod profiling;
 mod r#ref;
+mod replay;
 mod resources;
 mod signatures;
+mod snapshot;
+#[cfg(feature = "async")]
+mod stack;
 mod store;
 mod trampoline;
 mod trap;
@@ -414,8 +421,10 @@ mod types;
 mod v128;
 mod values;
 
+pub use crate::checkpoint::Checkpoint;
 pub use crate::config::*;
 pub use crate::coredump::*;
+pub use crate::debug::{DebugFrame, DebugStep};
 pub use crate::engine::*;
 pub use crate::externals::*;
 pub use crate::func::*;
@@ -423,11 +432,14 @@ pub use crate::instance::{Instance, InstancePre};
 pub use crate::limits::*;
 pub use crate::linker::*;
 pub use crate::memory::*;
-pub use crate::module::Module;
+pub use crate::module::{Module, ModuleProfile};
 pub use crate::profiling::GuestProfiler;
 pub use crate::r#ref::ExternRef;
+pub use crate::replay::HostCallLog;
 pub use crate::resources::*;
 #[cfg(feature = "async")]
+pub use crate::stack::*;
+#[cfg(feature = "async")]
 pub use crate::store::CallHookHandler;
 pub use crate::store::{
     AsContext, AsContextMut, CallHook, Store, StoreContext, StoreContextMut, UpdateDeadline,
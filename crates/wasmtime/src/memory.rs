@@ -1,3 +1,4 @@
+use crate::replay::{CallWrites, HostCalls};
 use crate::store::{StoreData, StoreOpaque, Stored};
 use crate::trampoline::generate_memory_export;
 use crate::Trap;
@@ -7,9 +8,10 @@ use std::cell::UnsafeCell;
 use std::convert::TryFrom;
 use std::ops::Range;
 use std::slice;
+use std::sync::Mutex;
 use std::time::Instant;
 use wasmtime_environ::MemoryPlan;
-use wasmtime_runtime::{RuntimeLinearMemory, VMMemoryImport};
+use wasmtime_runtime::{RuntimeLinearMemory, VMMemoryDefinition, VMMemoryImport};
 
 pub use wasmtime_runtime::WaitResult;
 
@@ -339,12 +341,16 @@ impl Memory {
         offset: usize,
         buffer: &[u8],
     ) -> Result<(), MemoryAccessError> {
-        let mut context = store.as_context_mut();
-        self.data_mut(&mut context)
+        let store = store.as_context_mut().0;
+        unsafe { self.untracked_data_mut(store) }
             .get_mut(offset..)
             .and_then(|s| s.get_mut(..buffer.len()))
             .ok_or(MemoryAccessError { _private: () })?
             .copy_from_slice(buffer);
+        let definition = store[self.0].definition;
+        if let Some(call) = store.host_calls_mut().call_mut() {
+            call.wrote(definition, offset, buffer.len());
+        }
         Ok(())
     }
 
@@ -374,11 +380,29 @@ impl Memory {
     ///
     /// Panics if this memory doesn't belong to `store`.
     pub fn data_mut<'a, T: 'a>(&self, store: impl Into<StoreContextMut<'a, T>>) -> &'a mut [u8] {
-        unsafe {
-            let store = store.into();
-            let definition = &*store[self.0].definition;
-            debug_assert!(!self.ty(store).is_shared());
-            slice::from_raw_parts_mut(definition.base, definition.current_length())
+        let store = store.into().0;
+        self.accessed(store);
+        unsafe { self.untracked_data_mut(store) }
+    }
+
+    /// Returns this memory as a mutable slice without noting that it was
+    /// accessed while recording host calls.
+    ///
+    /// # Safety
+    ///
+    /// Whatever is written to the returned slice while recording a host call
+    /// must be noted with `CallWrites::wrote`.
+    unsafe fn untracked_data_mut<'a>(&self, store: &'a mut StoreOpaque) -> &'a mut [u8] {
+        let definition = &*store[self.0].definition;
+        debug_assert!(!self.wasmtime_ty(store.store_data()).shared);
+        slice::from_raw_parts_mut(definition.base, definition.current_length())
+    }
+
+    /// Notes that this memory was handed out whole, to be compared once the
+    /// host call being recorded, if any, returns.
+    fn accessed(&self, store: &StoreOpaque) {
+        if let Some(call) = store.host_calls().call() {
+            unsafe { call.lock().unwrap().accessed(store[self.0].definition) };
         }
     }
 
@@ -415,6 +439,39 @@ impl Memory {
         }
     }
 
+    /// Same as [`Memory::data_and_store_mut`], but for hosts which report
+    /// every region of this memory they write to with the returned
+    /// [`MemoryWrites`].
+    ///
+    /// This only makes a difference while the store is recording host calls
+    /// with [`Store::record_host_calls`](crate::Store::record_host_calls):
+    /// memory returned by [`Memory::data_mut`] may be written anywhere, so it's
+    /// copied to be compared once the host call returns, whereas only the
+    /// regions reported to [`MemoryWrites`] are logged here. Writes which
+    /// aren't reported won't be replayed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn data_and_store_mut_with_writes<'a, T: 'a>(
+        &self,
+        store: impl Into<StoreContextMut<'a, T>>,
+    ) -> (&'a mut [u8], &'a mut T, MemoryWrites<'a>) {
+        // See `data_and_store_mut` for the unsafety here, which additionally
+        // borrows the host call state of the store, disjoint from both the
+        // memory and the `T`.
+        unsafe {
+            let mut store = store.into();
+            let data = &mut *(store.data_mut() as *mut T);
+            let store = store.0;
+            let writes = MemoryWrites {
+                call: (*(store.host_calls() as *const HostCalls)).call(),
+                definition: store[self.0].definition,
+            };
+            (self.untracked_data_mut(store), data, writes)
+        }
+    }
+
     /// Returns the base pointer, in the host's address space, that the memory
     /// is located at.
     ///
@@ -425,7 +482,9 @@ impl Memory {
     ///
     /// Panics if this memory doesn't belong to `store`.
     pub fn data_ptr(&self, store: impl AsContext) -> *mut u8 {
-        unsafe { (*store.as_context()[self.0].definition).base }
+        let store = store.as_context().0;
+        self.accessed(store);
+        unsafe { (*store[self.0].definition).base }
     }
 
     /// Returns the byte length of this memory.
@@ -588,6 +647,38 @@ impl Memory {
     }
 }
 
+/// Reports the regions of a [`Memory`] written by the host, returned by
+/// [`Memory::data_and_store_mut_with_writes`].
+///
+/// Regions are only noted while a host call is being recorded; otherwise
+/// [`MemoryWrites::wrote`] does nothing.
+#[derive(Clone, Copy)]
+pub struct MemoryWrites<'a> {
+    call: Option<&'a Mutex<CallWrites>>,
+    definition: *mut VMMemoryDefinition,
+}
+
+// The definition is only used as the identity of the memory.
+unsafe impl Send for MemoryWrites<'_> {}
+unsafe impl Sync for MemoryWrites<'_> {}
+
+impl MemoryWrites<'_> {
+    /// Notes that `len` bytes of the memory were written at `offset`.
+    pub fn wrote(&self, offset: usize, len: usize) {
+        if let Some(call) = self.call {
+            call.lock().unwrap().wrote(self.definition, offset, len);
+        }
+    }
+}
+
+impl std::fmt::Debug for MemoryWrites<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryWrites")
+            .field("recording", &self.call.is_some())
+            .finish()
+    }
+}
+
 /// A linear memory. This trait provides an interface for raw memory buffers
 /// which are used by wasmtime, e.g. inside ['Memory']. Such buffers are in
 /// principle not thread safe. By implementing this trait together with
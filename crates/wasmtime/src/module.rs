@@ -16,9 +16,11 @@ use std::sync::Arc;
 use wasmparser::{Parser, ValidPayload, Validator};
 use wasmtime_environ::{
     DefinedFuncIndex, DefinedMemoryIndex, HostPtr, ModuleEnvironment, ModuleTypes, ObjectKind,
-    VMOffsets,
+    PrimaryMap, VMOffsets,
 };
 use wasmtime_jit::{CodeMemory, CompiledModule, CompiledModuleInfo};
+
+pub use wasmtime_environ::ModuleProfile;
 use wasmtime_runtime::{
     CompiledModuleId, MemoryImage, MmapVec, ModuleMemoryImages, VMArrayCallFunction,
     VMNativeCallFunction, VMSharedSignatureIndex, VMWasmCallFunction,
@@ -317,7 +319,7 @@ impl Module {
 
                     // Cache miss, compute the actual artifacts
                     |(engine, wasm)| -> Result<_> {
-                        let (mmap, info) = Module::build_artifacts(engine.0, wasm)?;
+                        let (mmap, info) = Module::build_artifacts(engine.0, wasm, None)?;
                         let code = publish_mmap(mmap)?;
                         Ok((code, info))
                     },
@@ -334,7 +336,7 @@ impl Module {
                     },
                 )?;
             } else {
-                let (mmap, info_and_types) = Module::build_artifacts(engine, binary)?;
+                let (mmap, info_and_types) = Module::build_artifacts(engine, binary, None)?;
                 let code = publish_mmap(mmap)?;
             }
         };
@@ -349,6 +351,56 @@ impl Module {
         }
     }
 
+    /// Creates a new WebAssembly `Module` like [`Module::new`], laying out its
+    /// compiled code according to an execution `profile` of the module.
+    ///
+    /// This is how a module is recompiled once a profile has been gathered by
+    /// running it, so that the paths it usually takes through its functions
+    /// run straight through, and code it never reached is moved out of the
+    /// way. The offsets of the branches in `profile` refer to the binary
+    /// encoding of the module; for the text format, that is the binary it is
+    /// converted to. See [`ModuleProfile`] for details.
+    ///
+    /// Modules compiled with a profile aren't stored in or loaded from the
+    /// compilation cache. Profiles are only used by Cranelift, and ignored
+    /// when compiling with Winch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let wasm = wat::parse_str(
+    ///     "(module (func (param i32) (if (local.get 0) (then nop))))",
+    /// )?;
+    /// // The `if` is at offset 0x1a, and its condition was always zero.
+    /// let mut profile = ModuleProfile::new();
+    /// profile.set_branch_counts(0x1a, 0, 1000);
+    /// let module = Module::new_with_profile(&engine, &wasm, &profile)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(any(feature = "cranelift", feature = "winch"))]
+    #[cfg_attr(nightlydoc, doc(cfg(any(feature = "cranelift", feature = "winch"))))]
+    pub fn new_with_profile(
+        engine: &Engine,
+        bytes: impl AsRef<[u8]>,
+        profile: &ModuleProfile,
+    ) -> Result<Module> {
+        let bytes = bytes.as_ref();
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes)?;
+        engine
+            .check_compatible_with_native_host()
+            .context("compilation settings are not compatible with the native host")?;
+
+        let (mmap, info_and_types) = Module::build_artifacts(engine, &bytes, Some(profile))?;
+        let mut code = CodeMemory::new(mmap)?;
+        code.publish()?;
+        Self::from_parts(engine, Arc::new(code), info_and_types)
+    }
+
     /// Creates a new WebAssembly `Module` from the contents of the given `file`
     /// on disk, but with assumptions that the file is from a trusted source.
     /// The file should be a binary- or text-format WebAssembly module, or a
@@ -394,10 +446,14 @@ impl Module {
     /// Additionally compilation returns an `Option` here which is always
     /// `Some`, notably compiled metadata about the module in addition to the
     /// type information found within.
+    ///
+    /// If a `profile` of the module is given, it guides the layout of the
+    /// compiled code.
     #[cfg(any(feature = "cranelift", feature = "winch"))]
     pub(crate) fn build_artifacts(
         engine: &Engine,
         wasm: &[u8],
+        profile: Option<&ModuleProfile>,
     ) -> Result<(MmapVec, Option<(CompiledModuleInfo, ModuleTypes)>)> {
         use crate::compiler::CompileInputs;
 
@@ -415,6 +471,9 @@ impl Module {
         let mut translation = ModuleEnvironment::new(tunables, &mut validator, &mut types)
             .translate(parser, wasm)
             .context("failed to parse WebAssembly module")?;
+        if let Some(profile) = profile {
+            translation.profile = profile.clone();
+        }
         let functions = mem::take(&mut translation.function_body_inputs);
         let types = types.finish();
 
@@ -917,6 +976,11 @@ impl Module {
         &self.inner.engine
     }
 
+    /// Returns whether `a` and `b` refer to the same compiled module.
+    pub fn same(a: &Module, b: &Module) -> bool {
+        Arc::ptr_eq(&a.inner, &b.inner)
+    }
+
     /// Returns a summary of the resources required to instantiate this
     /// [`Module`].
     ///
@@ -994,6 +1058,24 @@ impl Module {
         self.inner.clone()
     }
 
+    /// Returns runtime information for instances of this module which use
+    /// `module` in place of this module's type information and start from
+    /// the copy-on-write `memory_images` instead of this module's.
+    ///
+    /// `module` must describe the same functions, tables, memories and globals
+    /// as this module, only their initialization may differ.
+    pub(crate) fn runtime_info_with(
+        &self,
+        module: wasmtime_environ::Module,
+        memory_images: PrimaryMap<DefinedMemoryIndex, Option<Arc<MemoryImage>>>,
+    ) -> Arc<dyn wasmtime_runtime::ModuleRuntimeInfo> {
+        Arc::new(ReinitializedModuleInfo {
+            inner: self.inner.clone(),
+            module: Arc::new(module),
+            memory_images,
+        })
+    }
+
     pub(crate) fn module_info(&self) -> &dyn wasmtime_runtime::ModuleInfo {
         &*self.inner
     }
@@ -1261,6 +1343,65 @@ impl wasmtime_runtime::ModuleInfo for ModuleInner {
     }
 }
 
+/// The runtime information of a module whose instances are initialized
+/// differently than the module itself describes, see
+/// `Module::runtime_info_with`.
+struct ReinitializedModuleInfo {
+    inner: Arc<ModuleInner>,
+    module: Arc<wasmtime_environ::Module>,
+    memory_images: PrimaryMap<DefinedMemoryIndex, Option<Arc<MemoryImage>>>,
+}
+
+impl wasmtime_runtime::ModuleRuntimeInfo for ReinitializedModuleInfo {
+    fn module(&self) -> &Arc<wasmtime_environ::Module> {
+        &self.module
+    }
+
+    fn function(&self, index: DefinedFuncIndex) -> NonNull<VMWasmCallFunction> {
+        self.inner.function(index)
+    }
+
+    fn native_to_wasm_trampoline(
+        &self,
+        index: DefinedFuncIndex,
+    ) -> Option<NonNull<VMNativeCallFunction>> {
+        self.inner.native_to_wasm_trampoline(index)
+    }
+
+    fn array_to_wasm_trampoline(&self, index: DefinedFuncIndex) -> Option<VMArrayCallFunction> {
+        self.inner.array_to_wasm_trampoline(index)
+    }
+
+    fn wasm_to_native_trampoline(
+        &self,
+        signature: VMSharedSignatureIndex,
+    ) -> Option<NonNull<VMWasmCallFunction>> {
+        self.inner.wasm_to_native_trampoline(signature)
+    }
+
+    fn memory_image(&self, memory: DefinedMemoryIndex) -> Result<Option<&Arc<MemoryImage>>> {
+        Ok(self.memory_images.get(memory).and_then(|i| i.as_ref()))
+    }
+
+    fn unique_id(&self) -> Option<CompiledModuleId> {
+        // The pooling allocator keys reuse of memory images on this, and the
+        // images here aren't the module's.
+        None
+    }
+
+    fn wasm_data(&self) -> &[u8] {
+        self.inner.wasm_data()
+    }
+
+    fn signature_ids(&self) -> &[VMSharedSignatureIndex] {
+        self.inner.signature_ids()
+    }
+
+    fn offsets(&self) -> &VMOffsets<HostPtr> {
+        self.inner.offsets()
+    }
+}
+
 /// A barebones implementation of ModuleRuntimeInfo that is useful for
 /// cases where a purpose-built environ::Module is used and a full
 /// CompiledModule does not exist (for example, for tests or for the
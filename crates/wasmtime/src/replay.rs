@@ -0,0 +1,746 @@
+//! Recording and replaying of host calls.
+//!
+//! Given the same inputs WebAssembly executes deterministically, provided NaN
+//! canonicalization and deterministic relaxed SIMD are enabled, so the only
+//! way for the runs of a guest to diverge is through the host functions it
+//! calls. While recording, the outcome of each host call made by wasm is
+//! logged: its results along with whatever it changed in the memories and
+//! mutable globals of the store. While replaying, host functions aren't called
+//! at all and their logged outcomes are applied instead.
+//!
+//! Only the host calls made directly by wasm are logged; anything a host
+//! function does by calling back into wasm is part of its outcome.
+//!
+//! Memories aren't copied to find what a host call changed. Instead the
+//! regions written through the accessors which know what they write, such as
+//! [`Memory::write`](crate::Memory::write),
+//! [`MemoryWrites`](crate::MemoryWrites) and the lowering of component
+//! values, are noted as they're written. A memory is only copied, so that it
+//! can be compared at the end of the call, once it's handed out whole to the
+//! host with [`Memory::data_mut`](crate::Memory::data_mut) and the like, or
+//! once the host calls back into wasm. The exception is the guest's `realloc`
+//! called to lower the results of a component host call: it's called again
+//! when replaying, after the host's writes which preceded it are applied,
+//! rather than logging what it changed.
+
+use crate::store::StoreOpaque;
+use crate::{Trap, ValRaw, ValType};
+use anyhow::{anyhow, bail, Context, Error, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::sync::Mutex;
+use wasmtime_environ::{WasmType, WASM_PAGE_SIZE};
+use wasmtime_runtime::{ExportGlobal, ExportMemory, VMGlobalDefinition, VMMemoryDefinition};
+
+/// The header at the start of every serialized host call log.
+const MAGIC: &[u8] = b"\0wasmtime-host-calls";
+
+/// The version of the format which follows [`MAGIC`], bumped whenever the
+/// layout of [`HostCallLog`] changes.
+const VERSION: u32 = 2;
+
+/// The size of the chunks in which copied memories are compared.
+const CHUNK_SIZE: usize = 64;
+
+/// The host calls made by the guests of a store.
+///
+/// Logs are recorded with
+/// [`Store::record_host_calls`](crate::Store::record_host_calls) and replayed
+/// with [`Store::replay_host_calls`](crate::Store::replay_host_calls). They can
+/// be converted to and from bytes with [`HostCallLog::serialize`] and
+/// [`HostCallLog::deserialize`] to replay them in another process.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct HostCallLog {
+    calls: Vec<HostCall>,
+}
+
+/// The outcome of a single host call.
+#[derive(Clone, Serialize, Deserialize)]
+struct HostCall {
+    /// The bits of the arguments of the call, used to detect when a replay
+    /// diverges from the recording.
+    params: Vec<u128>,
+    outcome: Outcome,
+    /// The calls to the guest's `realloc` made to lower the results of a
+    /// component host call, in order.
+    reallocs: Vec<Realloc>,
+    /// The changes made to memories, on top of those of `reallocs`.
+    memories: Vec<MemoryChange>,
+    /// The mutable globals changed by the call and their new bits.
+    globals: Vec<(u32, u128)>,
+}
+
+/// What a host call returned.
+#[derive(Clone, Serialize, Deserialize)]
+enum Outcome {
+    /// The bits of the results of the call.
+    Results(Vec<u128>),
+    /// The call raised a trap.
+    Trap(Trap),
+    /// The call returned an error carrying an exit status, as recognized by
+    /// [`Store::host_call_exits`](crate::Store::host_call_exits).
+    Exit(i32),
+    /// The call returned any other error, logged as its message.
+    Error(String),
+}
+
+/// A call to the guest's `realloc` made while lowering the results of a
+/// component host call.
+#[derive(Clone, Serialize, Deserialize)]
+struct Realloc {
+    /// What the host wrote to memory before `realloc` was called.
+    memories: Vec<MemoryChange>,
+    /// The `old`, `old_size`, `old_align` and `new_size` arguments.
+    params: [u32; 4],
+    /// The pointer returned by `realloc`, or `None` if it failed.
+    result: Option<u32>,
+}
+
+/// The changes made by a host call to one memory of the store.
+#[derive(Clone, Serialize, Deserialize)]
+struct MemoryChange {
+    memory: u32,
+    /// The size of the memory, in bytes, after the changes.
+    size: u64,
+    writes: Vec<(u64, Vec<u8>)>,
+}
+
+impl HostCallLog {
+    /// Returns the number of host calls in this log.
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Returns whether this log has no host calls.
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Serializes this log into bytes which can later be passed to
+    /// [`HostCallLog::deserialize`].
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self)?;
+        Ok(bytes)
+    }
+
+    /// Deserializes a log produced by [`HostCallLog::serialize`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` isn't a host call log or was produced by an
+    /// incompatible version of Wasmtime.
+    pub fn deserialize(bytes: &[u8]) -> Result<HostCallLog> {
+        let bytes = bytes
+            .strip_prefix(MAGIC)
+            .context("bytes are not a serialized host call log")?;
+        if bytes.get(..4) != Some(&VERSION.to_le_bytes()[..]) {
+            bail!("the host call log was serialized by an incompatible version of Wasmtime");
+        }
+        bincode::deserialize(&bytes[4..]).context("failed to deserialize host call log")
+    }
+}
+
+impl fmt::Debug for HostCallLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostCallLog")
+            .field("calls", &self.calls.len())
+            .finish()
+    }
+}
+
+/// Whether a store is recording or replaying host calls.
+#[derive(Default)]
+pub(crate) enum HostCalls {
+    #[default]
+    Off,
+    Record {
+        log: HostCallLog,
+        /// What the host call which is being recorded has written so far, if
+        /// one is in progress.
+        ///
+        /// This is behind a lock for [`MemoryWrites`](crate::MemoryWrites),
+        /// which is used while the rest of the store is borrowed.
+        call: Option<Mutex<CallWrites>>,
+    },
+    Replay {
+        log: HostCallLog,
+        /// The index in `log` of the next call to replay.
+        next: usize,
+        /// Whether the `realloc`s of a host call are being made again, during
+        /// which host calls are made as usual, as they were when recording.
+        in_call: bool,
+    },
+}
+
+/// What a host call which is being recorded has written to memory so far.
+#[derive(Default)]
+pub(crate) struct CallWrites {
+    /// The regions written through accessors which report them, along with
+    /// the address of the definition of their memory.
+    regions: Vec<(usize, Range<usize>)>,
+    /// Copies of memories, by the address of their definition, taken when
+    /// they were first handed out whole.
+    snapshots: Vec<(usize, Vec<u8>)>,
+    reallocs: Vec<Realloc>,
+    /// Whether the guest's `realloc` is being called to lower results.
+    in_realloc: bool,
+    /// Whether the host called back into wasm, which may have changed any
+    /// memory, other than to call `realloc`.
+    called_back: bool,
+}
+
+impl CallWrites {
+    /// Notes that `len` bytes at `offset` of the memory defined at
+    /// `definition` were written.
+    pub(crate) fn wrote(&mut self, definition: *mut VMMemoryDefinition, offset: usize, len: usize) {
+        let memory = definition as usize;
+        let end = offset.saturating_add(len);
+        if let Some((last_memory, last)) = self.regions.last_mut() {
+            if *last_memory == memory && last.end == offset {
+                last.end = end;
+                return;
+            }
+        }
+        self.regions.push((memory, offset..end));
+    }
+
+    /// Notes that the memory defined at `definition` was handed out whole, so
+    /// that it's copied unless it already was.
+    ///
+    /// # Safety
+    ///
+    /// `definition` must be the valid definition of a memory of the store.
+    pub(crate) unsafe fn accessed(&mut self, definition: *mut VMMemoryDefinition) {
+        let memory = definition as usize;
+        if !self.snapshots.iter().any(|(m, _)| *m == memory) {
+            let definition = &*definition;
+            let data = std::slice::from_raw_parts(definition.base, definition.current_length());
+            self.snapshots.push((memory, data.to_vec()));
+        }
+    }
+}
+
+impl HostCalls {
+    pub(crate) fn is_off(&self) -> bool {
+        matches!(self, HostCalls::Off)
+    }
+
+    /// Returns what the host call being recorded has written, if one is in
+    /// progress.
+    pub(crate) fn call(&self) -> Option<&Mutex<CallWrites>> {
+        match self {
+            HostCalls::Record { call, .. } => call.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Same as [`HostCalls::call`], without locking.
+    pub(crate) fn call_mut(&mut self) -> Option<&mut CallWrites> {
+        match self {
+            HostCalls::Record {
+                call: Some(call), ..
+            } => Some(call.get_mut().unwrap()),
+            _ => None,
+        }
+    }
+
+    /// Stops recording or replaying, returning the calls recorded so far or
+    /// the calls which have yet to be replayed.
+    pub(crate) fn take_log(&mut self) -> Option<HostCallLog> {
+        match std::mem::take(self) {
+            HostCalls::Off => None,
+            HostCalls::Record { log, .. } => Some(log),
+            HostCalls::Replay { mut log, next, .. } => {
+                log.calls.drain(..next);
+                Some(log)
+            }
+        }
+    }
+}
+
+/// How a host call is to be made, as decided by [`start`].
+pub(crate) enum Start {
+    /// Call the host function as usual.
+    Call,
+    /// Call the host function and then pass its outcome and this state to
+    /// [`finish`] to record it.
+    Record(Recording),
+    /// The call was replayed; its results have been written.
+    Replayed,
+}
+
+/// The state of the store before a host call which is being recorded.
+pub(crate) struct Recording {
+    params: Vec<u128>,
+    /// The size of each memory.
+    sizes: Vec<usize>,
+    globals: Vec<Option<u128>>,
+}
+
+/// Recognizes the exit status carried by an error and creates the error for
+/// an exit status, see [`Store::host_call_exits`](crate::Store::host_call_exits).
+pub(crate) type HostCallExits = (fn(&Error) -> Option<i32>, fn(i32) -> Error);
+
+/// Calls the guest's `realloc` with the given arguments to replay a component
+/// host call.
+pub(crate) type ReplayRealloc<'a> = &'a mut dyn FnMut(&mut StoreOpaque, [u32; 4]) -> Result<u32>;
+
+/// Starts a host call with the arguments `values[..params.len()]`.
+///
+/// When the call is replayed its results are written to
+/// `values[..results.len()]` and its error, if it returned one, is returned.
+/// Component host calls pass `realloc` to call the guest's `realloc` again.
+pub(crate) fn start(
+    store: &mut StoreOpaque,
+    params: &[ValType],
+    results: &[ValType],
+    values: &mut [ValRaw],
+    realloc: Option<ReplayRealloc<'_>>,
+) -> Result<Start> {
+    match store.host_calls_mut() {
+        HostCalls::Off
+        | HostCalls::Record { call: Some(_), .. }
+        | HostCalls::Replay { in_call: true, .. } => return Ok(Start::Call),
+        HostCalls::Record { call: None, .. } => {
+            if results.iter().any(|ty| ty.is_ref()) {
+                bail!("host functions returning references can't be recorded");
+            }
+        }
+        HostCalls::Replay { .. } => {
+            if results.iter().any(|ty| ty.is_ref()) {
+                bail!("host functions returning references can't be replayed");
+            }
+            return replay(store, params, results, values, realloc).map(|()| Start::Replayed);
+        }
+    }
+
+    let (memories, globals) = store.defined_memories_and_globals();
+    if memories.iter().any(|memory| memory.memory.memory.shared) {
+        bail!("host calls can't be recorded in stores with shared memories");
+    }
+    let recording = Recording {
+        params: params
+            .iter()
+            .zip(values.iter())
+            .map(|(ty, val)| to_bits(ty, val))
+            .collect(),
+        sizes: memories
+            .iter()
+            .map(|memory| unsafe { (*memory.definition).current_length() })
+            .collect(),
+        globals: globals
+            .iter()
+            .map(|global| unsafe { global_bits(global) })
+            .collect(),
+    };
+    if let HostCalls::Record { call, .. } = store.host_calls_mut() {
+        *call = Some(Mutex::default());
+    }
+    Ok(Start::Record(recording))
+}
+
+/// Records a host call once it's been made, `values[..results.len()]` holding
+/// its results unless it returned `error`.
+pub(crate) fn finish(
+    store: &mut StoreOpaque,
+    recording: Recording,
+    results: &[ValType],
+    values: &[ValRaw],
+    error: Option<&Error>,
+) {
+    let outcome = match error {
+        Some(e) => match store.host_call_exits().and_then(|(status, _)| status(e)) {
+            Some(status) => Outcome::Exit(status),
+            None => match e.downcast_ref::<Trap>() {
+                Some(trap) => Outcome::Trap(*trap),
+                None => Outcome::Error(format!("{e:#}")),
+            },
+        },
+        None => Outcome::Results(
+            results
+                .iter()
+                .zip(values.iter())
+                .map(|(ty, val)| to_bits(ty, val))
+                .collect(),
+        ),
+    };
+    let writes = match store.host_calls_mut() {
+        HostCalls::Record { call, .. } => call.take().map(|c| c.into_inner().unwrap()),
+        _ => None,
+    }
+    .unwrap_or_default();
+
+    let (memories, globals) = store.defined_memories_and_globals();
+    let mut call = HostCall {
+        params: recording.params,
+        outcome,
+        reallocs: writes.reallocs,
+        memories: Vec::new(),
+        globals: Vec::new(),
+    };
+    for (i, memory) in memories.iter().enumerate() {
+        let data = unsafe { memory_data(memory) };
+        let mut ranges = regions_of(memory, &writes.regions);
+        let snapshot = writes
+            .snapshots
+            .iter()
+            .find(|(m, _)| *m == memory.definition as usize);
+        match (snapshot, recording.sizes.get(i)) {
+            (Some((_, before)), _) => ranges.extend(diff(before, data)),
+            // Memories created by the call are compared with their initial
+            // zeroes.
+            (None, None) => ranges.extend(diff(&[], data)),
+            (None, Some(_)) => {}
+        }
+        let writes = coalesce(ranges, data);
+        if !writes.is_empty() || recording.sizes.get(i) != Some(&data.len()) {
+            call.memories.push(MemoryChange {
+                memory: i as u32,
+                size: data.len() as u64,
+                writes,
+            });
+        }
+    }
+    for (i, global) in globals.iter().enumerate() {
+        let after = unsafe { global_bits(global) };
+        if let Some(bits) = after {
+            if recording.globals.get(i) != Some(&after) {
+                call.globals.push((i as u32, bits));
+            }
+        }
+    }
+
+    if let HostCalls::Record { log, .. } = store.host_calls_mut() {
+        log.calls.push(call);
+    }
+}
+
+/// Ends a host call which is being recorded without recording it, because
+/// the host function panicked.
+pub(crate) fn abandon(store: &mut StoreOpaque, _recording: Recording) {
+    if let HostCalls::Record { call, .. } = store.host_calls_mut() {
+        *call = None;
+    }
+}
+
+/// Called whenever wasm is entered, which while a host call is being recorded
+/// means that it's calling back into wasm.
+pub(crate) fn entering_wasm(store: &mut StoreOpaque) {
+    match store.host_calls_mut().call_mut() {
+        Some(call) if !call.in_realloc && !call.called_back => call.called_back = true,
+        _ => return,
+    }
+    let (memories, _) = store.defined_memories_and_globals();
+    let call = store.host_calls_mut().call_mut().unwrap();
+    for memory in memories.iter() {
+        unsafe { call.accessed(memory.definition) };
+    }
+}
+
+/// Called before the guest's `realloc` is called with `params` to lower the
+/// results of a component host call, returning whether the call is logged and
+/// [`finish_realloc`] must be called.
+///
+/// `realloc` isn't logged when the host already called back into wasm, in
+/// which case all memories were copied, or when `realloc` is reentered.
+#[cfg(feature = "component-model")]
+pub(crate) fn start_realloc(store: &mut StoreOpaque, params: [u32; 4]) -> bool {
+    let regions = match store.host_calls_mut().call_mut() {
+        Some(call) if !call.in_realloc && !call.called_back => {
+            call.in_realloc = true;
+            std::mem::take(&mut call.regions)
+        }
+        _ => return false,
+    };
+
+    // What the host wrote so far is logged now, as `realloc` may read it.
+    let (memories, _) = store.defined_memories_and_globals();
+    let changes = memories
+        .iter()
+        .enumerate()
+        .filter_map(|(i, memory)| {
+            let data = unsafe { memory_data(memory) };
+            let writes = coalesce(regions_of(memory, &regions), data);
+            if writes.is_empty() {
+                return None;
+            }
+            Some(MemoryChange {
+                memory: i as u32,
+                size: data.len() as u64,
+                writes,
+            })
+        })
+        .collect();
+    let call = store.host_calls_mut().call_mut().unwrap();
+    call.reallocs.push(Realloc {
+        memories: changes,
+        params,
+        result: None,
+    });
+    true
+}
+
+/// Called once a `realloc` for which [`start_realloc`] returned `true`
+/// returned `result`, or failed.
+#[cfg(feature = "component-model")]
+pub(crate) fn finish_realloc(store: &mut StoreOpaque, result: Option<u32>) {
+    if let Some(call) = store.host_calls_mut().call_mut() {
+        call.in_realloc = false;
+        if let Some(realloc) = call.reallocs.last_mut() {
+            realloc.result = result;
+        }
+    }
+}
+
+fn replay(
+    store: &mut StoreOpaque,
+    params: &[ValType],
+    results: &[ValType],
+    values: &mut [ValRaw],
+    realloc: Option<ReplayRealloc<'_>>,
+) -> Result<()> {
+    let (index, call) = match store.host_calls_mut() {
+        HostCalls::Replay { log, next, .. } => {
+            let index = *next;
+            let call = log
+                .calls
+                .get(index)
+                .with_context(|| format!("host call {index} is past the end of the log"))?
+                .clone();
+            *next += 1;
+            (index, call)
+        }
+        _ => unreachable!(),
+    };
+
+    let diverged = call.params.len() != params.len()
+        || params
+            .iter()
+            .zip(values.iter())
+            .zip(call.params.iter())
+            .any(|((ty, val), bits)| to_bits(ty, val) != *bits);
+    if diverged {
+        bail!("host call {index} diverged from the log: its arguments differ");
+    }
+
+    if !call.reallocs.is_empty() {
+        let realloc = realloc.with_context(|| {
+            format!("host call {index} diverged from the log: it called `realloc`")
+        })?;
+        set_replay_in_call(store, true);
+        let result = replay_reallocs(store, index, &call.reallocs, realloc);
+        set_replay_in_call(store, false);
+        result?;
+    }
+    apply(store, index, &call.memories)?;
+    let (_, globals) = store.defined_memories_and_globals();
+    for (i, bits) in call.globals.iter() {
+        let global = globals.get(*i as usize).with_context(|| {
+            format!("host call {index} changed a global missing from the store")
+        })?;
+        unsafe { set_global_bits(global, *bits) };
+    }
+
+    match call.outcome {
+        Outcome::Results(bits) if bits.len() == results.len() => {
+            for ((ty, bits), val) in results.iter().zip(bits).zip(values.iter_mut()) {
+                *val = from_bits(ty, bits);
+            }
+            Ok(())
+        }
+        Outcome::Results(_) => bail!("host call {index} diverged from the log: its results differ"),
+        Outcome::Trap(trap) => Err(trap.into()),
+        Outcome::Exit(status) => match store.host_call_exits() {
+            Some((_, exit)) => Err(exit(status)),
+            None => bail!("host call {index} exited with status {status}"),
+        },
+        Outcome::Error(message) => Err(anyhow!(message)),
+    }
+}
+
+fn replay_reallocs(
+    store: &mut StoreOpaque,
+    index: usize,
+    reallocs: &[Realloc],
+    realloc: ReplayRealloc<'_>,
+) -> Result<()> {
+    for call in reallocs {
+        apply(store, index, &call.memories)?;
+        if let Some(expected) = call.result {
+            if realloc(store, call.params)? != expected {
+                bail!(
+                    "host call {index} diverged from the log: `realloc` returned another pointer"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn set_replay_in_call(store: &mut StoreOpaque, value: bool) {
+    if let HostCalls::Replay { in_call, .. } = store.host_calls_mut() {
+        *in_call = value;
+    }
+}
+
+/// Applies the `changes` to memories logged for host call `index`.
+fn apply(store: &mut StoreOpaque, index: usize, changes: &[MemoryChange]) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let (memories, _) = store.defined_memories_and_globals();
+    for change in changes {
+        let memory = memories.get(change.memory as usize).with_context(|| {
+            format!("host call {index} changed a memory missing from the store")
+        })?;
+        unsafe {
+            grow_memory(store, memory, change.size)
+                .with_context(|| format!("failed to replay host call {index}"))?;
+            let definition = &*memory.definition;
+            let data = std::slice::from_raw_parts_mut(definition.base, definition.current_length());
+            for (offset, bytes) in change.writes.iter() {
+                usize::try_from(*offset)
+                    .ok()
+                    .and_then(|offset| data.get_mut(offset..))
+                    .and_then(|data| data.get_mut(..bytes.len()))
+                    .with_context(|| format!("host call {index} wrote out of bounds"))?
+                    .copy_from_slice(bytes);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the ranges of `regions` which were written to `memory`.
+fn regions_of(memory: &ExportMemory, regions: &[(usize, Range<usize>)]) -> Vec<Range<usize>> {
+    regions
+        .iter()
+        .filter(|(m, _)| *m == memory.definition as usize)
+        .map(|(_, range)| range.clone())
+        .collect()
+}
+
+/// Merges the overlapping and adjacent `ranges` of `data`, returning their
+/// contents.
+fn coalesce(mut ranges: Vec<Range<usize>>, data: &[u8]) -> Vec<(u64, Vec<u8>)> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        let range = range.start.min(data.len())..range.end.min(data.len());
+        if range.is_empty() {
+            continue;
+        }
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+        .into_iter()
+        .map(|range| (range.start as u64, data[range].to_vec()))
+        .collect()
+}
+
+/// Returns the runs of `CHUNK_SIZE` chunks which differ between `before` and
+/// `after`, where `after` may be larger than `before` if the memory grew.
+fn diff(before: &[u8], after: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut run = None;
+    for start in (0..after.len()).step_by(CHUNK_SIZE) {
+        let end = after.len().min(start + CHUNK_SIZE);
+        let changed = match before.get(start..end) {
+            Some(old) => old != &after[start..end],
+            None => after[start..end].iter().any(|byte| *byte != 0),
+        };
+        match (changed, run) {
+            (true, None) => run = Some(start),
+            (false, Some(run_start)) => {
+                ranges.push(run_start..start);
+                run = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(run_start) = run {
+        ranges.push(run_start..after.len());
+    }
+    ranges
+}
+
+fn to_bits(ty: &ValType, val: &ValRaw) -> u128 {
+    match ty {
+        ValType::I32 => val.get_u32().into(),
+        ValType::I64 => val.get_u64().into(),
+        ValType::F32 => val.get_f32().into(),
+        ValType::F64 => val.get_f64().into(),
+        ValType::V128 => val.get_v128(),
+        // References can't be compared across runs.
+        ValType::ExternRef | ValType::FuncRef => 0,
+    }
+}
+
+fn from_bits(ty: &ValType, bits: u128) -> ValRaw {
+    match ty {
+        ValType::I32 => ValRaw::u32(bits as u32),
+        ValType::I64 => ValRaw::u64(bits as u64),
+        ValType::F32 => ValRaw::f32(bits as u32),
+        ValType::F64 => ValRaw::f64(bits as u64),
+        ValType::V128 => ValRaw::v128(bits),
+        ValType::ExternRef | ValType::FuncRef => unreachable!(),
+    }
+}
+
+/// Returns the contents of `memory`, which isn't shared.
+unsafe fn memory_data(memory: &ExportMemory) -> &[u8] {
+    let definition = &*memory.definition;
+    std::slice::from_raw_parts(definition.base, definition.current_length())
+}
+
+/// Grows `memory` to `size` bytes if it's smaller.
+unsafe fn grow_memory(store: &mut StoreOpaque, memory: &ExportMemory, size: u64) -> Result<()> {
+    let current = memory_data(memory).len() as u64;
+    if size <= current {
+        return Ok(());
+    }
+    let delta = (size - current) / u64::from(WASM_PAGE_SIZE);
+    let mem = wasmtime_runtime::Instance::from_vmctx(memory.vmctx, |handle| {
+        handle.get_defined_memory(memory.index)
+    });
+    match (*mem).grow(delta, Some(&mut *store.traitobj()))? {
+        Some(_) => {
+            *memory.definition = (*mem).vmmemory();
+            Ok(())
+        }
+        None => bail!("failed to grow memory by `{delta}`"),
+    }
+}
+
+/// Returns the bits of `global`, or `None` if it's immutable or holds a
+/// reference.
+unsafe fn global_bits(global: &ExportGlobal) -> Option<u128> {
+    if !global.global.mutability {
+        return None;
+    }
+    let definition: &VMGlobalDefinition = &*global.definition;
+    Some(match global.global.wasm_ty {
+        WasmType::I32 => (*definition.as_u32()).into(),
+        WasmType::I64 => (*definition.as_u64()).into(),
+        WasmType::F32 => (*definition.as_u32()).into(),
+        WasmType::F64 => (*definition.as_u64()).into(),
+        WasmType::V128 => *definition.as_u128(),
+        WasmType::Ref(_) => return None,
+    })
+}
+
+unsafe fn set_global_bits(global: &ExportGlobal, bits: u128) {
+    let definition = &mut *global.definition;
+    match global.global.wasm_ty {
+        WasmType::I32 | WasmType::F32 => *definition.as_u32_mut() = bits as u32,
+        WasmType::I64 | WasmType::F64 => *definition.as_u64_mut() = bits as u64,
+        WasmType::V128 => *definition.as_u128_mut() = bits,
+        WasmType::Ref(_) => {}
+    }
+}
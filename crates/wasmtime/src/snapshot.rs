@@ -0,0 +1,296 @@
+//! Pre-initialization snapshots of instances.
+//!
+//! A snapshot rewrites the original binary of a module so that instantiating
+//! the new module produces the state that an instance of the original module
+//! had when the snapshot was taken. This is the same transformation that the
+//! external Wizer tool performs: linear memories are captured as active data
+//! segments, mutable globals get their current values as initializers, and
+//! the start function is removed since its effects are already captured.
+//!
+//! Components are snapshotted by snapshotting each of their core instances
+//! and replacing the corresponding core modules in the component's binary.
+
+use crate::{AsContext, Instance, Memory, Mutability, StoreContextMut, Val};
+use anyhow::{bail, Context, Result};
+use wasm_encoder::{ConstExpr, Encode, RawSection, SectionId};
+use wasmparser::{DataKind, Parser, Payload};
+
+/// The size of the chunks in which memory is captured into data segments.
+///
+/// As with core dumps this balances the number of data segments against the
+/// size of the snapshot: each chunk becomes one segment with runs of zeroes at
+/// its start and end trimmed, and chunks which are entirely zero are omitted.
+const CHUNK_SIZE: usize = 4096;
+
+/// Rewrites `wasm`, the original binary of `instance`'s module, to capture the
+/// current state of `instance`.
+///
+/// If `allow_imported_state` is false then instances which import memories or
+/// mutable globals are rejected since their state would not be captured. The
+/// core instances of a component set it since the state they import is
+/// captured by the instance which defines it.
+pub(crate) fn snapshot_module<T>(
+    store: &mut StoreContextMut<'_, T>,
+    instance: &Instance,
+    wasm: &[u8],
+    allow_imported_state: bool,
+) -> Result<Vec<u8>> {
+    let module = instance.module(store.as_context()).clone();
+    let env = module.env_module();
+    if !allow_imported_state {
+        if env.num_imported_memories > 0 {
+            bail!("cannot snapshot an instance of a module which imports a memory");
+        }
+        let imported_globals = env.globals.values().take(env.num_imported_globals);
+        if imported_globals.clone().any(|g| g.mutability) {
+            bail!("cannot snapshot an instance of a module which imports a mutable global");
+        }
+    }
+
+    let memories = instance
+        .all_memories(&mut store.0)
+        .skip(env.num_imported_memories)
+        .map(|(_, memory)| memory)
+        .collect::<Vec<_>>();
+    let globals = instance
+        .all_globals(&mut store.0)
+        .skip(env.num_imported_globals)
+        .map(|(_, global)| global)
+        .collect::<Vec<_>>();
+
+    let payloads = Parser::new(0)
+        .parse_all(wasm)
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to parse the module to snapshot")?;
+
+    let mut defined_memories = 0;
+    let mut defined_globals = 0;
+    let mut has_data_count = false;
+    for payload in payloads.iter() {
+        match payload {
+            Payload::MemorySection(s) => defined_memories = s.count() as usize,
+            Payload::GlobalSection(s) => defined_globals = s.count() as usize,
+            Payload::DataCountSection { .. } => has_data_count = true,
+            _ => {}
+        }
+    }
+    if defined_memories != memories.len() || defined_globals != globals.len() {
+        bail!("the module to snapshot is not the module of the instance");
+    }
+
+    // Original data segments come first so that the indices used by
+    // `memory.init` and `data.drop` keep referring to the same segments. An
+    // active segment behaves like an empty passive segment once it has been
+    // applied, so that is what it becomes. Without a data count section no
+    // instruction may refer to a segment and active segments are simply
+    // dropped.
+    let mut data = wasm_encoder::DataSection::new();
+    let mut has_data = false;
+    for payload in payloads.iter() {
+        if let Payload::DataSection(s) = payload {
+            has_data = true;
+            for segment in s.clone() {
+                let segment = segment?;
+                match segment.kind {
+                    DataKind::Passive => {
+                        data.passive(segment.data.iter().copied());
+                    }
+                    DataKind::Active { .. } if has_data_count => {
+                        data.passive([]);
+                    }
+                    DataKind::Active { .. } => {}
+                }
+            }
+        }
+    }
+    for (i, memory) in memories.iter().enumerate() {
+        snapshot_memory(
+            store,
+            memory,
+            (env.num_imported_memories + i) as u32,
+            &mut data,
+        )?;
+    }
+
+    let mut out = wasm_encoder::Module::new();
+    let mut code_seen = false;
+    let mut data_emitted = !has_data && data.is_empty();
+    for payload in payloads.iter() {
+        // The data section comes after the code section, so if the original
+        // module had none it's added before whatever follows the code.
+        let is_data = matches!(payload, Payload::DataSection(_));
+        if code_seen && !data_emitted && !is_data && payload.as_section().is_some() {
+            out.section(&data);
+            data_emitted = true;
+        }
+
+        match payload {
+            Payload::MemorySection(s) => {
+                let mut section = wasm_encoder::MemorySection::new();
+                for (ty, memory) in s.clone().into_iter().zip(&memories) {
+                    let ty = ty?;
+                    section.memory(wasm_encoder::MemoryType {
+                        minimum: memory.size(&*store),
+                        maximum: ty.maximum,
+                        memory64: ty.memory64,
+                        shared: ty.shared,
+                    });
+                }
+                out.section(&section);
+            }
+            Payload::GlobalSection(s) => {
+                let mut section = Vec::new();
+                for (item, global) in s.clone().into_iter_with_offsets().zip(&globals) {
+                    let (offset, item) = item?;
+                    let mut init = item.init_expr.get_binary_reader();
+                    let init_offset = init.original_position();
+
+                    // Keep the encoding of the global's type as-is.
+                    section.extend_from_slice(&wasm[offset..init_offset]);
+                    if item.ty.mutable {
+                        global_init(store, global)?.encode(&mut section);
+                    } else {
+                        // Immutable globals can't have changed, and their
+                        // initializer may refer to an imported global.
+                        section.extend_from_slice(init.read_bytes(init.bytes_remaining())?);
+                    }
+                }
+                let mut contents = Vec::new();
+                s.count().encode(&mut contents);
+                contents.extend_from_slice(&section);
+                out.section(&RawSection {
+                    id: SectionId::Global.into(),
+                    data: &contents,
+                });
+            }
+            Payload::StartSection { .. } => {}
+            Payload::DataCountSection { .. } => {
+                out.section(&wasm_encoder::DataCountSection { count: data.len() });
+            }
+            Payload::DataSection(_) => {
+                out.section(&data);
+                data_emitted = true;
+            }
+            Payload::CodeSectionStart { range, .. } => {
+                out.section(&RawSection {
+                    id: SectionId::Code.into(),
+                    data: &wasm[range.clone()],
+                });
+                code_seen = true;
+            }
+            Payload::End(_) => {
+                if !data_emitted {
+                    out.section(&data);
+                    data_emitted = true;
+                }
+            }
+            payload => {
+                if let Some((id, range)) = payload.as_section() {
+                    out.section(&RawSection {
+                        id,
+                        data: &wasm[range],
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(out.finish())
+}
+
+/// Appends active data segments capturing the contents of `memory`.
+fn snapshot_memory<T>(
+    store: &StoreContextMut<'_, T>,
+    memory: &Memory,
+    index: u32,
+    data: &mut wasm_encoder::DataSection,
+) -> Result<()> {
+    let ty = memory.ty(store);
+    if ty.is_shared() {
+        bail!("cannot snapshot an instance with a shared memory");
+    }
+    for (i, chunk) in memory.data(store).chunks(CHUNK_SIZE).enumerate() {
+        if let Some(start) = chunk.iter().position(|byte| *byte != 0) {
+            let end = chunk.iter().rposition(|byte| *byte != 0).unwrap() + 1;
+            let offset = i * CHUNK_SIZE + start;
+            let offset = if ty.is_64() {
+                ConstExpr::i64_const(offset as i64)
+            } else {
+                ConstExpr::i32_const(offset as i32)
+            };
+            data.active(index, &offset, chunk[start..end].iter().copied());
+        }
+    }
+    Ok(())
+}
+
+/// Returns an initializer for a global which produces its current value.
+fn global_init<T>(store: &mut StoreContextMut<'_, T>, global: &crate::Global) -> Result<ConstExpr> {
+    debug_assert!(matches!(global.ty(&*store).mutability(), Mutability::Var));
+    Ok(match global.get(&mut *store) {
+        Val::I32(x) => ConstExpr::i32_const(x),
+        Val::I64(x) => ConstExpr::i64_const(x),
+        Val::F32(x) => ConstExpr::f32_const(f32::from_bits(x)),
+        Val::F64(x) => ConstExpr::f64_const(f64::from_bits(x)),
+        Val::V128(x) => ConstExpr::v128_const(x.as_u128() as i128),
+        Val::FuncRef(None) => ConstExpr::ref_null(wasm_encoder::HeapType::Func),
+        Val::ExternRef(None) => ConstExpr::ref_null(wasm_encoder::HeapType::Extern),
+        Val::FuncRef(Some(_)) | Val::ExternRef(Some(_)) => {
+            bail!("cannot snapshot a global which holds a non-null reference")
+        }
+    })
+}
+
+/// Rewrites the component `wasm`, replacing each of the core modules it
+/// contains, in the order in which they appear, with the result of `module`.
+#[cfg(feature = "component-model")]
+pub(crate) fn rewrite_component(
+    wasm: &[u8],
+    module: &mut dyn FnMut(&[u8]) -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    use wasm_encoder::ComponentSectionId;
+    use wasmparser::Chunk;
+
+    let mut out = wasm_encoder::Component::new();
+    let mut parser = Parser::new(0);
+    let mut offset = 0;
+    loop {
+        let payload = match parser.parse(&wasm[offset..], true)? {
+            Chunk::Parsed { consumed, payload } => {
+                offset += consumed;
+                payload
+            }
+            // this state isn't possible with `eof = true`
+            Chunk::NeedMoreData(_) => unreachable!(),
+        };
+        match payload {
+            Payload::Version { .. } => {}
+            Payload::ModuleSection { range, .. } => {
+                offset += range.len();
+                let bytes = module(&wasm[range])?;
+                out.section(&RawSection {
+                    id: ComponentSectionId::CoreModule.into(),
+                    data: &bytes,
+                });
+            }
+            Payload::ComponentSection { range, .. } => {
+                offset += range.len();
+                let bytes = rewrite_component(&wasm[range], module)?;
+                out.section(&RawSection {
+                    id: ComponentSectionId::Component.into(),
+                    data: &bytes,
+                });
+            }
+            Payload::End(_) => break,
+            payload => {
+                if let Some((id, range)) = payload.as_section() {
+                    out.section(&RawSection {
+                        id,
+                        data: &wasm[range],
+                    });
+                }
+            }
+        }
+    }
+    Ok(out.finish())
+}
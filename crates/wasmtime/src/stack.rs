@@ -0,0 +1,85 @@
+use crate::Result;
+use anyhow::{bail, Context};
+use std::ops::Range;
+use std::sync::Arc;
+use wasmtime_fiber::{FiberStack, RuntimeFiberStack};
+use wasmtime_runtime::RuntimeFiberStackCreator;
+
+/// A stack creator. Can be used to provide a stack creator to wasmtime
+/// which supplies stacks for async support.
+///
+/// # Safety
+///
+/// This trait is unsafe, as memory safety depends on a proper implementation
+/// of memory management. Stacks created by the StackCreator should always be
+/// treated as owned by an wasmtime instance, and any modification of them
+/// outside of wasmtime invoked routines is unsafe and may lead to corruption.
+///
+/// Note that this is a relatively new and experimental feature and it is
+/// recommended to be familiar with wasmtime runtime code to use it.
+pub unsafe trait StackCreator: Send + Sync {
+    /// Create a new `StackMemory` object with the specified size.
+    ///
+    /// The `size` parameter is the expected size of the stack without any guard
+    /// pages, as configured with
+    /// [`Config::async_stack_size`](crate::Config::async_stack_size). The
+    /// stack returned must provide at least this many bytes.
+    ///
+    /// The stack is released by dropping the returned object, which happens
+    /// once the store using it has finished with it.
+    fn new_stack(&self, size: usize) -> Result<Box<dyn StackMemory>>;
+}
+
+/// A stack memory. This trait provides an interface for raw memory buffers
+/// which are used by wasmtime inside of stores with async support. By
+/// implementing this trait together with StackCreator, one can supply
+/// wasmtime with custom allocated host managed stacks.
+///
+/// # Safety
+///
+/// The memory should be page aligned and a multiple of page size. To prevent
+/// stack overflows from silently corrupting other memory, the page directly
+/// beneath the stack must be unmapped as a guard page. The memory must stay
+/// readable and writable for as long as this object exists.
+///
+/// Note that this is a relatively new and experimental feature and it is
+/// recommended to be familiar with wasmtime runtime code to use it.
+pub unsafe trait StackMemory: Send + Sync {
+    /// The top of the allocated stack.
+    ///
+    /// This address must be the end of [`StackMemory::range`] and page
+    /// aligned.
+    fn top(&self) -> *mut u8;
+
+    /// The range of where this stack resides in memory, excluding guard
+    /// pages.
+    fn range(&self) -> Range<usize>;
+}
+
+pub(crate) struct StackCreatorProxy(pub Arc<dyn StackCreator>);
+
+impl RuntimeFiberStackCreator for StackCreatorProxy {
+    fn new_stack(&self, size: usize) -> Result<FiberStack> {
+        let stack = self.0.new_stack(size)?;
+        if stack.range().len() < size {
+            bail!(
+                "custom stack of {} bytes is smaller than the requested {size} bytes",
+                stack.range().len()
+            );
+        }
+        FiberStack::from_custom(Box::new(FiberStackProxy(stack)))
+            .context("failed to use a custom stack")
+    }
+}
+
+struct FiberStackProxy(Box<dyn StackMemory>);
+
+unsafe impl RuntimeFiberStack for FiberStackProxy {
+    fn top(&self) -> *mut u8 {
+        self.0.top()
+    }
+
+    fn range(&self) -> Range<usize> {
+        self.0.range()
+    }
+}
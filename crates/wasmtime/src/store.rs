@@ -76,9 +76,11 @@
 //! contents of `StoreOpaque`. This is an invariant that we, as the authors of
 //! `wasmtime`, must uphold for the public interface to be safe.
 
+use crate::debug::DebugState;
 use crate::instance::InstanceData;
 use crate::linker::Definition;
 use crate::module::{BareModuleInfo, RegisteredModuleId};
+use crate::replay::{HostCallExits, HostCallLog, HostCalls};
 use crate::trampoline::VMHostGlobalContext;
 use crate::{module::ModuleRegistry, Engine, Module, Trap, Val, ValRaw};
 use crate::{Global, Instance, Memory};
@@ -95,10 +97,11 @@ use std::ptr;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use wasmtime_environ::FuncIndex;
 use wasmtime_runtime::{
-    mpk::ProtectionKey, ExportGlobal, InstanceAllocationRequest, InstanceAllocator, InstanceHandle,
-    ModuleInfo, OnDemandInstanceAllocator, SignalHandler, StoreBox, StorePtr, VMContext,
-    VMExternRef, VMExternRefActivationsTable, VMFuncRef, VMRuntimeLimits, WasmFault,
+    mpk::ProtectionKey, ExportGlobal, ExportMemory, InstanceAllocationRequest, InstanceAllocator,
+    InstanceHandle, ModuleInfo, OnDemandInstanceAllocator, SignalHandler, StoreBox, StorePtr,
+    VMContext, VMExternRef, VMExternRefActivationsTable, VMFuncRef, VMRuntimeLimits, WasmFault,
 };
 
 mod context;
@@ -207,6 +210,7 @@ pub struct StoreInner<T> {
     call_hook: Option<CallHookInner<T>>,
     epoch_deadline_behavior:
         Option<Box<dyn FnMut(StoreContextMut<T>) -> Result<UpdateDeadline> + Send + Sync>>,
+    debug_handler: Option<DebugHandler<T>>,
     // for comments about `ManuallyDrop`, see `Store::into_data`
     data: ManuallyDrop<T>,
 }
@@ -232,6 +236,12 @@ enum CallHookInner<T> {
     Async(Box<dyn CallHookHandler<T> + Send + Sync>),
 }
 
+type DebugHandler<T> = Box<
+    dyn FnMut(StoreContextMut<T>, &mut crate::DebugFrame<'_>) -> Result<crate::DebugStep>
+        + Send
+        + Sync,
+>;
+
 /// What to do after returning from a callback when the engine epoch reaches
 /// the deadline for a Store during execution of a function using that store.
 pub enum UpdateDeadline {
@@ -355,6 +365,17 @@ pub struct StoreOpaque {
     component_host_table: wasmtime_runtime::component::ResourceTable,
     #[cfg(feature = "component-model")]
     component_calls: wasmtime_runtime::component::CallContexts,
+
+    /// Breakpoints and stepping state used when compiled code is
+    /// instrumented with `Config::guest_debug`.
+    debug: DebugState,
+
+    /// Whether host calls are being recorded or replayed, see
+    /// `Store::record_host_calls`.
+    host_calls: HostCalls,
+    /// How errors carrying an exit status are recognized and created when
+    /// recording and replaying host calls, see `Store::host_call_exits`.
+    host_call_exits: Option<HostCallExits>,
 }
 
 #[cfg(feature = "async")]
@@ -515,10 +536,14 @@ impl<T> Store<T> {
                 component_host_table: Default::default(),
                 #[cfg(feature = "component-model")]
                 component_calls: Default::default(),
+                debug: DebugState::default(),
+                host_calls: HostCalls::Off,
+                host_call_exits: None,
             },
             limiter: None,
             call_hook: None,
             epoch_deadline_behavior: None,
+            debug_handler: None,
             data: ManuallyDrop::new(data),
         });
 
@@ -1029,6 +1054,187 @@ impl<T> Store<T> {
     pub fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
         self.inner.epoch_deadline_async_yield_and_update(delta);
     }
+
+    /// Captures the state of all instances in this store, along with its
+    /// fuel and epoch deadline, into a [`Checkpoint`](crate::Checkpoint).
+    ///
+    /// Since this takes `&mut self` no WebAssembly can be executing in the
+    /// store, so a checkpoint is always taken between calls into wasm. See
+    /// [`Checkpoint`](crate::Checkpoint) for what is and isn't captured.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store contains state which can't be
+    /// captured, such as a shared memory or a table or global which refers
+    /// to a host function or a non-null `externref`.
+    pub fn checkpoint(&mut self) -> Result<crate::Checkpoint> {
+        crate::Checkpoint::capture(&mut self.as_context_mut())
+    }
+
+    /// Restores the state captured by [`Store::checkpoint`] into this store.
+    ///
+    /// This store must have instantiated the same modules and created the
+    /// same memories, tables and globals with [`Memory::new`],
+    /// [`Table::new`](crate::Table::new) and [`Global::new`], in the same
+    /// order, as the store the checkpoint was taken from, typically by
+    /// running the same setup code. Linear memories and tables are grown to
+    /// their size in the checkpoint; it's an error for them to already be
+    /// larger.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instances of this store don't match those of
+    /// the checkpoint, or if fuel was enabled for the checkpointed store but
+    /// isn't for this one.
+    pub fn restore(&mut self, checkpoint: &crate::Checkpoint) -> Result<()> {
+        checkpoint.restore(&mut self.as_context_mut())
+    }
+
+    /// Configures the handler invoked whenever WebAssembly executing in this
+    /// store pauses, either at a breakpoint added with
+    /// [`Store::add_breakpoint`] or while single-stepping.
+    ///
+    /// The handler is given the paused frame, whose locals it can inspect and
+    /// modify, and returns how execution should resume. Returning an error
+    /// raises it as a trap at the paused instruction.
+    ///
+    /// Execution only ever pauses in code compiled with
+    /// [`Config::guest_debug`](crate::Config::guest_debug) enabled, and only
+    /// while a handler is configured.
+    pub fn debug_handler(
+        &mut self,
+        handler: impl FnMut(StoreContextMut<T>, &mut crate::DebugFrame<'_>) -> Result<crate::DebugStep>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.inner.debug_handler = Some(Box::new(handler));
+    }
+
+    /// Adds a breakpoint before the instruction at `func_offset` in the
+    /// function `func_index` of `module`.
+    ///
+    /// The offset is relative to the start of the function's body, in the
+    /// same terms as [`FrameInfo::func_offset`](crate::FrameInfo::func_offset).
+    /// A breakpoint at an offset which isn't the start of an instruction is
+    /// never hit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `module` wasn't compiled with
+    /// [`Config::guest_debug`](crate::Config::guest_debug) enabled by this
+    /// store's engine, or if `func_index` isn't a function defined by
+    /// `module`.
+    pub fn add_breakpoint(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        func_offset: usize,
+    ) -> Result<()> {
+        self.inner.add_breakpoint(module, func_index, func_offset)
+    }
+
+    /// Removes a breakpoint previously added with [`Store::add_breakpoint`],
+    /// returning whether it existed.
+    pub fn remove_breakpoint(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        func_offset: usize,
+    ) -> bool {
+        self.inner
+            .remove_breakpoint(module, func_index, func_offset)
+    }
+
+    /// Configures whether execution pauses before the next WebAssembly
+    /// instruction executed in this store.
+    ///
+    /// This is reset every time execution pauses; the value returned by the
+    /// [`Store::debug_handler`] decides whether to keep stepping.
+    pub fn debug_single_step(&mut self, enable: bool) {
+        self.inner.debug.set_single_step(enable);
+    }
+
+    /// Starts recording the host calls made by WebAssembly in this store into
+    /// a [`HostCallLog`], which can later be replayed with
+    /// [`Store::replay_host_calls`] to reproduce this run.
+    ///
+    /// Each host call made directly by WebAssembly is logged along with its
+    /// results, or the error it returned, and whatever it changed in the
+    /// memories and mutable globals of this store's instances. Host functions
+    /// which return references, and component host functions which take or
+    /// return resources, can't be recorded and trap instead, as do host calls
+    /// in stores with shared memories.
+    ///
+    /// Writes made through [`Memory::write`], [`MemoryWrites`](crate::MemoryWrites)
+    /// and the lowering of component values are logged as they're made. A
+    /// memory accessed whole during a host call, with [`Memory::data_mut`],
+    /// [`Memory::data_and_store_mut`] or [`Memory::data_ptr`], is instead
+    /// copied and compared once the call returns, as are all memories when a
+    /// host function calls back into WebAssembly, which makes such calls much
+    /// slower. Memory written through pointers obtained before the host call
+    /// started isn't logged.
+    ///
+    /// Errors carrying an exit status are logged as such once
+    /// [`Store::host_call_exits`] is configured.
+    ///
+    /// For runs to be reproducible the remaining sources of nondeterminism
+    /// should be disabled with
+    /// [`Config::cranelift_nan_canonicalization`](crate::Config::cranelift_nan_canonicalization)
+    /// and
+    /// [`Config::relaxed_simd_deterministic`](crate::Config::relaxed_simd_deterministic).
+    /// Any log previously recorded or being replayed is discarded.
+    pub fn record_host_calls(&mut self) {
+        self.inner.host_calls = HostCalls::Record {
+            log: HostCallLog::default(),
+            call: None,
+        };
+    }
+
+    /// Replays `log`, recorded with [`Store::record_host_calls`], in this
+    /// store.
+    ///
+    /// While replaying, host calls made by WebAssembly don't call the host
+    /// function. Instead the next call of the log is applied: its changes to
+    /// memories and globals are made and its results are returned, or its
+    /// error is raised as a trap with the same message. Side effects outside
+    /// of the store, such as printing, therefore don't happen again.
+    ///
+    /// This store must instantiate the same modules, in the same order, and
+    /// make the same calls into them as the recorded store. A host call whose
+    /// arguments differ from the log's, or which is made once the log is
+    /// exhausted, traps.
+    pub fn replay_host_calls(&mut self, log: HostCallLog) {
+        self.inner.host_calls = HostCalls::Replay {
+            log,
+            next: 0,
+            in_call: false,
+        };
+    }
+
+    /// Configures how errors returned by host functions which carry an exit
+    /// status, such as WASI's `I32Exit`, are logged while recording host calls
+    /// and raised again while replaying them.
+    ///
+    /// `status` returns the exit status an error carries, if any, and `exit`
+    /// creates the error raised when replaying a host call which exited with
+    /// that status. Other errors are logged as the [`Trap`] they are, if any,
+    /// and as their message otherwise.
+    pub fn host_call_exits(
+        &mut self,
+        status: fn(&anyhow::Error) -> Option<i32>,
+        exit: fn(i32) -> anyhow::Error,
+    ) {
+        self.inner.host_call_exits = Some((status, exit));
+    }
+
+    /// Stops recording or replaying host calls.
+    ///
+    /// Returns the calls recorded so far when recording, the calls which have
+    /// yet to be replayed when replaying, and `None` otherwise.
+    pub fn take_host_call_log(&mut self) -> Option<HostCallLog> {
+        self.inner.host_calls.take_log()
+    }
 }
 
 impl<'a, T> StoreContext<'a, T> {
@@ -1158,6 +1364,38 @@ impl<'a, T> StoreContextMut<'a, T> {
     pub fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
         self.0.epoch_deadline_async_yield_and_update(delta);
     }
+
+    /// Adds a breakpoint to this store.
+    ///
+    /// For more information see [`Store::add_breakpoint`].
+    pub fn add_breakpoint(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        func_offset: usize,
+    ) -> Result<()> {
+        self.0.add_breakpoint(module, func_index, func_offset)
+    }
+
+    /// Removes a breakpoint from this store.
+    ///
+    /// For more information see [`Store::remove_breakpoint`].
+    pub fn remove_breakpoint(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        func_offset: usize,
+    ) -> bool {
+        self.0.remove_breakpoint(module, func_index, func_offset)
+    }
+
+    /// Configures whether execution pauses before the next WebAssembly
+    /// instruction.
+    ///
+    /// For more information see [`Store::debug_single_step`].
+    pub fn debug_single_step(&mut self, enable: bool) {
+        self.0.debug.set_single_step(enable);
+    }
 }
 
 impl<T> StoreInner<T> {
@@ -1349,6 +1587,12 @@ impl StoreOpaque {
             .map(|i| Instance::from_wasmtime(i, self))
     }
 
+    /// Returns the ids of all instances in this store, including the dummy
+    /// instances which hold host-created memories and tables.
+    pub(crate) fn all_instance_ids(&self) -> impl ExactSizeIterator<Item = InstanceId> {
+        (0..self.instances.len()).map(InstanceId::from_index)
+    }
+
     /// Get all memories (host- or Wasm-defined) within this store.
     pub fn all_memories<'a>(&'a mut self) -> impl Iterator<Item = Memory> + 'a {
         // NB: Host-created memories have dummy instances. Therefore, we can get
@@ -1390,6 +1634,47 @@ impl StoreOpaque {
         }
     }
 
+    /// Same as `all_memories` and `all_globals` but without registering each
+    /// memory and global in the store's data, which is important when called
+    /// for every host call.
+    pub(crate) fn defined_memories_and_globals(
+        &mut self,
+    ) -> (Vec<ExportMemory>, Vec<ExportGlobal>) {
+        let memories = self
+            .instances
+            .iter_mut()
+            .flat_map(|instance| instance.handle.defined_memories())
+            .collect();
+        let mut globals = self
+            .host_globals()
+            .iter()
+            .map(|global| unsafe {
+                ExportGlobal {
+                    definition: &mut (*global.get()).global as *mut _,
+                    global: (*global.get()).ty.to_wasm_type(),
+                }
+            })
+            .collect::<Vec<_>>();
+        globals.extend(
+            self.instances
+                .iter_mut()
+                .flat_map(|instance| instance.handle.defined_globals().map(|(_i, global)| global)),
+        );
+        (memories, globals)
+    }
+
+    pub(crate) fn host_calls(&self) -> &HostCalls {
+        &self.host_calls
+    }
+
+    pub(crate) fn host_calls_mut(&mut self) -> &mut HostCalls {
+        &mut self.host_calls
+    }
+
+    pub(crate) fn host_call_exits(&self) -> Option<HostCallExits> {
+        self.host_call_exits
+    }
+
     #[cfg_attr(not(target_os = "linux"), allow(dead_code))] // not used on all platforms
     pub fn set_signal_handler(&mut self, handler: Option<Box<SignalHandler<'static>>>) {
         self.signal_handler = handler;
@@ -1458,6 +1743,50 @@ impl StoreOpaque {
         Some(u64::try_from(-consumed).unwrap())
     }
 
+    fn add_breakpoint(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        func_offset: usize,
+    ) -> Result<()> {
+        let (func, offset) = self.breakpoint_location(module, func_index, func_offset)?;
+        self.debug.add_breakpoint(module.id(), func, offset);
+        Ok(())
+    }
+
+    fn remove_breakpoint(&mut self, module: &Module, func_index: u32, func_offset: usize) -> bool {
+        match self.breakpoint_location(module, func_index, func_offset) {
+            Ok((func, offset)) => self.debug.remove_breakpoint(module.id(), func, offset),
+            Err(_) => false,
+        }
+    }
+
+    /// Validates the location of a breakpoint in `module` for
+    /// `Store::add_breakpoint` and friends.
+    fn breakpoint_location(
+        &self,
+        module: &Module,
+        func_index: u32,
+        func_offset: usize,
+    ) -> Result<(FuncIndex, u32)> {
+        if !Engine::same(&self.engine, module.engine()) {
+            bail!("cross-`Engine` breakpoints are not supported");
+        }
+        if !self.engine.config().tunables.guest_debug {
+            bail!("breakpoints require `Config::guest_debug` to be enabled");
+        }
+        let env_module = module.env_module();
+        let func = FuncIndex::from_u32(func_index);
+        if func_index as usize >= env_module.functions.len()
+            || env_module.defined_func_index(func).is_none()
+        {
+            bail!("function {func_index} is not defined by the module");
+        }
+        let offset = u32::try_from(func_offset)
+            .map_err(|_| anyhow!("offset {func_offset} is out of bounds"))?;
+        Ok((func, offset))
+    }
+
     fn out_of_fuel_trap(&mut self) {
         self.out_of_gas_behavior = OutOfGas::Trap;
     }
@@ -2233,6 +2562,48 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
         delta_result
     }
 
+    fn debug_check(
+        &mut self,
+        module: Option<wasmtime_runtime::CompiledModuleId>,
+        func: FuncIndex,
+        offset: u32,
+    ) -> bool {
+        self.debug_handler.is_some() && self.debug.should_pause(module, func, offset)
+    }
+
+    fn debug_break(
+        &mut self,
+        vmctx: *mut VMContext,
+        func: FuncIndex,
+        offset: u32,
+        locals: *mut u8,
+        count: u32,
+    ) -> Result<(), anyhow::Error> {
+        self.debug.set_single_step(false);
+
+        // Temporarily take the handler to avoid mutably borrowing multiple
+        // times, as in `new_epoch` above.
+        let mut handler = match self.debug_handler.take() {
+            Some(handler) => handler,
+            None => return Ok(()),
+        };
+        let instance = unsafe {
+            wasmtime_runtime::Instance::from_vmctx(vmctx, |i| {
+                *i.host_state().downcast_ref::<Instance>().unwrap()
+            })
+        };
+        let mut frame = unsafe { crate::DebugFrame::new(instance, func, offset, locals, count) };
+        let result = handler((&mut *self).as_context_mut(), &mut frame);
+
+        // Put back the handler unless it was replaced while running.
+        if self.debug_handler.is_none() {
+            self.debug_handler = Some(handler);
+        }
+        let step = result?;
+        self.debug.set_single_step(step == crate::DebugStep::Step);
+        Ok(())
+    }
+
     #[cfg(feature = "component-model")]
     fn component_calls(&mut self) -> &mut wasmtime_runtime::component::CallContexts {
         &mut self.component_calls
@@ -2278,7 +2649,7 @@ impl<T> StoreInner<T> {
         let _ = delta; // suppress warning in non-async build
     }
 
-    fn get_epoch_deadline(&self) -> u64 {
+    pub(crate) fn get_epoch_deadline(&self) -> u64 {
         // Safety: this is safe because, as above, it is only invoked
         // from within `new_epoch` which is called from guest Wasm
         // code, which will have an exclusive borrow on the Store.
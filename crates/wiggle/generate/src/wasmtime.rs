@@ -115,9 +115,9 @@ fn generate_func(
         let export = caller.get_export("memory");
         let (mem, ctx) = match &export {
             Some(wiggle::wasmtime_crate::Extern::Memory(m)) => {
-                let (mem, ctx) = m.data_and_store_mut(&mut caller);
+                let (mem, ctx, writes) = m.data_and_store_mut_with_writes(&mut caller);
                 let ctx = get_cx(ctx);
-                (wiggle::wasmtime::WasmtimeGuestMemory::new(mem), ctx)
+                (wiggle::wasmtime::WasmtimeGuestMemory::with_writes(mem, writes), ctx)
             }
             Some(wiggle::wasmtime_crate::Extern::SharedMemory(m)) => {
                 let ctx = get_cx(caller.data_mut());
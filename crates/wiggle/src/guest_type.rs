@@ -109,6 +109,7 @@ macro_rules! integer_primitives {
                 if ptr.mem().is_shared_borrowed(region) || ptr.mem().is_mut_borrowed(region) {
                     return Err(GuestError::PtrBorrowed(region));
                 }
+                ptr.mem().wrote(region);
                 let atomic_value_ref: &$ty_atomic =
                     unsafe { &*(host_ptr.get().cast::<$ty_atomic>()) };
                 atomic_value_ref.store(val, Ordering::Relaxed);
@@ -161,6 +162,7 @@ macro_rules! float_primitives {
                 if ptr.mem().is_shared_borrowed(region) || ptr.mem().is_mut_borrowed(region) {
                     return Err(GuestError::PtrBorrowed(region));
                 }
+                ptr.mem().wrote(region);
                 let atomic_value_ref: &$ty_atomic =
                     unsafe { &*(host_ptr.get().cast::<$ty_atomic>()) };
                 let le_value = $ty_unsigned::to_le(val.to_bits());
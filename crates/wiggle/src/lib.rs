@@ -151,6 +151,13 @@ pub unsafe trait GuestMemory: Send + Sync {
     fn is_shared_memory(&self) -> bool {
         false
     }
+
+    /// Notes that a region of linear memory was written to, or mutably
+    /// borrowed to be written to, by the host. This is called for every write
+    /// made through `GuestPtr` and for every `GuestSliceMut` and `GuestStrMut`
+    /// handed out, so that memories which need to know what the host changed
+    /// can track it.
+    fn wrote(&self, _r: Region) {}
 }
 
 /// Validates a guest-relative pointer given various attributes, and returns
@@ -233,6 +240,9 @@ unsafe impl<'a, T: ?Sized + GuestMemory> GuestMemory for &'a T {
     fn shared_unborrow(&self, h: BorrowHandle) {
         T::shared_unborrow(self, h)
     }
+    fn wrote(&self, r: Region) {
+        T::wrote(self, r)
+    }
 }
 
 unsafe impl<'a, T: ?Sized + GuestMemory> GuestMemory for &'a mut T {
@@ -260,6 +270,9 @@ unsafe impl<'a, T: ?Sized + GuestMemory> GuestMemory for &'a mut T {
     fn shared_unborrow(&self, h: BorrowHandle) {
         T::shared_unborrow(self, h)
     }
+    fn wrote(&self, r: Region) {
+        T::wrote(self, r)
+    }
 }
 
 unsafe impl<T: ?Sized + GuestMemory> GuestMemory for Box<T> {
@@ -287,6 +300,9 @@ unsafe impl<T: ?Sized + GuestMemory> GuestMemory for Box<T> {
     fn shared_unborrow(&self, h: BorrowHandle) {
         T::shared_unborrow(self, h)
     }
+    fn wrote(&self, r: Region) {
+        T::wrote(self, r)
+    }
 }
 
 unsafe impl<T: ?Sized + GuestMemory> GuestMemory for Arc<T> {
@@ -314,6 +330,9 @@ unsafe impl<T: ?Sized + GuestMemory> GuestMemory for Arc<T> {
     fn shared_unborrow(&self, h: BorrowHandle) {
         T::shared_unborrow(self, h)
     }
+    fn wrote(&self, r: Region) {
+        T::wrote(self, r)
+    }
 }
 
 /// A *guest* pointer into host memory.
@@ -1012,11 +1031,14 @@ impl<'a, T> UnsafeGuestSlice<'a, T> {
             UnsafeBorrowResult::Shared(self)
         } else {
             match self.mem.mut_borrow(self.region) {
-                Ok(borrow) => UnsafeBorrowResult::Ok(GuestSliceMut {
-                    ptr: self.ptr,
-                    mem: self.mem,
-                    borrow,
-                }),
+                Ok(borrow) => {
+                    self.mem.wrote(self.region);
+                    UnsafeBorrowResult::Ok(GuestSliceMut {
+                        ptr: self.ptr,
+                        mem: self.mem,
+                        borrow,
+                    })
+                }
                 Err(e) => UnsafeBorrowResult::Err(e),
             }
         }
@@ -8,6 +8,7 @@ pub struct WasmtimeGuestMemory<'a> {
     mem: &'a [UnsafeCell<u8>],
     bc: BorrowChecker,
     shared: bool,
+    writes: Option<wasmtime::MemoryWrites<'a>>,
 }
 
 // These need to be reapplied due to the usage of `UnsafeCell` internally.
@@ -38,6 +39,17 @@ impl<'a> WasmtimeGuestMemory<'a> {
             // https://github.com/bytecodealliance/wasmtime/issues/1917
             bc: BorrowChecker::new(),
             shared: false,
+            writes: None,
+        }
+    }
+
+    /// Same as [`WasmtimeGuestMemory::new`], but reports the regions written
+    /// to `writes`, as returned by
+    /// [`wasmtime::Memory::data_and_store_mut_with_writes`].
+    pub fn with_writes(mem: &'a mut [u8], writes: wasmtime::MemoryWrites<'a>) -> Self {
+        Self {
+            writes: Some(writes),
+            ..Self::new(mem)
         }
     }
 
@@ -46,6 +58,7 @@ impl<'a> WasmtimeGuestMemory<'a> {
             mem,
             bc: BorrowChecker::new(),
             shared: true,
+            writes: None,
         }
     }
 }
@@ -98,4 +111,10 @@ unsafe impl GuestMemory for WasmtimeGuestMemory<'_> {
     fn is_shared_memory(&self) -> bool {
         self.shared
     }
+    #[inline]
+    fn wrote(&self, r: Region) {
+        if let Some(writes) = &self.writes {
+            writes.wrote(r.start as usize, r.len as usize);
+        }
+    }
 }
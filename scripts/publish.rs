@@ -37,6 +37,8 @@ const CRATES_TO_PUBLISH: &[&str] = &[
     "cranelift-interpreter",
     "cranelift",
     "wasmtime-jit-icache-coherence",
+    "wasmtime-versioned-export-macros",
+    "wasmtime-jit-debug",
     "cranelift-jit",
     // wiggle
     "wiggle-generate",
@@ -45,11 +47,9 @@ const CRATES_TO_PUBLISH: &[&str] = &[
     "winch",
     // wasmtime
     "wasmtime-asm-macros",
-    "wasmtime-versioned-export-macros",
     "wasmtime-component-util",
     "wasmtime-wit-bindgen",
     "wasmtime-component-macro",
-    "wasmtime-jit-debug",
     "wasmtime-fiber",
     "wasmtime-environ",
     "wasmtime-wmemcheck",
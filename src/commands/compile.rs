@@ -5,8 +5,11 @@ use clap::Parser;
 use once_cell::sync::Lazy;
 use std::fs;
 use std::path::PathBuf;
-use wasmtime::Engine;
+use wasmtime::{Engine, Linker, Module, Store};
 use wasmtime_cli_flags::CommonOptions;
+#[cfg(feature = "component-model")]
+use wasmtime_wasi::preview2;
+use wasmtime_wasi::sync::WasiCtxBuilder;
 
 static AFTER_HELP: Lazy<String> = Lazy::new(|| {
     format!(
@@ -24,7 +27,11 @@ static AFTER_HELP: Lazy<String> = Lazy::new(|| {
         \n\
         Compiling for a specific platform (Linux) and CPU preset (Skylake):\n\
         \n  \
-        wasmtime compile --target x86_64-unknown-linux -Ccranelift-skylake foo.wasm\n",
+        wasmtime compile --target x86_64-unknown-linux -Ccranelift-skylake foo.wasm\n\
+        \n\
+        Pre-initializing a module by running its `init` export first:\n\
+        \n  \
+        wasmtime compile --init-func init foo.wasm\n",
     )
 });
 
@@ -51,6 +58,25 @@ pub struct CompileCommand {
     #[clap(long = "emit-clif", value_name = "PATH")]
     emit_clif: Option<PathBuf>,
 
+    /// Pre-initialize the module by running this export before compiling.
+    ///
+    /// The module or component is instantiated on the host and the named
+    /// export, which must take no arguments and return nothing, is called.
+    /// The state of the instance afterwards, i.e. the contents of its linear
+    /// memories and the values of its mutable globals, is then captured in the
+    /// output so that instantiating it starts from that state. WASI is
+    /// available to the initialization function with inherited stdio but no
+    /// arguments, environment variables or preopened directories; other
+    /// imports of modules trap when called.
+    #[clap(long, value_name = "NAME")]
+    init_func: Option<String>,
+
+    /// Write the pre-initialized WebAssembly instead of compiling it.
+    ///
+    /// The output defaults to <MODULE>.init.wasm.
+    #[clap(long, requires = "init_func")]
+    emit_wasm: bool,
+
     /// The path of the WebAssembly to compile
     #[clap(index = 1, value_name = "MODULE")]
     module: PathBuf,
@@ -87,15 +113,32 @@ impl CompileCommand {
             );
         }
 
-        let input = wat::parse_file(&self.module).with_context(|| "failed to read input file")?;
+        let mut input =
+            wat::parse_file(&self.module).with_context(|| "failed to read input file")?;
+
+        if let Some(init_func) = &self.init_func {
+            // Initialization runs on the host, whatever the compilation target.
+            let host_engine = Engine::new(&self.common.config(None)?)?;
+            input = if wasmparser::Parser::is_component(&input) {
+                pre_initialize_component(&host_engine, &input, init_func)?
+            } else {
+                pre_initialize_module(&host_engine, &input, init_func)?
+            };
+        }
 
         let output = self.output.take().unwrap_or_else(|| {
             let mut output: PathBuf = self.module.file_name().unwrap().into();
-            output.set_extension("cwasm");
+            if self.emit_wasm {
+                output.set_extension("init.wasm");
+            } else {
+                output.set_extension("cwasm");
+            }
             output
         });
 
-        let output_bytes = if wasmparser::Parser::is_component(&input) {
+        let output_bytes = if self.emit_wasm {
+            input
+        } else if wasmparser::Parser::is_component(&input) {
             engine.precompile_component(&input)?
         } else {
             engine.precompile_module(&input)?
@@ -107,6 +150,74 @@ impl CompileCommand {
     }
 }
 
+/// Instantiates the module `wasm`, calls its `init_func` export and returns a
+/// snapshot of the resulting instance.
+fn pre_initialize_module(engine: &Engine, wasm: &[u8], init_func: &str) -> Result<Vec<u8>> {
+    let module = Module::new(engine, wasm)?;
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |cx| cx)?;
+    linker.define_unknown_imports_as_traps(&module)?;
+
+    let wasi = WasiCtxBuilder::new().inherit_stdio().build();
+    let mut store = Store::new(engine, wasi);
+    let instance = linker.instantiate(&mut store, &module)?;
+    instance
+        .get_typed_func::<(), ()>(&mut store, init_func)?
+        .call(&mut store, ())
+        .with_context(|| format!("failed to run initialization function `{init_func}`"))?;
+    instance.snapshot(&mut store, wasm)
+}
+
+#[cfg(feature = "component-model")]
+struct InitHost {
+    table: preview2::Table,
+    ctx: preview2::WasiCtx,
+}
+
+#[cfg(feature = "component-model")]
+impl preview2::WasiView for InitHost {
+    fn table(&self) -> &preview2::Table {
+        &self.table
+    }
+    fn table_mut(&mut self) -> &mut preview2::Table {
+        &mut self.table
+    }
+    fn ctx(&self) -> &preview2::WasiCtx {
+        &self.ctx
+    }
+    fn ctx_mut(&mut self) -> &mut preview2::WasiCtx {
+        &mut self.ctx
+    }
+}
+
+/// Instantiates the component `wasm`, calls its `init_func` export and returns
+/// a snapshot of the resulting instance.
+#[cfg(feature = "component-model")]
+fn pre_initialize_component(engine: &Engine, wasm: &[u8], init_func: &str) -> Result<Vec<u8>> {
+    use wasmtime::component::{Component, Linker};
+
+    let component = Component::new(engine, wasm)?;
+    let mut linker = Linker::new(engine);
+    preview2::command::sync::add_to_linker(&mut linker)?;
+
+    let host = InitHost {
+        table: preview2::Table::new(),
+        ctx: preview2::WasiCtxBuilder::new().inherit_stdio().build(),
+    };
+    let mut store = Store::new(engine, host);
+    let instance = linker.instantiate(&mut store, &component)?;
+    let func = instance.get_typed_func::<(), ()>(&mut store, init_func)?;
+    func.call(&mut store, ())
+        .with_context(|| format!("failed to run initialization function `{init_func}`"))?;
+    func.post_return(&mut store)?;
+    instance.snapshot(&mut store, wasm)
+}
+
+#[cfg(not(feature = "component-model"))]
+fn pre_initialize_component(_: &Engine, _: &[u8], _: &str) -> Result<Vec<u8>> {
+    bail!("pre-initializing components requires the `component-model` feature")
+}
+
 #[cfg(all(test, not(miri)))]
 mod test {
     use super::*;
@@ -145,6 +256,43 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_init_func_compile() -> Result<()> {
+        let (mut input, input_path) = NamedTempFile::new()?.into_parts();
+        input.write_all(
+            r#"(module
+                (global $g (mut i32) (i32.const 0))
+                (func (export "init") (global.set $g (i32.const 42)))
+                (func (export "g") (result i32) global.get $g))"#
+                .as_bytes(),
+        )?;
+        drop(input);
+
+        let output_path = NamedTempFile::new()?.into_temp_path();
+
+        let command = CompileCommand::try_parse_from(vec![
+            "compile",
+            "-Dlogging=n",
+            "--init-func",
+            "init",
+            "-o",
+            output_path.to_str().unwrap(),
+            input_path.to_str().unwrap(),
+        ])?;
+
+        command.execute()?;
+
+        let engine = Engine::default();
+        let contents = std::fs::read(output_path)?;
+        let module = unsafe { Module::deserialize(&engine, contents)? };
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let g = instance.get_typed_func::<(), i32>(&mut store, "g")?;
+        assert_eq!(g.call(&mut store, ())?, 42);
+
+        Ok(())
+    }
+
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_x64_flags_compile() -> Result<()> {
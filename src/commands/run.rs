@@ -6,17 +6,19 @@
 )]
 
 use crate::common::{Profile, RunCommon, RunTarget};
+#[cfg(feature = "gdbstub")]
+use crate::gdbstub::Debugger;
 
 use anyhow::{anyhow, bail, Context as _, Error, Result};
 use clap::Parser;
 use std::fs::File;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
 use wasmtime::{
-    AsContextMut, Engine, Func, GuestProfiler, Module, Store, StoreLimits, UpdateDeadline, Val,
-    ValType,
+    AsContextMut, Engine, Func, GuestProfiler, HostCallLog, Module, Store, StoreLimits,
+    UpdateDeadline, Val, ValType,
 };
 use wasmtime_wasi::maybe_exit_on_error;
 use wasmtime_wasi::preview2;
@@ -135,7 +137,8 @@ impl RunCommand {
         let engine = Engine::new(&config)?;
 
         // Read the wasm module binary either as `*.wat` or a raw binary.
-        let main = self.run.load_module(&engine, &self.module_and_args[0])?;
+        #[cfg_attr(not(feature = "gdbstub"), allow(unused_variables))]
+        let (main, main_binary) = self.load_module(&engine, &self.module_and_args[0])?;
 
         // Validate coredump-on-trap argument
         if let Some(path) = &self.run.common.debug.coredump {
@@ -176,6 +179,51 @@ impl RunCommand {
             store.add_fuel(fuel)?;
         }
 
+        let debug = &self.run.common.debug;
+        store.host_call_exits(
+            |e| {
+                e.downcast_ref::<wasmtime_wasi::I32Exit>()
+                    .map(|e| e.0)
+                    .or_else(|| e.downcast_ref::<preview2::I32Exit>().map(|e| e.0))
+            },
+            |status| wasmtime_wasi::I32Exit(status).into(),
+        );
+        match (&debug.record, &debug.replay) {
+            (Some(_), Some(_)) => bail!("`record` and `replay` cannot be used together"),
+            (Some(_), None) => store.record_host_calls(),
+            (None, Some(path)) => {
+                let log = std::fs::read(path)
+                    .with_context(|| format!("failed to read host calls from `{path}`"))?;
+                store.replay_host_calls(HostCallLog::deserialize(&log)?);
+            }
+            (None, None) => {}
+        }
+
+        // Wait for a debugger, if one was requested, before any wasm runs.
+        #[cfg(feature = "gdbstub")]
+        let debugger = match self.run.common.debug.gdbstub {
+            Some(port) => {
+                let debugger = Debugger::attach(&mut store, port)?;
+                match &main {
+                    RunTarget::Core(m) => {
+                        if let Some(binary) = main_binary {
+                            debugger.add_module(&self.module_and_args[0], binary, m)?;
+                        }
+                    }
+                    #[cfg(feature = "component-model")]
+                    RunTarget::Component(_) => {
+                        bail!("components cannot be debugged with `gdbstub`")
+                    }
+                }
+                Some(debugger)
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "gdbstub"))]
+        if self.run.common.debug.gdbstub.is_some() {
+            bail!("support for `gdbstub` was not enabled at compile time");
+        }
+
         // Load the preload wasm modules.
         let mut modules = Vec::new();
         if let RunTarget::Core(m) = &main {
@@ -183,12 +231,19 @@ impl RunCommand {
         }
         for (name, path) in self.preloads.iter() {
             // Read the wasm module binary either as `*.wat` or a raw binary
-            let module = match self.run.load_module(&engine, path)? {
-                RunTarget::Core(m) => m,
+            #[cfg_attr(not(feature = "gdbstub"), allow(unused_variables))]
+            let (module, binary) = match self.load_module(&engine, path)? {
+                (RunTarget::Core(m), binary) => (m, binary),
                 #[cfg(feature = "component-model")]
-                RunTarget::Component(_) => bail!("components cannot be loaded with `--preload`"),
+                (RunTarget::Component(_), _) => {
+                    bail!("components cannot be loaded with `--preload`")
+                }
             };
             modules.push((name.clone(), module.clone()));
+            #[cfg(feature = "gdbstub")]
+            if let (Some(debugger), Some(binary)) = (&debugger, binary) {
+                debugger.add_module(path, binary, &module)?;
+            }
 
             // Add the module's functions to the linker.
             match &mut linker {
@@ -207,14 +262,24 @@ impl RunCommand {
         }
 
         // Load the main wasm module.
-        match self
+        let result = self
             .load_main_module(&mut store, &mut linker, &main, modules)
             .with_context(|| {
                 format!(
                     "failed to run main module `{}`",
                     self.module_and_args[0].display()
                 )
-            }) {
+            });
+        #[cfg(feature = "gdbstub")]
+        if let Some(debugger) = &debugger {
+            debugger.finish(&result);
+        }
+        if let Some(path) = &self.run.common.debug.record {
+            let log = store.take_host_call_log().unwrap();
+            std::fs::write(path, log.serialize()?)
+                .with_context(|| format!("failed to write host calls to `{path}`"))?;
+        }
+        match result {
             Ok(()) => (),
             Err(e) => {
                 // Exit the process if Wasmtime understands the error;
@@ -227,6 +292,22 @@ impl RunCommand {
         Ok(())
     }
 
+    /// Loads the module at `path`, along with the WebAssembly binary it was
+    /// compiled from if a debugger needs it.
+    fn load_module(&self, engine: &Engine, path: &Path) -> Result<(RunTarget, Option<Vec<u8>>)> {
+        if self.run.common.debug.gdbstub.is_none() {
+            return Ok((self.run.load_module(engine, path)?, None));
+        }
+        match self.run.load_module_and_binary(engine, path)? {
+            (_, None) => bail!(
+                "`{}` is precompiled and can't be debugged with `gdbstub`, \
+                 debug the WebAssembly module it was compiled from instead",
+                path.display()
+            ),
+            loaded => Ok(loaded),
+        }
+    }
+
     fn compute_preopen_dirs(&self) -> Result<Vec<(String, Dir)>> {
         let mut preopen_dirs = Vec::new();
 
@@ -590,16 +671,8 @@ impl RunCommand {
                         })?;
                     }
                 }
-                let graphs = self
-                    .run
-                    .common
-                    .wasi
-                    .nn_graph
-                    .iter()
-                    .map(|g| (g.format.clone(), g.dir.clone()))
-                    .collect::<Vec<_>>();
-                let (backends, registry) = wasmtime_wasi_nn::preload(&graphs)?;
-                store.data_mut().wasi_nn = Some(Arc::new(WasiNnCtx::new(backends, registry)));
+                let ctx = self.run.nn_graphs()?.ctx();
+                store.data_mut().wasi_nn = Some(Arc::new(ctx));
             }
         }
 
@@ -1,28 +1,38 @@
 use crate::common::{Profile, RunCommon, RunTarget};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use std::{
-    path::PathBuf,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
     pin::Pin,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
     },
+    time::Instant,
 };
 use wasmtime::component::{InstancePre, Linker};
-use wasmtime::{Engine, Store, StoreLimits};
-use wasmtime_wasi::preview2::{Table, WasiCtx, WasiCtxBuilder, WasiView};
+use wasmtime::{Engine, ResourceLimiter, Store, StoreLimits, StoreLimitsBuilder};
+use wasmtime_wasi::preview2::{
+    DirPerms, FilePerms, HostOutputStream, StdoutStream, StreamResult, Subscribe, Table, WasiCtx,
+    WasiCtxBuilder, WasiView,
+};
+use wasmtime_wasi::sync::{ambient_authority, Dir};
 use wasmtime_wasi_http::{body::HyperOutgoingBody, WasiHttpCtx, WasiHttpView};
 
 #[cfg(feature = "wasi-nn")]
 use wasmtime_wasi_nn::WasiNnCtx;
 
+mod metrics;
+
+use self::metrics::Metrics;
+
 struct Host {
     table: Table,
     ctx: WasiCtx,
     http: WasiHttpCtx,
 
-    limits: StoreLimits,
+    limiter: RequestLimiter,
 
     #[cfg(feature = "wasi-nn")]
     nn: Option<WasiNnCtx>,
@@ -56,6 +66,87 @@ impl WasiHttpView for Host {
     }
 }
 
+/// Enforces the [`StoreLimits`] of a request while keeping track of how much
+/// linear memory it has used.
+struct RequestLimiter {
+    limits: StoreLimits,
+    memory: usize,
+    peak_memory: usize,
+    /// The growth allowed by the last call to `memory_growing` and the peak
+    /// memory before it, which are rolled back if the growth then fails.
+    last_growth: (usize, usize),
+}
+
+impl RequestLimiter {
+    fn new(limits: StoreLimits) -> RequestLimiter {
+        RequestLimiter {
+            limits,
+            memory: 0,
+            peak_memory: 0,
+            last_growth: (0, 0),
+        }
+    }
+}
+
+impl ResourceLimiter for RequestLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> Result<bool> {
+        let allow = self.limits.memory_growing(current, desired, maximum)?;
+        if allow {
+            self.last_growth = (desired - current, self.peak_memory);
+            self.memory += desired - current;
+            self.peak_memory = self.peak_memory.max(self.memory);
+        }
+        Ok(allow)
+    }
+
+    fn memory_grow_failed(&mut self, error: anyhow::Error) -> Result<()> {
+        let (growth, peak_memory) = std::mem::take(&mut self.last_growth);
+        self.memory -= growth;
+        self.peak_memory = peak_memory;
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn table_grow_failed(&mut self, error: anyhow::Error) -> Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+/// Header with which trusted callers may override the maximum size of each
+/// linear memory for a request.
+const MAX_MEMORY_SIZE_HEADER: &str = "wasmtime-max-memory-size";
+
+/// Header with which trusted callers may override the fuel given to a
+/// request.
+const FUEL_HEADER: &str = "wasmtime-fuel";
+
+/// Limits of a single request which were overridden by its headers.
+#[derive(Default)]
+struct LimitOverrides {
+    max_memory_size: Option<usize>,
+    fuel: Option<u64>,
+}
+
 const DEFAULT_ADDR: std::net::SocketAddr = std::net::SocketAddr::new(
     std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0)),
     8080,
@@ -72,9 +163,172 @@ pub struct ServeCommand {
     #[clap(long = "addr", value_name = "SOCKADDR", default_value_t = DEFAULT_ADDR )]
     addr: std::net::SocketAddr,
 
+    /// Where guest stdout and stderr are sent (valid options are: inherit,
+    /// discard, log)
+    ///
+    /// `inherit` writes guest output directly to the stdout and stderr of this
+    /// process, where output from concurrent requests may be interleaved.
+    /// `discard` drops all guest output. `log` captures the output of each
+    /// request separately and emits every line as a log message under the
+    /// `wasmtime_cli::serve::guest` target, tagged with the request id, the
+    /// component name and the stream it was written to; these messages are at
+    /// the `info` level and so are only shown when enabled through `RUST_LOG`,
+    /// for example with `RUST_LOG=wasmtime_cli::serve::guest=info`.
+    #[clap(
+        long,
+        value_name = "MODE",
+        default_value = "inherit",
+        value_parser = GuestOutput::parse,
+    )]
+    guest_output: GuestOutput,
+
+    /// Socket address on which to serve metrics in the Prometheus text format.
+    ///
+    /// When specified, `GET /metrics` on this address reports, for each
+    /// route, the number of requests handled, how many of them trapped and
+    /// with which kind of trap, and histograms of request duration,
+    /// instantiation time and peak linear memory usage.
+    #[clap(long, value_name = "SOCKADDR")]
+    metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Allow requests to override their own limits with headers.
+    ///
+    /// With this flag the `wasmtime-max-memory-size` header sets the maximum
+    /// size in bytes of each linear memory and the `wasmtime-fuel` header sets
+    /// the amount of fuel given to the request, which requires `-W fuel` to be
+    /// enabled. These headers are removed before the request reaches the
+    /// guest. Only use this flag when all clients are trusted.
+    #[clap(long)]
+    trusted_limit_headers: bool,
+
+    /// Serve several components according to the routes in the given file.
+    ///
+    /// The file is TOML with one `[[route]]` table per component. Each route
+    /// names its `component` and may restrict the requests it handles with a
+    /// `host` name and a `path` prefix. Requests are dispatched to the
+    /// matching route with a `host` if there is one, and then to the one with
+    /// the longest `path`; requests which match no route receive a 404.
+    /// Routes are identified in logs and metrics by their `name`, which
+    /// defaults to the component's file name without its extension and must
+    /// be unique.
+    ///
+    /// Routes may also set `env` (a table of environment variables), `dirs`
+    /// (a list of `HOST_DIR[::GUEST_DIR]` preopens) and `max-memory-size`,
+    /// `max-table-elements`, `max-instances`, `max-tables` and
+    /// `max-memories`, which override the corresponding `-W` options for that
+    /// route. Relative paths are resolved against the directory containing the
+    /// file.
+    #[clap(long, value_name = "PATH", conflicts_with = "component")]
+    routes: Option<PathBuf>,
+
     /// The WebAssembly component to run.
-    #[clap(value_name = "WASM", required = true)]
+    #[clap(value_name = "WASM", required_unless_present = "routes")]
+    component: Option<PathBuf>,
+
+    /// The wasi-nn graphs shared by all requests, loaded once at startup.
+    #[cfg(feature = "wasi-nn")]
+    #[clap(skip)]
+    nn_graphs: Option<crate::common::NnGraphs>,
+}
+
+/// The contents of a `--routes` file.
+#[derive(serde_derive::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RoutesConfig {
+    #[serde(rename = "route", default)]
+    routes: Vec<RouteConfig>,
+}
+
+/// A single `[[route]]` within a `--routes` file.
+#[derive(serde_derive::Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct RouteConfig {
     component: PathBuf,
+    name: Option<String>,
+    host: Option<String>,
+    path: Option<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    dirs: Vec<String>,
+    max_memory_size: Option<usize>,
+    max_table_elements: Option<u32>,
+    max_instances: Option<usize>,
+    max_tables: Option<usize>,
+    max_memories: Option<usize>,
+}
+
+/// The resource limits of each request handled by a route.
+struct RouteLimits {
+    memory_size: Option<usize>,
+    table_elements: Option<u32>,
+    instances: Option<usize>,
+    tables: Option<usize>,
+    memories: Option<usize>,
+    trap_on_grow_failure: Option<bool>,
+}
+
+impl RouteLimits {
+    /// Builds the limits for a request, using `memory_size` as the maximum
+    /// size of each linear memory instead if specified.
+    fn build(&self, memory_size: Option<usize>) -> StoreLimits {
+        let mut limits = StoreLimitsBuilder::new();
+        if let Some(max) = memory_size.or(self.memory_size) {
+            limits = limits.memory_size(max);
+        }
+        if let Some(max) = self.table_elements {
+            limits = limits.table_elements(max);
+        }
+        if let Some(max) = self.instances {
+            limits = limits.instances(max);
+        }
+        if let Some(max) = self.tables {
+            limits = limits.tables(max);
+        }
+        if let Some(max) = self.memories {
+            limits = limits.memories(max);
+        }
+        if let Some(enable) = self.trap_on_grow_failure {
+            limits = limits.trap_on_grow_failure(enable);
+        }
+        limits.build()
+    }
+}
+
+/// A component mounted by `wasmtime serve` along with the configuration used
+/// to create the store for each request it handles.
+struct Route {
+    name: Arc<str>,
+    host: Option<String>,
+    path: Option<String>,
+    instance_pre: InstancePre<Host>,
+    limits: RouteLimits,
+    env: Vec<(String, String)>,
+    dirs: Vec<(String, Dir)>,
+}
+
+impl Route {
+    /// Returns how specifically this route matches a request for `path` on
+    /// `host`, or `None` if it doesn't match at all.
+    fn matches(&self, host: Option<&str>, path: &str) -> Option<(bool, usize)> {
+        if let Some(expected) = &self.host {
+            match host {
+                Some(host) if host.eq_ignore_ascii_case(expected) => {}
+                _ => return None,
+            }
+        }
+        let prefix_len = match &self.path {
+            Some(prefix) => {
+                let rest = path.strip_prefix(prefix.as_str())?;
+                if !(rest.is_empty() || rest.starts_with('/')) {
+                    return None;
+                }
+                prefix.len()
+            }
+            None => 0,
+        };
+        Some((self.host.is_some(), prefix_len))
+    }
 }
 
 impl ServeCommand {
@@ -135,17 +389,37 @@ impl ServeCommand {
         Ok(())
     }
 
-    fn new_store(&self, engine: &Engine) -> Result<Store<Host>> {
+    fn new_store(
+        &self,
+        engine: &Engine,
+        route: &Route,
+        req_id: u64,
+        overrides: &LimitOverrides,
+    ) -> Result<Store<Host>> {
         let mut builder = WasiCtxBuilder::new();
 
-        // TODO: connect stdio to logging infrastructure
+        builder.envs(&route.env);
+        for (guest, dir) in route.dirs.iter() {
+            builder.preopened_dir(dir.try_clone()?, DirPerms::all(), FilePerms::all(), guest);
+        }
+
+        match self.guest_output {
+            GuestOutput::Inherit => {
+                builder.inherit_stdout().inherit_stderr();
+            }
+            GuestOutput::Discard => {}
+            GuestOutput::Log => {
+                builder.stdout(LogStream::new(req_id, route.name.clone(), "stdout"));
+                builder.stderr(LogStream::new(req_id, route.name.clone(), "stderr"));
+            }
+        }
 
         let mut host = Host {
             table: Table::new(),
             ctx: builder.build(),
             http: WasiHttpCtx,
 
-            limits: StoreLimits::default(),
+            limiter: RequestLimiter::new(route.limits.build(overrides.max_memory_size)),
 
             #[cfg(feature = "wasi-nn")]
             nn: None,
@@ -154,16 +428,8 @@ impl ServeCommand {
         if self.run.common.wasi.nn == Some(true) {
             #[cfg(feature = "wasi-nn")]
             {
-                let graphs = self
-                    .run
-                    .common
-                    .wasi
-                    .nn_graph
-                    .iter()
-                    .map(|g| (g.format.clone(), g.dir.clone()))
-                    .collect::<Vec<_>>();
-                let (backends, registry) = wasmtime_wasi_nn::preload(&graphs)?;
-                host.nn.replace(WasiNnCtx::new(backends, registry));
+                let graphs = self.nn_graphs.as_ref().unwrap();
+                host.nn.replace(graphs.ctx());
             }
         }
 
@@ -173,18 +439,46 @@ impl ServeCommand {
             store.set_epoch_deadline(1);
         }
 
-        store.data_mut().limits = self.run.store_limits();
-        store.limiter(|t| &mut t.limits);
+        store.limiter(|t| &mut t.limiter);
 
         // If fuel has been configured, we want to add the configured
         // fuel amount to this store.
-        if let Some(fuel) = self.run.common.wasm.fuel {
+        if let Some(fuel) = overrides.fuel.or(self.run.common.wasm.fuel) {
             store.add_fuel(fuel)?;
         }
 
         Ok(store)
     }
 
+    /// Removes the limit override headers from `req`, returning the limits
+    /// they specify if `--trusted-limit-headers` is enabled.
+    fn limit_overrides(&self, req: &mut Request) -> Result<LimitOverrides> {
+        let mut overrides = LimitOverrides::default();
+        if !self.trusted_limit_headers {
+            return Ok(overrides);
+        }
+
+        let headers = req.headers_mut();
+        if let Some(value) = headers.remove(MAX_MEMORY_SIZE_HEADER) {
+            let value = value.to_str().ok().and_then(|v| v.parse().ok());
+            match value {
+                Some(max) => overrides.max_memory_size = Some(max),
+                None => bail!("invalid `{MAX_MEMORY_SIZE_HEADER}` header"),
+            }
+        }
+        if let Some(value) = headers.remove(FUEL_HEADER) {
+            if self.run.common.wasm.fuel.is_none() {
+                bail!("`{FUEL_HEADER}` header requires fuel to be enabled with `-W fuel`");
+            }
+            let value = value.to_str().ok().and_then(|v| v.parse().ok());
+            match value {
+                Some(fuel) => overrides.fuel = Some(fuel),
+                None => bail!("invalid `{FUEL_HEADER}` header"),
+            }
+        }
+        Ok(overrides)
+    }
+
     fn add_to_linker(&self, linker: &mut Linker<Host>) -> Result<()> {
         // wasi-http and the component model are implicitly enabled for `wasmtime serve`, so we
         // don't test for `self.run.common.wasi.common` or `self.run.common.wasi.http` in this
@@ -202,6 +496,130 @@ impl ServeCommand {
         Ok(())
     }
 
+    fn load_routes(&self, engine: &Engine, linker: &Linker<Host>) -> Result<Vec<Route>> {
+        let path = match &self.routes {
+            Some(path) => path,
+            None => {
+                let component = self.component.as_ref().unwrap();
+                return Ok(vec![Route {
+                    name: component_name(component),
+                    host: None,
+                    path: None,
+                    instance_pre: self.instantiate_pre(engine, linker, component)?,
+                    limits: self.route_limits(None),
+                    env: Vec::new(),
+                    dirs: Vec::new(),
+                }]);
+            }
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read routes file: {}", path.display()))?;
+        let config = toml::from_str::<RoutesConfig>(&contents)
+            .with_context(|| format!("failed to parse routes file: {}", path.display()))?;
+        if config.routes.is_empty() {
+            bail!(
+                "routes file `{}` does not define any routes",
+                path.display()
+            );
+        }
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        let mut routes = Vec::<Route>::new();
+        for route in config.routes {
+            let component = base.join(&route.component);
+            let route = self
+                .load_route(engine, linker, base, &component, route)
+                .with_context(|| format!("failed to load route for {}", component.display()))?;
+            if routes.iter().any(|r| r.name == route.name) {
+                bail!(
+                    "more than one route is named `{}`; give each route a distinct `name`",
+                    route.name
+                );
+            }
+            routes.push(route);
+        }
+        Ok(routes)
+    }
+
+    fn load_route(
+        &self,
+        engine: &Engine,
+        linker: &Linker<Host>,
+        base: &Path,
+        component: &Path,
+        config: RouteConfig,
+    ) -> Result<Route> {
+        let limits = self.route_limits(Some(&config));
+
+        let path = match config.path {
+            Some(path) if !path.starts_with('/') => {
+                bail!("route path `{path}` must start with `/`")
+            }
+            Some(path) => match path.trim_end_matches('/') {
+                "" => None,
+                trimmed => Some(trimmed.to_string()),
+            },
+            None => None,
+        };
+
+        let mut dirs = Vec::new();
+        for dir in config.dirs.iter() {
+            let (host, guest) = match dir.split_once("::") {
+                Some((host, guest)) => (host, guest),
+                None => (dir.as_str(), dir.as_str()),
+            };
+            let host = base.join(host);
+            let dir = Dir::open_ambient_dir(&host, ambient_authority())
+                .with_context(|| format!("failed to open directory '{}'", host.display()))?;
+            dirs.push((guest.to_string(), dir));
+        }
+
+        Ok(Route {
+            name: match config.name {
+                Some(name) => name.into(),
+                None => component_name(component),
+            },
+            host: config.host,
+            path,
+            instance_pre: self.instantiate_pre(engine, linker, component)?,
+            limits,
+            env: config.env.into_iter().collect(),
+            dirs,
+        })
+    }
+
+    /// Returns the limits configured for a route, falling back to those of the
+    /// `-W` options for anything it doesn't specify.
+    fn route_limits(&self, config: Option<&RouteConfig>) -> RouteLimits {
+        let wasm = &self.run.common.wasm;
+        RouteLimits {
+            memory_size: config
+                .and_then(|c| c.max_memory_size)
+                .or(wasm.max_memory_size),
+            table_elements: config
+                .and_then(|c| c.max_table_elements)
+                .or(wasm.max_table_elements),
+            instances: config.and_then(|c| c.max_instances).or(wasm.max_instances),
+            tables: config.and_then(|c| c.max_tables).or(wasm.max_tables),
+            memories: config.and_then(|c| c.max_memories).or(wasm.max_memories),
+            trap_on_grow_failure: wasm.trap_on_grow_failure,
+        }
+    }
+
+    fn instantiate_pre(
+        &self,
+        engine: &Engine,
+        linker: &Linker<Host>,
+        path: &Path,
+    ) -> Result<InstancePre<Host>> {
+        let component = match self.run.load_module(engine, path)? {
+            RunTarget::Core(_) => bail!("The serve command currently requires a component"),
+            RunTarget::Component(c) => c,
+        };
+        linker.instantiate_pre(&component)
+    }
+
     async fn serve(mut self) -> Result<()> {
         use hyper::server::conn::http1;
 
@@ -229,12 +647,12 @@ impl ServeCommand {
 
         self.add_to_linker(&mut linker)?;
 
-        let component = match self.run.load_module(&engine, &self.component)? {
-            RunTarget::Core(_) => bail!("The serve command currently requires a component"),
-            RunTarget::Component(c) => c,
-        };
+        let routes = self.load_routes(&engine, &linker)?;
 
-        let instance = linker.instantiate_pre(&component)?;
+        #[cfg(feature = "wasi-nn")]
+        if self.run.common.wasi.nn == Some(true) {
+            self.nn_graphs = Some(self.run.nn_graphs()?);
+        }
 
         let listener = tokio::net::TcpListener::bind(self.addr).await?;
 
@@ -244,7 +662,18 @@ impl ServeCommand {
             None
         };
 
-        let handler = ProxyHandler::new(self, engine, instance);
+        let metrics = Arc::new(Metrics::new(routes.iter().map(|r| r.name.clone())));
+        if let Some(addr) = self.metrics_addr {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            eprintln!(
+                "Serving metrics on http://{}/metrics",
+                listener.local_addr()?
+            );
+            tokio::task::spawn(serve_metrics(listener, metrics.clone()));
+        }
+
+        eprintln!("Serving HTTP on http://{}/", listener.local_addr()?);
+        let handler = ProxyHandler::new(self, engine, routes, metrics);
 
         loop {
             let (stream, _) = listener.accept().await?;
@@ -262,6 +691,164 @@ impl ServeCommand {
     }
 }
 
+/// Serves `GET /metrics` requests on `listener` until an error occurs.
+async fn serve_metrics(listener: tokio::net::TcpListener, metrics: Arc<Metrics>) -> Result<()> {
+    use http_body_util::Full;
+    use hyper::server::conn::http1;
+    use hyper::{Method, Response, StatusCode};
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let metrics = metrics.clone();
+        let service = hyper::service::service_fn(move |req: Request| {
+            let response = if req.method() == Method::GET && req.uri().path() == "/metrics" {
+                Response::builder()
+                    .header(
+                        hyper::header::CONTENT_TYPE,
+                        "text/plain; version=0.0.4; charset=utf-8",
+                    )
+                    .body(Full::new(bytes::Bytes::from(metrics.render())))
+            } else {
+                Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(Full::default())
+            };
+            std::future::ready(response)
+        });
+        tokio::task::spawn(async move {
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(stream, service)
+                .await
+            {
+                eprintln!("error: {e:?}");
+            }
+        });
+    }
+}
+
+/// Returns the name used to identify `path`'s component in logs.
+fn component_name(path: &Path) -> Arc<str> {
+    match path.file_stem() {
+        Some(name) => name.to_string_lossy().into(),
+        None => "component".into(),
+    }
+}
+
+/// How the output of guests is handled by `wasmtime serve`.
+#[derive(Clone, Copy)]
+enum GuestOutput {
+    Inherit,
+    Discard,
+    Log,
+}
+
+impl GuestOutput {
+    fn parse(s: &str) -> Result<GuestOutput> {
+        match s {
+            "inherit" => Ok(GuestOutput::Inherit),
+            "discard" => Ok(GuestOutput::Discard),
+            "log" => Ok(GuestOutput::Log),
+            _ => bail!("unknown guest output mode: {s}"),
+        }
+    }
+}
+
+/// Lines longer than this are emitted in pieces rather than buffered
+/// indefinitely.
+const MAX_LOG_LINE: usize = 16 * 1024;
+
+/// A guest output stream which splits what is written to it into lines and
+/// emits each line through `tracing`.
+///
+/// All streams handed out for the same guest stream share one line buffer, and
+/// any trailing partial line is emitted once the last of them is dropped.
+#[derive(Clone)]
+struct LogStream(Arc<Mutex<LineBuffer>>);
+
+struct LineBuffer {
+    req_id: u64,
+    component: Arc<str>,
+    stream: &'static str,
+    buf: Vec<u8>,
+}
+
+impl LogStream {
+    fn new(req_id: u64, component: Arc<str>, stream: &'static str) -> Self {
+        LogStream(Arc::new(Mutex::new(LineBuffer {
+            req_id,
+            component,
+            stream,
+            buf: Vec::new(),
+        })))
+    }
+}
+
+impl LineBuffer {
+    fn write(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+        while let Some(i) = self.buf.iter().position(|b| *b == b'\n') {
+            let line = self.buf.drain(..=i).collect::<Vec<_>>();
+            self.emit(&line[..i]);
+        }
+        if self.buf.len() >= MAX_LOG_LINE {
+            let line = std::mem::take(&mut self.buf);
+            self.emit(&line);
+        }
+    }
+
+    fn emit(&self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.strip_suffix('\r').unwrap_or(&line);
+        tracing::info!(
+            target: "wasmtime_cli::serve::guest",
+            req_id = self.req_id,
+            component = &*self.component,
+            stream = self.stream,
+            "{line}"
+        );
+    }
+}
+
+impl Drop for LineBuffer {
+    fn drop(&mut self) {
+        if !self.buf.is_empty() {
+            self.emit(&self.buf);
+        }
+    }
+}
+
+impl StdoutStream for LogStream {
+    fn stream(&self) -> Box<dyn HostOutputStream> {
+        Box::new(self.clone())
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+impl HostOutputStream for LogStream {
+    fn write(&mut self, bytes: bytes::Bytes) -> StreamResult<()> {
+        self.0.lock().unwrap().write(&bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        // Complete lines are emitted as soon as they're written, and partial
+        // lines are held until they're completed.
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(usize::MAX)
+    }
+}
+
+#[async_trait::async_trait]
+impl Subscribe for LogStream {
+    async fn ready(&mut self) {}
+}
+
 struct EpochThread {
     shutdown: Arc<AtomicBool>,
     handle: Option<std::thread::JoinHandle<()>>,
@@ -297,63 +884,179 @@ impl Drop for EpochThread {
 struct ProxyHandlerInner {
     cmd: ServeCommand,
     engine: Engine,
-    instance_pre: InstancePre<Host>,
+    routes: Vec<Route>,
+    metrics: Arc<Metrics>,
+    next_id: AtomicU64,
+}
+
+impl ProxyHandlerInner {
+    fn next_req_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Selects the route which handles `req`, preferring routes for a
+    /// specific host and then the longest matching path prefix.
+    fn route(&self, req: &Request) -> Option<usize> {
+        let host = req
+            .headers()
+            .get(hyper::header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .or_else(|| req.uri().host())
+            .map(|h| match h.rsplit_once(':') {
+                Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+                _ => h,
+            });
+        let path = req.uri().path();
+        self.routes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, route)| Some((route.matches(host, path)?, i)))
+            .max_by_key(|(specificity, i)| (*specificity, std::cmp::Reverse(*i)))
+            .map(|(_, i)| i)
+    }
 }
 
 #[derive(Clone)]
 struct ProxyHandler(Arc<ProxyHandlerInner>);
 
 impl ProxyHandler {
-    fn new(cmd: ServeCommand, engine: Engine, instance_pre: InstancePre<Host>) -> Self {
+    fn new(cmd: ServeCommand, engine: Engine, routes: Vec<Route>, metrics: Arc<Metrics>) -> Self {
         Self(Arc::new(ProxyHandlerInner {
             cmd,
             engine,
-            instance_pre,
+            routes,
+            metrics,
+            next_id: AtomicU64::from(0),
         }))
     }
 }
 
 type Request = hyper::Request<hyper::body::Incoming>;
 
+/// Creates a response to a request which the host answers on its own rather
+/// than passing it on to a guest.
+fn host_response(
+    status: hyper::StatusCode,
+    body: String,
+) -> Result<hyper::Response<HyperOutgoingBody>> {
+    use http_body_util::BodyExt;
+
+    let body = http_body_util::Full::new(bytes::Bytes::from(body))
+        .map_err(|never| match never {})
+        .boxed();
+    Ok(hyper::Response::builder().status(status).body(body)?)
+}
+
 impl hyper::service::Service<Request> for ProxyHandler {
     type Response = hyper::Response<HyperOutgoingBody>;
     type Error = anyhow::Error;
     type Future = Pin<Box<dyn std::future::Future<Output = Result<Self::Response>> + Send>>;
 
-    fn call(&mut self, req: Request) -> Self::Future {
+    fn call(&mut self, mut req: Request) -> Self::Future {
         use http_body_util::BodyExt;
 
         let handler = self.clone();
+        let req_id = handler.0.next_req_id();
+
+        let index = match handler.0.route(&req) {
+            Some(index) => index,
+            None => {
+                let resp = host_response(hyper::StatusCode::NOT_FOUND, String::new());
+                return Box::pin(std::future::ready(resp));
+            }
+        };
+
+        let overrides = match handler.0.cmd.limit_overrides(&mut req) {
+            Ok(overrides) => overrides,
+            Err(e) => {
+                let resp = host_response(hyper::StatusCode::BAD_REQUEST, format!("{e}\n"));
+                return Box::pin(std::future::ready(resp));
+            }
+        };
 
         let (sender, receiver) = tokio::sync::oneshot::channel();
 
         // TODO: need to track the join handle, but don't want to block the response on it
         tokio::task::spawn(async move {
-            let mut store = handler.0.cmd.new_store(&handler.0.engine)?;
-
-            let req = store.data_mut().new_incoming_request(
-                req.map(|body| body.map_err(|e| anyhow::anyhow!(e)).boxed()),
-            )?;
-
-            let out = store.data_mut().new_response_outparam(sender)?;
+            let route = &handler.0.routes[index];
+            let metrics = handler.0.metrics.route(index);
+            metrics.request();
+
+            let start = Instant::now();
+            let mut store =
+                match handler
+                    .0
+                    .cmd
+                    .new_store(&handler.0.engine, route, req_id, &overrides)
+                {
+                    Ok(store) => store,
+                    Err(e) => {
+                        let result = Err(e);
+                        metrics.finished(start.elapsed(), 0, &result);
+                        return result;
+                    }
+                };
+
+            let result = async {
+                let req = store.data_mut().new_incoming_request(
+                    req.map(|body| body.map_err(|e| anyhow::anyhow!(e)).boxed()),
+                )?;
+
+                let out = store.data_mut().new_response_outparam(sender)?;
+
+                let instantiate_start = Instant::now();
+                let (proxy, _inst) = wasmtime_wasi_http::proxy::Proxy::instantiate_pre(
+                    &mut store,
+                    &route.instance_pre,
+                )
+                .await?;
+                metrics.instantiated(instantiate_start.elapsed());
 
-            let (proxy, _inst) = wasmtime_wasi_http::proxy::Proxy::instantiate_pre(
-                &mut store,
-                &handler.0.instance_pre,
-            )
-            .await?;
+                proxy
+                    .wasi_http_incoming_handler()
+                    .call_handle(&mut store, req, out)
+                    .await?;
 
-            proxy
-                .wasi_http_incoming_handler()
-                .call_handle(store, req, out)
-                .await?;
+                Ok::<_, anyhow::Error>(())
+            }
+            .await;
 
-            Ok::<_, anyhow::Error>(())
+            let peak_memory = store.data().limiter.peak_memory;
+            metrics.finished(start.elapsed(), peak_memory, &result);
+            result
         });
 
         Box::pin(async move {
-            let resp = receiver.await.unwrap()?;
-            Ok(resp)
+            match receiver.await {
+                Ok(resp) => Ok(resp?),
+                // The guest never set a response, for example because it
+                // exceeded its limits during instantiation or trapped.
+                Err(_) => host_response(hyper::StatusCode::INTERNAL_SERVER_ERROR, String::new()),
+            }
         })
     }
 }
+
+#[cfg(all(test, not(miri)))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limiter_tracks_memory() -> Result<()> {
+        let mut limiter = RequestLimiter::new(StoreLimitsBuilder::new().memory_size(4096).build());
+
+        assert!(limiter.memory_growing(0, 1024, None)?);
+        assert!(limiter.memory_growing(1024, 3072, None)?);
+        assert_eq!((limiter.memory, limiter.peak_memory), (3072, 3072));
+        assert!(!limiter.memory_growing(3072, 8192, None)?);
+        assert_eq!((limiter.memory, limiter.peak_memory), (3072, 3072));
+
+        // A growth which the limiter allowed but which then failed is no
+        // longer counted against the request.
+        assert!(limiter.memory_growing(3072, 4096, None)?);
+        assert_eq!((limiter.memory, limiter.peak_memory), (4096, 4096));
+        limiter.memory_grow_failed(anyhow::anyhow!("out of memory"))?;
+        assert_eq!((limiter.memory, limiter.peak_memory), (3072, 3072));
+        Ok(())
+    }
+}
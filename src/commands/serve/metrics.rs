@@ -0,0 +1,275 @@
+//! Metrics collected by `wasmtime serve` and their exposition in the
+//! Prometheus text format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wasmtime::Trap;
+
+/// Upper bounds, in seconds, of the buckets used for duration histograms.
+const DURATION_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Upper bounds, in bytes, of the buckets used for memory histograms.
+const MEMORY_BUCKETS: &[f64] = &[
+    65536.0,
+    1048576.0,
+    4194304.0,
+    16777216.0,
+    67108864.0,
+    268435456.0,
+    1073741824.0,
+    4294967296.0,
+];
+
+/// A cumulative histogram of observed values.
+struct Histogram {
+    bounds: &'static [f64],
+    /// The number of observations in each bucket, with one extra bucket at
+    /// the end for `+Inf`.
+    counts: Vec<AtomicU64>,
+    sum: Mutex<f64>,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: Mutex::new(0.0),
+        }
+    }
+
+    fn observe(&self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        *self.sum.lock().unwrap() += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut total = 0;
+        for (i, count) in self.counts.iter().enumerate() {
+            total += count.load(Ordering::Relaxed);
+            let le = match self.bounds.get(i) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{le}\"}} {total}");
+        }
+        let sum = *self.sum.lock().unwrap();
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {sum}");
+        let _ = writeln!(out, "{name}_count{{{labels}}} {total}");
+    }
+}
+
+/// Metrics for the requests handled by a single route.
+pub struct RouteMetrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    traps: Mutex<BTreeMap<String, u64>>,
+    duration: Histogram,
+    instantiation: Histogram,
+    peak_memory: Histogram,
+}
+
+impl RouteMetrics {
+    fn new() -> RouteMetrics {
+        RouteMetrics {
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            traps: Mutex::new(BTreeMap::new()),
+            duration: Histogram::new(DURATION_BUCKETS),
+            instantiation: Histogram::new(DURATION_BUCKETS),
+            peak_memory: Histogram::new(MEMORY_BUCKETS),
+        }
+    }
+
+    /// Records the start of a request.
+    pub fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records how long it took to instantiate the component for a request.
+    pub fn instantiated(&self, duration: Duration) {
+        self.instantiation.observe(duration.as_secs_f64());
+    }
+
+    /// Records the completion of a request, successful or not, along with the
+    /// peak amount of linear memory it used.
+    pub fn finished(&self, duration: Duration, peak_memory: usize, result: &anyhow::Result<()>) {
+        self.duration.observe(duration.as_secs_f64());
+        self.peak_memory.observe(peak_memory as f64);
+        if let Err(e) = result {
+            match e.downcast_ref::<Trap>() {
+                Some(trap) => {
+                    *self
+                        .traps
+                        .lock()
+                        .unwrap()
+                        .entry(trap_kind(*trap))
+                        .or_insert(0) += 1;
+                }
+                None => {
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the `kind` label used for `trap`.
+fn trap_kind(trap: Trap) -> String {
+    match trap {
+        Trap::OutOfFuel => "out_of_fuel".to_string(),
+        Trap::Interrupt => "epoch_timeout".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// Metrics for all routes of a `wasmtime serve` instance.
+pub struct Metrics {
+    routes: Vec<(Arc<str>, RouteMetrics)>,
+}
+
+impl Metrics {
+    /// Creates metrics for routes with the given names, which are indexed in
+    /// the same order by [`Metrics::route`].
+    pub fn new(names: impl IntoIterator<Item = Arc<str>>) -> Metrics {
+        Metrics {
+            routes: names
+                .into_iter()
+                .map(|name| (name, RouteMetrics::new()))
+                .collect(),
+        }
+    }
+
+    pub fn route(&self, index: usize) -> &RouteMetrics {
+        &self.routes[index].1
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP wasmtime_serve_requests_total Requests handled by each route.\n");
+        out.push_str("# TYPE wasmtime_serve_requests_total counter\n");
+        for (name, route) in self.routes.iter() {
+            let count = route.requests.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "wasmtime_serve_requests_total{{route=\"{}\"}} {count}",
+                escape(name)
+            );
+        }
+
+        out.push_str(
+            "# HELP wasmtime_serve_request_errors_total Requests which failed with an error other than a trap.\n",
+        );
+        out.push_str("# TYPE wasmtime_serve_request_errors_total counter\n");
+        for (name, route) in self.routes.iter() {
+            let count = route.errors.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "wasmtime_serve_request_errors_total{{route=\"{}\"}} {count}",
+                escape(name)
+            );
+        }
+
+        out.push_str(
+            "# HELP wasmtime_serve_traps_total Requests which trapped, by kind of trap.\n",
+        );
+        out.push_str("# TYPE wasmtime_serve_traps_total counter\n");
+        for (name, route) in self.routes.iter() {
+            for (kind, count) in route.traps.lock().unwrap().iter() {
+                let _ = writeln!(
+                    out,
+                    "wasmtime_serve_traps_total{{route=\"{}\",kind=\"{}\"}} {count}",
+                    escape(name),
+                    escape(kind)
+                );
+            }
+        }
+
+        let histograms: [(&str, &str, fn(&RouteMetrics) -> &Histogram); 3] = [
+            (
+                "wasmtime_serve_request_duration_seconds",
+                "Time taken to handle each request.",
+                |r| &r.duration,
+            ),
+            (
+                "wasmtime_serve_instantiation_duration_seconds",
+                "Time taken to instantiate the component for each request.",
+                |r| &r.instantiation,
+            ),
+            (
+                "wasmtime_serve_request_peak_memory_bytes",
+                "Peak linear memory used by each request.",
+                |r| &r.peak_memory,
+            ),
+        ];
+        for (metric, help, histogram) in histograms {
+            let _ = writeln!(out, "# HELP {metric} {help}");
+            let _ = writeln!(out, "# TYPE {metric} histogram");
+            for (name, route) in self.routes.iter() {
+                let labels = format!("route=\"{}\"", escape(name));
+                histogram(route).render(&mut out, metric, &labels);
+            }
+        }
+
+        out
+    }
+}
+
+/// Escapes a label value for the Prometheus text format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(all(test, not(miri)))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::new(["a".into(), "b\"".into()]);
+        let a = metrics.route(0);
+        a.request();
+        a.instantiated(Duration::from_millis(2));
+        a.finished(Duration::from_millis(3), 100_000, &Ok(()));
+        a.request();
+        a.finished(Duration::from_secs(20), 0, &Err(Trap::OutOfFuel.into()));
+        a.request();
+        a.finished(Duration::ZERO, 0, &Err(anyhow::anyhow!("failed")));
+
+        let out = metrics.render();
+        let lines = out.lines().collect::<Vec<_>>();
+        for expected in [
+            "wasmtime_serve_requests_total{route=\"a\"} 3",
+            "wasmtime_serve_requests_total{route=\"b\\\"\"} 0",
+            "wasmtime_serve_request_errors_total{route=\"a\"} 1",
+            "wasmtime_serve_traps_total{route=\"a\",kind=\"out_of_fuel\"} 1",
+            "wasmtime_serve_request_duration_seconds_bucket{route=\"a\",le=\"0.0005\"} 1",
+            "wasmtime_serve_request_duration_seconds_bucket{route=\"a\",le=\"0.005\"} 2",
+            "wasmtime_serve_request_duration_seconds_bucket{route=\"a\",le=\"10\"} 2",
+            "wasmtime_serve_request_duration_seconds_bucket{route=\"a\",le=\"+Inf\"} 3",
+            "wasmtime_serve_request_duration_seconds_count{route=\"a\"} 3",
+            "wasmtime_serve_instantiation_duration_seconds_count{route=\"a\"} 1",
+            "wasmtime_serve_request_peak_memory_bytes_bucket{route=\"a\",le=\"65536\"} 2",
+            "wasmtime_serve_request_peak_memory_bytes_bucket{route=\"a\",le=\"1048576\"} 3",
+            "wasmtime_serve_request_peak_memory_bytes_sum{route=\"a\"} 100000",
+            "wasmtime_serve_request_peak_memory_bytes_count{route=\"b\\\"\"} 0",
+        ] {
+            assert!(lines.contains(&expected), "missing `{expected}` in:\n{out}");
+        }
+    }
+}
@@ -97,6 +97,35 @@ impl RunCommon {
         limits.build()
     }
 
+    /// Loads the wasi-nn graphs configured with `nn-graph`, or prepares to
+    /// load them on demand from `nn-graph-dir`.
+    #[cfg(feature = "wasi-nn")]
+    pub fn nn_graphs(&self) -> Result<NnGraphs> {
+        let wasi = &self.common.wasi;
+        match &wasi.nn_graph_dir {
+            Some(dir) => {
+                if !wasi.nn_graph.is_empty() {
+                    bail!("`nn-graph` and `nn-graph-dir` cannot be used together");
+                }
+                let budget = wasi.nn_graph_budget.unwrap_or(usize::MAX);
+                let registry = wasmtime_wasi_nn::lazy(&dir.format, &dir.dir, budget)?;
+                Ok(NnGraphs::Lazy(registry))
+            }
+            None => {
+                if wasi.nn_graph_budget.is_some() {
+                    bail!("`nn-graph-budget` requires `nn-graph-dir`");
+                }
+                let graphs = wasi
+                    .nn_graph
+                    .iter()
+                    .map(|g| (g.format.clone(), g.dir.clone()))
+                    .collect::<Vec<_>>();
+                let (_, registry) = wasmtime_wasi_nn::preload(&graphs)?;
+                Ok(NnGraphs::Preloaded(registry))
+            }
+        }
+    }
+
     pub fn ensure_allow_precompiled(&self) -> Result<()> {
         if self.allow_precompiled {
             Ok(())
@@ -115,6 +144,25 @@ impl RunCommon {
     }
 
     pub fn load_module(&self, engine: &Engine, path: &Path) -> Result<RunTarget> {
+        Ok(self.load(engine, path, false)?.0)
+    }
+
+    /// Like [`RunCommon::load_module`], but also returns the WebAssembly
+    /// binary the module was compiled from, or `None` if it was precompiled.
+    pub fn load_module_and_binary(
+        &self,
+        engine: &Engine,
+        path: &Path,
+    ) -> Result<(RunTarget, Option<Vec<u8>>)> {
+        self.load(engine, path, true)
+    }
+
+    fn load(
+        &self,
+        engine: &Engine,
+        path: &Path,
+        keep_binary: bool,
+    ) -> Result<(RunTarget, Option<Vec<u8>>)> {
         let path = match path.to_str() {
             #[cfg(unix)]
             Some("-") => "/dev/stdin".as_ref(),
@@ -144,6 +192,7 @@ impl RunCommon {
                 engine,
                 path,
                 &map,
+                keep_binary,
                 || unsafe { Module::deserialize_file(engine, path) },
                 #[cfg(feature = "component-model")]
                 || unsafe { Component::deserialize_file(engine, path) },
@@ -155,6 +204,7 @@ impl RunCommon {
                     engine,
                     path,
                     &bytes,
+                    keep_binary,
                     || unsafe { Module::deserialize(engine, &bytes) },
                     #[cfg(feature = "component-model")]
                     || unsafe { Component::deserialize(engine, &bytes) },
@@ -163,24 +213,28 @@ impl RunCommon {
         }
     }
 
+    /// Loads the module or component in `bytes`, read from `path`, also
+    /// returning the WebAssembly binary it was compiled from if
+    /// `keep_binary` is set and it wasn't precompiled.
     pub fn load_module_contents(
         &self,
         engine: &Engine,
         path: &Path,
         bytes: &[u8],
+        keep_binary: bool,
         deserialize_module: impl FnOnce() -> Result<Module>,
         #[cfg(feature = "component-model")] deserialize_component: impl FnOnce() -> Result<Component>,
-    ) -> Result<RunTarget> {
+    ) -> Result<(RunTarget, Option<Vec<u8>>)> {
         Ok(match engine.detect_precompiled(bytes) {
             Some(Precompiled::Module) => {
                 self.ensure_allow_precompiled()?;
-                RunTarget::Core(deserialize_module()?)
+                (RunTarget::Core(deserialize_module()?), None)
             }
             #[cfg(feature = "component-model")]
             Some(Precompiled::Component) => {
                 self.ensure_allow_precompiled()?;
                 self.ensure_allow_components()?;
-                RunTarget::Component(deserialize_component()?)
+                (RunTarget::Component(deserialize_component()?), None)
             }
             #[cfg(not(feature = "component-model"))]
             Some(Precompiled::Component) => {
@@ -193,7 +247,7 @@ impl RunCommon {
                     e.set_path(path);
                     e
                 })?;
-                if wasmparser::Parser::is_component(&wasm) {
+                let target = if wasmparser::Parser::is_component(&wasm) {
                     #[cfg(feature = "component-model")]
                     {
                         self.ensure_allow_components()?;
@@ -205,12 +259,38 @@ impl RunCommon {
                     }
                 } else {
                     RunTarget::Core(Module::new(engine, &wasm)?)
-                }
+                };
+                (target, keep_binary.then(|| wasm.into_owned()))
             }
         })
     }
 }
 
+/// The wasi-nn graphs available to guests, which are shared by every
+/// [`WasiNnCtx`](wasmtime_wasi_nn::WasiNnCtx) created from them.
+#[cfg(feature = "wasi-nn")]
+#[derive(Clone)]
+pub enum NnGraphs {
+    Preloaded(wasmtime_wasi_nn::InMemoryRegistry),
+    Lazy(wasmtime_wasi_nn::LazyRegistry),
+}
+
+#[cfg(feature = "wasi-nn")]
+impl NnGraphs {
+    /// Creates a context for a new store using these graphs.
+    pub fn ctx(&self) -> wasmtime_wasi_nn::WasiNnCtx {
+        let backends = wasmtime_wasi_nn::backend::list();
+        match self {
+            NnGraphs::Preloaded(registry) => {
+                wasmtime_wasi_nn::WasiNnCtx::new(backends, registry.clone())
+            }
+            NnGraphs::Lazy(registry) => {
+                wasmtime_wasi_nn::WasiNnCtx::new(backends, registry.clone())
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Profile {
     Native(wasmtime::ProfilingStrategy),
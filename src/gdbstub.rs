@@ -0,0 +1,351 @@
+//! Support for debugging guests over the GDB remote serial protocol, enabled
+//! with `-D gdbstub=PORT`.
+
+use anyhow::{bail, Context as _, Result};
+use std::convert::TryInto;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use wasmparser::{Parser, Payload, TypeRef};
+use wasmtime::{DebugFrame, DebugStep, Module, Store, StoreContextMut, Val, WasmBacktrace};
+use wasmtime_jit_debug::gdbstub::{GdbStub, Resume, Target, WasmAddr, WasmAddrSpace};
+
+/// The signal reported to the debugger when a guest traps.
+const SIGABRT: u8 = 6;
+
+/// A debugger connected to the guests of a store.
+#[derive(Clone)]
+pub struct Debugger {
+    inner: Arc<Mutex<DebuggerInner>>,
+}
+
+struct DebuggerInner {
+    stub: GdbStub<TcpStream>,
+    modules: Vec<DebugModule>,
+    detached: bool,
+}
+
+/// A module known to the debugger, whose id is its index in
+/// `DebuggerInner::modules`.
+struct DebugModule {
+    name: String,
+    module: Module,
+    binary: Vec<u8>,
+    num_imported_funcs: u32,
+    /// The range of the body of each defined function within `binary`.
+    bodies: Vec<Range<u32>>,
+}
+
+impl Debugger {
+    /// Waits for a debugger to connect to the local TCP `port` and lets it
+    /// debug the guests of `store`, starting from their first instruction.
+    pub fn attach<T>(store: &mut Store<T>, port: u16) -> Result<Debugger> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .with_context(|| format!("failed to bind to port {port} for the debugger"))?;
+        eprintln!(
+            "Waiting for a debugger to connect to {}",
+            listener.local_addr()?
+        );
+        let (conn, _) = listener
+            .accept()
+            .context("failed to accept a connection from the debugger")?;
+        conn.set_nodelay(true)?;
+
+        let debugger = Debugger {
+            inner: Arc::new(Mutex::new(DebuggerInner {
+                stub: GdbStub::new(conn),
+                modules: Vec::new(),
+                detached: false,
+            })),
+        };
+        let inner = debugger.inner.clone();
+        store.debug_handler(move |store, frame| inner.lock().unwrap().paused(store, frame));
+        store.debug_single_step(true);
+        Ok(debugger)
+    }
+
+    /// Makes `module`, loaded from `path` and compiled from `binary`, known to
+    /// the debugger.
+    pub fn add_module(&self, path: &Path, binary: Vec<u8>, module: &Module) -> Result<()> {
+        let mut num_imported_funcs = 0;
+        let mut bodies = Vec::new();
+        for payload in Parser::new(0).parse_all(&binary) {
+            match payload? {
+                Payload::ImportSection(imports) => {
+                    for import in imports {
+                        if let TypeRef::Func(_) = import?.ty {
+                            num_imported_funcs += 1;
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let range = body.range();
+                    bodies.push(range.start.try_into()?..range.end.try_into()?);
+                }
+                _ => {}
+            }
+        }
+        self.inner.lock().unwrap().modules.push(DebugModule {
+            name: path.display().to_string(),
+            module: module.clone(),
+            binary,
+            num_imported_funcs,
+            bodies,
+        });
+        Ok(())
+    }
+
+    /// Reports to the debugger how the guests finished running.
+    pub fn finish(&self, result: &Result<()>) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.detached {
+            return;
+        }
+        let exit_code = match result {
+            Ok(()) => Some(0),
+            Err(e) => e
+                .downcast_ref::<wasmtime_wasi::I32Exit>()
+                .map(|e| e.0)
+                .or_else(|| {
+                    e.downcast_ref::<wasmtime_wasi::preview2::I32Exit>()
+                        .map(|e| e.0)
+                }),
+        };
+        // The debugger going away at this point is of no consequence.
+        let _ = match exit_code {
+            Some(code) => inner.stub.exited(code as u8),
+            None => inner.stub.terminated(SIGABRT),
+        };
+    }
+}
+
+impl DebuggerInner {
+    fn paused<T>(
+        &mut self,
+        store: StoreContextMut<'_, T>,
+        frame: &mut DebugFrame<'_>,
+    ) -> Result<DebugStep> {
+        if self.detached {
+            return Ok(DebugStep::Continue);
+        }
+        let mut guest = PausedGuest {
+            store,
+            frame,
+            modules: &self.modules,
+        };
+        let resume = self
+            .stub
+            .stopped(&mut guest)
+            .context("failed to communicate with the debugger")?;
+        match resume {
+            Resume::Continue => Ok(DebugStep::Continue),
+            Resume::Step => Ok(DebugStep::Step),
+            Resume::Detach => {
+                self.detached = true;
+                Ok(DebugStep::Continue)
+            }
+            Resume::Kill => {
+                self.detached = true;
+                bail!("killed by the debugger")
+            }
+        }
+    }
+}
+
+/// The guest as seen by the debugger while it's paused at `frame`.
+struct PausedGuest<'a, 'f, T> {
+    store: StoreContextMut<'a, T>,
+    frame: &'a mut DebugFrame<'f>,
+    modules: &'a [DebugModule],
+}
+
+impl<T> PausedGuest<'_, '_, T> {
+    fn module_id(&self, module: &Module) -> Option<u32> {
+        let id = self
+            .modules
+            .iter()
+            .position(|m| Module::same(&m.module, module))?;
+        Some(id as u32)
+    }
+
+    fn pc(&self) -> Option<WasmAddr> {
+        let module = self.frame.instance().module(&self.store);
+        let id = self.module_id(module)?;
+        let module = &self.modules[id as usize];
+        let body = module.bodies.get(
+            self.frame
+                .func_index()
+                .checked_sub(module.num_imported_funcs)? as usize,
+        )?;
+        let offset = body.start + u32::try_from(self.frame.func_offset()).ok()?;
+        Some(WasmAddr::code(id, offset))
+    }
+
+    /// Returns the function index and offset within it of the code address
+    /// `addr`.
+    fn func_location(&self, addr: WasmAddr) -> Option<(&DebugModule, u32, usize)> {
+        if addr.space != WasmAddrSpace::Code {
+            return None;
+        }
+        let module = self.modules.get(addr.module as usize)?;
+        let i = module
+            .bodies
+            .iter()
+            .position(|body| body.contains(&addr.offset))?;
+        let func_offset = addr.offset - module.bodies[i].start;
+        Some((
+            module,
+            module.num_imported_funcs + i as u32,
+            func_offset as usize,
+        ))
+    }
+
+    /// Returns the memory range of the paused frame's instance which `addr`
+    /// and `len` refer to, clamped to its memory.
+    fn memory_range(
+        &mut self,
+        addr: WasmAddr,
+        len: usize,
+    ) -> Option<(wasmtime::Memory, Range<usize>)> {
+        if addr.space != WasmAddrSpace::Memory || Some(addr.module) != self.pc().map(|pc| pc.module)
+        {
+            return None;
+        }
+        let memory = self.frame.memory(&mut self.store, 0)?;
+        let size = memory.data_size(&self.store);
+        let start = addr.offset as usize;
+        if start >= size {
+            return None;
+        }
+        Some((memory, start..size.min(start.saturating_add(len))))
+    }
+}
+
+impl<T> Target for PausedGuest<'_, '_, T> {
+    fn modules(&mut self) -> Vec<String> {
+        self.modules.iter().map(|m| m.name.clone()).collect()
+    }
+
+    fn call_stack(&mut self) -> Vec<WasmAddr> {
+        let mut stack = self.pc().into_iter().collect::<Vec<_>>();
+        let backtrace = WasmBacktrace::force_capture(&self.store);
+        for frame in backtrace.frames().iter().skip(1) {
+            let id = self.module_id(frame.module());
+            let offset = frame.module_offset().and_then(|o| u32::try_from(o).ok());
+            if let (Some(id), Some(offset)) = (id, offset) {
+                stack.push(WasmAddr::code(id, offset));
+            }
+        }
+        stack
+    }
+
+    // Only the locals of the paused frame are available.
+
+    fn num_locals(&mut self, frame: usize) -> usize {
+        if frame == 0 {
+            self.frame.num_locals()
+        } else {
+            0
+        }
+    }
+
+    fn local(&mut self, frame: usize, index: usize) -> Option<Vec<u8>> {
+        if frame != 0 {
+            return None;
+        }
+        val_to_bytes(&self.frame.local(index)?)
+    }
+
+    fn set_local(&mut self, frame: usize, index: usize, value: &[u8]) -> bool {
+        if frame != 0 {
+            return false;
+        }
+        let val = self
+            .frame
+            .local(index)
+            .and_then(|current| val_from_bytes(&current, value));
+        match val {
+            Some(val) => self.frame.set_local(index, val).is_ok(),
+            None => false,
+        }
+    }
+
+    fn global(&mut self, frame: usize, index: usize) -> Option<Vec<u8>> {
+        if frame != 0 {
+            return None;
+        }
+        let global = self
+            .frame
+            .global(&mut self.store, u32::try_from(index).ok()?)?;
+        val_to_bytes(&global.get(&mut self.store))
+    }
+
+    fn read(&mut self, addr: WasmAddr, len: usize) -> Option<Vec<u8>> {
+        match addr.space {
+            WasmAddrSpace::Code => {
+                let module = self.modules.get(addr.module as usize)?;
+                let data = module.binary.get(addr.offset as usize..)?;
+                let data = &data[..data.len().min(len)];
+                (!data.is_empty()).then(|| data.to_vec())
+            }
+            WasmAddrSpace::Memory => {
+                let (memory, range) = self.memory_range(addr, len)?;
+                Some(memory.data(&self.store)[range].to_vec())
+            }
+        }
+    }
+
+    fn write(&mut self, addr: WasmAddr, data: &[u8]) -> bool {
+        match self.memory_range(addr, data.len()) {
+            Some((memory, range)) if range.len() == data.len() => {
+                memory.data_mut(&mut self.store)[range].copy_from_slice(data);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn add_breakpoint(&mut self, addr: WasmAddr) -> bool {
+        match self.func_location(addr) {
+            Some((module, func, offset)) => {
+                let module = module.module.clone();
+                self.store.add_breakpoint(&module, func, offset).is_ok()
+            }
+            None => false,
+        }
+    }
+
+    fn remove_breakpoint(&mut self, addr: WasmAddr) -> bool {
+        match self.func_location(addr) {
+            Some((module, func, offset)) => {
+                let module = module.module.clone();
+                self.store.remove_breakpoint(&module, func, offset)
+            }
+            None => false,
+        }
+    }
+}
+
+fn val_to_bytes(val: &Val) -> Option<Vec<u8>> {
+    Some(match val {
+        Val::I32(v) => v.to_le_bytes().to_vec(),
+        Val::I64(v) => v.to_le_bytes().to_vec(),
+        Val::F32(v) => v.to_le_bytes().to_vec(),
+        Val::F64(v) => v.to_le_bytes().to_vec(),
+        Val::V128(v) => v.as_u128().to_le_bytes().to_vec(),
+        _ => return None,
+    })
+}
+
+/// Decodes `bytes` as a value of the same type as `current`.
+fn val_from_bytes(current: &Val, bytes: &[u8]) -> Option<Val> {
+    Some(match current {
+        Val::I32(_) => Val::I32(i32::from_le_bytes(bytes.try_into().ok()?)),
+        Val::I64(_) => Val::I64(i64::from_le_bytes(bytes.try_into().ok()?)),
+        Val::F32(_) => Val::F32(u32::from_le_bytes(bytes.try_into().ok()?)),
+        Val::F64(_) => Val::F64(u64::from_le_bytes(bytes.try_into().ok()?)),
+        Val::V128(_) => Val::V128(u128::from_le_bytes(bytes.try_into().ok()?).into()),
+        _ => return None,
+    })
+}
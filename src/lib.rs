@@ -13,3 +13,5 @@
 pub mod commands;
 
 pub(crate) mod common;
+#[cfg(feature = "gdbstub")]
+pub(crate) mod gdbstub;
@@ -0,0 +1,306 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (global $g (mut i32) (i32.const 0))
+        (global $f (mut funcref) (ref.null func))
+        (table $t 1 funcref)
+        (func $one (result i32) i32.const 1)
+        (func $two (result i32) i32.const 2)
+        (elem declare func $one $two)
+        (func (export "run")
+            (i32.store (i32.const 100) (i32.add (i32.load (i32.const 100)) (i32.const 1)))
+            (drop (memory.grow (i32.const 1)))
+            (i32.store8 (i32.const 70000) (i32.const 7))
+            (global.set $g (i32.add (global.get $g) (i32.const 10)))
+            (global.set $f (ref.func $two))
+            (drop (table.grow $t (ref.func $one) (i32.const 1))))
+        (func (export "g") (result i32) global.get $g)
+        (func (export "call_table") (param i32) (result i32)
+            (call_indirect $t (result i32) (local.get 0)))
+        (func (export "call_global") (result i32)
+            (table.set $t (i32.const 0) (global.get $f))
+            (call_indirect $t (result i32) (i32.const 0)))
+    )
+"#;
+
+fn instantiate(engine: &Engine) -> Result<(Store<()>, Instance)> {
+    let mut store = Store::new(engine, ());
+    let module = Module::new(engine, WAT)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    Ok((store, instance))
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn checkpoint_round_trip() -> Result<()> {
+    let engine = Engine::default();
+    let (mut store, instance) = instantiate(&engine)?;
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    let bytes = store.checkpoint()?.serialize()?;
+
+    // Restoring into a store which has only instantiated the module
+    // reproduces the state after `run`.
+    let (mut store, instance) = instantiate(&engine)?;
+    store.restore(&Checkpoint::deserialize(&bytes)?)?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(memory.size(&store), 2);
+    assert_eq!(memory.data(&store)[100], 1);
+    assert_eq!(memory.data(&store)[70000], 7);
+    let g = instance.get_typed_func::<(), i32>(&mut store, "g")?;
+    assert_eq!(g.call(&mut store, ())?, 10);
+    let call_table = instance.get_typed_func::<i32, i32>(&mut store, "call_table")?;
+    assert_eq!(call_table.call(&mut store, 1)?, 1);
+    let call_global = instance.get_typed_func::<(), i32>(&mut store, "call_global")?;
+    assert_eq!(call_global.call(&mut store, ())?, 2);
+
+    // Restoring into a store which has run further rewinds it.
+    let (mut other, instance) = instantiate(&engine)?;
+    let run = instance.get_typed_func::<(), ()>(&mut other, "run")?;
+    run.call(&mut other, ())?;
+    run.call(&mut other, ())?;
+    let err = other
+        .restore(&Checkpoint::deserialize(&bytes)?)
+        .unwrap_err();
+    assert!(err.to_string().contains("larger"), "{err}");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn checkpoint_fuel_and_host_state() -> Result<()> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+    let (mut store, _) = instantiate(&engine)?;
+    store.add_fuel(1000)?;
+    store.consume_fuel(300)?;
+    let mut checkpoint = store.checkpoint()?;
+    checkpoint.set_host_state(b"state".to_vec());
+    let checkpoint = Checkpoint::deserialize(&checkpoint.serialize()?)?;
+    assert_eq!(checkpoint.host_state(), b"state");
+
+    let (mut store, _) = instantiate(&engine)?;
+    store.add_fuel(5)?;
+    store.restore(&checkpoint)?;
+    assert_eq!(store.fuel_remaining(), Some(700));
+
+    let (mut store, _) = instantiate(&Engine::default())?;
+    let err = store.restore(&checkpoint).unwrap_err();
+    assert!(err.to_string().contains("fuel"), "{err}");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn checkpoint_mismatch() -> Result<()> {
+    let engine = Engine::default();
+    let (mut store, _) = instantiate(&engine)?;
+    let checkpoint = store.checkpoint()?;
+
+    let mut empty = Store::new(&engine, ());
+    let err = empty.restore(&checkpoint).unwrap_err();
+    assert!(err.to_string().contains("instances"), "{err}");
+
+    let mut other = Store::new(&engine, ());
+    let module = Module::new(&engine, "(module (memory 1))")?;
+    Instance::new(&mut other, &module, &[])?;
+    let err = other.restore(&checkpoint).unwrap_err();
+    assert!(err.to_string().contains("does not match"), "{err}");
+
+    assert!(Checkpoint::deserialize(b"garbage").is_err());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn checkpoint_internal_functions() -> Result<()> {
+    // Functions which are never referenced don't have a `VMFuncRef` and are
+    // skipped when mapping references.
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $internal (result i32) i32.const 1)
+                (func (export "f") (result i32) call $internal))
+        "#,
+    )?;
+    Instance::new(&mut store, &module, &[])?;
+    let checkpoint = store.checkpoint()?;
+    store.restore(&checkpoint)?;
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn checkpoint_host_functions() -> Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "f" (func $f))
+                (table (export "t") 2 funcref)
+                (elem (i32.const 0) $f)
+                (func (export "g") call $f)
+            )
+        "#,
+    )?;
+    let f = Func::wrap(&mut store, || {});
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+
+    // Host functions imported by an instance are captured as that import.
+    store.checkpoint()?;
+
+    // Other host functions can't be captured.
+    let table = instance.get_table(&mut store, "t").unwrap();
+    let other = Func::wrap(&mut store, || {});
+    table.set(&mut store, 1, other.into())?;
+    let err = store.checkpoint().unwrap_err();
+    assert!(format!("{err:?}").contains("host functions"), "{err:?}");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn checkpoint_host_created_imports() -> Result<()> {
+    const IMPORTS: &str = r#"
+        (module
+            (import "" "memory" (memory 1))
+            (import "" "table" (table 1 funcref))
+            (import "" "global" (global (mut i32)))
+            (func $seven (result i32) i32.const 7)
+            (elem declare func $seven)
+            (func (export "run")
+                (i32.store (i32.const 100) (i32.const 42))
+                (global.set 0 (i32.const 3))
+                (table.set (i32.const 0) (ref.func $seven)))
+            (func (export "call") (result i32)
+                (call_indirect (result i32) (i32.const 0)))
+        )
+    "#;
+
+    fn instantiate(engine: &Engine) -> Result<(Store<()>, Memory, Global, Instance)> {
+        let mut store = Store::new(engine, ());
+        let memory = Memory::new(&mut store, MemoryType::new(1, None))?;
+        let table = Table::new(
+            &mut store,
+            TableType::new(ValType::FuncRef, 1, None),
+            Val::FuncRef(None),
+        )?;
+        let global = Global::new(
+            &mut store,
+            GlobalType::new(ValType::I32, Mutability::Var),
+            Val::I32(0),
+        )?;
+        let module = Module::new(engine, IMPORTS)?;
+        let imports = [memory.into(), table.into(), global.into()];
+        let instance = Instance::new(&mut store, &module, &imports)?;
+        Ok((store, memory, global, instance))
+    }
+
+    let engine = Engine::default();
+    let (mut store, _, _, instance) = instantiate(&engine)?;
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    let bytes = store.checkpoint()?.serialize()?;
+
+    let (mut store, memory, global, instance) = instantiate(&engine)?;
+    store.restore(&Checkpoint::deserialize(&bytes)?)?;
+    assert_eq!(memory.data(&store)[100], 42);
+    assert_eq!(global.get(&mut store).unwrap_i32(), 3);
+    let call = instance.get_typed_func::<(), i32>(&mut store, "call")?;
+    assert_eq!(call.call(&mut store, ())?, 7);
+
+    // The host objects are part of what must match.
+    let (mut store, ..) = instantiate(&engine)?;
+    let ty = GlobalType::new(ValType::I32, Mutability::Var);
+    Global::new(&mut store, ty, Val::I32(0))?;
+    let err = store
+        .restore(&Checkpoint::deserialize(&bytes)?)
+        .unwrap_err();
+    assert!(err.to_string().contains("host globals"), "{err}");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn checkpoint_wasi_descriptors() -> Result<()> {
+    use wasmtime_wasi::sync::{ambient_authority, Dir, TcpListener, WasiCtxBuilder};
+    use wasmtime_wasi::{WasiCheckpoint, WasiCtx};
+
+    const WASI: &str = r#"
+        (module
+            (import "wasi_snapshot_preview1" "path_open"
+                (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_read"
+                (func $fd_read (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "hello.txt")
+            ;; Opens `hello.txt` in the preopen at fd 3, storing its fd at 16.
+            (func (export "open") (result i32)
+                (call $path_open (i32.const 3) (i32.const 1) (i32.const 0) (i32.const 9)
+                    (i32.const 0) (i64.const 0x1fffffff) (i64.const 0x1fffffff) (i32.const 0) (i32.const 16)))
+            ;; Reads two bytes of it to 64.
+            (func (export "read") (result i32)
+                (i32.store (i32.const 32) (i32.const 64))
+                (i32.store (i32.const 36) (i32.const 2))
+                (call $fd_read (i32.load (i32.const 16)) (i32.const 32) (i32.const 1)
+                    (i32.const 48)))
+        )
+    "#;
+
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("hello.txt"), "abcdef")?;
+    let engine = Engine::default();
+    let module = Module::new(&engine, WASI)?;
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |cx| cx)?;
+    let instantiate = || -> Result<(Store<WasiCtx>, Instance)> {
+        let preopen = Dir::open_ambient_dir(dir.path(), ambient_authority())?;
+        let wasi = WasiCtxBuilder::new().preopened_dir(preopen, "/")?.build();
+        let mut store = Store::new(&engine, wasi);
+        let instance = linker.instantiate(&mut store, &module)?;
+        Ok((store, instance))
+    };
+    let block_on = tokio::runtime::Builder::new_current_thread().build()?;
+
+    let (mut store, instance) = instantiate()?;
+    let open = instance.get_typed_func::<(), i32>(&mut store, "open")?;
+    let read = instance.get_typed_func::<(), i32>(&mut store, "read")?;
+    assert_eq!(open.call(&mut store, ())?, 0);
+    assert_eq!(read.call(&mut store, ())?, 0);
+    let mut checkpoint = store.checkpoint()?;
+    let wasi = block_on.block_on(store.data().checkpoint())?;
+    checkpoint.set_host_state(serde_json::to_vec(&wasi)?);
+    let bytes = checkpoint.serialize()?;
+
+    // The opened file is reopened at the same fd and position.
+    let (mut store, instance) = instantiate()?;
+    let checkpoint = Checkpoint::deserialize(&bytes)?;
+    store.restore(&checkpoint)?;
+    let wasi: WasiCheckpoint = serde_json::from_slice(checkpoint.host_state())?;
+    block_on.block_on(store.data().restore(&wasi))?;
+    let read = instance.get_typed_func::<(), i32>(&mut store, "read")?;
+    assert_eq!(read.call(&mut store, ())?, 0);
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[64..66], b"cd");
+
+    // Sockets can't be reopened.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let wasi = WasiCtxBuilder::new()
+        .preopened_socket(3, TcpListener::from_std(listener))?
+        .build();
+    let err = block_on.block_on(wasi.checkpoint()).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("cannot be checkpointed"),
+        "{err:?}"
+    );
+    Ok(())
+}
@@ -1019,3 +1019,540 @@ fn preview2_stdin() -> Result<()> {
     }
     Ok(())
 }
+
+#[test]
+#[cfg_attr(not(feature = "gdbstub"), ignore)]
+fn gdbstub() -> Result<()> {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    /// A minimal scripted GDB remote protocol client, which tracks whether
+    /// acknowledgements were turned off.
+    struct Client(TcpStream, bool);
+
+    impl Client {
+        fn request(&mut self, packet: &str) -> Result<String> {
+            let checksum = packet.bytes().fold(0u8, |a, b| a.wrapping_add(b));
+            write!(self.0, "${packet}#{checksum:02x}")?;
+            let mut reply = Vec::new();
+            let mut byte = [0];
+            loop {
+                self.0.read_exact(&mut byte)?;
+                match byte[0] {
+                    b'+' if reply.is_empty() => {}
+                    b'$' if reply.is_empty() => reply.push(b'$'),
+                    b'#' => break,
+                    b => reply.push(b),
+                }
+            }
+            let mut checksum = [0; 2];
+            self.0.read_exact(&mut checksum)?;
+            if !self.1 {
+                self.0.write_all(b"+")?;
+            }
+            Ok(String::from_utf8(reply[1..].to_vec())?)
+        }
+
+        /// Returns the program counter reported by a stop reply.
+        fn pc(stop: &str) -> Result<u64> {
+            let hex = match stop.split(';').find_map(|s| s.strip_prefix("T0500:")) {
+                Some(hex) => hex,
+                None => bail!("bad stop reply: {stop}"),
+            };
+            let bytes = (0..8)
+                .map(|i| u8::from_str_radix(&hex[i * 2..][..2], 16))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+        }
+    }
+
+    let mut child = get_wasmtime_command()?
+        .args(["run", "-Dgdbstub=0", "tests/all/cli_tests/gdbstub.wat"])
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let result = (|| {
+        let mut stderr = BufReader::new(child.stderr.take().unwrap());
+        let mut line = String::new();
+        stderr.read_line(&mut line)?;
+        let addr = match line.trim().rsplit(' ').next() {
+            Some(addr) if line.starts_with("Waiting for a debugger") => addr,
+            _ => bail!("unexpected output: {line}"),
+        };
+        let stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(Duration::from_secs(60)))?;
+        let mut client = Client(stream, false);
+
+        let supported = client.request("qSupported:xmlRegisters=i386")?;
+        assert!(supported.contains("qXfer:libraries:read+"), "{supported}");
+        assert_eq!(client.request("QStartNoAckMode")?, "OK");
+        client.1 = true;
+        let libraries = client.request("qXfer:libraries:read::0,1000")?;
+        assert!(libraries.starts_with("l<library-list>"), "{libraries}");
+        assert!(libraries.contains("gdbstub.wat"), "{libraries}");
+        assert!(libraries.contains("0x4000000000000000"), "{libraries}");
+        assert_eq!(client.request("m4000000000000000,4")?, "0061736d");
+
+        // Execution is paused before the first instruction; step past the
+        // `local.set` and check its effect.
+        let pc = Client::pc(&client.request("?")?)?;
+        assert_eq!(pc >> 62, 1, "pc {pc:#x} isn't a code address");
+        assert_eq!(client.request("p1")?, "00000000");
+        assert_eq!(
+            client.request("qRegisterInfo1")?,
+            "name:local0;bitsize:32;offset:8;encoding:uint;format:hex;set:Locals;"
+        );
+        assert_eq!(client.request("qRegisterInfo2")?, "E45");
+        assert_eq!(client.request("qWasmGlobal:0;0")?, "07000000");
+        Client::pc(&client.request("s")?)?;
+        let pc = Client::pc(&client.request("s")?)?;
+        assert_eq!(client.request("p1")?, "28000000");
+        assert_eq!(client.request("qWasmLocal:0;0")?, "28000000");
+
+        // Overwrite the local before it's read, then break at the final `end`
+        // after `i32.const 16; local.get 0; i32.const 2; i32.add; i32.store`.
+        assert_eq!(client.request("P1=64000000")?, "OK");
+        let end = pc + 2 + 2 + 2 + 1 + 3;
+        assert_eq!(client.request(&format!("Z0,{end:x},1"))?, "OK");
+        assert_eq!(Client::pc(&client.request("c")?)?, end);
+        assert_eq!(client.request("m10,4")?, "66000000");
+        assert_eq!(client.request("qWasmMem:0;10;4")?, "66000000");
+        assert_eq!(client.request("qWasmCallStack:1")?.len(), 16);
+        assert_eq!(client.request(&format!("z0,{end:x},1"))?, "OK");
+
+        assert_eq!(client.request("c")?, "W00");
+        Ok(())
+    })();
+    if result.is_err() {
+        child.kill()?;
+    }
+    let status = child.wait()?;
+    result?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(not(feature = "gdbstub"), ignore)]
+fn gdbstub_precompiled() -> Result<()> {
+    let dir = TempDir::new()?;
+    let cwasm = dir.path().join("gdbstub.cwasm");
+    run_wasmtime(&[
+        "compile",
+        "-Ccache=n",
+        // Compile with the same settings as the run below.
+        "-Dgdbstub=0",
+        "tests/all/cli_tests/gdbstub.wat",
+        "-o",
+        cwasm.to_str().unwrap(),
+    ])?;
+    let output = run_wasmtime_for_output(
+        &[
+            "run",
+            "-Ccache=n",
+            "--allow-precompiled",
+            "-Dgdbstub=0",
+            cwasm.to_str().unwrap(),
+        ],
+        None,
+    )?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("can't be debugged with `gdbstub`"),
+        "{stderr}"
+    );
+    Ok(())
+}
+
+#[test]
+fn record_and_replay() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/replay.wat")?;
+    let wasm = wasm.path().to_str().unwrap();
+    let log = NamedTempFile::new()?;
+    let record = format!("-Drecord={}", log.path().display());
+    let replay = format!("-Dreplay={}", log.path().display());
+
+    let recorded = run_wasmtime(&["run", "-Ccache=n", &record, "--invoke", "run", wasm])?;
+    let rerun = run_wasmtime(&["run", "-Ccache=n", "--invoke", "run", wasm])?;
+    assert_ne!(recorded, rerun);
+    let replayed = run_wasmtime(&["run", "-Ccache=n", &replay, "--invoke", "run", wasm])?;
+    assert_eq!(recorded, replayed);
+
+    // Exits are replayed with their status.
+    let record = &["run", "-Ccache=n", &record, "--invoke", "exit", wasm];
+    let output = run_wasmtime_for_output(record, None)?;
+    assert_eq!(output.status.code().unwrap(), 42);
+    let replay = &["run", "-Ccache=n", &replay, "--invoke", "exit", wasm];
+    let output = run_wasmtime_for_output(replay, None)?;
+    assert_eq!(output.status.code().unwrap(), 42);
+    Ok(())
+}
+
+#[cfg(feature = "serve")]
+mod serve {
+    use super::*;
+
+    /// A running `wasmtime serve` process, which is killed when dropped.
+    struct Server {
+        child: std::process::Child,
+        addr: String,
+        metrics_addr: Option<String>,
+    }
+
+    impl Server {
+        /// Starts `wasmtime serve` with `args` on a free port and waits until it
+        /// has bound its sockets.
+        fn spawn(args: &[&str]) -> Result<Server> {
+            use std::io::{BufRead, BufReader, Read};
+
+            let mut child = get_wasmtime_command()?
+                .args(["serve", "-Ccache=n", "--addr=127.0.0.1:0"])
+                .args(args)
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            let mut stderr = BufReader::new(child.stderr.take().unwrap());
+            let mut metrics_addr = None;
+            let addr = loop {
+                let mut line = String::new();
+                stderr.read_line(&mut line)?;
+                let line = line.trim();
+                if let Some(addr) = line.strip_prefix("Serving metrics on http://") {
+                    metrics_addr = Some(addr.trim_end_matches("/metrics").to_string());
+                } else if let Some(addr) = line.strip_prefix("Serving HTTP on http://") {
+                    break addr.trim_end_matches('/').to_string();
+                } else {
+                    let _ = child.kill();
+                    let mut rest = String::new();
+                    stderr.read_to_string(&mut rest)?;
+                    bail!("unexpected output: {line}\n{rest}");
+                }
+            };
+            // Keep draining stderr so that the server never blocks on it.
+            std::thread::spawn(move || std::io::copy(&mut stderr, &mut std::io::sink()));
+            Ok(Server {
+                child,
+                addr,
+                metrics_addr,
+            })
+        }
+
+        /// Sends a `GET` request for `path` with the extra `headers` and returns
+        /// the status code and body of the response.
+        fn get(&self, host: &str, path: &str, headers: &[(&str, &str)]) -> Result<(u16, String)> {
+            http_get(&self.addr, host, path, headers)
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    fn http_get(
+        addr: &str,
+        host: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<(u16, String)> {
+        use std::io::Read;
+        use std::net::TcpStream;
+
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(60)))?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n"
+        )?;
+        for (name, value) in headers {
+            write!(stream, "{name}: {value}\r\n")?;
+        }
+        write!(stream, "\r\n")?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let status = match response.split(' ').nth(1).map(|s| s.parse()) {
+            Some(Ok(status)) => status,
+            _ => bail!("bad response: {response:?}"),
+        };
+        let body = match response.split_once("\r\n\r\n") {
+            Some((_, body)) => body.to_string(),
+            None => String::new(),
+        };
+        Ok((status, body))
+    }
+
+    /// Writes a `wasi:http/proxy` component which responds to every request with
+    /// `status` and whose linear memory is `pages` pages large.
+    fn component(dir: &Path, name: &str, status: u16, pages: u32) -> Result<String> {
+        let version = "0.2.0-rc-2023-10-18";
+        let wat = format!(
+            r#"
+    (component
+      (import (interface "wasi:http/types@{version}") (instance $types
+        (export $fields "fields" (type (sub resource)))
+        (export $incoming-request "incoming-request" (type (sub resource)))
+        (export $outgoing-response "outgoing-response" (type (sub resource)))
+        (export $response-outparam "response-outparam" (type (sub resource)))
+        (type $error-def (variant
+          (case "invalid-url" string)
+          (case "timeout-error" string)
+          (case "protocol-error" string)
+          (case "unexpected-error" string)))
+        (export $error "error" (type (eq $error-def)))
+        (export "[constructor]fields"
+          (func (param "entries" (list (tuple string (list u8)))) (result (own $fields))))
+        (export "[constructor]outgoing-response"
+          (func (param "status-code" u16) (param "headers" (borrow $fields))
+            (result (own $outgoing-response))))
+        (export "[static]response-outparam.set"
+          (func (param "param" (own $response-outparam))
+            (param "response" (result (own $outgoing-response) (error $error)))))
+      ))
+      (alias export $types "incoming-request" (type $incoming-request))
+      (alias export $types "response-outparam" (type $response-outparam))
+
+      (core module $Memory (memory (export "memory") {pages}))
+      (core instance $memory (instantiate $Memory))
+      (alias core export $memory "memory" (core memory $mem))
+
+      (core func $fields-new
+        (canon lower (func $types "[constructor]fields") (memory $mem)))
+      (core func $response-new
+        (canon lower (func $types "[constructor]outgoing-response")))
+      (core func $outparam-set
+        (canon lower (func $types "[static]response-outparam.set") (memory $mem)))
+
+      (core module $Handler
+        (import "types" "fields-new" (func $fields-new (param i32 i32) (result i32)))
+        (import "types" "response-new" (func $response-new (param i32 i32) (result i32)))
+        (import "types" "outparam-set" (func $outparam-set (param i32 i32 i32 i32 i32)))
+        (func (export "handle") (param $request i32) (param $out i32)
+          (local $response i32)
+          (local.set $response
+            (call $response-new
+              (i32.const {status})
+              (call $fields-new (i32.const 0) (i32.const 0))))
+          (call $outparam-set
+            (local.get $out) (i32.const 0) (local.get $response) (i32.const 0) (i32.const 0)))
+      )
+      (core instance $handler (instantiate $Handler
+        (with "types" (instance
+          (export "fields-new" (func $fields-new))
+          (export "response-new" (func $response-new))
+          (export "outparam-set" (func $outparam-set))))))
+
+      (func $handle
+        (param "request" (own $incoming-request))
+        (param "response-out" (own $response-outparam))
+        (canon lift (core func $handler "handle")))
+      (instance $incoming-handler
+        (export "incoming-request" (type $incoming-request))
+        (export "response-outparam" (type $response-outparam))
+        (export "handle" (func $handle)))
+      (export (interface "wasi:http/incoming-handler@{version}") (instance $incoming-handler))
+    )
+    "#
+        );
+        let path = dir.join(format!("{name}.wat"));
+        std::fs::write(&path, wat)?;
+        Ok(path.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn routes() -> Result<()> {
+        let dir = TempDir::new()?;
+        component(dir.path(), "root", 200, 1)?;
+        component(dir.path(), "api", 201, 1)?;
+        component(dir.path(), "example", 202, 1)?;
+        let routes = dir.path().join("routes.toml");
+        std::fs::write(
+            &routes,
+            r#"
+    [[route]]
+    component = "api.wat"
+    path = "/api"
+
+    [[route]]
+    component = "root.wat"
+    path = "/"
+
+    [[route]]
+    component = "example.wat"
+    host = "example.com"
+    path = "/"
+    "#,
+        )?;
+        let server = Server::spawn(&["--routes", routes.to_str().unwrap()])?;
+
+        assert_eq!(server.get("localhost", "/", &[])?.0, 200);
+        assert_eq!(server.get("localhost", "/index.html", &[])?.0, 200);
+        assert_eq!(server.get("localhost", "/api", &[])?.0, 201);
+        assert_eq!(server.get("localhost", "/api/users", &[])?.0, 201);
+        assert_eq!(server.get("example.com", "/api", &[])?.0, 202);
+        assert_eq!(server.get("example.com:8080", "/", &[])?.0, 202);
+        Ok(())
+    }
+
+    #[test]
+    fn unmatched_route() -> Result<()> {
+        let dir = TempDir::new()?;
+        component(dir.path(), "api", 200, 1)?;
+        let routes = dir.path().join("routes.toml");
+        std::fs::write(
+            &routes,
+            r#"
+    [[route]]
+    component = "api.wat"
+    path = "/api"
+    "#,
+        )?;
+        let server = Server::spawn(&["--routes", routes.to_str().unwrap()])?;
+
+        assert_eq!(server.get("localhost", "/api", &[])?.0, 200);
+        assert_eq!(server.get("localhost", "/", &[])?.0, 404);
+        assert_eq!(server.get("localhost", "/other", &[])?.0, 404);
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_route_names() -> Result<()> {
+        let dir = TempDir::new()?;
+        component(dir.path(), "api", 200, 1)?;
+        let routes = dir.path().join("routes.toml");
+        std::fs::write(
+            &routes,
+            r#"
+    [[route]]
+    component = "api.wat"
+    path = "/v1"
+
+    [[route]]
+    component = "api.wat"
+    path = "/v2"
+    "#,
+        )?;
+        let output = run_wasmtime_for_output(
+            &["serve", "-Ccache=n", "--routes", routes.to_str().unwrap()],
+            None,
+        )?;
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("more than one route is named `api`"),
+            "{stderr}"
+        );
+
+        // Naming the routes distinguishes them.
+        std::fs::write(
+            &routes,
+            r#"
+    [[route]]
+    component = "api.wat"
+    name = "v1"
+    path = "/v1"
+
+    [[route]]
+    component = "api.wat"
+    name = "v2"
+    path = "/v2"
+    "#,
+        )?;
+        let server = Server::spawn(&["--routes", routes.to_str().unwrap()])?;
+        assert_eq!(server.get("localhost", "/v2", &[])?.0, 200);
+        Ok(())
+    }
+
+    #[test]
+    fn route_limits() -> Result<()> {
+        let dir = TempDir::new()?;
+        component(dir.path(), "small", 200, 1)?;
+        component(dir.path(), "large", 200, 2)?;
+        let routes = dir.path().join("routes.toml");
+        std::fs::write(
+            &routes,
+            r#"
+[[route]]
+component = "small.wat"
+path = "/small"
+max-memory-size = 65536
+
+[[route]]
+component = "large.wat"
+path = "/large"
+max-memory-size = 65536
+
+[[route]]
+component = "large.wat"
+name = "unlimited"
+path = "/unlimited"
+"#,
+        )?;
+        let server = Server::spawn(&["--routes", routes.to_str().unwrap()])?;
+
+        assert_eq!(server.get("localhost", "/small", &[])?.0, 200);
+        assert_eq!(server.get("localhost", "/large", &[])?.0, 500);
+        assert_eq!(server.get("localhost", "/unlimited", &[])?.0, 200);
+        Ok(())
+    }
+
+    #[test]
+    fn metrics() -> Result<()> {
+        let dir = TempDir::new()?;
+        let large = component(dir.path(), "large", 200, 2)?;
+        let server = Server::spawn(&[
+            "--metrics-addr=127.0.0.1:0",
+            "--trusted-limit-headers",
+            &large,
+        ])?;
+        let metrics_addr = server.metrics_addr.as_deref().unwrap();
+
+        assert_eq!(server.get("localhost", "/", &[])?.0, 200);
+        let limit = [("wasmtime-max-memory-size", "65536")];
+        assert_eq!(server.get("localhost", "/", &limit)?.0, 500);
+
+        let (status, metrics) = http_get(metrics_addr, "localhost", "/metrics", &[])?;
+        assert_eq!(status, 200);
+        let lines = metrics.lines().collect::<Vec<_>>();
+        for expected in [
+            "wasmtime_serve_requests_total{route=\"large\"} 2",
+            "wasmtime_serve_request_errors_total{route=\"large\"} 1",
+            "wasmtime_serve_instantiation_duration_seconds_count{route=\"large\"} 1",
+            "wasmtime_serve_request_peak_memory_bytes_bucket{route=\"large\",le=\"65536\"} 1",
+            "wasmtime_serve_request_peak_memory_bytes_bucket{route=\"large\",le=\"1048576\"} 2",
+            "wasmtime_serve_request_peak_memory_bytes_sum{route=\"large\"} 131072",
+        ] {
+            assert!(
+                lines.contains(&expected),
+                "missing `{expected}` in:\n{metrics}"
+            );
+        }
+
+        assert_eq!(http_get(metrics_addr, "localhost", "/", &[])?.0, 404);
+        Ok(())
+    }
+
+    #[test]
+    fn trusted_limit_headers() -> Result<()> {
+        let dir = TempDir::new()?;
+        let large = component(dir.path(), "large", 200, 2)?;
+        let limit = [("wasmtime-max-memory-size", "65536")];
+
+        let server = Server::spawn(&["--trusted-limit-headers", &large])?;
+        assert_eq!(server.get("localhost", "/", &[])?.0, 200);
+        assert_eq!(server.get("localhost", "/", &limit)?.0, 500);
+        let enough = [("wasmtime-max-memory-size", "131072")];
+        assert_eq!(server.get("localhost", "/", &enough)?.0, 200);
+        let invalid = [("wasmtime-max-memory-size", "lots")];
+        assert_eq!(server.get("localhost", "/", &invalid)?.0, 400);
+        let fuel = [("wasmtime-fuel", "1000")];
+        assert_eq!(server.get("localhost", "/", &fuel)?.0, 400);
+
+        // Without `--trusted-limit-headers` the headers are ignored.
+        let server = Server::spawn(&[&large])?;
+        assert_eq!(server.get("localhost", "/", &limit)?.0, 200);
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+(module
+    (memory (export "memory") 1)
+    (global $g (mut i32) (i32.const 7))
+    (func (export "_start")
+        (local $x i32)
+        i32.const 40
+        local.set $x
+        (i32.store (i32.const 16) (i32.add (local.get $x) (i32.const 2)))
+    )
+)
@@ -0,0 +1,12 @@
+(module
+  (import "wasi_snapshot_preview1" "random_get"
+    (func $random_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit"
+    (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (func (export "run") (result i64)
+    (drop (call $random_get (i32.const 0) (i32.const 8)))
+    (i64.load (i32.const 0)))
+  (func (export "exit")
+    (call $proc_exit (i32.const 42)))
+)
@@ -0,0 +1,245 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (global $g (mut i32) (i32.const 0))
+        (global $f (mut funcref) (ref.null func))
+        (table $t 1 funcref)
+        (func $one (result i32) i32.const 1)
+        (func $two (result i32) i32.const 2)
+        (func $bump (param i32) (result i32)
+            (i32.add (local.get 0) (i32.const 1)))
+        (elem declare func $one $two)
+        (func (export "run")
+            (i32.store (i32.const 100) (call $bump (i32.load (i32.const 100))))
+            (drop (memory.grow (i32.const 1)))
+            (i32.store8 (i32.const 70000) (i32.const 7))
+            (global.set $g (i32.add (global.get $g) (i32.const 10)))
+            (global.set $f (ref.func $two))
+            (drop (table.grow $t (ref.func $one) (i32.const 1))))
+        (func (export "g") (result i32) global.get $g)
+        (func (export "call_table") (param i32) (result i32)
+            (call_indirect $t (result i32) (local.get 0)))
+        (func (export "call_global") (result i32)
+            (table.set $t (i32.const 0) (global.get $f))
+            (call_indirect $t (result i32) (i32.const 0)))
+    )
+"#;
+
+fn assert_state(store: &mut Store<()>, instance: Instance, runs: u8) -> Result<()> {
+    let memory = instance.get_memory(&mut *store, "memory").unwrap();
+    assert_eq!(memory.size(&*store), 1 + u64::from(runs));
+    assert_eq!(memory.data(&*store)[100], runs);
+    assert_eq!(memory.data(&*store)[70000], 7);
+    let g = instance.get_typed_func::<(), i32>(&mut *store, "g")?;
+    assert_eq!(g.call(&mut *store, ())?, 10 * i32::from(runs));
+    let call_table = instance.get_typed_func::<i32, i32>(&mut *store, "call_table")?;
+    for i in 1..=u32::from(runs) {
+        assert_eq!(call_table.call(&mut *store, i as i32)?, 1);
+    }
+    assert!(call_table.call(&mut *store, 1 + i32::from(runs)).is_err());
+    Ok(())
+}
+
+fn fork_state(config: &Config) -> Result<()> {
+    let engine = Engine::new(config)?;
+    let module = Module::new(&engine, WAT)?;
+    let linker = Linker::new(&engine);
+    let pre = linker.instantiate_pre(&module)?;
+
+    let mut store = Store::new(&engine, ());
+    let parent = pre.instantiate(&mut store)?;
+    let run = parent.get_typed_func::<(), ()>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    let fork = pre.fork(&mut store, &parent)?;
+
+    // Forks start out with the state of the parent when it was forked, and
+    // changes to the parent after that aren't visible in them.
+    run.call(&mut store, ())?;
+    assert_state(&mut store, parent, 2)?;
+    let child = fork.instantiate(&mut store)?;
+    assert_state(&mut store, child, 1)?;
+
+    // Forks are independent of each other, also across stores.
+    let run = child.get_typed_func::<(), ()>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    assert_state(&mut store, child, 2)?;
+    let mut other = Store::new(&engine, ());
+    let sibling = fork.instantiate(&mut other)?;
+    assert_state(&mut other, sibling, 1)?;
+
+    // References to functions of the parent refer to those of the fork.
+    let call_global = sibling.get_typed_func::<(), i32>(&mut other, "call_global")?;
+    assert_eq!(call_global.call(&mut other, ())?, 2);
+
+    // Forks can be forked again.
+    let fork = fork.fork(&mut other, &sibling)?;
+    let grandchild = fork.instantiate(&mut other)?;
+    let call_table = grandchild.get_typed_func::<i32, i32>(&mut other, "call_table")?;
+    assert_eq!(call_table.call(&mut other, 0)?, 2);
+    assert_state(&mut other, grandchild, 1)?;
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn fork_default() -> Result<()> {
+    fork_state(&Config::new())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn fork_without_cow() -> Result<()> {
+    let mut config = Config::new();
+    config.memory_init_cow(false);
+    fork_state(&config)
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn fork_pooling() -> Result<()> {
+    let mut pool = PoolingAllocationConfig::default();
+    pool.total_memories(10)
+        .total_tables(10)
+        .total_core_instances(10)
+        .memory_pages(10);
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+    fork_state(&config)
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn fork_start_function_not_run() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (global $g (export "g") (mut i32) (i32.const 0))
+                (func $start (global.set $g (i32.add (global.get $g) (i32.const 1))))
+                (start $start))
+        "#,
+    )?;
+    let pre = Linker::new(&engine).instantiate_pre(&module)?;
+    let mut store = Store::new(&engine, ());
+    let parent = pre.instantiate(&mut store)?;
+    let child = pre.fork(&mut store, &parent)?.instantiate(&mut store)?;
+    let g = child.get_global(&mut store, "g").unwrap();
+    assert_eq!(g.get(&mut store).i32(), Some(1));
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn fork_dropped_segments() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (table 1 funcref)
+                (func $f)
+                (data $d "abcd")
+                (elem $e func $f)
+                (func (export "drop_data") (data.drop $d))
+                (func (export "drop_elem") (elem.drop $e))
+                (func (export "init_data")
+                    (memory.init $d (i32.const 0) (i32.const 0) (i32.const 4)))
+                (func (export "init_elem")
+                    (table.init $e (i32.const 0) (i32.const 0) (i32.const 1))))
+        "#,
+    )?;
+    let pre = Linker::new(&engine).instantiate_pre(&module)?;
+    let call = |store: &mut Store<()>, instance: Instance, name: &str| {
+        let func = instance.get_typed_func::<(), ()>(&mut *store, name)?;
+        func.call(&mut *store, ())
+    };
+
+    // Segments dropped before forking are dropped in the fork too...
+    let mut store = Store::new(&engine, ());
+    let parent = pre.instantiate(&mut store)?;
+    call(&mut store, parent, "drop_data")?;
+    let fork = pre.fork(&mut store, &parent)?;
+    let child = fork.instantiate(&mut store)?;
+    assert!(call(&mut store, parent, "init_data").is_err());
+    assert!(call(&mut store, child, "init_data").is_err());
+    call(&mut store, child, "init_elem")?;
+
+    // ... unlike those dropped afterwards, also when forking forks.
+    call(&mut store, child, "drop_elem")?;
+    let grandchild = fork.fork(&mut store, &child)?.instantiate(&mut store)?;
+    assert!(call(&mut store, grandchild, "init_data").is_err());
+    assert!(call(&mut store, grandchild, "init_elem").is_err());
+    let sibling = fork.instantiate(&mut store)?;
+    call(&mut store, sibling, "init_elem")?;
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn fork_errors() -> Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let linker = Linker::new(&engine);
+
+    let module = Module::new(&engine, WAT)?;
+    let other = Module::new(&engine, WAT)?;
+    let pre = linker.instantiate_pre(&module)?;
+    let instance = linker.instantiate(&mut store, &other)?;
+    let err = pre.fork(&mut store, &instance).err().unwrap();
+    assert!(
+        err.to_string().contains("not an instance of this module"),
+        "{err:?}"
+    );
+
+    let module = Module::new(
+        &engine,
+        r#"(module (global (export "g") (mut externref) (ref.null extern)))"#,
+    )?;
+    let pre = linker.instantiate_pre(&module)?;
+    let instance = pre.instantiate(&mut store)?;
+    pre.fork(&mut store, &instance)?;
+    let g = instance.get_global(&mut store, "g").unwrap();
+    g.set(&mut store, Val::ExternRef(Some(ExternRef::new(1))))?;
+    let err = pre.fork(&mut store, &instance).err().unwrap();
+    assert!(err.to_string().contains("externref"), "{err:?}");
+
+    let module = Module::new(&engine, r#"(module (table (export "t") 1 funcref))"#)?;
+    let pre = linker.instantiate_pre(&module)?;
+    let instance = pre.instantiate(&mut store)?;
+    let t = instance.get_table(&mut store, "t").unwrap();
+    let f = Func::wrap(&mut store, || {});
+    t.set(&mut store, 0, f.into())?;
+    let err = pre.fork(&mut store, &instance).err().unwrap();
+    assert!(
+        format!("{err:?}").contains("functions of other instances"),
+        "{err:?}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn fork_async() -> Result<()> {
+    let mut config = Config::new();
+    config.async_support(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, WAT)?;
+    let pre = Linker::new(&engine).instantiate_pre(&module)?;
+
+    let mut store = Store::new(&engine, ());
+    let parent = pre.instantiate_async(&mut store).await?;
+    let run = parent.get_typed_func::<(), ()>(&mut store, "run")?;
+    run.call_async(&mut store, ()).await?;
+    let child = pre
+        .fork(&mut store, &parent)?
+        .instantiate_async(&mut store)
+        .await?;
+    let g = child.get_typed_func::<(), i32>(&mut store, "g")?;
+    assert_eq!(g.call_async(&mut store, ()).await?, 10);
+    Ok(())
+}
@@ -0,0 +1,212 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (memory 1)
+        (global $g (mut i32) (i32.const 5))
+        (func $add (export "add") (param i32 i32) (result i32)
+            (local i64)
+            local.get 0
+            local.get 1
+            i32.add)
+        (func (export "call_add") (result i32)
+            (call $add (i32.const 1) (i32.const 2)))
+        (func (export "bump")
+            (global.set $g (i32.add (global.get $g) (i32.const 1)))
+            (i32.store (i32.const 8) (global.get $g)))
+    )
+"#;
+
+fn engine() -> Engine {
+    let mut config = Config::new();
+    config.guest_debug(true);
+    Engine::new(&config).unwrap()
+}
+
+/// Single-steps through `name` with `args`, recording the function index and
+/// offset of each pause.
+fn step_through(
+    store: &mut Store<Vec<(u32, usize)>>,
+    instance: Instance,
+    name: &str,
+    args: &[Val],
+) -> Result<Vec<(u32, usize)>> {
+    store.data_mut().clear();
+    store.debug_handler(|mut store, frame| {
+        let pause = (frame.func_index(), frame.func_offset());
+        store.data_mut().push(pause);
+        Ok(DebugStep::Step)
+    });
+    store.debug_single_step(true);
+    let func = instance.get_func(&mut *store, name).unwrap();
+    let mut results = vec![Val::I32(0); func.ty(&*store).results().len()];
+    func.call(&mut *store, args, &mut results)?;
+    store.debug_single_step(false);
+    Ok(store.data().clone())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn single_step() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, Vec::new());
+    let instance = Instance::new(&mut store, &module, &[])?;
+
+    // `add` pauses before each of its four instructions, including the final
+    // `end`.
+    let pauses = step_through(&mut store, instance, "add", &[Val::I32(1), Val::I32(2)])?;
+    assert_eq!(pauses.len(), 4);
+    assert!(pauses.iter().all(|(func, _)| *func == 0));
+    assert!(pauses.windows(2).all(|w| w[0].1 < w[1].1));
+
+    // Stepping follows calls into other functions.
+    let pauses = step_through(&mut store, instance, "call_add", &[])?;
+    let funcs = pauses.iter().map(|(func, _)| *func).collect::<Vec<_>>();
+    assert_eq!(funcs, [1, 1, 1, 0, 0, 0, 0, 1]);
+
+    // Once stepping stops nothing pauses anymore.
+    store.data_mut().clear();
+    let add = instance.get_typed_func::<(i32, i32), i32>(&mut store, "add")?;
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    assert!(store.data().is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn breakpoints() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, Vec::new());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let pauses = step_through(&mut store, instance, "add", &[Val::I32(1), Val::I32(2)])?;
+
+    // Break before `i32.add` and check the frame matches the backtrace.
+    let (_, offset) = pauses[2];
+    store.add_breakpoint(&module, 0, offset)?;
+    store.debug_handler(move |mut store, frame| {
+        let backtrace = WasmBacktrace::force_capture(&store);
+        let top = &backtrace.frames()[0];
+        assert_eq!(top.func_index(), frame.func_index());
+        assert_eq!(top.func_offset(), Some(frame.func_offset()));
+        store
+            .data_mut()
+            .push((frame.func_index(), frame.func_offset()));
+        Ok(DebugStep::Continue)
+    });
+    store.data_mut().clear();
+    let call_add = instance.get_typed_func::<(), i32>(&mut store, "call_add")?;
+    assert_eq!(call_add.call(&mut store, ())?, 3);
+    assert_eq!(call_add.call(&mut store, ())?, 3);
+    assert_eq!(*store.data(), [(0, offset), (0, offset)]);
+
+    assert!(store.remove_breakpoint(&module, 0, offset));
+    assert!(!store.remove_breakpoint(&module, 0, offset));
+    store.data_mut().clear();
+    assert_eq!(call_add.call(&mut store, ())?, 3);
+    assert!(store.data().is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn locals() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, Vec::new());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let pauses = step_through(&mut store, instance, "add", &[Val::I32(1), Val::I32(2)])?;
+
+    // Before the first `local.get` rewrite the first parameter.
+    store.add_breakpoint(&module, 0, pauses[0].1)?;
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen2 = seen.clone();
+    store.debug_handler(move |_store, frame| {
+        assert_eq!(frame.num_locals(), 3);
+        let locals = (0..frame.num_locals())
+            .map(|i| match frame.local(i)? {
+                Val::I32(v) => Some(i64::from(v)),
+                Val::I64(v) => Some(v),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        seen2.lock().unwrap().push(locals);
+        assert!(frame.local(3).is_none());
+        assert!(frame.set_local(0, Val::I64(1)).is_err());
+        assert!(frame.set_local(3, Val::I32(1)).is_err());
+        frame.set_local(0, Val::I32(40))?;
+        frame.set_local(2, Val::I64(-1))?;
+        Ok(DebugStep::Continue)
+    });
+    let add = instance.get_typed_func::<(i32, i32), i32>(&mut store, "add")?;
+    assert_eq!(add.call(&mut store, (1, 2))?, 42);
+    assert_eq!(*seen.lock().unwrap(), [[Some(1), Some(2), Some(0)]]);
+
+    // Errors returned from the handler are raised as traps.
+    store.debug_handler(|_, _| anyhow::bail!("stop here"));
+    let err = add.call(&mut store, (1, 2)).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("stop here"),
+        "bad error: {err:?}"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn globals_and_memories() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, Vec::new());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let pauses = step_through(&mut store, instance, "bump", &[])?;
+
+    // Neither the global nor the memory is exported, yet both are reachable
+    // from the paused frame. Stepping above already bumped the global once.
+    store.add_breakpoint(&module, 2, pauses[0].1)?;
+    store.debug_handler(|mut store, frame| {
+        let global = frame.global(&mut store, 0).unwrap();
+        assert_eq!(global.get(&mut store).unwrap_i32(), 6);
+        global.set(&mut store, Val::I32(99))?;
+        assert!(frame.global(&mut store, 1).is_none());
+
+        let memory = frame.memory(&mut store, 0).unwrap();
+        memory.data_mut(&mut store)[0] = 1;
+        assert!(frame.memory(&mut store, 1).is_none());
+        Ok(DebugStep::Continue)
+    });
+    let bump = instance.get_typed_func::<(), ()>(&mut store, "bump")?;
+    bump.call(&mut store, ())?;
+
+    let bytes = Arc::new(Mutex::new([0; 12]));
+    let bytes2 = bytes.clone();
+    store.debug_handler(move |mut store, frame| {
+        let memory = frame.memory(&mut store, 0).unwrap();
+        memory.read(&store, 0, &mut *bytes2.lock().unwrap())?;
+        Ok(DebugStep::Continue)
+    });
+    bump.call(&mut store, ())?;
+    let bytes = *bytes.lock().unwrap();
+    assert_eq!(bytes[0], 1);
+    assert_eq!(bytes[8..], 100i32.to_le_bytes());
+    Ok(())
+}
+
+#[test]
+fn breakpoints_require_guest_debug() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, ());
+    let err = store.add_breakpoint(&module, 0, 0).unwrap_err();
+    assert!(err.to_string().contains("guest_debug"), "bad error: {err}");
+
+    let engine = self::engine();
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, ());
+    let err = store.add_breakpoint(&module, 3, 0).unwrap_err();
+    assert!(err.to_string().contains("not defined"), "bad error: {err}");
+    Ok(())
+}
@@ -0,0 +1,507 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (import "host" "random" (func $random (result i64)))
+        (import "host" "fill" (func $fill (param i32 i32)))
+        (import "host" "grow" (func $grow))
+        (import "host" "fail" (func $fail (param i32)))
+        (memory (export "memory") 1)
+        (global $g (export "g") (mut i32) (i32.const 0))
+        (func (export "run") (result i64)
+            (call $fill (i32.const 16) (i32.const 4))
+            (i64.add
+                (call $random)
+                (i64.extend_i32_u (i32.load (i32.const 16)))))
+        (func (export "grow") (result i32)
+            (call $grow)
+            (i32.add (memory.size) (i32.load8_u (i32.const 70000))))
+        (func (export "fail") (param i32) (call $fail (local.get 0)))
+    )
+"#;
+
+/// Defines the host functions of `WAT`, with results that differ from one
+/// call to the next, in a store whose data counts the calls made.
+fn linker(engine: &Engine) -> Result<Linker<u32>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap("host", "random", |mut caller: Caller<'_, u32>| {
+        *caller.data_mut() += 1;
+        i64::from(*caller.data()) * 1000
+    })?;
+    linker.func_wrap(
+        "host",
+        "fill",
+        |mut caller: Caller<'_, u32>, ptr: i32, len: i32| {
+            *caller.data_mut() += 1;
+            let byte = *caller.data() as u8;
+            let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+            memory.write(&mut caller, ptr as usize, &vec![byte; len as usize])?;
+            let global = caller.get_export("g").unwrap().into_global().unwrap();
+            global.set(&mut caller, Val::I32(byte.into()))?;
+            Ok(())
+        },
+    )?;
+    linker.func_new(
+        "host",
+        "grow",
+        FuncType::new([], []),
+        |mut caller, _params, _results| {
+            *caller.data_mut() += 1;
+            let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+            memory.grow(&mut caller, 1)?;
+            memory.data_mut(&mut caller)[70000] = 7;
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "host",
+        "fail",
+        |_: Caller<'_, u32>, code: i32| -> Result<()> { bail!("failed with {code}") },
+    )?;
+    Ok(linker)
+}
+
+/// Defines the host functions of `WAT` such that they fail the test when
+/// called.
+fn unreachable_linker(engine: &Engine) -> Result<Linker<u32>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap("host", "random", || -> i64 { unreachable!() })?;
+    linker.func_wrap("host", "fill", |_: i32, _: i32| unreachable!())?;
+    linker.func_new(
+        "host",
+        "grow",
+        FuncType::new([], []),
+        |_, _, _| unreachable!(),
+    )?;
+    linker.func_wrap("host", "fail", |_: i32| unreachable!())?;
+    Ok(linker)
+}
+
+/// Runs the exports of `WAT`, returning what they returned and the final
+/// value of the global.
+fn run(store: &mut Store<u32>, linker: &Linker<u32>, module: &Module) -> Result<Vec<i64>> {
+    let instance = linker.instantiate(&mut *store, module)?;
+    let run = instance.get_typed_func::<(), i64>(&mut *store, "run")?;
+    let grow = instance.get_typed_func::<(), i32>(&mut *store, "grow")?;
+    let g = instance.get_global(&mut *store, "g").unwrap();
+    let mut results = vec![run.call(&mut *store, ())?, run.call(&mut *store, ())?];
+    results.push(grow.call(&mut *store, ())?.into());
+    results.push(g.get(&mut *store).unwrap_i32().into());
+    Ok(results)
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn record_and_replay() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT)?;
+
+    let mut store = Store::new(&engine, 0);
+    store.record_host_calls();
+    let recorded = run(&mut store, &linker(&engine)?, &module)?;
+    assert_eq!(recorded, [0x01010101 + 2000, 0x03030303 + 4000, 9, 3]);
+    let log = store.take_host_call_log().unwrap();
+    assert_eq!(log.len(), 5);
+    assert!(store.take_host_call_log().is_none());
+
+    // Rerunning calls the host functions again, which now return different
+    // results...
+    let mut store = Store::new(&engine, 100);
+    assert_ne!(run(&mut store, &linker(&engine)?, &module)?, recorded);
+
+    // ... unlike replaying, which doesn't call them at all.
+    let log = HostCallLog::deserialize(&log.serialize()?)?;
+    let mut store = Store::new(&engine, 0);
+    store.replay_host_calls(log);
+    assert_eq!(
+        run(&mut store, &unreachable_linker(&engine)?, &module)?,
+        recorded
+    );
+    assert!(store.take_host_call_log().unwrap().is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn replay_errors() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT)?;
+
+    let mut store = Store::new(&engine, 0);
+    store.record_host_calls();
+    let instance = linker(&engine)?.instantiate(&mut store, &module)?;
+    let fail = instance.get_typed_func::<i32, ()>(&mut store, "fail")?;
+    let err = fail.call(&mut store, 1).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("failed with 1"),
+        "bad error: {err:?}"
+    );
+    let log = store.take_host_call_log().unwrap();
+
+    // The recorded error is raised again.
+    let mut store = Store::new(&engine, 0);
+    store.replay_host_calls(log.clone());
+    let instance = unreachable_linker(&engine)?.instantiate(&mut store, &module)?;
+    let fail = instance.get_typed_func::<i32, ()>(&mut store, "fail")?;
+    let err = fail.call(&mut store, 1).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("failed with 1"),
+        "bad error: {err:?}"
+    );
+
+    // Once the log is exhausted host calls trap.
+    let err = fail.call(&mut store, 1).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("past the end of the log"),
+        "bad error: {err:?}"
+    );
+
+    // As do host calls whose arguments differ from the recording.
+    store.replay_host_calls(log);
+    let err = fail.call(&mut store, 2).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("diverged"),
+        "bad error: {err:?}"
+    );
+    Ok(())
+}
+
+/// An error carrying an exit status, like WASI's `I32Exit`.
+#[derive(Debug)]
+struct Exit(i32);
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exited with {}", self.0)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn traps_and_exits_are_replayed() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "trap" (func $trap))
+                (import "" "exit" (func $exit (param i32)))
+                (func (export "trap") (call $trap))
+                (func (export "exit") (param i32) (call $exit (local.get 0))))
+        "#,
+    )?;
+    let status = |e: &anyhow::Error| e.downcast_ref::<Exit>().map(|e| e.0);
+    let raise = |status| Exit(status).into();
+    let run = |store: &mut Store<()>, trap: Func, exit: Func| -> Result<Vec<anyhow::Error>> {
+        let instance = Instance::new(&mut *store, &module, &[trap.into(), exit.into()])?;
+        let trap = instance.get_typed_func::<(), ()>(&mut *store, "trap")?;
+        let exit = instance.get_typed_func::<i32, ()>(&mut *store, "exit")?;
+        Ok(vec![
+            trap.call(&mut *store, ()).unwrap_err(),
+            exit.call(&mut *store, 3).unwrap_err(),
+        ])
+    };
+    let check = |errors: Vec<anyhow::Error>| {
+        assert_eq!(
+            errors[0].downcast_ref::<Trap>(),
+            Some(&Trap::IntegerOverflow)
+        );
+        assert_eq!(errors[1].downcast_ref::<Exit>().map(|e| e.0), Some(3));
+    };
+
+    let mut store = Store::new(&engine, ());
+    store.record_host_calls();
+    store.host_call_exits(status, raise);
+    let trap = Func::wrap(&mut store, || -> Result<()> {
+        Err(Trap::IntegerOverflow.into())
+    });
+    let exit = Func::wrap(&mut store, |status: i32| -> Result<()> {
+        Err(Exit(status).into())
+    });
+    check(run(&mut store, trap, exit)?);
+    let log = store.take_host_call_log().unwrap();
+
+    // The trap and the exit are raised again as what they were...
+    let mut store = Store::new(&engine, ());
+    store.replay_host_calls(log.clone());
+    store.host_call_exits(status, raise);
+    let trap = Func::wrap(&mut store, || -> Result<()> { unreachable!() });
+    let exit = Func::wrap(&mut store, |_: i32| -> Result<()> { unreachable!() });
+    check(run(&mut store, trap, exit)?);
+
+    // ... and without a way to raise exits, their status is still reported.
+    let mut store = Store::new(&engine, ());
+    store.replay_host_calls(log);
+    let trap = Func::wrap(&mut store, || -> Result<()> { unreachable!() });
+    let exit = Func::wrap(&mut store, |_: i32| -> Result<()> { unreachable!() });
+    let errors = run(&mut store, trap, exit)?;
+    assert!(
+        format!("{:?}", errors[1]).contains("exited with status 3"),
+        "bad error: {:?}",
+        errors[1]
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn shared_memories_cannot_be_recorded() -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_threads(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "f" (func $f))
+                (memory 1 1 shared)
+                (func (export "run") (call $f)))
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    store.record_host_calls();
+    let f = Func::wrap(&mut store, || {});
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    let err = run.call(&mut store, ()).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("shared memories"),
+        "bad error: {err:?}"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn references_cannot_be_recorded() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "f" (func $f (result externref)))
+                (func (export "run") (drop (call $f))))
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    store.record_host_calls();
+    let f = Func::wrap(&mut store, || -> Option<ExternRef> { None });
+    let instance = Instance::new(&mut store, &module, &[f.into()])?;
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    let err = run.call(&mut store, ()).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("can't be recorded"),
+        "bad error: {err:?}"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn component_host_calls() -> Result<()> {
+    use wasmtime::component::{Component, Linker};
+
+    // `get` returns a string which is written through `realloc`, which bumps
+    // a global, so replaying it needs both of those changes.
+    let component = r#"
+        (component
+            (import "get" (func $get (result string)))
+            (core module $libc
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 100))
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                    (global.get $next)
+                    (global.set $next (i32.add (global.get $next) (local.get 3))))
+            )
+            (core instance $libc (instantiate $libc))
+            (core func $get_lower
+                (canon lower (func $get)
+                    (memory $libc "memory")
+                    (realloc (func $libc "realloc"))))
+            (core module $m
+                (import "libc" "memory" (memory 1))
+                (import "host" "get" (func $get (param i32)))
+                (func (export "run") (result i32)
+                    (call $get (i32.const 8))
+                    (i32.or
+                        (i32.or
+                            (i32.shl (i32.load (i32.const 8)) (i32.const 16))
+                            (i32.shl (i32.load (i32.const 12)) (i32.const 8)))
+                        (i32.load8_u (i32.load (i32.const 8)))))
+            )
+            (core instance $i (instantiate $m
+                (with "libc" (instance $libc))
+                (with "host" (instance (export "get" (func $get_lower))))
+            ))
+            (func (export "run") (result u32)
+                (canon lift (core func $i "run")))
+        )
+    "#;
+
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    let engine = Engine::new(&config)?;
+    let component = Component::new(&engine, component)?;
+    let run = |store: &mut Store<u32>, linker: &Linker<u32>| -> Result<Vec<u32>> {
+        let instance = linker.instantiate(&mut *store, &component)?;
+        let run = instance.get_typed_func::<(), (u32,)>(&mut *store, "run")?;
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            results.push(run.call(&mut *store, ())?.0);
+            run.post_return(&mut *store)?;
+        }
+        Ok(results)
+    };
+
+    let mut linker = Linker::new(&engine);
+    linker
+        .root()
+        .func_wrap("get", |mut store: StoreContextMut<'_, u32>, (): ()| {
+            *store.data_mut() += 1;
+            Ok((format!("{}xyz", store.data()),))
+        })?;
+    let mut store = Store::new(&engine, 0);
+    store.record_host_calls();
+    let recorded = run(&mut store, &linker)?;
+    assert_eq!(
+        recorded,
+        [
+            100 << 16 | 4 << 8 | b'1' as u32,
+            104 << 16 | 4 << 8 | b'2' as u32
+        ]
+    );
+    let log = store.take_host_call_log().unwrap();
+    assert_eq!(log.len(), 2);
+
+    let mut linker = Linker::new(&engine);
+    linker.root().func_wrap(
+        "get",
+        |_: StoreContextMut<'_, u32>, (): ()| -> Result<(String,)> { unreachable!() },
+    )?;
+    let mut store = Store::new(&engine, 0);
+    store.replay_host_calls(log);
+    assert_eq!(run(&mut store, &linker)?, recorded);
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn component_params_are_checked() -> Result<()> {
+    use wasmtime::component::{Component, Linker};
+
+    let component = r#"
+        (component
+            (import "f" (func $f (param "x" u32) (param "y" string)))
+            (core module $libc
+                (memory (export "memory") 1)
+                (data (i32.const 0) "abcd"))
+            (core instance $libc (instantiate $libc))
+            (core func $f_lower
+                (canon lower (func $f) (memory $libc "memory")))
+            (core module $m
+                (import "host" "f" (func $f (param i32 i32 i32)))
+                (func (export "run") (param i32 i32)
+                    (call $f (local.get 0) (i32.const 0) (local.get 1)))
+            )
+            (core instance $i (instantiate $m
+                (with "host" (instance (export "f" (func $f_lower))))
+            ))
+            (func (export "run") (param "x" u32) (param "len" u32)
+                (canon lift (core func $i "run")))
+        )
+    "#;
+
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    let engine = Engine::new(&config)?;
+    let component = Component::new(&engine, component)?;
+    let run = |store: &mut Store<()>, linker: &Linker<()>, params| -> Result<()> {
+        let instance = linker.instantiate(&mut *store, &component)?;
+        let run = instance.get_typed_func::<(u32, u32), ()>(&mut *store, "run")?;
+        run.call(&mut *store, params)?;
+        run.post_return(&mut *store)
+    };
+
+    let mut linker = Linker::new(&engine);
+    linker
+        .root()
+        .func_wrap("f", |_: StoreContextMut<'_, ()>, (_, _): (u32, String)| {
+            Ok(())
+        })?;
+    let mut store = Store::new(&engine, ());
+    store.record_host_calls();
+    run(&mut store, &linker, (1, 4))?;
+    let log = store.take_host_call_log().unwrap();
+
+    let mut linker = Linker::new(&engine);
+    linker.root().func_wrap(
+        "f",
+        |_: StoreContextMut<'_, ()>, _: (u32, String)| -> Result<()> { unreachable!() },
+    )?;
+    let mut store = Store::new(&engine, ());
+    store.replay_host_calls(log.clone());
+    run(&mut store, &linker, (1, 4))?;
+
+    // Both the flat arguments and the string's pointer and length are
+    // compared with the log's.
+    for params in [(2, 4), (1, 3)] {
+        let mut store = Store::new(&engine, ());
+        store.replay_host_calls(log.clone());
+        let err = run(&mut store, &linker, params).unwrap_err();
+        assert!(
+            format!("{err:?}").contains("arguments differ"),
+            "bad error: {err:?}"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn component_resources_cannot_be_recorded() -> Result<()> {
+    use wasmtime::component::{Component, Linker, Resource};
+
+    struct R;
+
+    let component = r#"
+        (component
+            (import "t" (type $t (sub resource)))
+            (import "f" (func $f (result (own $t))))
+            (core func $f_lower (canon lower (func $f)))
+            (core module $m
+                (import "host" "f" (func $f (result i32)))
+                (func (export "run") (drop (call $f)))
+            )
+            (core instance $i (instantiate $m
+                (with "host" (instance (export "f" (func $f_lower))))
+            ))
+            (func (export "run") (canon lift (core func $i "run")))
+        )
+    "#;
+
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    let engine = Engine::new(&config)?;
+    let component = Component::new(&engine, component)?;
+    let mut linker = Linker::new(&engine);
+    linker.root().resource::<R>("t", |_, _| Ok(()))?;
+    linker
+        .root()
+        .func_wrap("f", |_: StoreContextMut<'_, ()>, (): ()| {
+            Ok((Resource::<R>::new_own(1),))
+        })?;
+    let mut store = Store::new(&engine, ());
+    store.record_host_calls();
+    let instance = linker.instantiate(&mut store, &component)?;
+    let run = instance.get_typed_func::<(), ()>(&mut store, "run")?;
+    let err = run.call(&mut store, ()).unwrap_err();
+    assert!(
+        format!("{err:?}").contains("resources can't be recorded"),
+        "bad error: {err:?}"
+    );
+    Ok(())
+}
@@ -2,6 +2,7 @@
 
 mod async_functions;
 mod call_hook;
+mod checkpoint;
 mod cli_tests;
 mod code_too_large;
 mod component_model;
@@ -10,11 +11,14 @@ mod custom_signal_handler;
 mod debug;
 mod epoch_interruption;
 mod externals;
+mod fork;
 mod fuel;
 mod func;
 mod funcref;
 mod gc;
 mod globals;
+mod guest_debug;
+mod host_call_replay;
 mod host_funcs;
 mod iloop;
 mod import_calling_export;
@@ -30,6 +34,8 @@ mod module_serialize;
 mod name;
 mod pooling_allocator;
 mod relocs;
+mod snapshot;
+mod stack_creator;
 mod stack_overflow;
 mod store;
 mod table;
@@ -239,3 +239,48 @@ fn large_add_chain_no_stack_overflow() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn new_with_profile() -> Result<()> {
+    let engine = Engine::default();
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (func (export "f") (param i32 i32) (result i32)
+                    (if (result i32) (local.get 0)
+                        (then (i32.mul (local.get 1) (i32.const 3)))
+                        (else (i32.div_u (local.get 1) (i32.const 7))))))
+        "#,
+    )?;
+    // `local.get 0` followed by the `if`.
+    let offset = wasm
+        .windows(3)
+        .position(|w| w == [0x20, 0x00, 0x04])
+        .unwrap()
+        + 2;
+    let offset = u32::try_from(offset).unwrap();
+
+    let mut profile = ModuleProfile::new();
+    profile.set_branch_counts(offset, 1000, 0);
+    let then_hot = Module::new_with_profile(&engine, &wasm, &profile)?;
+    profile.set_branch_counts(offset, 0, 1000);
+    let else_hot = Module::new_with_profile(&engine, &wasm, &profile)?;
+    // Counts which don't match the branch are ignored.
+    profile.set_br_table_counts(offset, &[1, 2], 3);
+    let mismatched = Module::new_with_profile(&engine, &wasm, &profile)?;
+    let module = Module::new(&engine, &wasm)?;
+
+    assert_ne!(then_hot.text(), else_hot.text());
+    assert_eq!(mismatched.text(), module.text());
+
+    for module in [then_hot, else_hot, mismatched, module] {
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let f = instance.get_typed_func::<(i32, i32), i32>(&mut store, "f")?;
+        assert_eq!(f.call(&mut store, (1, 14))?, 42);
+        assert_eq!(f.call(&mut store, (0, 14))?, 2);
+    }
+
+    Ok(())
+}
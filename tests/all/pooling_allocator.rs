@@ -1183,3 +1183,114 @@ fn total_memories_limit() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn pooling_allocator_stats() -> Result<()> {
+    let mut pool = crate::small_pool_config();
+    pool.total_memories(2).total_core_instances(2);
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, r#"(module (memory 1) (data (i32.const 0) "x"))"#)?;
+
+    let stats = engine.pooling_allocator_stats().unwrap();
+    assert_eq!(stats.core_instances, 0);
+    assert_eq!(stats.memories.capacity, 2);
+    assert_eq!(stats.memories.in_use, 0);
+    assert_eq!(stats.memories.unused_cold, 2);
+    assert_eq!(stats.memory_stripes.len(), 1);
+    assert_eq!(stats.memories.affinity_hit_rate(), None);
+
+    let mut store = Store::new(&engine, ());
+    Instance::new(&mut store, &module, &[])?;
+    let stats = engine.pooling_allocator_stats().unwrap();
+    assert_eq!(stats.core_instances, 1);
+    assert_eq!(stats.memories.in_use, 1);
+    assert_eq!(stats.memories.unused_cold, 1);
+    assert_eq!(stats.memories.affinity_requests, 1);
+    assert_eq!(stats.memories.affinity_hits, 0);
+    drop(store);
+
+    let stats = engine.pooling_allocator_stats().unwrap();
+    assert_eq!(stats.core_instances, 0);
+    assert_eq!(stats.memories.in_use, 0);
+    assert_eq!(stats.memories.unused_warm, 1);
+    assert_eq!(stats.memories.unused_cold, 1);
+
+    // Instantiating the same module again reuses its warm slot.
+    let mut store = Store::new(&engine, ());
+    Instance::new(&mut store, &module, &[])?;
+    let stats = engine.pooling_allocator_stats().unwrap();
+    assert_eq!(stats.memories.in_use, 1);
+    assert_eq!(stats.memories.unused_warm, 0);
+    assert_eq!(stats.memories.affinity_hits, 1);
+    assert_eq!(stats.memories.affinity_hit_rate(), Some(0.5));
+
+    Instance::new(&mut store, &module, &[])?;
+    assert!(Instance::new(&mut store, &module, &[]).is_err());
+    let stats = engine.pooling_allocator_stats().unwrap();
+    assert_eq!(stats.core_instances, 2);
+    assert_eq!(stats.core_instances_exhausted, 1);
+    assert_eq!(stats.memories.exhausted, 0);
+
+    // The on-demand allocator has no statistics.
+    assert!(Engine::default().pooling_allocator_stats().is_none());
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+#[cfg(target_pointer_width = "64")]
+fn memory64_larger_than_4gib() -> Result<()> {
+    const GIB: u64 = 1 << 30;
+
+    let mut pool = crate::small_pool_config();
+    pool.max_memory_size(6 * GIB);
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+    config.wasm_memory64(true);
+    config.static_memory_maximum_size(6 * GIB);
+
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "m") i64 1 0x20000)
+                (func (export "grow") (param i64) (result i64)
+                    (memory.grow (local.get 0)))
+                (func (export "store") (param i64 i32)
+                    (i32.store offset=4 (local.get 0) (local.get 1)))
+                (func (export "load") (param i64) (result i32)
+                    (i32.load offset=4 (local.get 0)))
+            )
+        "#,
+    )?;
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let grow = instance.get_typed_func::<u64, i64>(&mut store, "grow")?;
+    let store_fn = instance.get_typed_func::<(u64, u32), ()>(&mut store, "store")?;
+    let load = instance.get_typed_func::<u64, u32>(&mut store, "load")?;
+
+    // Accesses beyond the current size trap, either through an explicit check
+    // or through the guard region.
+    assert!(load.call(&mut store, 65536 - 4).is_err());
+    assert!(load.call(&mut store, 4 * GIB).is_err());
+
+    // Grow past 4GiB and access the memory above it.
+    assert_eq!(grow.call(&mut store, 0x10000)?, 1);
+    store_fn.call(&mut store, (4 * GIB, 42))?;
+    assert_eq!(load.call(&mut store, 4 * GIB)?, 42);
+    assert!(load.call(&mut store, 4 * GIB + 65536 - 4).is_err());
+
+    // Growth is limited by the pool's slot size, which is smaller than the
+    // module's own maximum of 8GiB.
+    assert_eq!(grow.call(&mut store, 0x8000)?, -1);
+    assert_eq!(grow.call(&mut store, 0x7fff)?, 0x10001);
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use anyhow::Result;
+use wasmtime::*;
+
+/// Instantiates `wasm`, calls its `init` export and returns a snapshot.
+fn snapshot_after_init(engine: &Engine, wasm: &[u8]) -> Result<Vec<u8>> {
+    let mut store = Store::new(engine, ());
+    let module = Module::new(engine, wasm)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let init = instance.get_typed_func::<(), ()>(&mut store, "init")?;
+    init.call(&mut store, ())?;
+    instance.snapshot(&mut store, wasm)
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn snapshot_captures_memory_and_globals() -> Result<()> {
+    let engine = Engine::default();
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (global $g (mut i32) (i32.const 1))
+                (global $h (mut i64) (i64.const 0))
+                (global $f (mut f64) (f64.const 0))
+                (global $start (mut i32) (i32.const 0))
+                (data (i32.const 0) "hello")
+                (func $start
+                    global.get $start
+                    i32.const 1
+                    i32.add
+                    global.set $start)
+                (start $start)
+                (func (export "init")
+                    (i32.store8 (i32.const 0) (i32.const 106))
+                    (drop (memory.grow (i32.const 1)))
+                    (i32.store (i32.const 70000) (i32.const 0x01020304))
+                    (global.set $g (i32.const 42))
+                    (global.set $h (i64.const -1))
+                    (global.set $f (f64.const 1.5)))
+                (func (export "g") (result i32) global.get $g)
+                (func (export "h") (result i64) global.get $h)
+                (func (export "f") (result f64) global.get $f)
+                (func (export "start") (result i32) global.get $start)
+            )
+        "#,
+    )?;
+    let snapshot = snapshot_after_init(&engine, &wasm)?;
+
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &snapshot)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(memory.size(&store), 2);
+    assert_eq!(&memory.data(&store)[..5], b"jello");
+    assert_eq!(&memory.data(&store)[70000..70004], &[4, 3, 2, 1]);
+
+    let g = instance.get_typed_func::<(), i32>(&mut store, "g")?;
+    assert_eq!(g.call(&mut store, ())?, 42);
+    let h = instance.get_typed_func::<(), i64>(&mut store, "h")?;
+    assert_eq!(h.call(&mut store, ())?, -1);
+    let f = instance.get_typed_func::<(), f64>(&mut store, "f")?;
+    assert_eq!(f.call(&mut store, ())?, 1.5);
+
+    // The start function ran once before the snapshot and not again after.
+    let start = instance.get_typed_func::<(), i32>(&mut store, "start")?;
+    assert_eq!(start.call(&mut store, ())?, 1);
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn snapshot_keeps_passive_segment_indices() -> Result<()> {
+    let engine = Engine::default();
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "active")
+                (data $passive "passive")
+                (func (export "init"))
+                (func (export "copy")
+                    (memory.init $passive (i32.const 100) (i32.const 0) (i32.const 7)))
+            )
+        "#,
+    )?;
+    let snapshot = snapshot_after_init(&engine, &wasm)?;
+
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &snapshot)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let copy = instance.get_typed_func::<(), ()>(&mut store, "copy")?;
+    copy.call(&mut store, ())?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[..6], b"active");
+    assert_eq!(&memory.data(&store)[100..107], b"passive");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn snapshot_rejects_imported_state() -> Result<()> {
+    let engine = Engine::default();
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (import "" "m" (memory 1))
+                (func (export "init"))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(&engine, &wasm)?;
+    let memory = Memory::new(&mut store, MemoryType::new(1, None))?;
+    let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    let err = instance.snapshot(&mut store, &wasm).unwrap_err();
+    assert!(err.to_string().contains("imports a memory"), "{err}");
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn snapshot_component() -> Result<()> {
+    use wasmtime::component::{Component, Linker};
+
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    let engine = Engine::new(&config)?;
+    let wasm = wat::parse_str(
+        r#"
+            (component
+                (core module $m
+                    (global $counter (mut i32) (i32.const 0))
+                    (func (export "init")
+                        (global.set $counter (i32.const 10)))
+                    (func (export "get") (result i32)
+                        global.get $counter)
+                )
+                (core instance $i (instantiate $m))
+                (func (export "init") (canon lift (core func $i "init")))
+                (func (export "get") (result u32) (canon lift (core func $i "get")))
+            )
+        "#,
+    )?;
+
+    let linker = Linker::new(&engine);
+    let mut store = Store::new(&engine, ());
+    let component = Component::new(&engine, &wasm)?;
+    let instance = linker.instantiate(&mut store, &component)?;
+    let init = instance.get_typed_func::<(), ()>(&mut store, "init")?;
+    init.call(&mut store, ())?;
+    init.post_return(&mut store)?;
+    let snapshot = instance.snapshot(&mut store, &wasm)?;
+
+    let mut store = Store::new(&engine, ());
+    let component = Component::new(&engine, &snapshot)?;
+    let instance = linker.instantiate(&mut store, &component)?;
+    let get = instance.get_typed_func::<(), (u32,)>(&mut store, "get")?;
+    assert_eq!(get.call(&mut store, ())?, (10,));
+    Ok(())
+}
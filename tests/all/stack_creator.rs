@@ -0,0 +1,149 @@
+#![cfg(all(not(target_os = "windows"), not(miri)))]
+use anyhow::{bail, Context};
+use std::{
+    ops::Range,
+    ptr::null_mut,
+    sync::{Arc, Mutex},
+};
+use wasmtime::*;
+
+/// A stack creator which maps its stacks itself and tracks how many are live.
+struct CustomStackCreator {
+    live: Arc<Mutex<usize>>,
+    created: Arc<Mutex<usize>>,
+}
+
+struct CustomStack {
+    base: usize,
+    len: usize,
+    guard_size: usize,
+    live: Arc<Mutex<usize>>,
+}
+
+unsafe impl StackCreator for CustomStackCreator {
+    fn new_stack(&self, size: usize) -> Result<Box<dyn StackMemory>> {
+        use rustix::mm::{mmap_anonymous, mprotect, MapFlags, MprotectFlags, ProtFlags};
+
+        let guard_size = rustix::param::page_size();
+        let size = (size + guard_size - 1) & !(guard_size - 1);
+        let len = size + guard_size;
+        unsafe {
+            let base = mmap_anonymous(null_mut(), len, ProtFlags::empty(), MapFlags::PRIVATE)
+                .context("failed to map stack")?;
+            mprotect(
+                base.cast::<u8>().add(guard_size).cast(),
+                size,
+                MprotectFlags::READ | MprotectFlags::WRITE,
+            )
+            .context("failed to protect stack")?;
+            *self.live.lock().unwrap() += 1;
+            *self.created.lock().unwrap() += 1;
+            Ok(Box::new(CustomStack {
+                base: base as usize,
+                len,
+                guard_size,
+                live: self.live.clone(),
+            }))
+        }
+    }
+}
+
+unsafe impl StackMemory for CustomStack {
+    fn top(&self) -> *mut u8 {
+        (self.base + self.len) as *mut u8
+    }
+
+    fn range(&self) -> Range<usize> {
+        self.base + self.guard_size..self.base + self.len
+    }
+}
+
+impl Drop for CustomStack {
+    fn drop(&mut self) {
+        *self.live.lock().unwrap() -= 1;
+        unsafe { rustix::mm::munmap(self.base as *mut _, self.len).expect("munmap failed") };
+    }
+}
+
+fn config(live: &Arc<Mutex<usize>>, created: &Arc<Mutex<usize>>) -> Config {
+    let mut config = Config::new();
+    config.async_support(true);
+    config.with_host_stack(Arc::new(CustomStackCreator {
+        live: live.clone(),
+        created: created.clone(),
+    }));
+    config
+}
+
+async fn run_recursive(engine: &Engine) -> Result<()> {
+    let mut store = Store::new(engine, ());
+    let module = Module::new(
+        engine,
+        r#"
+            (module
+                (func $f (export "f") (param i32) (result i32)
+                    local.get 0
+                    i32.eqz
+                    if (result i32)
+                        i32.const 0
+                    else
+                        (i32.sub (local.get 0) (i32.const 1))
+                        call $f
+                        i32.const 1
+                        i32.add
+                    end)
+            )
+        "#,
+    )?;
+    let instance = Instance::new_async(&mut store, &module, &[]).await?;
+    let f = instance.get_typed_func::<i32, i32>(&mut store, "f")?;
+    if f.call_async(&mut store, 1000).await? != 1000 {
+        bail!("wrong result");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_stacks_on_demand() -> Result<()> {
+    let live = Arc::new(Mutex::new(0));
+    let created = Arc::new(Mutex::new(0));
+    let engine = Engine::new(&config(&live, &created))?;
+    run_recursive(&engine).await?;
+    assert!(*created.lock().unwrap() > 0);
+    assert_eq!(*live.lock().unwrap(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_stacks_pooling() -> Result<()> {
+    let live = Arc::new(Mutex::new(0));
+    let created = Arc::new(Mutex::new(0));
+    let mut config = config(&live, &created);
+    let mut pool = PoolingAllocationConfig::default();
+    pool.total_stacks(1);
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling(pool));
+    let engine = Engine::new(&config)?;
+    run_recursive(&engine).await?;
+    assert!(*created.lock().unwrap() > 0);
+    assert_eq!(*live.lock().unwrap(), 0);
+
+    // The pool's limit on the number of stacks still applies.
+    let mut store1 = Store::new(&engine, ());
+    let mut store2 = Store::new(&engine, ());
+    let func1 = Func::wrap0_async(&mut store1, |_| Box::new(std::future::pending::<()>()));
+    let func2 = Func::wrap0_async(&mut store2, |_| Box::new(async {}));
+    let pending = func1.call_async(&mut store1, &[], &mut []);
+    let ready = async {
+        tokio::task::yield_now().await;
+        func2.call_async(&mut store2, &[], &mut []).await
+    };
+    tokio::select! {
+        biased;
+        _ = pending => unreachable!(),
+        result = ready => {
+            let err = result.unwrap_err();
+            assert!(err.to_string().contains("maximum concurrent fiber limit"), "{err}");
+        }
+    }
+    Ok(())
+}